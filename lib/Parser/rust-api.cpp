@@ -443,6 +443,35 @@ enum class MagicCommentKind : uint32_t {
   SourceMappingUrl = 1,
 };
 
+enum class CommentKind : uint32_t {
+  Line = 0,
+  Block = 1,
+  Hashbang = 2,
+};
+
+/// A comment in the input, in the layout expected by Rust.
+struct Comment {
+  /// The kind of the comment.
+  CommentKind kind;
+  /// Location of the comment, including the delimiters.
+  SMRange range;
+
+  explicit Comment(const parser::StoredComment &comment)
+      : range(comment.getSourceRange()) {
+    switch (comment.getKind()) {
+      case parser::StoredComment::Kind::Line:
+        kind = CommentKind::Line;
+        break;
+      case parser::StoredComment::Kind::Block:
+        kind = CommentKind::Block;
+        break;
+      case parser::StoredComment::Kind::Hashbang:
+        kind = CommentKind::Hashbang;
+        break;
+    }
+  }
+};
+
 /// This object contains the entire parser state.
 struct ParserContext {
   /// Parser context with allocators, string table, etc.
@@ -459,6 +488,8 @@ struct ParserContext {
   llvh::Optional<size_t> firstError_;
   /// AST.
   ESTree::ProgramNode *ast_ = nullptr;
+  /// All comments in the input, in source order.
+  std::vector<Comment> comments_{};
 
   explicit ParserContext() {
     context_.getSourceErrorManager().setDiagHandler(
@@ -531,7 +562,10 @@ hermes_parser_parse(ParserFlags flags, const char *source, size_t len) {
   parserCtx->setInputBuffer(StringRef(source, len));
   parser::JSParser parser(
       parserCtx->context_, parserCtx->bufId_, hermes::parser::FullParse);
+  parser.setStoreComments(true);
   auto ast = parser.parse();
+  for (const auto &comment : parser.getStoredComments())
+    parserCtx->comments_.emplace_back(comment);
 
   if (!parserCtx->firstError_) {
     if (!ast) {
@@ -558,6 +592,10 @@ extern "C" DataRef hermes_parser_get_messages(const ParserContext *parserCtx) {
   return toDataRef(parserCtx->convertedMessages_);
 }
 
+extern "C" DataRef hermes_parser_get_comments(const ParserContext *parserCtx) {
+  return toDataRef(parserCtx->comments_);
+}
+
 extern "C" ESTree::ProgramNode *hermes_parser_get_ast(
     const ParserContext *parserCtx) {
   return parserCtx->ast_;
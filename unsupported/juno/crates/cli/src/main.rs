@@ -5,13 +5,15 @@
  * LICENSE file in the root directory of this source tree.
  */
 
-use anyhow::{self, ensure, Context, Error};
-use juno::ast::{self, validate_tree, NodePtr, SourceRange};
+use anyhow::{self, bail, ensure, Context, Error};
+use juno::ast::{self, validate_tree, NodePtr};
 use juno::gen_js;
-use juno::hparser::{self, MagicCommentKind, ParsedJS};
-use juno::sourcemap::merge_sourcemaps;
-use pass::PassManager;
-use sourcemap::SourceMap;
+use juno::hparser::{self, MagicCommentKind, ParsedJS, ParserDialect, ParserFlags};
+use juno::sema::check_early_errors;
+use juno::source_manager::JSONSink;
+use juno::sourcemap::{InputSourceMaps, SourceMapInfo};
+use pass::{Define, Definition, JsxOptions, LowerJsx, Lowering, PassManager, StripTypes};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
 use std::path::{Path, PathBuf};
@@ -40,6 +42,122 @@ arg_enum! {
     }
 }
 
+arg_enum! {
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    enum SourceMapMode {
+        External,
+        Linked,
+        Inline,
+    }
+}
+
+arg_enum! {
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    enum Dialect {
+        JavaScript,
+        Flow,
+        FlowUnambiguous,
+        TypeScript,
+    }
+}
+
+impl From<Dialect> for ParserDialect {
+    fn from(dialect: Dialect) -> Self {
+        match dialect {
+            Dialect::JavaScript => ParserDialect::JavaScript,
+            Dialect::Flow => ParserDialect::Flow,
+            Dialect::FlowUnambiguous => ParserDialect::FlowUnambiguous,
+            Dialect::TypeScript => ParserDialect::TypeScript,
+        }
+    }
+}
+
+arg_enum! {
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    enum Comments {
+        All,
+        None,
+        Legal,
+        LegalHoisted,
+    }
+}
+
+impl From<Comments> for gen_js::Comments {
+    fn from(comments: Comments) -> Self {
+        match comments {
+            Comments::All => gen_js::Comments::All,
+            Comments::None => gen_js::Comments::None,
+            Comments::Legal => gen_js::Comments::Legal,
+            Comments::LegalHoisted => gen_js::Comments::LegalHoisted,
+        }
+    }
+}
+
+arg_enum! {
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    enum JsxRuntime {
+        Classic,
+        Automatic,
+    }
+}
+
+impl From<JsxRuntime> for pass::JsxRuntime {
+    fn from(runtime: JsxRuntime) -> Self {
+        match runtime {
+            JsxRuntime::Classic => pass::JsxRuntime::Classic,
+            JsxRuntime::Automatic => pass::JsxRuntime::Automatic,
+        }
+    }
+}
+
+arg_enum! {
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    enum Lower {
+        OptionalChaining,
+        ClassFields,
+        Classes,
+        TemplateLiterals,
+        ArrowFunctions,
+        Spread,
+        Destructuring,
+        ShorthandProperties,
+        ComputedProperties,
+        BlockScoping,
+        AsyncFunctions,
+        Generators,
+        ES2015,
+    }
+}
+
+impl Lower {
+    /// Return the features compiled by the lowering.
+    fn features(self) -> &'static [Lowering] {
+        match self {
+            Lower::OptionalChaining => &[Lowering::OptionalChaining],
+            Lower::ClassFields => &[Lowering::ClassFields],
+            Lower::Classes => &[Lowering::ClassFields, Lowering::Classes],
+            Lower::TemplateLiterals => &[Lowering::TemplateLiterals],
+            Lower::ArrowFunctions => &[Lowering::ArrowFunctions],
+            Lower::Spread => &[Lowering::Spread],
+            Lower::Destructuring => &[Lowering::Destructuring],
+            Lower::ShorthandProperties => &[Lowering::ShorthandProperties],
+            Lower::ComputedProperties => &[Lowering::ComputedProperties],
+            Lower::BlockScoping => &[Lowering::BlockScoping],
+            Lower::AsyncFunctions => &[Lowering::AsyncFunctions],
+            Lower::Generators => &[Lowering::Generators],
+            Lower::ES2015 => Lowering::ALL,
+        }
+    }
+}
+
+arg_enum! {
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    enum DiagnosticsFormat {
+        Text,
+        Json,
+    }
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(name = "juno", about = "A JavaScript Compiler", setting = AppSettings::DeriveDisplayOrder)]
 struct Opt {
@@ -47,25 +165,70 @@ struct Opt {
     #[structopt(long)]
     no_pretty: bool,
 
+    /// Which comments of the input to emit in the output:
+    /// `all` keeps every comment,
+    /// `none` drops them,
+    /// `legal` keeps only legal comments (`/*! ... */`, `@license`, `@preserve`) in place,
+    /// `legalhoisted` moves the legal comments to the top of the output.
+    /// Only `all` and `none` apply to the AST dump.
+    #[structopt(long, possible_values = &Comments::variants(),
+                case_insensitive = true, default_value="All")]
+    comments: Comments,
+
     /// Select what to emit.
     #[structopt(flatten)]
     gen: Gen,
 
-    /// Input file to parse.
-    #[structopt(parse(from_os_str))]
-    input_path: PathBuf,
+    /// Input files to parse. Directories are searched recursively for JS and TS files
+    /// and glob patterns (e.g. `src/**/*.js`) are expanded.
+    /// `-` means `stdin`.
+    #[structopt(parse(from_os_str), required = true)]
+    input_path: Vec<PathBuf>,
+
+    /// Language dialect of the input.
+    /// When not specified, it is inferred from the file extension (`.ts`, `.tsx`)
+    /// and from a `@flow` pragma in the leading comments.
+    #[structopt(long, possible_values = &Dialect::variants(), case_insensitive = true)]
+    dialect: Option<Dialect>,
+
+    /// Enable JSX parsing.
+    /// Implied by the `.jsx` and `.tsx` file extensions.
+    #[structopt(long)]
+    jsx: bool,
+
+    /// Parse the input in strict mode.
+    #[structopt(long)]
+    strict_mode: bool,
 
     /// Path to output to.
     /// Defaults to `-`, which is `stdout`.
     #[structopt(long = "out", short = "o", default_value = "-", parse(from_os_str))]
     output_path: PathBuf,
 
+    /// Directory to write the outputs to, one per input file, mirroring the
+    /// relative paths of the inputs with a `.js` extension, or `.json` for the AST.
+    /// Required when there are multiple inputs.
+    #[structopt(long, parse(from_os_str), conflicts_with = "output-path")]
+    out_dir: Option<PathBuf>,
+
     /// Whether to output a source map.
     /// The source map will be merged with an input source map if provided.
     /// Can only be used when generating JS.
     #[structopt(long)]
     sourcemap: bool,
 
+    /// Embed the contents of the sources in the source map.
+    #[structopt(long)]
+    sources_content: bool,
+
+    /// How to emit the source map:
+    /// `external` writes it to `<output>.map`,
+    /// `linked` additionally appends a `sourceMappingURL` comment referencing it to the output,
+    /// `inline` embeds it in the output as a base64 data URL and also works with `stdout`.
+    #[structopt(long, possible_values = &SourceMapMode::variants(),
+                case_insensitive = true, default_value="External")]
+    sourcemap_mode: SourceMapMode,
+
     /// Base URL to prepend to relative URLs.
     #[structopt(long)]
     base_url: Option<Url>,
@@ -75,10 +238,57 @@ struct Opt {
                 case_insensitive = true, default_value="Auto")]
     input_source_map: InputSourceMap,
 
+    /// How to print errors and warnings.
+    /// `json` prints every diagnostic as a JSON object on a separate line.
+    #[structopt(long, possible_values = &DiagnosticsFormat::variants(),
+                case_insensitive = true, default_value="Text")]
+    diagnostics_format: DiagnosticsFormat,
+
+    /// Remove Flow and TypeScript type annotations, declarations, imports and exports,
+    /// producing plain JavaScript.
+    #[structopt(long)]
+    strip_types: bool,
+
+    /// Compile JSX to function calls:
+    /// `classic` calls the `--jsx-pragma` function with the children as arguments,
+    /// `automatic` imports the `jsx` and `jsxs` functions from `<import-source>/jsx-runtime`.
+    #[structopt(long, possible_values = &JsxRuntime::variants(), case_insensitive = true)]
+    jsx_runtime: Option<JsxRuntime>,
+
+    /// Function creating elements with the classic JSX runtime.
+    #[structopt(long, default_value = "React.createElement")]
+    jsx_pragma: String,
+
+    /// Component of fragments with the classic JSX runtime.
+    #[structopt(long, default_value = "React.Fragment")]
+    jsx_pragma_frag: String,
+
+    /// Module providing the automatic JSX runtime.
+    #[structopt(long, default_value = "react")]
+    jsx_import_source: String,
+
+    /// Compile a language feature to older JS. `ES2015` compiles all of them down to ES5, and
+    /// `Classes` also compiles class fields.
+    /// The passes run in a fixed order after type stripping and JSX, regardless of the order
+    /// of the flags. Can be repeated.
+    /// Destructured arrays are assumed to be array-like rather than iterable.
+    #[structopt(long, possible_values = &Lower::variants(), case_insensitive = true,
+                number_of_values = 1)]
+    lower: Vec<Lower>,
+
     /// Whether to run optimization passes.
+    /// Combined with `--no-pretty`, the output is also minified: statements and literals are
+    /// compacted and local variables are renamed.
     #[structopt(short = "O")]
     optimize: bool,
 
+    /// Replace reads of a global variable or property chain with a JSON value before
+    /// optimizing, e.g. `__DEV__=false` or `process.env.NODE_ENV="production"`.
+    /// Variables declared in the input with the same name are left alone.
+    /// Can be repeated.
+    #[structopt(long = "define", value_name = "KEY=VALUE", number_of_values = 1)]
+    defines: Vec<Definition>,
+
     /// Measure and print times.
     #[structopt(long = "Xtime")]
     xtime: bool,
@@ -90,13 +300,110 @@ impl Opt {
     fn validate(&self) -> anyhow::Result<()> {
         if self.sourcemap {
             ensure!(
-                self.output_path != Path::new("-"),
+                self.output_path != Path::new("-")
+                    || self.out_dir.is_some()
+                    || self.sourcemap_mode == SourceMapMode::Inline,
                 "Source map requires an output path",
             );
             ensure!(self.gen.js, "Source map requires JS output",);
         }
         Ok(())
     }
+
+    /// Whether any output has been requested.
+    fn has_output(&self) -> bool {
+        self.gen.ast || self.gen.js
+    }
+}
+
+/// A single file to compile.
+struct Input {
+    /// Path of the file, or `-` for stdin.
+    path: PathBuf,
+    /// Path of the outputs relative to the output directory.
+    rel_path: PathBuf,
+}
+
+/// File extensions of the files picked up when searching input directories.
+const SOURCE_EXTENSIONS: &[&str] = &["js", "jsx", "mjs", "cjs", "ts", "tsx"];
+
+/// Characters which indicate that an input path is a glob pattern.
+const GLOB_CHARS: &[char] = &['*', '?', '['];
+
+/// Expand the input paths specified on the command line into a list of files.
+fn expand_inputs(paths: &[PathBuf]) -> anyhow::Result<Vec<Input>> {
+    let mut inputs = Vec::new();
+    for path in paths {
+        let pattern = path.to_string_lossy();
+        if path == Path::new("-") {
+            inputs.push(Input {
+                path: path.clone(),
+                rel_path: PathBuf::from("stdin.js"),
+            });
+        } else if path.is_dir() {
+            let start = inputs.len();
+            collect_dir(path, path, &mut inputs)?;
+            ensure!(
+                inputs.len() > start,
+                "{}: no source files in directory",
+                path.display()
+            );
+        } else if !path.exists() && pattern.contains(GLOB_CHARS) {
+            let base = glob_base(path);
+            let start = inputs.len();
+            for entry in glob::glob(&pattern).with_context(|| pattern.to_string())? {
+                let file = entry?;
+                if file.is_file() {
+                    let rel_path = file.strip_prefix(&base).unwrap_or(&file).to_path_buf();
+                    inputs.push(Input {
+                        path: file,
+                        rel_path,
+                    });
+                }
+            }
+            ensure!(inputs.len() > start, "{}: no matching files", pattern);
+        } else {
+            let rel_path = PathBuf::from(path.file_name().unwrap_or_else(|| path.as_os_str()));
+            inputs.push(Input {
+                path: path.clone(),
+                rel_path,
+            });
+        }
+    }
+    Ok(inputs)
+}
+
+/// Recursively add all source files in `dir` to `inputs`, in sorted order.
+/// Relative paths are computed relative to `base`.
+fn collect_dir(dir: &Path, base: &Path, inputs: &mut Vec<Input>) -> anyhow::Result<()> {
+    let mut entries = std::fs::read_dir(dir)
+        .with_context(|| dir.display().to_string())?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| dir.display().to_string())?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            collect_dir(&path, base, inputs)?;
+        } else if matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some(ext) if SOURCE_EXTENSIONS.contains(&ext)
+        ) {
+            inputs.push(Input {
+                rel_path: path.strip_prefix(base).unwrap().to_path_buf(),
+                path,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Return the leading components of the glob `pattern` which don't contain wildcards.
+fn glob_base(pattern: &Path) -> PathBuf {
+    pattern
+        .components()
+        .take_while(|c| !c.as_os_str().to_string_lossy().contains(GLOB_CHARS))
+        .collect()
 }
 
 /// Read the specified file or stdin into a null terminated buffer.
@@ -111,6 +418,24 @@ fn read_file_or_stdin(input: &Path) -> anyhow::Result<NullTerminatedBuf> {
     }
 }
 
+/// Determine the parser flags for `input`, whose contents are `buf`.
+/// Explicitly specified options take precedence over what is inferred from the
+/// file name and contents.
+fn parser_flags(opt: &Opt, input: &Path, buf: &NullTerminatedBuf) -> ParserFlags {
+    let ext = input.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    let dialect = match opt.dialect {
+        Some(dialect) => dialect.into(),
+        None if ext == "ts" || ext == "tsx" => ParserDialect::TypeScript,
+        None if hparser::has_flow_pragma(buf.as_bytes()) => ParserDialect::Flow,
+        None => ParserDialect::JavaScript,
+    };
+    ParserFlags {
+        strict_mode: opt.strict_mode,
+        enable_jsx: opt.jsx || ext == "jsx" || ext == "tsx",
+        dialect,
+    }
+}
+
 /// If there is a magic comment of the specified type, attempt to parse it as an URL.
 fn parse_magic_url(
     parsed: &ParsedJS,
@@ -127,42 +452,82 @@ fn parse_magic_url(
 /// Consume the URL and fetch and parse the source map from it.
 /// The URL is consumed to ensure that it is freed immediately after it is no
 /// longer needed. Data URLs can potentially contain megabytes of data.
-fn load_source_map(url: Url) -> anyhow::Result<SourceMap> {
+fn load_source_map(url: Url) -> anyhow::Result<SourceMapInfo> {
     fetchurl::fetch_url(&url, Default::default())
         .with_context(|| format!("Source map: {}", &url))
         .and_then(|data| {
-            SourceMap::from_slice(data.as_ref())
+            SourceMapInfo::from_slice(data.as_ref())
                 .with_context(|| format!("Source map: {}: error parsing", &url))
         })
 }
 
 /// Generate the specified output, if any.
 /// Returns whether any output was generated.
+/// `output_path` is the path of the output file, or `-` for stdout.
+/// `typescript` is whether the input was parsed as TypeScript.
 fn gen_output(
     opt: &Opt,
     ctx: &mut ast::Context,
     root: NodePtr,
-    input_map: &Option<SourceMap>,
+    typescript: bool,
+    input_maps: &InputSourceMaps,
+    output_path: &Path,
 ) -> anyhow::Result<bool> {
-    let out: Box<dyn Write> = if opt.output_path == Path::new("-") {
-        Box::new(std::io::stdout())
-    } else {
-        Box::new(
-            File::create(&opt.output_path)
-                .with_context(|| opt.output_path.display().to_string())?,
-        )
-    };
-
-    let final_ast = if opt.optimize {
-        let pm = PassManager::standard();
-        pm.run(ctx, root)
-    } else {
-        root
-    };
+    let mut pm = PassManager::new();
+    if opt.strip_types {
+        pm.add_pass(Box::new(StripTypes::new(typescript)));
+    }
+    if let Some(runtime) = opt.jsx_runtime {
+        pm.add_pass(Box::new(LowerJsx::new(JsxOptions {
+            runtime: runtime.into(),
+            pragma: opt.jsx_pragma.clone(),
+            pragma_frag: opt.jsx_pragma_frag.clone(),
+            import_source: opt.jsx_import_source.clone(),
+        })));
+    }
+    if !opt.lower.is_empty() {
+        let lowerings: Vec<Lowering> = opt
+            .lower
+            .iter()
+            .flat_map(|lower| lower.features())
+            .copied()
+            .collect();
+        pm.add_lowering_passes(&lowerings);
+    }
+    if !opt.defines.is_empty() {
+        pm.add_pass(Box::new(Define::new(opt.defines.clone())));
+    }
+    if opt.optimize {
+        // Without pretty printing, minify the output.
+        if opt.no_pretty {
+            pm.add_minify_passes();
+        } else {
+            pm.add_standard_passes();
+        }
+    }
+    let num_errors = ctx.sm().num_errors();
+    let final_ast = pm.run(ctx, root.clone());
+    // Errors have already been reported by the source manager.
+    if ctx.sm().num_errors() != num_errors {
+        return Ok(false);
+    }
+    // Transformed code may have errors which weren't in the input, which has already been
+    // checked.
+    if final_ast != root && check_early_errors(ctx, &final_ast).is_err() {
+        return Ok(false);
+    }
 
+    // The output is generated in memory and only written once it is complete, so failures
+    // don't leave empty or truncated files behind.
+    let mut out = Vec::new();
+    if output_path != Path::new("-") && opt.has_output() {
+        if let Some(parent) = output_path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| parent.display().to_string())?;
+        }
+    }
     if opt.gen.ast {
         ast::dump_json(
-            out,
+            &mut out,
             ctx,
             &final_ast,
             if opt.no_pretty {
@@ -171,34 +536,72 @@ fn gen_output(
                 ast::Pretty::Yes
             },
         )?;
-        Ok(true)
     } else if opt.gen.js {
         let generated_map = gen_js::generate(
-            out,
+            &mut out,
             ctx,
             &final_ast,
-            if opt.no_pretty {
-                gen_js::Pretty::No
-            } else {
-                gen_js::Pretty::Yes
+            &gen_js::Opt {
+                pretty: if opt.no_pretty {
+                    gen_js::Pretty::No
+                } else {
+                    gen_js::Pretty::Yes
+                },
+                file: if output_path == Path::new("-") {
+                    None
+                } else {
+                    output_path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                },
+                sources_content: opt.sources_content,
+                comments: opt.comments.into(),
             },
         )?;
         if opt.sourcemap {
-            // Workaround because `PathBuf` doesn't have a way to append an extension,
-            // only to replace the existing one.
-            let mut path = opt.output_path.clone().into_os_string();
-            path.push(".map");
-            let sourcemap_file = File::create(PathBuf::from(path))?;
-            let merged_map = match input_map {
-                None => generated_map,
-                Some(input_map) => merge_sourcemaps(input_map, &generated_map),
+            let generated_map = SourceMapInfo::from(generated_map);
+            let merged_map = if input_maps.is_empty() {
+                generated_map
+            } else {
+                input_maps.compose(ctx.sm(), &generated_map)
             };
-            merged_map.to_writer(sourcemap_file)?;
+            if opt.sourcemap_mode == SourceMapMode::Inline {
+                let mut data = Vec::new();
+                merged_map.to_writer(&mut data)?;
+                writeln!(
+                    out,
+                    "//# sourceMappingURL={}",
+                    fetchurl::make_data_url("application/json;charset=utf-8", &data)
+                )?;
+            } else {
+                // Workaround because `PathBuf` doesn't have a way to append an extension,
+                // only to replace the existing one.
+                let mut path = output_path.to_path_buf().into_os_string();
+                path.push(".map");
+                let path = PathBuf::from(path);
+                let sourcemap_file =
+                    File::create(&path).with_context(|| path.display().to_string())?;
+                merged_map.to_writer(sourcemap_file)?;
+                if opt.sourcemap_mode == SourceMapMode::Linked {
+                    // The map is next to the output, so refer to it by its file name.
+                    writeln!(
+                        out,
+                        "//# sourceMappingURL={}",
+                        path.file_name().unwrap().to_string_lossy()
+                    )?;
+                }
+            }
         }
-        Ok(true)
     } else {
-        Ok(false)
+        return Ok(false);
     }
+
+    if output_path == Path::new("-") {
+        std::io::stdout().write_all(&out)?;
+    } else {
+        std::fs::write(output_path, &out).with_context(|| output_path.display().to_string())?;
+    }
+    Ok(true)
 }
 
 /// TransformStatus indicates whether there were parse or validation errors
@@ -217,26 +620,45 @@ enum TransformStatus {
     Error,
 }
 
-fn run(opt: &Opt) -> anyhow::Result<TransformStatus> {
-    opt.validate()?;
+/// Return the path to write the output for `input` to.
+/// In the output directory, the extension of the input is replaced with `.json` for the AST
+/// and `.js` for JS, e.g. `a.ts` is compiled to `a.js`.
+fn output_path(opt: &Opt, input: &Input) -> PathBuf {
+    match &opt.out_dir {
+        None => opt.output_path.clone(),
+        Some(dir) => {
+            dir.join(&input.rel_path)
+                .with_extension(if opt.gen.ast { "json" } else { "js" })
+        }
+    }
+}
 
-    let mut ctx = ast::Context::new();
+/// Compile a single input file using the shared `ctx`.
+/// The input source map of the file, if any, is recorded in `input_maps`.
+fn compile_file(
+    opt: &Opt,
+    ctx: &mut ast::Context,
+    input_maps: &mut InputSourceMaps,
+    input: &Input,
+) -> anyhow::Result<TransformStatus> {
+    let num_errors = ctx.sm().num_errors();
 
     // Read the input into memory.
-    let input = opt.input_path.as_path();
+    let path = input.path.as_path();
     let file_id = ctx
         .sm_mut()
-        .add_source(input.display().to_string(), read_file_or_stdin(input)?);
+        .add_source(path.display().to_string(), read_file_or_stdin(path)?);
     let buf = ctx.sm().source_buffer_rc(file_id);
 
     // Start measuring time.
     let mut timer = Timer::new();
 
     // Parse.
-    let parsed = hparser::ParsedJS::parse(Default::default(), &buf);
+    let flags = parser_flags(opt, path, &buf);
+    let parsed = hparser::ParsedJS::parse(flags, &buf);
     timer.mark("Parse");
-    if let Some(e) = parsed.first_error() {
-        ctx.sm().error(SourceRange::from_loc(file_id, e.0), e.1);
+    parsed.report_diagnostics(ctx.sm(), file_id);
+    if parsed.has_errors() {
         return Ok(TransformStatus::Error);
     }
 
@@ -249,34 +671,120 @@ fn run(opt: &Opt) -> anyhow::Result<TransformStatus> {
 
     let ast = {
         // Convert to Juno AST.
-        let gc = ast::GCContext::new(&mut ctx);
+        let gc = ast::GCContext::new(ctx);
         NodePtr::from_node(&gc, parsed.to_ast(&gc, file_id).unwrap())
     };
     // We don't need the original parser anymore.
     drop(parsed);
+    if opt.comments == Comments::None {
+        ctx.comments_mut().clear();
+    }
     timer.mark("Cvt");
 
-    validate_tree(&mut ctx, &ast).with_context(|| input.display().to_string())?;
+    // Validation errors have already been reported by the source manager.
+    if validate_tree(ctx, &ast).is_err() {
+        return Ok(TransformStatus::Error);
+    }
     timer.mark("Validate AST");
 
+    if check_early_errors(ctx, &ast).is_err() {
+        return Ok(TransformStatus::Error);
+    }
+    timer.mark("Early errors");
+
     // Fetch and parse the source map before we generate the output.
-    let source_map = sm_url.map(load_source_map).transpose()?;
+    if let Some(url) = sm_url {
+        input_maps.insert(file_id, load_source_map(url)?);
+    }
 
     // Generate output.
-    if gen_output(opt, &mut ctx, ast, &source_map)? {
+    let typescript = flags.dialect == ParserDialect::TypeScript;
+    if gen_output(
+        opt,
+        ctx,
+        ast,
+        typescript,
+        input_maps,
+        &output_path(opt, input),
+    )? {
         timer.mark("Gen");
     }
 
-    // Drop the AST. We are doing it explicitly just to measure the time.
-    drop(ctx);
+    // Free the AST, which is no longer referenced. We are doing it explicitly
+    // just to measure the time.
+    ctx.gc();
     timer.mark("Drop");
 
     // Optionally print elapsed times.
     if opt.xtime {
+        if opt.out_dir.is_some() {
+            println!("{}:", path.display());
+        }
         print!("{:#}", timer);
     }
 
-    Ok(TransformStatus::Success)
+    if ctx.sm().num_errors() != num_errors {
+        Ok(TransformStatus::Error)
+    } else {
+        Ok(TransformStatus::Success)
+    }
+}
+
+fn run(opt: &Opt) -> anyhow::Result<TransformStatus> {
+    opt.validate()?;
+
+    let inputs = expand_inputs(&opt.input_path)?;
+    if inputs.len() > 1 || opt.out_dir.is_some() {
+        ensure!(
+            !inputs.iter().any(|input| input.path == Path::new("-")),
+            "stdin can only be used as the only input"
+        );
+    }
+    if inputs.len() > 1 && opt.has_output() {
+        ensure!(opt.out_dir.is_some(), "Multiple inputs require --out-dir");
+    }
+    if opt.out_dir.is_some() {
+        // Explicit files are written by name, so files from different directories may clash.
+        let mut outputs = HashMap::new();
+        for input in &inputs {
+            let output = output_path(opt, input);
+            if let Some(other) = outputs.insert(output.clone(), &input.path) {
+                bail!(
+                    "{} and {} would both be written to {}",
+                    other.display(),
+                    input.path.display(),
+                    output.display()
+                );
+            }
+        }
+    }
+
+    // All files share the same context and source manager. Input source maps are tracked
+    // per file, since each file may have its own.
+    let mut ctx = ast::Context::new();
+    let mut input_maps = InputSourceMaps::new();
+    if opt.strict_mode {
+        ctx.enable_strict_mode();
+    }
+    if opt.diagnostics_format == DiagnosticsFormat::Json {
+        ctx.sm_mut()
+            .set_diagnostic_sink(Box::new(JSONSink::new(std::io::stderr())));
+    }
+
+    // Keep going after a file fails, so all errors are reported.
+    let mut status = TransformStatus::Success;
+    for input in &inputs {
+        match compile_file(opt, &mut ctx, &mut input_maps, input) {
+            Ok(TransformStatus::Success) => {}
+            Ok(TransformStatus::Error) => status = TransformStatus::Error,
+            Err(e) => {
+                eprintln!("{:#}", e);
+                status = TransformStatus::Error;
+            }
+        }
+    }
+
+    Ok(status)
 }
 
 fn main() {
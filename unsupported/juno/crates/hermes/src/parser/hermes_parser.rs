@@ -5,7 +5,7 @@
  * LICENSE file in the root directory of this source tree.
  */
 
-use super::node::{Node, NodePtr, NodePtrOpt, SMLoc, StringRef};
+use super::node::{Node, NodePtr, NodePtrOpt, SMLoc, SMRange, StringRef};
 use crate::utf::utf8_with_surrogates_to_string_lossy;
 use std::fmt::Formatter;
 use std::marker::PhantomData;
@@ -54,7 +54,7 @@ impl Default for ParserFlags {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 #[repr(u32)]
 pub enum DiagKind {
     Error,
@@ -169,6 +169,44 @@ impl MagicCommentKind {
     }
 }
 
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CommentKind {
+    /// Comment that begins with "//".
+    Line = 0,
+    /// Comment that is delimited by "/*" and "*/".
+    Block = 1,
+    /// Comment that begins with "#!" and starts at the first byte of the file.
+    Hashbang = 2,
+}
+
+/// A comment in the input.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct StoredComment {
+    /// The kind of the comment.
+    pub kind: CommentKind,
+    /// Location of the comment, including the delimiters.
+    pub range: SMRange,
+}
+
+impl StoredComment {
+    /// Return the text of the comment without the delimiters (`//`, `/*`, `*/`, `#!`).
+    pub fn text(&self) -> &[u8] {
+        let start = self.range.start.as_ptr();
+        let end = self.range.end.as_ptr();
+        let (from, to) = match self.kind {
+            CommentKind::Block => (2, 2),
+            CommentKind::Line | CommentKind::Hashbang => (2, 0),
+        };
+        unsafe {
+            let len = end.offset_from(start) as usize;
+            debug_assert!(len >= from + to, "invalid comment range");
+            std::slice::from_raw_parts(start.add(from), len - from - to)
+        }
+    }
+}
+
 #[repr(C)]
 struct ParserContext {
     _unused: i32,
@@ -186,6 +224,9 @@ extern "C" {
     fn hermes_parser_get_messages<'a>(parser_ctx: *const ParserContext)
         -> DataRef<'a, DiagMessage>;
     fn hermes_parser_get_ast(parser_ctx: *const ParserContext) -> NodePtrOpt;
+    fn hermes_parser_get_comments<'a>(
+        parser_ctx: *const ParserContext,
+    ) -> DataRef<'a, StoredComment>;
     fn hermes_parser_find_location(
         parser_ctx: *mut ParserContext,
         loc: SMLoc,
@@ -262,6 +303,11 @@ impl HermesParser<'_> {
         unsafe { hermes_parser_get_ast(self.parser_ctx) }.as_node_ptr()
     }
 
+    /// Return all comments in the input, in source order.
+    pub fn comments(&self) -> &[StoredComment] {
+        unsafe { hermes_parser_get_comments(self.parser_ctx).as_slice() }
+    }
+
     /// Translate a source coordinate represented as a SMLoc (a pointer) into
     /// line and column.
     pub fn find_coord(&self, loc: SMLoc) -> Option<Coord> {
@@ -356,4 +402,21 @@ mod tests {
             "my map URL"
         );
     }
+
+    #[test]
+    fn comments() {
+        let buf = NullTerminatedBuf::from_str_check("#!/bin/js\n/* a */ var x; // b\n");
+        let p = HermesParser::parse(Default::default(), &buf);
+        assert!(!p.has_errors());
+        let comments: Vec<(CommentKind, &[u8])> =
+            p.comments().iter().map(|c| (c.kind, c.text())).collect();
+        assert_eq!(
+            comments,
+            [
+                (CommentKind::Hashbang, &b"/bin/js"[..]),
+                (CommentKind::Block, b" a "),
+                (CommentKind::Line, b" b"),
+            ]
+        );
+    }
 }
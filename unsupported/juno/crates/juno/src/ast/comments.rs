@@ -0,0 +1,96 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use super::SourceRange;
+use std::collections::HashMap;
+
+/// The syntactic form of a comment.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CommentKind {
+    /// `// ...`
+    Line,
+    /// `/* ... */`
+    Block,
+    /// `#! ...` at the start of the file.
+    Hashbang,
+}
+
+/// A comment from the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    pub kind: CommentKind,
+
+    /// Location of the comment, including the delimiters.
+    pub range: SourceRange,
+
+    /// Text of the comment without the delimiters.
+    pub text: String,
+}
+
+impl Comment {
+    /// Return true if this is a legal comment, which must be preserved even in minified output:
+    /// a comment starting with `//!` or `/*!`, or containing `@license` or `@preserve`.
+    pub fn is_legal(&self) -> bool {
+        (self.kind != CommentKind::Hashbang && self.text.starts_with('!'))
+            || self.text.contains("@license")
+            || self.text.contains("@preserve")
+    }
+}
+
+/// The comments attached to a single node.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeComments {
+    /// Comments preceding the node.
+    pub leading: Vec<Comment>,
+
+    /// Comments following the node.
+    pub trailing: Vec<Comment>,
+
+    /// Comments inside the node which don't precede or follow any of its children,
+    /// e.g. in an empty block.
+    pub inner: Vec<Comment>,
+}
+
+impl NodeComments {
+    pub fn is_empty(&self) -> bool {
+        self.leading.is_empty() && self.trailing.is_empty() && self.inner.is_empty()
+    }
+}
+
+/// Side table associating comments with AST nodes.
+///
+/// Comments are keyed by the source range of the node they are attached to rather than by the
+/// node itself, so they remain attached when a node is rebuilt by a transformation.
+/// Nested nodes may share a range (e.g. an expression statement without a semicolon and its
+/// expression), in which case the comments belong to the outermost node, except for a
+/// `Program`, which only owns inner comments.
+#[derive(Debug, Default)]
+pub struct CommentTable {
+    map: HashMap<SourceRange, NodeComments>,
+}
+
+impl CommentTable {
+    /// Return the comments attached to nodes with the specified range, if any.
+    pub fn get(&self, range: &SourceRange) -> Option<&NodeComments> {
+        self.map.get(range)
+    }
+
+    /// Return the comments attached to nodes with the specified range, adding an empty
+    /// entry if there are none.
+    pub fn entry(&mut self, range: SourceRange) -> &mut NodeComments {
+        self.map.entry(range).or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Remove all comments.
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+}
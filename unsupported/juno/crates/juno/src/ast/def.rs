@@ -23,6 +23,7 @@
 /// which includes any constructible `NodeKind`s` as well as interfaces like
 /// `Statement`, `Expression`, etc.
 /// If multiple constraints are provided, at least one must be satisfied.
+/// A field without constraints accepts any node.
 /// The `null` constraint is encoded via `Option`, it need not be listed explicitly.
 /// See [`gen_nodekind_enum`] for an example of how to pattern match on the information
 /// provided by this macro.
@@ -39,8 +40,8 @@ macro_rules! nodekind_defs {
                 id: Option<&'a Node<'a>>[Identifier],
                 params: NodeList<'a>[Pattern],
                 body: &'a Node<'a>[BlockStatement],
-                type_parameters: Option<&'a Node<'a>>[TypeParameterDeclaration],
-                return_type: Option<&'a Node<'a>>[TypeAnnotation],
+                type_parameters: Option<&'a Node<'a>>[TypeParameterDeclaration, TSTypeParameterDeclaration],
+                return_type: Option<&'a Node<'a>>[TypeAnnotation, TSTypeAnnotation],
                 predicate: Option<&'a Node<'a>>[InferredPredicate, DeclaredPredicate],
                 generator: bool,
                 is_async: bool,
@@ -49,8 +50,8 @@ macro_rules! nodekind_defs {
                 id: Option<&'a Node<'a>>[Identifier],
                 params: NodeList<'a>[Pattern],
                 body: &'a Node<'a>[Expression, BlockStatement],
-                type_parameters: Option<&'a Node<'a>>[TypeParameterDeclaration],
-                return_type: Option<&'a Node<'a>>[TypeAnnotation],
+                type_parameters: Option<&'a Node<'a>>[TypeParameterDeclaration, TSTypeParameterDeclaration],
+                return_type: Option<&'a Node<'a>>[TypeAnnotation, TSTypeAnnotation],
                 predicate: Option<&'a Node<'a>>[InferredPredicate, DeclaredPredicate],
                 expression: bool,
                 is_async: bool,
@@ -59,8 +60,8 @@ macro_rules! nodekind_defs {
                 id: Option<&'a Node<'a>>[Identifier],
                 params: NodeList<'a>[Pattern],
                 body: &'a Node<'a>[BlockStatement],
-                type_parameters: Option<&'a Node<'a>>[TypeParameterDeclaration],
-                return_type: Option<&'a Node<'a>>[TypeAnnotation],
+                type_parameters: Option<&'a Node<'a>>[TypeParameterDeclaration, TSTypeParameterDeclaration],
+                return_type: Option<&'a Node<'a>>[TypeAnnotation, TSTypeAnnotation],
                 predicate: Option<&'a Node<'a>>[InferredPredicate, DeclaredPredicate],
                 generator: bool,
                 is_async: bool,
@@ -153,7 +154,7 @@ macro_rules! nodekind_defs {
                 expressions: NodeList<'a>[Expression],
             },
             ObjectExpression[Expression] {
-                properties: NodeList<'a>[Property],
+                properties: NodeList<'a>[Property, SpreadElement],
             },
             ArrayExpression[Expression] {
                 elements: NodeList<'a>[Expression, SpreadElement],
@@ -164,7 +165,7 @@ macro_rules! nodekind_defs {
             },
             NewExpression[Expression] {
                 callee: &'a Node<'a>[Expression],
-                type_arguments: Option<&'a Node<'a>>[TypeParameterInstantiation],
+                type_arguments: Option<&'a Node<'a>>[TypeParameterInstantiation, TSTypeParameterInstantiation],
                 arguments: NodeList<'a>[Expression, SpreadElement],
             },
             YieldExpression[Expression] {
@@ -180,13 +181,13 @@ macro_rules! nodekind_defs {
             },
             CallExpression[Expression] {
                 callee: &'a Node<'a>[Expression, Super],
-                type_arguments: Option<&'a Node<'a>>[TypeParameterInstantiation],
-                arguments: NodeList<'a>[Expression],
+                type_arguments: Option<&'a Node<'a>>[TypeParameterInstantiation, TSTypeParameterInstantiation],
+                arguments: NodeList<'a>[Expression, SpreadElement],
             },
             OptionalCallExpression[Expression] {
                 callee: &'a Node<'a>[Expression, Super],
-                type_arguments: Option<&'a Node<'a>>[TypeParameterInstantiation],
-                arguments: NodeList<'a>[Expression],
+                type_arguments: Option<&'a Node<'a>>[TypeParameterInstantiation, TSTypeParameterInstantiation],
+                arguments: NodeList<'a>[Expression, SpreadElement],
                 optional: bool,
             },
             AssignmentExpression[Expression] {
@@ -205,13 +206,13 @@ macro_rules! nodekind_defs {
                 prefix: bool,
             },
             MemberExpression[LVal] {
-                object: &'a Node<'a>[Expression],
-                property: &'a Node<'a>[Expression],
+                object: &'a Node<'a>[Expression, Super],
+                property: &'a Node<'a>[Expression, PrivateName],
                 computed: bool,
             },
             OptionalMemberExpression[Expression] {
                 object: &'a Node<'a>[Expression],
-                property: &'a Node<'a>[Expression],
+                property: &'a Node<'a>[Expression, PrivateName],
                 computed: bool,
                 optional: bool,
             },
@@ -238,7 +239,7 @@ macro_rules! nodekind_defs {
             },
             Identifier[Pattern] {
                 name: NodeLabel,
-                type_annotation: Option<&'a Node<'a>>[TypeAnnotation],
+                type_annotation: Option<&'a Node<'a>>[TypeAnnotation, TSTypeAnnotation],
                 optional: bool,
             },
             PrivateName[LVal] {
@@ -287,18 +288,18 @@ macro_rules! nodekind_defs {
             },
             ClassDeclaration[Declaration] {
                 id: Option<&'a Node<'a>>[Identifier],
-                type_parameters: Option<&'a Node<'a>>[TypeParameterDeclaration],
+                type_parameters: Option<&'a Node<'a>>[TypeParameterDeclaration, TSTypeParameterDeclaration],
                 super_class: Option<&'a Node<'a>>[Expression],
-                super_type_parameters: Option<&'a Node<'a>>[TypeParameterDeclaration],
+                super_type_parameters: Option<&'a Node<'a>>[TypeParameterInstantiation, TSTypeParameterInstantiation],
                 implements: NodeList<'a>[ClassImplements],
                 decorators: NodeList<'a>,
                 body: &'a Node<'a>[ClassBody],
             },
             ClassExpression[Expression] {
                 id: Option<&'a Node<'a>>[Identifier],
-                type_parameters: Option<&'a Node<'a>>[TypeParameterDeclaration],
+                type_parameters: Option<&'a Node<'a>>[TypeParameterDeclaration, TSTypeParameterDeclaration],
                 super_class: Option<&'a Node<'a>>[Expression],
-                super_type_parameters: Option<&'a Node<'a>>[TypeParameterDeclaration],
+                super_type_parameters: Option<&'a Node<'a>>[TypeParameterInstantiation, TSTypeParameterInstantiation],
                 implements: NodeList<'a>[ClassImplements],
                 decorators: NodeList<'a>,
                 body: &'a Node<'a>[ClassBody],
@@ -314,16 +315,16 @@ macro_rules! nodekind_defs {
                 declare: bool,
                 optional: bool,
                 variance: Option<&'a Node<'a>>[Variance],
-                type_annotation: Option<&'a Node<'a>>[TypeAnnotation],
+                type_annotation: Option<&'a Node<'a>>[TypeAnnotation, TSTypeAnnotation],
             },
             ClassPrivateProperty {
-                key: &'a Node<'a>[PrivateName],
+                key: &'a Node<'a>[Identifier],
                 value: Option<&'a Node<'a>>[Expression],
                 is_static: bool,
                 declare: bool,
                 optional: bool,
                 variance: Option<&'a Node<'a>>[Variance],
-                type_annotation: Option<&'a Node<'a>>[TypeAnnotation],
+                type_annotation: Option<&'a Node<'a>>[TypeAnnotation, TSTypeAnnotation],
             },
             MethodDefinition {
                 key: &'a Node<'a>[Expression],
@@ -333,7 +334,7 @@ macro_rules! nodekind_defs {
                 is_static: bool,
             },
             ImportDeclaration[Declaration] {
-                specifiers: NodeList<'a>[ImportSpecifier],
+                specifiers: NodeList<'a>[ImportSpecifier, ImportDefaultSpecifier, ImportNamespaceSpecifier],
                 source: &'a Node<'a>[StringLiteral],
                 assertions: Option<NodeList<'a>>[ImportAttribute],
                 import_kind: ImportKind,
@@ -367,19 +368,19 @@ macro_rules! nodekind_defs {
                 exported: &'a Node<'a>[Identifier],
             },
             ExportDefaultDeclaration[Declaration] {
-                declaration: &'a Node<'a>[Declaration],
+                declaration: &'a Node<'a>[Declaration, Expression],
             },
             ExportAllDeclaration[Declaration] {
                 source: &'a Node<'a>[StringLiteral],
                 export_kind: ExportKind,
             },
             ObjectPattern[Pattern] {
-                properties: NodeList<'a>[Property],
-                type_annotation: Option<&'a Node<'a>>[TypeAnnotation],
+                properties: NodeList<'a>[Property, RestElement],
+                type_annotation: Option<&'a Node<'a>>[TypeAnnotation, TSTypeAnnotation],
             },
             ArrayPattern[Pattern] {
                 elements: NodeList<'a>[Pattern],
-                type_annotation: Option<&'a Node<'a>>[TypeAnnotation],
+                type_annotation: Option<&'a Node<'a>>[TypeAnnotation, TSTypeAnnotation],
             },
             RestElement[Pattern] {
                 argument: &'a Node<'a>[Pattern],
@@ -511,41 +512,41 @@ macro_rules! nodekind_defs {
                 extends: NodeList<'a>,
                 body: Option<&'a Node<'a>>,
             },
-            TypeAlias {
+            TypeAlias[Declaration] {
                 id: &'a Node<'a>,
                 type_parameters: Option<&'a Node<'a>>,
                 right: &'a Node<'a>,
             },
-            OpaqueType {
+            OpaqueType[Declaration] {
                 id: &'a Node<'a>,
                 type_parameters: Option<&'a Node<'a>>,
                 impltype: &'a Node<'a>,
                 supertype: Option<&'a Node<'a>>,
             },
-            InterfaceDeclaration {
+            InterfaceDeclaration[Declaration] {
                 id: &'a Node<'a>,
                 type_parameters: Option<&'a Node<'a>>,
                 extends: NodeList<'a>,
                 body: &'a Node<'a>,
             },
-            DeclareTypeAlias {
+            DeclareTypeAlias[Declaration] {
                 id: &'a Node<'a>,
                 type_parameters: Option<&'a Node<'a>>,
                 right: &'a Node<'a>,
             },
-            DeclareOpaqueType {
+            DeclareOpaqueType[Declaration] {
                 id: &'a Node<'a>,
                 type_parameters: Option<&'a Node<'a>>,
                 impltype: Option<&'a Node<'a>>,
                 supertype: Option<&'a Node<'a>>,
             },
-            DeclareInterface {
+            DeclareInterface[Declaration] {
                 id: &'a Node<'a>,
                 type_parameters: Option<&'a Node<'a>>,
                 extends: NodeList<'a>,
                 body: &'a Node<'a>,
             },
-            DeclareClass {
+            DeclareClass[Declaration] {
                 id: &'a Node<'a>,
                 type_parameters: Option<&'a Node<'a>>,
                 extends: NodeList<'a>,
@@ -553,28 +554,28 @@ macro_rules! nodekind_defs {
                 mixins: NodeList<'a>,
                 body: &'a Node<'a>,
             },
-            DeclareFunction {
+            DeclareFunction[Declaration] {
                 id: &'a Node<'a>,
                 predicate: Option<&'a Node<'a>>,
             },
-            DeclareVariable {
+            DeclareVariable[Declaration] {
                 id: &'a Node<'a>,
             },
-            DeclareExportDeclaration {
+            DeclareExportDeclaration[Declaration] {
                 declaration: Option<&'a Node<'a>>,
                 specifiers: NodeList<'a>,
                 source: Option<&'a Node<'a>>,
                 default: bool,
             },
-            DeclareExportAllDeclaration {
+            DeclareExportAllDeclaration[Declaration] {
                 source: &'a Node<'a>,
             },
-            DeclareModule {
+            DeclareModule[Declaration] {
                 id: &'a Node<'a>,
                 body: &'a Node<'a>,
                 kind: NodeLabel,
             },
-            DeclareModuleExports {
+            DeclareModuleExports[Declaration] {
                 type_annotation: &'a Node<'a>,
             },
             InterfaceExtends {
@@ -642,7 +643,7 @@ macro_rules! nodekind_defs {
             TypeParameterInstantiation {
                 params: NodeList<'a>,
             },
-            TypeCastExpression {
+            TypeCastExpression[Expression] {
                 expression: &'a Node<'a>,
                 type_annotation: &'a Node<'a>,
             },
@@ -650,7 +651,7 @@ macro_rules! nodekind_defs {
             DeclaredPredicate {
                 value: &'a Node<'a>,
             },
-            EnumDeclaration {
+            EnumDeclaration[Declaration] {
                 id: &'a Node<'a>,
                 body: &'a Node<'a>,
             },
@@ -734,11 +735,11 @@ macro_rules! nodekind_defs {
             TSTupleType {
                 element_types: NodeList<'a>,
             },
-            TSTypeAssertion {
+            TSTypeAssertion[Expression] {
                 type_annotation: &'a Node<'a>,
                 expression: &'a Node<'a>,
             },
-            TSAsExpression {
+            TSAsExpression[Expression] {
                 expression: &'a Node<'a>,
                 type_annotation: &'a Node<'a>,
             },
@@ -749,12 +750,12 @@ macro_rules! nodekind_defs {
                 is_static: bool,
                 export: bool,
             },
-            TSTypeAliasDeclaration {
+            TSTypeAliasDeclaration[Declaration] {
                 id: &'a Node<'a>,
                 type_parameters: Option<&'a Node<'a>>,
                 type_annotation: &'a Node<'a>,
             },
-            TSInterfaceDeclaration {
+            TSInterfaceDeclaration[Declaration] {
                 id: &'a Node<'a>,
                 body: &'a Node<'a>,
                 extends: NodeList<'a>,
@@ -767,7 +768,7 @@ macro_rules! nodekind_defs {
             TSInterfaceBody {
                 body: NodeList<'a>,
             },
-            TSEnumDeclaration {
+            TSEnumDeclaration[Declaration] {
                 id: &'a Node<'a>,
                 members: NodeList<'a>,
             },
@@ -775,7 +776,7 @@ macro_rules! nodekind_defs {
                 id: &'a Node<'a>,
                 initializer: Option<&'a Node<'a>>,
             },
-            TSModuleDeclaration {
+            TSModuleDeclaration[Declaration] {
                 id: &'a Node<'a>,
                 body: &'a Node<'a>,
             },
@@ -6,16 +6,41 @@
  */
 
 use super::{
-    AssignmentExpressionOperator, BinaryExpressionOperator, Context, ExportKind, GCContext,
-    ImportKind, LogicalExpressionOperator, MethodDefinitionKind, Node, NodeLabel, NodeList,
-    NodePtr, NodeString, PropertyKind, UnaryExpressionOperator, UpdateExpressionOperator,
-    VariableDeclarationKind,
+    AssignmentExpressionOperator, BinaryExpressionOperator, Comment, CommentKind, Context,
+    ExportKind, GCContext, ImportKind, LogicalExpressionOperator, MethodDefinitionKind, Node,
+    NodeLabel, NodeList, NodePtr, NodeString, PropertyKind, SourceRange, UnaryExpressionOperator,
+    UpdateExpressionOperator, VariableDeclarationKind,
 };
+use std::collections::HashSet;
 use std::io::{self, Write};
+use std::ops::{Deref, DerefMut};
 use support::{case::ascii_snake_to_camel, json::*};
 
 pub use support::json::Pretty;
 
+/// Wraps the emitter with the state needed while dumping the AST.
+struct Dumper<W: Write> {
+    emitter: JSONEmitter<W>,
+
+    /// Ranges of the nodes whose comments have been dumped. Nested nodes may share a range,
+    /// and the comments only belong to the outermost one.
+    dumped_comments: HashSet<SourceRange>,
+}
+
+impl<W: Write> Deref for Dumper<W> {
+    type Target = JSONEmitter<W>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.emitter
+    }
+}
+
+impl<W: Write> DerefMut for Dumper<W> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.emitter
+    }
+}
+
 /// Generate boilerplate code for the `NodeKind` enum.
 macro_rules! gen_dumper {
     ($name:ident {
@@ -33,12 +58,25 @@ macro_rules! gen_dumper {
         fn dump_node<'gc, W: Write>(
             ctx: &'gc GCContext,
             node: &'gc Node<'gc>,
-            emitter: &mut JSONEmitter<W>,
+            emitter: &mut Dumper<W>,
         ) {
             use crate::ast::*;
             emitter.open_dict();
             emitter.emit_key("type");
             emitter.emit_string(node.name());
+            // Claim the comments before dumping the children, which may share the range.
+            // A program may share its range with its only statement, which owns the leading
+            // and trailing comments.
+            let comments = match node {
+                Node::Program(_) => ctx.ctx().comments().get(node.range()).map(|c| NodeComments {
+                    inner: c.inner.clone(),
+                    ..Default::default()
+                }),
+                _ if emitter.dumped_comments.insert(*node.range()) => {
+                    ctx.ctx().comments().get(node.range()).cloned()
+                }
+                _ => None,
+            };
             match node {
                 $(
                     Node::$kind($kind {$($($field,)*)? .. }) => {
@@ -49,6 +87,11 @@ macro_rules! gen_dumper {
                     }
                 ),*
             }
+            if let Some(comments) = comments {
+                dump_comments("leadingComments", &comments.leading, emitter);
+                dump_comments("trailingComments", &comments.trailing, emitter);
+                dump_comments("innerComments", &comments.inner, emitter);
+            }
             emitter.close_dict();
         }
     }
@@ -56,96 +99,119 @@ macro_rules! gen_dumper {
 
 nodekind_defs! { gen_dumper }
 
+/// Dump `comments` as an array under `key`, unless there are none.
+/// Comments use the Babel representation.
+fn dump_comments<W: Write>(key: &str, comments: &[Comment], emitter: &mut Dumper<W>) {
+    if comments.is_empty() {
+        return;
+    }
+    emitter.emit_key(key);
+    emitter.open_array();
+    for comment in comments {
+        emitter.open_dict();
+        emitter.emit_key("type");
+        emitter.emit_string(match comment.kind {
+            CommentKind::Line => "CommentLine",
+            CommentKind::Block => "CommentBlock",
+            CommentKind::Hashbang => "InterpreterDirective",
+        });
+        emitter.emit_key("value");
+        emitter.emit_string(&comment.text);
+        emitter.close_dict();
+    }
+    emitter.close_array();
+}
+
 trait DumpChild<'gc> {
-    fn dump<W: Write>(&self, ctx: &'gc GCContext, emitter: &mut JSONEmitter<W>);
+    fn dump<W: Write>(&self, ctx: &'gc GCContext, emitter: &mut Dumper<W>);
 }
 
 impl<'gc> DumpChild<'gc> for f64 {
-    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut JSONEmitter<W>) {
+    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut Dumper<W>) {
         emitter.emit_number(*self);
     }
 }
 
 impl<'gc> DumpChild<'gc> for bool {
-    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut JSONEmitter<W>) {
+    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut Dumper<W>) {
         emitter.emit_bool(*self);
     }
 }
 
 impl<'gc> DumpChild<'gc> for NodeLabel {
-    fn dump<W: Write>(&self, ctx: &'gc GCContext, emitter: &mut JSONEmitter<W>) {
+    fn dump<W: Write>(&self, ctx: &'gc GCContext, emitter: &mut Dumper<W>) {
         emitter.emit_string(ctx.str(*self));
     }
 }
 
 impl<'gc> DumpChild<'gc> for UnaryExpressionOperator {
-    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut JSONEmitter<W>) {
+    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut Dumper<W>) {
         emitter.emit_string(self.as_str());
     }
 }
 
 impl<'gc> DumpChild<'gc> for BinaryExpressionOperator {
-    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut JSONEmitter<W>) {
+    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut Dumper<W>) {
         emitter.emit_string(self.as_str());
     }
 }
 
 impl<'gc> DumpChild<'gc> for LogicalExpressionOperator {
-    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut JSONEmitter<W>) {
+    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut Dumper<W>) {
         emitter.emit_string(self.as_str());
     }
 }
 
 impl<'gc> DumpChild<'gc> for UpdateExpressionOperator {
-    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut JSONEmitter<W>) {
+    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut Dumper<W>) {
         emitter.emit_string(self.as_str());
     }
 }
 
 impl<'gc> DumpChild<'gc> for AssignmentExpressionOperator {
-    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut JSONEmitter<W>) {
+    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut Dumper<W>) {
         emitter.emit_string(self.as_str());
     }
 }
 
 impl<'gc> DumpChild<'gc> for VariableDeclarationKind {
-    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut JSONEmitter<W>) {
+    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut Dumper<W>) {
         emitter.emit_string(self.as_str());
     }
 }
 
 impl<'gc> DumpChild<'gc> for PropertyKind {
-    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut JSONEmitter<W>) {
+    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut Dumper<W>) {
         emitter.emit_string(self.as_str());
     }
 }
 
 impl<'gc> DumpChild<'gc> for MethodDefinitionKind {
-    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut JSONEmitter<W>) {
+    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut Dumper<W>) {
         emitter.emit_string(self.as_str());
     }
 }
 
 impl<'gc> DumpChild<'gc> for ImportKind {
-    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut JSONEmitter<W>) {
+    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut Dumper<W>) {
         emitter.emit_string(self.as_str());
     }
 }
 
 impl<'gc> DumpChild<'gc> for ExportKind {
-    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut JSONEmitter<W>) {
+    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut Dumper<W>) {
         emitter.emit_string(self.as_str());
     }
 }
 
 impl<'gc> DumpChild<'gc> for NodeString {
-    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut JSONEmitter<W>) {
+    fn dump<W: Write>(&self, _ctx: &'gc GCContext, emitter: &mut Dumper<W>) {
         emitter.emit_string_literal(&self.str);
     }
 }
 
 impl<'gc, T: DumpChild<'gc>> DumpChild<'gc> for Option<T> {
-    fn dump<W: Write>(&self, ctx: &'gc GCContext, emitter: &mut JSONEmitter<W>) {
+    fn dump<W: Write>(&self, ctx: &'gc GCContext, emitter: &mut Dumper<W>) {
         match self {
             None => emitter.emit_null(),
             Some(t) => t.dump(ctx, emitter),
@@ -154,13 +220,13 @@ impl<'gc, T: DumpChild<'gc>> DumpChild<'gc> for Option<T> {
 }
 
 impl<'gc> DumpChild<'gc> for &'gc Node<'gc> {
-    fn dump<W: Write>(&self, ctx: &'gc GCContext, emitter: &mut JSONEmitter<W>) {
+    fn dump<W: Write>(&self, ctx: &'gc GCContext, emitter: &mut Dumper<W>) {
         dump_node(ctx, self, emitter);
     }
 }
 
 impl<'gc> DumpChild<'gc> for NodeList<'gc> {
-    fn dump<W: Write>(&self, ctx: &'gc GCContext, emitter: &mut JSONEmitter<W>) {
+    fn dump<W: Write>(&self, ctx: &'gc GCContext, emitter: &mut Dumper<W>) {
         emitter.open_array();
         for &elem in self {
             dump_node(ctx, elem, emitter);
@@ -176,7 +242,10 @@ pub fn dump_json<W: Write>(
     pretty: Pretty,
 ) -> io::Result<()> {
     let gc = GCContext::new(ctx);
-    let mut emitter = JSONEmitter::new(writer, pretty);
-    dump_node(&gc, root.node(&gc), &mut emitter);
-    emitter.end()
+    let mut dumper = Dumper {
+        emitter: JSONEmitter::new(writer, pretty),
+        dumped_comments: HashSet::new(),
+    };
+    dump_node(&gc, root.node(&gc), &mut dumper);
+    dumper.emitter.end()
 }
@@ -30,7 +30,7 @@ use crate::source_manager::{SourceId, SourceManager};
 use libc::c_void;
 use memoffset::offset_of;
 use std::{
-    cell::{Cell, UnsafeCell},
+    cell::{Cell, Ref, RefCell, RefMut, UnsafeCell},
     fmt,
     marker::PhantomData,
     pin::Pin,
@@ -43,6 +43,7 @@ use thiserror::Error;
 #[macro_use]
 mod def;
 mod atom_table;
+mod comments;
 mod dump;
 mod kind;
 mod validate;
@@ -54,6 +55,7 @@ pub use kind::*;
 pub use validate::{validate_tree, validate_tree_pure, TreeValidationError, ValidationError};
 
 pub use atom_table::{Atom, AtomTable, INVALID_ATOM};
+pub use comments::{Comment, CommentKind, CommentTable, NodeComments};
 use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 
@@ -149,6 +151,10 @@ pub struct Context<'ast> {
     /// Source manager of this context.
     source_mgr: SourceManager,
 
+    /// Comments attached to the nodes of this context.
+    /// Stored in a `RefCell` so they can be attached while nodes are being created.
+    comments: RefCell<CommentTable>,
+
     /// `true` if `1` indicates an entry is marked, `false` if `0` indicates an entry is marked.
     /// Flipped every time GC occurs.
     markbit_marked: bool,
@@ -181,6 +187,7 @@ impl<'ast> Context<'ast> {
             })),
             atom_tab: Default::default(),
             source_mgr: Default::default(),
+            comments: Default::default(),
             next_chunk_capacity: Cell::new(MIN_CHUNK_CAPACITY),
             markbit_marked: true,
             strict_mode: false,
@@ -264,6 +271,18 @@ impl<'ast> Context<'ast> {
         &mut self.source_mgr
     }
 
+    /// Return the table of comments attached to nodes.
+    pub fn comments(&self) -> Ref<'_, CommentTable> {
+        self.comments.borrow()
+    }
+
+    /// Return a mutable reference to the table of comments attached to nodes.
+    /// Note that it takes immutable `self`, so comments can be attached while a
+    /// `GCContext` is active.
+    pub fn comments_mut(&self) -> RefMut<'_, CommentTable> {
+        self.comments.borrow_mut()
+    }
+
     /// Return true if strict mode has been forced globally.
     pub fn strict_mode(&self) -> bool {
         self.strict_mode
@@ -470,7 +489,7 @@ impl<'gc> NodeRef<'gc> {
 
 impl<'gc> PartialEq for NodeRef<'gc> {
     fn eq(&self, other: &Self) -> bool {
-        std::ptr::eq(self, other)
+        std::ptr::eq(self.0, other.0)
     }
 }
 
@@ -653,7 +672,7 @@ pub trait VisitorMut<'gc> {
 /// A source range within a single JS file.
 ///
 /// Represented as a closed interval: [start, end].
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct SourceRange {
     /// Index of the file this range is in.
     pub file: SourceId,
@@ -719,7 +738,8 @@ impl Default for TemplateMetadata<'_> {
 }
 
 /// Line and column of a file.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// Locations are ordered by line, then by column.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct SourceLoc {
     /// 1-based line number.
     pub line: u32,
@@ -99,6 +99,9 @@ impl<'gc> ValidChild<'gc> for &Node<'gc> {
         node: &'gc Node<'gc>,
         constraints: &[NodeVariant],
     ) -> Result<(), ValidationError> {
+        if constraints.is_empty() {
+            return Ok(());
+        }
         for &constraint in constraints {
             if instanceof(self.variant(), constraint) {
                 return Ok(());
@@ -119,6 +122,9 @@ impl<'gc> ValidChild<'gc> for NodeList<'gc> {
         node: &'gc Node<'gc>,
         constraints: &[NodeVariant],
     ) -> Result<(), ValidationError> {
+        if constraints.is_empty() {
+            return Ok(());
+        }
         'elems: for elem in self {
             for &constraint in constraints {
                 if instanceof(elem.variant(), constraint) {
@@ -174,7 +180,11 @@ fn validate_custom<'gc>(ctx: &'gc GCContext, node: &'gc Node<'gc>) -> Result<(),
             if *computed {
                 property.validate_child(ctx, node, &[NodeVariant::Expression])?;
             } else {
-                property.validate_child(ctx, node, &[NodeVariant::Identifier])?;
+                property.validate_child(
+                    ctx,
+                    node,
+                    &[NodeVariant::Identifier, NodeVariant::PrivateName],
+                )?;
             }
         }
 
@@ -6,8 +6,10 @@
  */
 
 use crate::ast::*;
+use crate::source_manager::SourceId;
 use sourcemap::{RawToken, SourceMap, SourceMapBuilder};
 use std::{
+    collections::{HashMap, HashSet},
     fmt,
     io::{self, BufWriter, Write},
 };
@@ -22,16 +24,73 @@ pub enum Pretty {
     Yes,
 }
 
+/// Which comments of the AST to print.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Comments {
+    /// Print all comments in their original positions.
+    All,
+    /// Don't print any comments.
+    None,
+    /// Print only legal comments (see [`Comment::is_legal`]) and the hashbang in their
+    /// original positions.
+    Legal,
+    /// Print only legal comments and the hashbang, all together at the top of the output.
+    /// Duplicate comments, e.g. the same license in several files, are printed once.
+    LegalHoisted,
+}
+
+/// Options for JS generation.
+#[derive(Debug, Clone)]
+pub struct Opt {
+    /// Whether to pretty-print the generated JS.
+    pub pretty: Pretty,
+
+    /// Name of the generated file, recorded as `file` in the source map.
+    pub file: Option<String>,
+
+    /// Whether to embed the contents of the sources in the source map.
+    pub sources_content: bool,
+
+    /// Which comments attached to the AST to print.
+    pub comments: Comments,
+}
+
+impl Default for Opt {
+    fn default() -> Self {
+        Opt {
+            pretty: Pretty::Yes,
+            file: None,
+            sources_content: false,
+            comments: Comments::All,
+        }
+    }
+}
+
 /// Generate JS for `root` and print it to `out`.
-/// FIXME: This currently only returns an empty SourceMap.
+/// Return a source map describing the mapping from the generated JS to the
+/// sources registered in the source manager.
 pub fn generate<W: Write>(
     out: W,
     ctx: &mut Context,
     root: &NodePtr,
-    pretty: Pretty,
+    opt: &Opt,
 ) -> io::Result<SourceMap> {
     let gc = GCContext::new(ctx);
-    GenJS::gen_root(out, &gc, root.node(&gc), pretty)
+    GenJS::gen_root(out, &gc, root.node(&gc), opt)
+}
+
+/// Return true if `comment` is printed when only legal comments are requested.
+/// The hashbang is required to run the output, so it is preserved too.
+fn is_preserved_comment(comment: &Comment) -> bool {
+    comment.is_legal() || comment.kind == CommentKind::Hashbang
+}
+
+/// Return true if any of `comments` contains a line break when printed inline.
+fn has_line_break(comments: &[Comment]) -> bool {
+    comments.iter().any(|comment| {
+        comment.kind == CommentKind::Block
+            && comment.text.contains(['\n', '\r', '\u{2028}', '\u{2029}'])
+    })
 }
 
 /// Associativity direction.
@@ -169,6 +228,30 @@ struct GenJS<W: Write> {
     /// Build a source map as we go along.
     sourcemap: SourceMapBuilder,
 
+    /// Index in the source map of every source referenced by the AST.
+    source_ids: HashMap<SourceId, u32>,
+
+    /// Offset of the start of every line of every source referenced by the AST, used to find
+    /// the original names of renamed identifiers.
+    line_offsets: HashMap<SourceId, Vec<usize>>,
+
+    /// Which comments attached to the AST to print.
+    comments: Comments,
+
+    /// Ranges of the nodes whose leading and trailing comments have been printed.
+    /// Nested nodes may share a range, and the comments only belong to the outermost one.
+    printed_comments: HashSet<SourceRange>,
+
+    /// Ranges of the nodes whose inner comments have been printed.
+    printed_inner_comments: HashSet<SourceRange>,
+
+    /// Whether a line comment has been printed, so a newline must precede any further output.
+    pending_newline: bool,
+
+    /// Whether the last token was `return`, `throw` or `yield`, which can't be followed by a
+    /// line break.
+    no_line_break: bool,
+
     /// Some(err) if an error has occurred when writing, else None.
     error: Option<io::Error>,
 }
@@ -200,19 +283,29 @@ impl<W: Write> GenJS<W> {
         writer: W,
         ctx: &'gc GCContext,
         root: &'gc Node<'gc>,
-        pretty: Pretty,
+        opt: &Opt,
     ) -> io::Result<SourceMap> {
         let mut gen_js = GenJS {
             out: BufWriter::new(writer),
-            pretty,
+            pretty: opt.pretty,
             indent_step: 2,
             indent: 0,
             position: SourceLoc { line: 1, col: 1 },
             cur_token: None,
-            // FIXME: Pass in file name here.
-            sourcemap: SourceMapBuilder::new(None),
+            sourcemap: SourceMapBuilder::new(opt.file.as_deref()),
+            source_ids: HashMap::new(),
+            line_offsets: HashMap::new(),
+            comments: opt.comments,
+            printed_comments: HashSet::new(),
+            printed_inner_comments: HashSet::new(),
+            pending_newline: false,
+            no_line_break: false,
             error: None,
         };
+        gen_js.register_sources(ctx, root, opt.sources_content);
+        if gen_js.comments == Comments::LegalHoisted {
+            gen_js.print_hoisted_comments(ctx, root);
+        }
         root.visit(ctx, &mut gen_js, None);
         gen_js.force_newline();
         gen_js.flush_cur_token();
@@ -230,6 +323,8 @@ impl<W: Write> GenJS<W> {
     /// Used via the `out!` macro.
     /// The output must be ASCII and contain no newlines.
     fn write_ascii(&mut self, args: fmt::Arguments<'_>) {
+        self.flush_pending_newline();
+        self.no_line_break = false;
         if self.error.is_none() {
             let buf = format!("{}", args);
             debug_assert!(buf.is_ascii(), "Output must be ASCII");
@@ -247,6 +342,8 @@ impl<W: Write> GenJS<W> {
     /// If we have seen any errors, do nothing.
     fn write_char(&mut self, ch: char, dst: &mut [u8]) {
         debug_assert!(ch != '\n', "Output must not contain newlines");
+        self.flush_pending_newline();
+        self.no_line_break = false;
         if self.error.is_none() {
             if let Err(e) = self.out.write_all(ch.encode_utf8(dst).as_bytes()) {
                 self.error = Some(e);
@@ -263,6 +360,8 @@ impl<W: Write> GenJS<W> {
             !s.chars().any(|c| c == '\n'),
             "Output must not contain newlines"
         );
+        self.flush_pending_newline();
+        self.no_line_break = false;
         if self.error.is_none() {
             if let Err(e) = self.out.write_all(s.as_bytes()) {
                 self.error = Some(e);
@@ -357,6 +456,7 @@ impl<W: Write> GenJS<W> {
                     && return_type.is_none()
                     && predicate.is_none()
                     && (*expression || self.pretty == Pretty::No)
+                    && !self.has_line_break_after(ctx, params[0])
                 {
                     if need_sep {
                         out!(self, " ");
@@ -482,7 +582,7 @@ impl<W: Write> GenJS<W> {
             Node::EmptyStatement(_) => {}
 
             Node::BlockStatement(BlockStatement { metadata: _, body }) => {
-                if body.is_empty() {
+                if body.is_empty() && !self.has_inner_comments(ctx, node) {
                     out!(self, "{{}}");
                 } else {
                     out!(self, "{{");
@@ -514,7 +614,9 @@ impl<W: Write> GenJS<W> {
                 metadata: _,
                 argument,
             }) => {
-                out_token!(self, node, "throw ");
+                out_token!(self, node, "throw");
+                self.keyword_space(ctx, argument, node);
+                self.no_line_break = true;
                 argument.visit(ctx, self, Some(node));
             }
             Node::ReturnStatement(ReturnStatement {
@@ -523,7 +625,8 @@ impl<W: Write> GenJS<W> {
             }) => {
                 out_token!(self, node, "return");
                 if let Some(argument) = argument {
-                    out!(self, " ");
+                    self.keyword_space(ctx, argument, node);
+                    self.no_line_break = true;
                     argument.visit(ctx, self, Some(node));
                 }
             }
@@ -566,7 +669,8 @@ impl<W: Write> GenJS<W> {
             }) => {
                 match test {
                     Some(test) => {
-                        out_token!(self, node, "case ");
+                        out_token!(self, node, "case");
+                        self.keyword_space(ctx, test, node);
                         test.visit(ctx, self, Some(node));
                     }
                     None => {
@@ -659,7 +763,7 @@ impl<W: Write> GenJS<W> {
                 out!(self, "\"");
             }
             Node::NumericLiteral(NumericLiteral { metadata: _, value }) => {
-                out_token!(self, node, "{}", convert::number_to_string(*value));
+                out_token!(self, node, "{}", self.number_to_string(*value));
             }
             Node::RegExpLiteral(RegExpLiteral {
                 metadata: _,
@@ -684,7 +788,10 @@ impl<W: Write> GenJS<W> {
                 metadata: _,
                 expressions,
             }) => {
-                out!(self, "(");
+                let parens = self.sequence_has_parens(parent);
+                if parens {
+                    out!(self, "(");
+                }
                 for (i, expr) in expressions.iter().enumerate() {
                     if i > 0 {
                         self.comma();
@@ -700,7 +807,9 @@ impl<W: Write> GenJS<W> {
                         },
                     );
                 }
-                out!(self, ")");
+                if parens {
+                    out!(self, ")");
+                }
             }
 
             Node::ObjectExpression(ObjectExpression {
@@ -768,8 +877,9 @@ impl<W: Write> GenJS<W> {
                 if *delegate {
                     out!(self, "*");
                     self.space(ForceSpace::No);
-                } else if argument.is_some() {
-                    out!(self, " ");
+                } else if let Some(argument) = argument {
+                    self.keyword_space(ctx, argument, node);
+                    self.no_line_break = true;
                 }
                 self.print_child(ctx, *argument, node, ChildPos::Right);
             }
@@ -777,7 +887,8 @@ impl<W: Write> GenJS<W> {
                 metadata: _,
                 argument,
             }) => {
-                out!(self, "await ");
+                out!(self, "await");
+                self.keyword_space(ctx, argument, node);
                 self.print_child(ctx, Some(*argument), node, ChildPos::Right);
             }
 
@@ -858,7 +969,7 @@ impl<W: Write> GenJS<W> {
                 if *prefix {
                     out!(self, "{}", operator.as_str());
                     if ident {
-                        out!(self, " ");
+                        self.keyword_space(ctx, argument, node);
                     }
                     self.print_child(ctx, Some(*argument), node, ChildPos::Right);
                 } else {
@@ -878,6 +989,11 @@ impl<W: Write> GenJS<W> {
                 if *prefix {
                     out!(self, "{}", operator.as_str());
                     self.print_child(ctx, Some(*argument), node, ChildPos::Right);
+                } else if self.has_line_break_after(ctx, argument) {
+                    // A line break before `++` or `--` would end the statement.
+                    out!(self, "(");
+                    argument.visit(ctx, self, Some(node));
+                    out!(self, "){}", operator.as_str());
                 } else {
                     self.print_child(ctx, Some(*argument), node, ChildPos::Left);
                     out!(self, "{}", operator.as_str());
@@ -892,7 +1008,7 @@ impl<W: Write> GenJS<W> {
                 match object {
                     Node::NumericLiteral(NumericLiteral { value, .. }) => {
                         // Account for possible `50..toString()`.
-                        let string = convert::number_to_string(*value);
+                        let string = self.number_to_string(*value);
                         // If there is an `e` or a decimal point, no need for an extra `.`.
                         let suffix = string.find::<&[char]>(&['E', 'e', '.']).map_or(".", |_| "");
                         out_token!(self, node, "{}{}", string, suffix);
@@ -982,8 +1098,10 @@ impl<W: Write> GenJS<W> {
                 type_annotation,
                 optional,
             }) => {
-                self.add_segment(node);
-                self.write_utf8(ctx.str(*name).as_ref());
+                let name = ctx.str(*name);
+                let exact = type_annotation.is_none() && !*optional;
+                self.add_name_segment(node, self.original_name(ctx, node, name, exact));
+                self.write_utf8(name);
                 if *optional {
                     out!(self, "?");
                 }
@@ -993,10 +1111,18 @@ impl<W: Write> GenJS<W> {
                     type_annotation.visit(ctx, self, Some(node));
                 }
             }
-            Node::PrivateName(PrivateName { metadata: _, id }) => {
-                out_token!(self, node, "#");
-                id.visit(ctx, self, Some(node));
-            }
+            Node::PrivateName(PrivateName { metadata: _, id }) => match id {
+                Node::Identifier(Identifier { name, .. }) => {
+                    let name = ctx.str(*name);
+                    self.add_name_segment(node, name);
+                    out!(self, "#");
+                    self.write_utf8(name);
+                }
+                _ => {
+                    out_token!(self, node, "#");
+                    id.visit(ctx, self, Some(node));
+                }
+            },
             Node::MetaProperty(MetaProperty {
                 metadata: _,
                 meta,
@@ -1249,14 +1375,25 @@ impl<W: Write> GenJS<W> {
             }
 
             Node::ClassBody(ClassBody { metadata: _, body }) => {
-                if body.is_empty() {
+                if body.is_empty() && !self.has_inner_comments(ctx, node) {
                     out!(self, "{{}}");
                 } else {
                     out!(self, "{{");
                     self.inc_indent();
                     self.newline();
                     for prop in body {
+                        let comments = self.take_comments(ctx, prop);
+                        if let Some((leading, _)) = &comments {
+                            self.print_leading_comments(leading, prop);
+                        }
                         prop.visit(ctx, self, Some(node));
+                        if let Some((_, trailing)) = &comments {
+                            self.print_trailing_comments(trailing, prop);
+                        }
+                        self.newline();
+                    }
+                    if body.is_empty() {
+                        self.print_inner_comments(ctx, node, true);
                         self.newline();
                     }
                     out!(self, "}}");
@@ -2645,6 +2782,55 @@ impl<W: Write> GenJS<W> {
         )
     }
 
+    /// Return whether a sequence expression prints its own parentheses as a child of `parent`.
+    /// Statements may contain an unparenthesized sequence, and expressions with a precedence
+    /// parenthesize it like their other operands of lower precedence.
+    fn sequence_has_parens(&self, parent: Option<&Node>) -> bool {
+        match parent {
+            Some(
+                Node::ExpressionStatement(_) | Node::ReturnStatement(_) | Node::ThrowStatement(_),
+            ) => false,
+            // The super class is printed without checking its precedence.
+            Some(Node::ClassExpression(_)) => true,
+            Some(parent) => self.get_precedence(parent).0 == precedence::ALWAYS_PAREN,
+            None => true,
+        }
+    }
+
+    /// Print the space between a keyword and the expression `child` of `parent` following it,
+    /// unless minifying and the expression starts with a string, template, array, object or
+    /// parenthesis, e.g. `return"x"`.
+    fn keyword_space<'gc>(
+        &mut self,
+        ctx: &'gc GCContext,
+        child: &'gc Node<'gc>,
+        parent: &'gc Node<'gc>,
+    ) {
+        let is_sequence = matches!(child, Node::SequenceExpression(_));
+        let parens = match parent {
+            // Statements are printed without parens around their expressions.
+            Node::ReturnStatement(_) | Node::ThrowStatement(_) => false,
+            Node::SwitchCase(_) => is_sequence,
+            _ => {
+                is_sequence
+                    || self.need_parens(ctx, parent, child, ChildPos::Right) == NeedParens::Yes
+            }
+        };
+        let punctuated = parens
+            || self.expr_starts_with(ctx, child, None, true, |node| {
+                matches!(
+                    node,
+                    Node::StringLiteral(_)
+                        | Node::TemplateLiteral(_)
+                        | Node::ArrayExpression(_)
+                        | Node::ObjectExpression(_)
+                )
+            });
+        if self.pretty == Pretty::Yes || !punctuated {
+            out!(self, " ");
+        }
+    }
+
     /// Print a ' ' if forced by ForceSpace::Yes or pretty mode.
     fn space(&mut self, force: ForceSpace) {
         if self.pretty == Pretty::Yes || force == ForceSpace::Yes {
@@ -2667,6 +2853,7 @@ impl<W: Write> GenJS<W> {
 
     /// Print a newline without any indent after.
     fn force_newline_without_indent(&mut self) {
+        self.pending_newline = false;
         if self.error.is_none() {
             if let Err(e) = self.out.write(&[b'\n']) {
                 self.error = Some(e);
@@ -2676,6 +2863,272 @@ impl<W: Write> GenJS<W> {
         self.position.col = 1;
     }
 
+    /// If a line comment has been printed, terminate it with a newline before printing anything
+    /// else.
+    fn flush_pending_newline(&mut self) {
+        if self.pending_newline {
+            if self.pretty == Pretty::Yes {
+                self.force_newline();
+            } else {
+                self.force_newline_without_indent();
+            }
+        }
+    }
+
+    /// Return the leading and trailing comments of `node` if they haven't been printed yet,
+    /// and mark them as printed.
+    fn take_comments<'gc>(
+        &mut self,
+        ctx: &'gc GCContext,
+        node: &'gc Node<'gc>,
+    ) -> Option<(Vec<Comment>, Vec<Comment>)> {
+        let range = node.range();
+        // A program may share its range with its only statement, which owns the leading and
+        // trailing comments.
+        if !self.prints_comments() || !range.file.is_valid() || matches!(node, Node::Program(_)) {
+            return None;
+        }
+        let table = ctx.ctx().comments();
+        // Skip the lookups entirely when there are no comments, e.g. in generated code.
+        if table.is_empty() {
+            return None;
+        }
+        let comments = table.get(range)?;
+        if !self.printed_comments.insert(*range) {
+            return None;
+        }
+        Some((
+            self.filter_comments(&comments.leading),
+            self.filter_comments(&comments.trailing),
+        ))
+    }
+
+    /// Return the inner comments of `node` if they haven't been printed yet, and mark them
+    /// as printed.
+    fn take_inner_comments<'gc>(
+        &mut self,
+        ctx: &'gc GCContext,
+        node: &'gc Node<'gc>,
+    ) -> Vec<Comment> {
+        let range = node.range();
+        if !self.prints_comments()
+            || !range.file.is_valid()
+            || self.printed_inner_comments.contains(range)
+        {
+            return Vec::new();
+        }
+        match ctx.ctx().comments().get(range) {
+            Some(comments) => {
+                self.printed_inner_comments.insert(*range);
+                self.filter_comments(&comments.inner)
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Return true if `node` has inner comments which haven't been printed yet.
+    fn has_inner_comments<'gc>(&self, ctx: &'gc GCContext, node: &'gc Node<'gc>) -> bool {
+        let range = node.range();
+        self.prints_comments()
+            && !self.printed_inner_comments.contains(range)
+            && matches!(
+                ctx.ctx().comments().get(range),
+                Some(c) if c.inner.iter().any(|comment| self.keeps_comment(comment))
+            )
+    }
+
+    /// Return true if comments are printed in their original positions.
+    fn prints_comments(&self) -> bool {
+        matches!(self.comments, Comments::All | Comments::Legal)
+    }
+
+    /// Return true if `comment` is printed in its original position.
+    fn keeps_comment(&self, comment: &Comment) -> bool {
+        match self.comments {
+            Comments::All => true,
+            Comments::Legal => is_preserved_comment(comment),
+            Comments::None | Comments::LegalHoisted => false,
+        }
+    }
+
+    /// Return the comments of `comments` which are printed in their original positions.
+    fn filter_comments(&self, comments: &[Comment]) -> Vec<Comment> {
+        comments
+            .iter()
+            .filter(|comment| self.keeps_comment(comment))
+            .cloned()
+            .collect()
+    }
+
+    /// Print all legal comments attached to the nodes of `root` on their own lines,
+    /// in source order and without duplicates.
+    fn print_hoisted_comments<'gc>(&mut self, ctx: &'gc GCContext, root: &'gc Node<'gc>) {
+        struct LegalCollector {
+            ranges: HashSet<SourceRange>,
+            comments: Vec<Comment>,
+        }
+        impl<'gc> Visitor<'gc> for LegalCollector {
+            fn call(
+                &mut self,
+                ctx: &'gc GCContext,
+                node: &'gc Node<'gc>,
+                _parent: Option<&'gc Node<'gc>>,
+            ) {
+                if self.ranges.insert(*node.range()) {
+                    if let Some(comments) = ctx.ctx().comments().get(node.range()) {
+                        self.comments.extend(
+                            (comments.leading.iter())
+                                .chain(&comments.inner)
+                                .chain(&comments.trailing)
+                                .filter(|comment| is_preserved_comment(comment))
+                                .cloned(),
+                        );
+                    }
+                }
+                node.visit_children(ctx, self);
+            }
+        }
+
+        let mut collector = LegalCollector {
+            ranges: HashSet::new(),
+            comments: Vec::new(),
+        };
+        root.visit(ctx, &mut collector, None);
+        // Sources are registered in the order in which they first appear in the AST.
+        collector.comments.sort_by_key(|comment| {
+            (
+                self.source_ids.get(&comment.range.file),
+                comment.range.start,
+            )
+        });
+        let mut printed = HashSet::new();
+        for (i, comment) in collector.comments.into_iter().enumerate() {
+            // Only a hashbang at the very start of the output is valid.
+            if comment.kind == CommentKind::Hashbang && i > 0 {
+                continue;
+            }
+            if printed.insert(comment.text.clone()) {
+                self.print_comment(&comment);
+                self.force_newline();
+            }
+        }
+    }
+
+    /// Print `text`, which may contain newlines.
+    fn write_comment_text(&mut self, text: &str) {
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.force_newline_without_indent();
+            }
+            self.write_utf8(line);
+        }
+    }
+
+    /// Print `comment` in its original form. A line comment is terminated by a newline
+    /// before any further output.
+    fn print_comment(&mut self, comment: &Comment) {
+        match comment.kind {
+            CommentKind::Line => {
+                out!(self, "//");
+                self.write_comment_text(&comment.text);
+                self.pending_newline = true;
+            }
+            CommentKind::Block => {
+                out!(self, "/*");
+                self.write_comment_text(&comment.text);
+                out!(self, "*/");
+            }
+            CommentKind::Hashbang => {
+                out!(self, "#!");
+                self.write_comment_text(&comment.text);
+                self.pending_newline = true;
+            }
+        }
+    }
+
+    /// Return true if a comment printed at the end of `node` contains a line break, which
+    /// would end the statement before a following `++`, `--` or `=>`.
+    fn has_line_break_after<'gc>(&self, ctx: &'gc GCContext, node: &'gc Node<'gc>) -> bool {
+        let trailing = match ctx.ctx().comments().get(node.range()) {
+            Some(comments) if !self.printed_comments.contains(node.range()) => {
+                self.filter_comments(&comments.trailing)
+            }
+            _ => Vec::new(),
+        };
+        has_line_break(&trailing)
+            || matches!(
+                node,
+                Node::MemberExpression(MemberExpression {
+                    property,
+                    computed: false,
+                    ..
+                }) if self.has_line_break_after(ctx, property)
+            )
+    }
+
+    /// Print `comment` as a block comment, so it can be placed between any two tokens.
+    fn print_inline_comment(&mut self, comment: &Comment) {
+        match comment.kind {
+            CommentKind::Block => self.print_comment(comment),
+            CommentKind::Line | CommentKind::Hashbang => {
+                out!(self, "/*");
+                // The comment must not be terminated early.
+                self.write_comment_text(&comment.text.replace("*/", "* /"));
+                out!(self, "*/");
+            }
+        }
+    }
+
+    /// Print the comments preceding the statement or class member `stmt`. Comments which were on their own
+    /// line in the source are followed by a newline.
+    fn print_leading_comments(&mut self, comments: &[Comment], stmt: &Node) {
+        for (i, comment) in comments.iter().enumerate() {
+            self.print_comment(comment);
+            let next_line = comments
+                .get(i + 1)
+                .map_or(stmt.range().start.line, |next| next.range.start.line);
+            // Line comments are always followed by a newline.
+            if comment.kind == CommentKind::Block {
+                if comment.range.end.line == next_line {
+                    self.space(ForceSpace::No);
+                } else if self.pretty == Pretty::Yes {
+                    self.force_newline();
+                }
+            }
+        }
+    }
+
+    /// Print the comments following the statement or class member `stmt`. Comments which were on the same line
+    /// as the end of the statement in the source remain on the same line.
+    fn print_trailing_comments(&mut self, comments: &[Comment], stmt: &Node) {
+        let mut line = stmt.range().end.line;
+        for comment in comments {
+            if comment.range.start.line == line {
+                self.space(ForceSpace::No);
+            } else {
+                self.newline();
+            }
+            self.print_comment(comment);
+            line = comment.range.end.line;
+        }
+    }
+
+    /// Print the inner comments of `node`, each on its own line in pretty mode.
+    /// `first` indicates that nothing has been printed in `node` yet.
+    fn print_inner_comments<'gc>(
+        &mut self,
+        ctx: &'gc GCContext,
+        node: &'gc Node<'gc>,
+        first: bool,
+    ) {
+        for (i, comment) in self.take_inner_comments(ctx, node).iter().enumerate() {
+            if i > 0 || !first {
+                self.newline();
+            }
+            self.print_comment(comment);
+        }
+    }
+
     /// Print the child of a `parent` node at the position `child_pos`.
     fn print_child<'gc>(
         &mut self,
@@ -2695,7 +3148,7 @@ impl<W: Write> GenJS<W> {
     }
 
     /// Print one expression in a sequence separated by comma. It needs parens
-    /// if its precedence is <= comma.
+    /// if its precedence is <= comma, unless it is a spread element.
     fn print_comma_expression<'gc>(
         &mut self,
         ctx: &'gc GCContext,
@@ -2706,7 +3159,10 @@ impl<W: Write> GenJS<W> {
             ctx,
             child,
             parent,
-            NeedParens::from(self.get_precedence(child).0 <= precedence::SEQ),
+            NeedParens::from(
+                !matches!(child, Node::SpreadElement(_))
+                    && self.get_precedence(child).0 <= precedence::SEQ,
+            ),
         )
     }
 
@@ -2951,7 +3407,7 @@ impl<W: Write> GenJS<W> {
         parent: &'gc Node<'gc>,
     ) -> bool {
         if let Node::BlockStatement(BlockStatement { metadata: _, body }) = &node {
-            if body.is_empty() {
+            if body.is_empty() && !self.has_inner_comments(ctx, node) {
                 self.space(ForceSpace::No);
                 out!(self, "{{}}");
                 return true;
@@ -2999,6 +3455,7 @@ impl<W: Write> GenJS<W> {
             }
             self.visit_stmt_in_block(ctx, *stmt, parent);
         }
+        self.print_inner_comments(ctx, parent, list.is_empty());
     }
 
     fn visit_stmt_in_block<'gc>(
@@ -3007,10 +3464,17 @@ impl<W: Write> GenJS<W> {
         stmt: &'gc Node<'gc>,
         parent: &'gc Node<'gc>,
     ) {
+        let comments = self.take_comments(ctx, stmt);
+        if let Some((leading, _)) = &comments {
+            self.print_leading_comments(leading, stmt);
+        }
         stmt.visit(ctx, self, Some(parent));
         if !stmt_skip_semi(ctx, Some(stmt)) {
             out!(self, ";");
         }
+        if let Some((_, trailing)) = &comments {
+            self.print_trailing_comments(trailing, stmt);
+        }
     }
 
     /// Return the precedence and associativity of `node`.
@@ -3020,6 +3484,7 @@ impl<W: Write> GenJS<W> {
         use precedence::*;
         match &node {
             Node::Identifier(_)
+            | Node::PrivateName(_)
             | Node::NullLiteral(_)
             | Node::BooleanLiteral(_)
             | Node::StringLiteral(_)
@@ -3030,6 +3495,7 @@ impl<W: Write> GenJS<W> {
             | Node::ArrayExpression(_)
             | Node::ObjectExpression(_)
             | Node::ObjectPattern(_)
+            | Node::ArrayPattern(_)
             | Node::FunctionExpression(_)
             | Node::ClassExpression(_)
             | Node::TemplateLiteral(_) => (PRIMARY, Assoc::Ltr),
@@ -3141,6 +3607,14 @@ impl<W: Write> GenJS<W> {
             }
         } else if matches!(parent, Node::ExpressionStatement(_)) {
             // Expression statement like (function () {} + 1) needs parens.
+            // Sequences aren't parenthesized, so they start with their first expression.
+            let child = match child {
+                Node::SequenceExpression(SequenceExpression {
+                    metadata: _,
+                    expressions,
+                }) => expressions[0],
+                _ => child,
+            };
             return NeedParens::from(self.root_starts_with(ctx, child, |kind| -> bool {
                 matches!(
                     kind,
@@ -3227,19 +3701,22 @@ impl<W: Write> GenJS<W> {
         expr: &'gc Node<'gc>,
         pred: F,
     ) -> bool {
-        self.expr_starts_with(ctx, expr, None, pred)
+        self.expr_starts_with(ctx, expr, None, false, pred)
     }
 
+    /// Whether `expr` starts with a node matching `pred`, or with a parenthesized subexpression
+    /// if `parens` is true.
     fn expr_starts_with<'gc, F: Fn(&'gc Node<'gc>) -> bool>(
         &self,
         ctx: &'gc GCContext,
         expr: &'gc Node<'gc>,
         parent: Option<&'gc Node<'gc>>,
+        parens: bool,
         pred: F,
     ) -> bool {
         if let Some(parent) = parent {
             if self.need_parens(ctx, parent, expr, ChildPos::Left) == NeedParens::Yes {
-                return false;
+                return parens;
             }
         }
 
@@ -3254,36 +3731,36 @@ impl<W: Write> GenJS<W> {
                 metadata: _,
                 callee,
                 ..
-            }) => self.expr_starts_with(ctx, *callee, Some(expr), pred),
+            }) => self.expr_starts_with(ctx, *callee, Some(expr), parens, pred),
             Node::OptionalCallExpression(OptionalCallExpression {
                 metadata: _,
                 callee,
                 ..
-            }) => self.expr_starts_with(ctx, *callee, Some(expr), pred),
+            }) => self.expr_starts_with(ctx, *callee, Some(expr), parens, pred),
             Node::BinaryExpression(BinaryExpression {
                 metadata: _, left, ..
-            }) => self.expr_starts_with(ctx, *left, Some(expr), pred),
+            }) => self.expr_starts_with(ctx, *left, Some(expr), parens, pred),
             Node::LogicalExpression(LogicalExpression {
                 metadata: _, left, ..
-            }) => self.expr_starts_with(ctx, *left, Some(expr), pred),
+            }) => self.expr_starts_with(ctx, *left, Some(expr), parens, pred),
             Node::ConditionalExpression(ConditionalExpression {
                 metadata: _, test, ..
-            }) => self.expr_starts_with(ctx, *test, Some(expr), pred),
+            }) => self.expr_starts_with(ctx, *test, Some(expr), parens, pred),
             Node::AssignmentExpression(AssignmentExpression {
                 metadata: _, left, ..
-            }) => self.expr_starts_with(ctx, *left, Some(expr), pred),
+            }) => self.expr_starts_with(ctx, *left, Some(expr), parens, pred),
             Node::UpdateExpression(UpdateExpression {
                 metadata: _,
                 prefix,
                 argument,
                 ..
-            }) => !*prefix && self.expr_starts_with(ctx, *argument, Some(expr), pred),
+            }) => !*prefix && self.expr_starts_with(ctx, *argument, Some(expr), parens, pred),
             Node::UnaryExpression(UnaryExpression {
                 metadata: _,
                 prefix,
                 argument,
                 ..
-            }) => !*prefix && self.expr_starts_with(ctx, *argument, Some(expr), pred),
+            }) => !*prefix && self.expr_starts_with(ctx, *argument, Some(expr), parens, pred),
             Node::MemberExpression(MemberExpression {
                 metadata: _,
                 object,
@@ -3293,31 +3770,142 @@ impl<W: Write> GenJS<W> {
                 metadata: _,
                 object,
                 ..
-            }) => self.expr_starts_with(ctx, *object, Some(expr), pred),
+            }) => self.expr_starts_with(ctx, *object, Some(expr), parens, pred),
             Node::TaggedTemplateExpression(TaggedTemplateExpression {
                 metadata: _, tag, ..
-            }) => self.expr_starts_with(ctx, *tag, Some(expr), pred),
+            }) => self.expr_starts_with(ctx, *tag, Some(expr), parens, pred),
             _ => false,
         }
     }
 
     /// Adds the current location as a segment pointing to the start of `node`.
     fn add_segment(&mut self, node: &Node) {
+        // The segment must start after any newline terminating a line comment.
+        self.flush_pending_newline();
+        let range = node.range();
         // Convert from 1-indexed to 0-indexed as expected by source map.
         // Use `wrapping_sub` in case the line/col are invalid (0) to ensure
         // the overflow goes to `u32::MAX`.
         let new_token = Some(RawToken {
             dst_line: self.position.line.wrapping_sub(1),
             dst_col: self.position.col.wrapping_sub(1),
-            src_line: node.range().start.line.wrapping_sub(1),
-            src_col: node.range().start.col.wrapping_sub(1),
-            src_id: 0,
+            src_line: range.start.line.wrapping_sub(1),
+            src_col: range.start.col.wrapping_sub(1),
+            // Nodes without a valid location (e.g. synthesized by a pass) are
+            // mapped to no source.
+            src_id: self.source_ids.get(&range.file).copied().unwrap_or(!0),
             name_id: !0,
         });
         self.flush_cur_token();
         self.cur_token = new_token;
     }
 
+    /// Emit a source mapping token for `node` like `add_segment`, recording
+    /// `name` as the original name of the identifier at this location.
+    fn add_name_segment(&mut self, node: &Node, name: &str) {
+        self.add_segment(node);
+        if let Some(token) = &mut self.cur_token {
+            // Names are only meaningful for locations in a source.
+            if token.src_id != !0 {
+                token.name_id = self.sourcemap.add_name(name);
+            }
+        }
+    }
+
+    /// Convert the value of a numeric literal to a string.
+    /// Without pretty printing, the shortest representation is used.
+    fn number_to_string(&self, value: f64) -> String {
+        match self.pretty {
+            Pretty::Yes => convert::number_to_string(value),
+            Pretty::No => convert::number_to_shortest_string(value),
+        }
+    }
+
+    /// Return the name of the identifier `node` in the source, which differs from its current
+    /// `name` if it has been renamed, e.g. by minification.
+    /// If `exact` is true, the range of `node` must only contain the name.
+    fn original_name<'a>(
+        &self,
+        ctx: &'a GCContext,
+        node: &Node,
+        name: &'a str,
+        exact: bool,
+    ) -> &'a str {
+        let range = node.range();
+        let start = match self
+            .line_offsets
+            .get(&range.file)
+            .and_then(|offsets| offsets.get(range.start.line.wrapping_sub(1) as usize))
+        {
+            Some(offset) => offset + range.start.col as usize - 1,
+            None => return name,
+        };
+        let buf = ctx.sm().source_buffer(range.file).as_bytes();
+        let len = buf[start.min(buf.len())..]
+            .iter()
+            .take_while(|&&c| c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || c >= 0x80)
+            .count();
+        // Identifiers synthesized by a pass may be located at a different node.
+        let end = SourceLoc {
+            line: range.start.line,
+            col: range.start.col + len as u32 - 1,
+        };
+        if len == 0 || (exact && range.end != end) || (!exact && range.end < end) {
+            return name;
+        }
+        std::str::from_utf8(&buf[start..start + len]).unwrap_or(name)
+    }
+
+    /// Add every source referenced by the AST rooted at `root` to the source
+    /// map, in order of first appearance, optionally with its contents.
+    fn register_sources<'gc>(
+        &mut self,
+        ctx: &'gc GCContext,
+        root: &'gc Node<'gc>,
+        sources_content: bool,
+    ) {
+        struct SourceCollector {
+            ids: Vec<SourceId>,
+        }
+        impl<'gc> Visitor<'gc> for SourceCollector {
+            fn call(
+                &mut self,
+                ctx: &'gc GCContext,
+                node: &'gc Node<'gc>,
+                _parent: Option<&'gc Node<'gc>>,
+            ) {
+                let file = node.range().file;
+                if file.is_valid() && !self.ids.contains(&file) {
+                    self.ids.push(file);
+                }
+                node.visit_children(ctx, self);
+            }
+        }
+
+        let mut collector = SourceCollector { ids: Vec::new() };
+        root.visit(ctx, &mut collector, None);
+        for id in collector.ids {
+            let src_id = self.sourcemap.add_source(ctx.sm().source_name(id));
+            if sources_content {
+                let buf = ctx.sm().source_buffer(id).as_bytes();
+                // Exclude the terminating zero.
+                let contents = String::from_utf8_lossy(&buf[..buf.len() - 1]);
+                self.sourcemap.set_source_contents(src_id, Some(&contents));
+            }
+            self.source_ids.insert(id, src_id);
+            let buf = ctx.sm().source_buffer(id).as_bytes();
+            let offsets = std::iter::once(0)
+                .chain(
+                    buf.iter()
+                        .enumerate()
+                        .filter(|(_, &c)| c == b'\n')
+                        .map(|(pos, _)| pos + 1),
+                )
+                .collect();
+            self.line_offsets.insert(id, offsets);
+        }
+    }
+
     /// Add the `cur_token` to the sourcemap and set `cur_token` to `None`.
     fn flush_cur_token(&mut self) {
         if let Some(cur) = self.cur_token {
@@ -3344,7 +3932,36 @@ impl<W: Write> GenJS<W> {
 
 impl<'gc, W: Write> Visitor<'gc> for GenJS<W> {
     fn call(&mut self, ctx: &'gc GCContext, node: &'gc Node<'gc>, parent: Option<&'gc Node<'gc>>) {
+        // Comments of statements are printed on their own lines by `visit_stmt_in_block`,
+        // so the ones remaining here are inside expressions and must be printed inline.
+        let comments = self.take_comments(ctx, node);
+        // A line break after `return`, `throw` or `yield` would end the statement, so the
+        // comments are kept in parentheses with the expression.
+        let parens = self.no_line_break
+            && matches!(&comments, Some((leading, _)) if has_line_break(leading));
+        if parens {
+            out!(self, "(");
+        }
+        if let Some((leading, _)) = &comments {
+            for comment in leading {
+                self.print_inline_comment(comment);
+                self.space(ForceSpace::No);
+            }
+        }
         self.gen_node(ctx, node, parent);
+        for comment in self.take_inner_comments(ctx, node) {
+            self.space(ForceSpace::No);
+            self.print_inline_comment(&comment);
+        }
+        if let Some((_, trailing)) = &comments {
+            for comment in trailing {
+                self.space(ForceSpace::No);
+                self.print_inline_comment(comment);
+            }
+        }
+        if parens {
+            out!(self, ")");
+        }
     }
 }
 
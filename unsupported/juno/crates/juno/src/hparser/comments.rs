@@ -0,0 +1,162 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Attachment of comments to the nodes of the converted AST.
+//!
+//! Every comment is attached to exactly one node:
+//! * A comment on the same line as the end of the preceding sibling trails that sibling,
+//!   unless the following sibling starts on that line too.
+//! * Otherwise it leads the following sibling, if there is one.
+//! * Otherwise it trails the preceding sibling, if there is one.
+//! * Otherwise it is an inner comment of the enclosing node (e.g. in an empty block).
+
+use super::convert::Converter;
+use crate::ast::{self, Comment, CommentKind, CommentTable, GCContext, Node, Visitor};
+use hermes::parser::{self, StoredComment};
+use hermes::utf::utf8_with_surrogates_to_string_lossy;
+
+/// Return true if `text` is the body of a `sourceURL` or `sourceMappingURL` magic comment.
+/// These are consumed by the parser and must not be copied to the output, which will
+/// have its own.
+fn is_magic_comment(text: &str) -> bool {
+    let text = text.trim_start();
+    match text.strip_prefix('#').or_else(|| text.strip_prefix('@')) {
+        Some(rest) => {
+            let rest = rest.trim_start();
+            rest.starts_with("sourceURL=") || rest.starts_with("sourceMappingURL=")
+        }
+        None => false,
+    }
+}
+
+fn cvt_comment(cvt: &mut Converter, comment: &StoredComment) -> Comment {
+    Comment {
+        kind: match comment.kind {
+            parser::CommentKind::Line => CommentKind::Line,
+            parser::CommentKind::Block => CommentKind::Block,
+            parser::CommentKind::Hashbang => CommentKind::Hashbang,
+        },
+        range: ast::SourceRange {
+            file: cvt.file_id,
+            start: cvt.cvt_smloc(comment.range.start),
+            end: cvt.cvt_smloc(comment.range.end.pred()),
+        },
+        text: utf8_with_surrogates_to_string_lossy(comment.text()),
+    }
+}
+
+/// Collects the children of a node.
+struct Children<'gc> {
+    nodes: Vec<&'gc Node<'gc>>,
+}
+
+impl<'gc> Visitor<'gc> for Children<'gc> {
+    fn call(
+        &mut self,
+        _ctx: &'gc GCContext,
+        node: &'gc Node<'gc>,
+        _parent: Option<&'gc Node<'gc>>,
+    ) {
+        self.nodes.push(node);
+    }
+}
+
+struct Attacher<'t> {
+    table: &'t mut CommentTable,
+}
+
+impl Attacher<'_> {
+    /// Attach `comments`, which are all located inside `node`, to `node` or its descendants.
+    fn attach_inside<'gc>(
+        &mut self,
+        ctx: &'gc GCContext,
+        node: &'gc Node<'gc>,
+        comments: &[Comment],
+    ) {
+        let mut children = Children { nodes: Vec::new() };
+        node.visit_children(ctx, &mut children);
+        let mut children = children.nodes;
+        children.retain(|child| child.range().file.is_valid());
+        children.sort_by_key(|child| child.range().start);
+
+        let mut rest = comments;
+        let mut prev = None;
+        for child in children {
+            let range = child.range();
+            let before = rest.partition_point(|c| c.range.start < range.start);
+            self.attach_between(node, prev, Some(child), &rest[..before]);
+            rest = &rest[before..];
+
+            let inside = rest.partition_point(|c| c.range.start <= range.end);
+            if inside > 0 {
+                self.attach_inside(ctx, child, &rest[..inside]);
+                rest = &rest[inside..];
+            }
+            prev = Some(child);
+        }
+        self.attach_between(node, prev, None, rest);
+    }
+
+    /// Attach `comments`, which are located inside `parent` between the siblings `prev`
+    /// and `next`.
+    fn attach_between<'gc>(
+        &mut self,
+        parent: &'gc Node<'gc>,
+        prev: Option<&'gc Node<'gc>>,
+        next: Option<&'gc Node<'gc>>,
+        comments: &[Comment],
+    ) {
+        for comment in comments {
+            let comment = comment.clone();
+            match (prev, next) {
+                (Some(prev), _)
+                    if comment.range.start.line == prev.range().end.line
+                        && !matches!(next, Some(next)
+                            if next.range().start.line == prev.range().end.line) =>
+                {
+                    self.table.entry(*prev.range()).trailing.push(comment)
+                }
+                (_, Some(next)) => self.table.entry(*next.range()).leading.push(comment),
+                (Some(prev), None) => self.table.entry(*prev.range()).trailing.push(comment),
+                (None, None) => self.table.entry(*parent.range()).inner.push(comment),
+            }
+        }
+    }
+}
+
+/// Convert `comments` and attach them to the nodes of `root` in the comment table of `ctx`.
+/// Comments preceding or following all statements are attached to the first or last statement.
+pub(super) fn attach_comments<'gc>(
+    cvt: &mut Converter,
+    ctx: &'gc GCContext,
+    root: &'gc Node<'gc>,
+    comments: &[StoredComment],
+) {
+    let comments: Vec<Comment> = comments
+        .iter()
+        .map(|c| cvt_comment(cvt, c))
+        .filter(|c| !is_magic_comment(&c.text))
+        .collect();
+    if comments.is_empty() {
+        return;
+    }
+    let mut table = ctx.ctx().comments_mut();
+    Attacher { table: &mut table }.attach_inside(ctx, root, &comments);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_magic_comment() {
+        assert!(is_magic_comment("# sourceMappingURL=a.map"));
+        assert!(is_magic_comment("@ sourceURL=a.js"));
+        assert!(!is_magic_comment(" sourceURL=a.js"));
+        assert!(!is_magic_comment("# foo"));
+    }
+}
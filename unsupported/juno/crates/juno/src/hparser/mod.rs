@@ -5,6 +5,7 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+mod comments;
 mod convert;
 mod generated_cvt;
 
@@ -17,8 +18,19 @@ use std::fmt::Formatter;
 use support::NullTerminatedBuf;
 use thiserror::Error;
 
-use crate::source_manager::SourceId;
-pub use hermes::parser::{MagicCommentKind, ParserDialect, ParserFlags};
+use crate::source_manager::{DiagLevel, Diagnostic, SourceId, SourceManager};
+pub use hermes::parser::{DiagKind, MagicCommentKind, ParserDialect, ParserFlags};
+
+/// A diagnostic message (error, warning, etc.) reported by the parser.
+#[derive(Debug, Clone)]
+pub struct ParserDiagnostic {
+    /// What kind of message.
+    pub kind: DiagKind,
+    /// Location of the message in the source buffer.
+    pub loc: ast::SourceLoc,
+    /// The message itself.
+    pub msg: String,
+}
 
 pub struct ParsedJS<'a> {
     parser: HermesParser<'a>,
@@ -44,22 +56,46 @@ impl<'parser> ParsedJS<'parser> {
         self.parser.magic_comment(kind)
     }
 
-    /// This function is a temporary hack returning the first error.
-    /// It returns (line, column, error_message) of the first error.
-    pub fn first_error(&self) -> Option<(ast::SourceLoc, String)> {
-        self.parser.first_error_index().map(|index| {
-            let msg = &self.parser.messages()[index];
-            (
-                ast::SourceLoc {
+    /// Return all diagnostic messages produced by the parser, in the order in which they
+    /// were reported.
+    pub fn diagnostics(&self) -> Vec<ParserDiagnostic> {
+        self.parser
+            .messages()
+            .iter()
+            .map(|msg| ParserDiagnostic {
+                kind: msg.diag_kind,
+                loc: ast::SourceLoc {
                     line: msg.coord.line as u32,
                     col: msg.coord.offset as u32 + 1,
                 },
-                utf8_with_surrogates_to_string_lossy(msg.message.as_slice()),
-            )
-        })
+                msg: utf8_with_surrogates_to_string_lossy(msg.message.as_slice()),
+            })
+            .collect()
+    }
+
+    /// Report all parser diagnostics to the source manager `sm`. Notes are attached to the
+    /// preceding error or warning. Remarks are reported as notes.
+    pub fn report_diagnostics(&self, sm: &SourceManager, file_id: SourceId) {
+        let mut diags: Vec<Diagnostic> = Vec::new();
+        for d in self.diagnostics() {
+            let range = ast::SourceRange::from_loc(file_id, d.loc);
+            match d.kind {
+                DiagKind::Error => diags.push(Diagnostic::error(range, d.msg)),
+                DiagKind::Warning => diags.push(Diagnostic::warning(range, d.msg)),
+                DiagKind::Note => match diags.pop() {
+                    Some(prev) => diags.push(prev.with_note(range, d.msg)),
+                    None => diags.push(Diagnostic::new(DiagLevel::Note, range, d.msg)),
+                },
+                DiagKind::Remark => diags.push(Diagnostic::new(DiagLevel::Note, range, d.msg)),
+            }
+        }
+        for d in diags {
+            sm.report(d);
+        }
     }
 
     /// Create and return an external representation of the AST, or None if there were parse errors.
+    /// Comments are attached to the nodes of the AST in the comment table of the context.
     pub fn to_ast<'gc, 'ast: 'gc>(
         &'parser self,
         ctx: &'gc ast::GCContext<'ast, '_>,
@@ -69,11 +105,63 @@ impl<'parser> ParsedJS<'parser> {
 
         match self.parser.root() {
             None => None,
-            Some(node) => Some(convert_ast(&mut cvt, ctx, node)),
+            Some(node) => {
+                let ast = convert_ast(&mut cvt, ctx, node);
+                comments::attach_comments(&mut cvt, ctx, ast, self.parser.comments());
+                Some(ast)
+            }
         }
     }
 }
 
+/// Return true if the leading comments of `source` contain a `@flow` pragma, indicating that
+/// the file should be parsed as Flow. Only comments preceding the first token are examined,
+/// matching the behavior of the Flow tools.
+pub fn has_flow_pragma(source: &[u8]) -> bool {
+    let mut rest = source;
+    loop {
+        // Skip whitespace, including the terminating zero of a NullTerminatedBuf.
+        while let Some((&c, tail)) = rest.split_first() {
+            if c.is_ascii_whitespace() || c == 0 {
+                rest = tail;
+            } else {
+                break;
+            }
+        }
+        let comment = if let Some(tail) = rest.strip_prefix(b"//") {
+            let end = tail.iter().position(|&c| c == b'\n').unwrap_or(tail.len());
+            rest = &tail[end..];
+            &tail[..end]
+        } else if let Some(tail) = rest.strip_prefix(b"/*") {
+            match tail.windows(2).position(|w| w == b"*/") {
+                Some(end) => {
+                    rest = &tail[end + 2..];
+                    &tail[..end]
+                }
+                // Unterminated comment.
+                None => return false,
+            }
+        } else {
+            return false;
+        };
+        if contains_flow_pragma(comment) {
+            return true;
+        }
+    }
+}
+
+/// Return true if `comment` contains `@flow` not followed by an identifier character.
+fn contains_flow_pragma(comment: &[u8]) -> bool {
+    const PRAGMA: &[u8] = b"@flow";
+    comment.windows(PRAGMA.len()).enumerate().any(|(i, w)| {
+        w == PRAGMA
+            && !matches!(
+                comment.get(i + PRAGMA.len()),
+                Some(&c) if c.is_ascii_alphanumeric() || c == b'_' || c == b'$'
+            )
+    })
+}
+
 fn convert_ast<'parser, 'gc, 'ast: 'gc>(
     cvt: &mut Converter<'parser>,
     ctx: &'gc ast::GCContext<'ast, '_>,
@@ -112,8 +200,15 @@ pub fn parse_with_flags(
     if let Some(ast) = parsed.to_ast(&gc, file_id) {
         Ok(ast::NodePtr::from_node(&gc, ast))
     } else {
-        let (loc, msg) = parsed.first_error().unwrap();
-        Err(ParseError { loc, msg })
+        let diag = parsed
+            .diagnostics()
+            .into_iter()
+            .find(|d| d.kind == DiagKind::Error)
+            .unwrap();
+        Err(ParseError {
+            loc: diag.loc,
+            msg: diag.msg,
+        })
     }
 }
 
@@ -133,4 +228,32 @@ mod tests {
         let mut ctx = ast::Context::new();
         parse(&mut ctx, "function foo(p1) { var x = (10 + p1); }").expect("Parse failed");
     }
+
+    #[test]
+    fn test_flow_pragma() {
+        assert!(has_flow_pragma(b"// @flow\nvar x;"));
+        assert!(has_flow_pragma(
+            b"/**\n * Copyright\n * @flow strict\n */\nvar x;"
+        ));
+        assert!(has_flow_pragma(b"/* a */ // b\n/* @flow */"));
+        assert!(!has_flow_pragma(b"var x; // @flow"));
+        assert!(!has_flow_pragma(b"// @flowtype\nvar x;"));
+        assert!(!has_flow_pragma(b"// @noflow\nvar x;"));
+        assert!(!has_flow_pragma(b"/* @flow"));
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let buf = NullTerminatedBuf::from_str_check("'use strict'; var x = 010;\nvar y = 011;");
+        let parsed = ParsedJS::parse(Default::default(), &buf);
+        assert!(parsed.has_errors());
+
+        let diags = parsed.diagnostics();
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[0].kind, DiagKind::Error);
+        assert_eq!(diags[0].loc, ast::SourceLoc { line: 1, col: 23 });
+        assert_eq!(diags[0].msg, "Octal literals must use '0o' in strict mode");
+        assert_eq!(diags[1].kind, DiagKind::Error);
+        assert_eq!(diags[1].loc, ast::SourceLoc { line: 2, col: 9 });
+    }
 }
@@ -0,0 +1,511 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Detection of the ECMAScript early errors which can't be expressed as structural constraints
+//! of the AST, such as duplicate lexical declarations or a `break` outside of a loop.
+//!
+//! The parser rejects most of them in the source, but transformations can easily introduce them,
+//! so the AST is checked again independently of how it was produced.
+
+use super::resolve::{is_module, is_type};
+use super::resolve_program;
+use crate::ast::{
+    ArrowFunctionExpression, BreakStatement, CallExpression, ClassDeclaration, ClassExpression,
+    ClassPrivateProperty, ClassProperty, Context, ContinueStatement, ExpressionStatement,
+    FunctionDeclaration, FunctionExpression, GCContext, Identifier, LabeledStatement, MetaProperty,
+    MethodDefinition, MethodDefinitionKind, Node, NodeList, NodePtr, NodeRef, ObjectExpression,
+    Program, Property, PropertyKind, StringLiteral, UnaryExpression, UnaryExpressionOperator,
+    Visitor,
+};
+use crate::source_manager::Diagnostic;
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("{0} early errors")]
+pub struct EarlyErrors(usize);
+
+/// Check the full AST for early errors.
+/// If any are found, report them to the source manager.
+pub fn check_early_errors(ctx: &mut Context, root: &NodePtr) -> Result<(), EarlyErrors> {
+    let gc = GCContext::new(ctx);
+    let errors = find_early_errors(&gc, root.node(&gc));
+    if errors.is_empty() {
+        return Ok(());
+    }
+    let count = errors.len();
+    for diag in errors {
+        gc.sm().report(diag);
+    }
+    Err(EarlyErrors(count))
+}
+
+/// Return the early errors in `program`, which must be a `Program` node, ordered by location.
+pub fn find_early_errors<'gc>(ctx: &'gc GCContext, program: &'gc Node<'gc>) -> Vec<Diagnostic> {
+    let mut errors = Vec::new();
+
+    let mut sem = resolve_program(ctx, program);
+    for &(decl, prev) in &sem.redeclarations {
+        let name = match decl {
+            Node::Identifier(Identifier { name, .. }) => ctx.str(*name),
+            _ => unreachable!("declarations must be identifiers"),
+        };
+        errors.push(
+            Diagnostic::error(*decl.range(), format!("'{}' is already declared", name))
+                .with_note(*prev.range(), "previous declaration"),
+        );
+    }
+
+    let mut checker = Checker {
+        errors,
+        func: FunctionContext::default(),
+        labels: Vec::new(),
+        strict: false,
+        derived_class: false,
+        duplicate_params: std::mem::take(&mut sem.duplicate_params),
+    };
+    checker.call(ctx, program, None);
+
+    let mut errors = checker.errors;
+    errors.sort_by_key(|diag| (diag.range.file.0, diag.range.start));
+    errors
+}
+
+/// Return true if `s` is the UTF-16 encoding of `expected`.
+fn utf16_eq(s: &[u16], expected: &str) -> bool {
+    s.iter().copied().eq(expected.encode_utf16())
+}
+
+/// Return true if the directive prologue of `body` contains a "use strict" directive.
+pub(super) fn has_use_strict(body: &NodeList) -> bool {
+    body.iter()
+        .map_while(|stmt| match stmt {
+            Node::ExpressionStatement(ExpressionStatement {
+                directive: Some(directive),
+                ..
+            }) => Some(directive),
+            _ => None,
+        })
+        .any(|directive| utf16_eq(&directive.str, "use strict"))
+}
+
+/// Return true if `node` is a loop, possibly labeled.
+fn is_loop(mut node: &Node) -> bool {
+    loop {
+        match node {
+            Node::LabeledStatement(LabeledStatement { body, .. }) => node = body,
+            Node::WhileStatement(_)
+            | Node::DoWhileStatement(_)
+            | Node::ForStatement(_)
+            | Node::ForInStatement(_)
+            | Node::ForOfStatement(_) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// Return true if `prop` is a `__proto__: value` property, which sets the prototype of the object.
+fn is_proto_setter(ctx: &GCContext, prop: &Node) -> bool {
+    match prop {
+        Node::Property(Property {
+            key,
+            kind: PropertyKind::Init,
+            computed: false,
+            method: false,
+            shorthand: false,
+            ..
+        }) => match key {
+            Node::Identifier(Identifier { name, .. }) => ctx.str(*name) == "__proto__",
+            Node::StringLiteral(StringLiteral { value, .. }) => utf16_eq(&value.str, "__proto__"),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// The constructs allowed by the innermost enclosing function.
+#[derive(Debug, Default, Copy, Clone)]
+struct FunctionContext {
+    /// Whether `return` is allowed.
+    is_function: bool,
+    /// Whether `await` is allowed.
+    is_async: bool,
+    /// Whether `yield` is allowed.
+    is_generator: bool,
+    /// Whether `super.x` is allowed.
+    super_property: bool,
+    /// Whether `super()` is allowed.
+    super_call: bool,
+    /// Whether `new.target` is allowed.
+    new_target: bool,
+    /// Number of enclosing loops and switch statements, which are targets of `break`.
+    breakables: u32,
+    /// Number of enclosing loops, which are targets of `continue`.
+    loops: u32,
+}
+
+/// A label of an enclosing statement.
+struct Label<'gc> {
+    /// The label identifier.
+    node: &'gc Node<'gc>,
+    name: &'gc str,
+    /// Whether the label is a target of `continue`.
+    is_loop: bool,
+}
+
+/// Finds the early errors which depend on the context of a node.
+struct Checker<'gc> {
+    errors: Vec<Diagnostic>,
+
+    /// The innermost enclosing function.
+    func: FunctionContext,
+
+    /// Labels of the enclosing statements in the current function, outermost first.
+    labels: Vec<Label<'gc>>,
+
+    /// Whether the current code is strict mode code.
+    strict: bool,
+
+    /// Whether the innermost enclosing class has a superclass.
+    derived_class: bool,
+
+    /// Parameters with the same name as a previous parameter of the same function, keyed by the
+    /// function.
+    duplicate_params: HashMap<NodeRef<'gc>, Vec<(&'gc Node<'gc>, &'gc Node<'gc>)>>,
+}
+
+impl<'gc> Checker<'gc> {
+    fn error(&mut self, node: &Node, msg: impl Into<String>) {
+        self.errors.push(Diagnostic::error(*node.range(), msg));
+    }
+
+    /// Run `f` in the function context `func`, optionally switching to strict mode. Labels of
+    /// the current function are not visible in the new context.
+    fn in_function<F: FnOnce(&mut Self)>(&mut self, func: FunctionContext, strict: bool, f: F) {
+        let saved_func = std::mem::replace(&mut self.func, func);
+        let saved_labels = std::mem::take(&mut self.labels);
+        let saved_strict = self.strict;
+        self.strict |= strict;
+        f(self);
+        self.func = saved_func;
+        self.labels = saved_labels;
+        self.strict = saved_strict;
+    }
+
+    /// Visit the children of the function `node` with body `body` in the context `func`.
+    fn function(
+        &mut self,
+        ctx: &'gc GCContext,
+        node: &'gc Node<'gc>,
+        body: &'gc Node<'gc>,
+        func: FunctionContext,
+    ) {
+        let strict = match body {
+            Node::BlockStatement(block) => has_use_strict(&block.body),
+            _ => false,
+        };
+        if let Some(duplicates) = self.duplicate_params.remove(&NodeRef(node)) {
+            let (params, is_arrow) = match node {
+                Node::FunctionDeclaration(FunctionDeclaration { params, .. })
+                | Node::FunctionExpression(FunctionExpression { params, .. }) => (params, false),
+                Node::ArrowFunctionExpression(ArrowFunctionExpression { params, .. }) => {
+                    (params, true)
+                }
+                _ => unreachable!("invalid function {}", node.name()),
+            };
+            // Duplicate parameters are only allowed in non-strict functions with simple
+            // parameter lists, which aren't arrow functions or methods.
+            let simple = params
+                .iter()
+                .all(|param| matches!(param, Node::Identifier(_)));
+            if self.strict || strict || is_arrow || func.super_property || !simple {
+                for (param, prev) in duplicates {
+                    let name = match param {
+                        Node::Identifier(Identifier { name, .. }) => ctx.str(*name),
+                        _ => unreachable!("parameters must be identifiers"),
+                    };
+                    self.errors.push(
+                        Diagnostic::error(
+                            *param.range(),
+                            format!("'{}' is already declared", name),
+                        )
+                        .with_note(*prev.range(), "previous declaration"),
+                    );
+                }
+            }
+        }
+        self.in_function(func, strict, |this| node.visit_children(ctx, this));
+    }
+
+    /// Visit the initializer `value` of the class field `node`.
+    fn initializer(&mut self, ctx: &'gc GCContext, node: &'gc Node<'gc>, value: &'gc Node<'gc>) {
+        // Initializers are evaluated like methods of the class.
+        let func = FunctionContext {
+            super_property: true,
+            new_target: true,
+            ..Default::default()
+        };
+        self.in_function(func, false, |this| value.visit(ctx, this, Some(node)));
+    }
+
+    /// Visit the children of the loop or switch statement `node`.
+    fn breakable(&mut self, ctx: &'gc GCContext, node: &'gc Node<'gc>, is_loop: bool) {
+        self.func.breakables += 1;
+        self.func.loops += is_loop as u32;
+        node.visit_children(ctx, self);
+        self.func.breakables -= 1;
+        self.func.loops -= is_loop as u32;
+    }
+
+    /// Return the label named `name` of an enclosing statement.
+    fn find_label(&self, name: &str) -> Option<&Label<'gc>> {
+        self.labels.iter().rev().find(|label| label.name == name)
+    }
+
+    /// Return the context of the function expression `node`, a child of `parent`, excluding
+    /// `async` and `generator`: methods may access `super`.
+    fn method_context(
+        &self,
+        node: &'gc Node<'gc>,
+        parent: Option<&'gc Node<'gc>>,
+    ) -> FunctionContext {
+        let (super_property, super_call) = match parent {
+            Some(Node::Property(Property {
+                value,
+                kind,
+                method,
+                ..
+            })) if std::ptr::eq(*value, node) => (*method || *kind != PropertyKind::Init, false),
+            Some(Node::MethodDefinition(MethodDefinition { value, kind, .. }))
+                if std::ptr::eq(*value, node) =>
+            {
+                (
+                    true,
+                    *kind == MethodDefinitionKind::Constructor && self.derived_class,
+                )
+            }
+            _ => (false, false),
+        };
+        FunctionContext {
+            is_function: true,
+            super_property,
+            super_call,
+            new_target: true,
+            ..Default::default()
+        }
+    }
+
+    /// Visit the class `node` with an optional superclass.
+    fn class(
+        &mut self,
+        ctx: &'gc GCContext,
+        node: &'gc Node<'gc>,
+        super_class: Option<&'gc Node<'gc>>,
+    ) {
+        // All parts of a class are strict mode code.
+        let saved_strict = std::mem::replace(&mut self.strict, true);
+        let saved_derived = std::mem::replace(&mut self.derived_class, super_class.is_some());
+        node.visit_children(ctx, self);
+        self.strict = saved_strict;
+        self.derived_class = saved_derived;
+    }
+}
+
+impl<'gc> Visitor<'gc> for Checker<'gc> {
+    fn call(&mut self, ctx: &'gc GCContext, node: &'gc Node<'gc>, parent: Option<&'gc Node<'gc>>) {
+        match node {
+            _ if is_type(node) => {}
+            Node::Program(Program { body, .. }) => {
+                // Await is allowed at the top level of modules.
+                let module = is_module(body);
+                self.func.is_async = module;
+                self.strict = ctx.ctx().strict_mode() || module || has_use_strict(body);
+                node.visit_children(ctx, self);
+            }
+            Node::FunctionDeclaration(FunctionDeclaration {
+                body,
+                generator,
+                is_async,
+                ..
+            })
+            | Node::FunctionExpression(FunctionExpression {
+                body,
+                generator,
+                is_async,
+                ..
+            }) => {
+                let func = FunctionContext {
+                    is_async: *is_async,
+                    is_generator: *generator,
+                    ..self.method_context(node, parent)
+                };
+                self.function(ctx, node, body, func);
+            }
+            Node::ArrowFunctionExpression(ArrowFunctionExpression { body, is_async, .. }) => {
+                // Arrow functions inherit `super` and `new.target`.
+                let func = FunctionContext {
+                    is_function: true,
+                    is_async: *is_async,
+                    is_generator: false,
+                    breakables: 0,
+                    loops: 0,
+                    ..self.func
+                };
+                self.function(ctx, node, body, func);
+            }
+            Node::ClassDeclaration(ClassDeclaration { super_class, .. })
+            | Node::ClassExpression(ClassExpression { super_class, .. }) => {
+                self.class(ctx, node, *super_class)
+            }
+            Node::ClassProperty(ClassProperty {
+                key,
+                value,
+                computed,
+                ..
+            }) => {
+                if *computed {
+                    key.visit(ctx, self, Some(node));
+                }
+                if let Some(value) = value {
+                    self.initializer(ctx, node, value);
+                }
+            }
+            Node::ClassPrivateProperty(ClassPrivateProperty { value, .. }) => {
+                if let Some(value) = value {
+                    self.initializer(ctx, node, value);
+                }
+            }
+            Node::WhileStatement(_)
+            | Node::DoWhileStatement(_)
+            | Node::ForStatement(_)
+            | Node::ForInStatement(_)
+            | Node::ForOfStatement(_) => self.breakable(ctx, node, true),
+            Node::SwitchStatement(_) => self.breakable(ctx, node, false),
+            Node::LabeledStatement(LabeledStatement { label, body, .. }) => {
+                let name = match label {
+                    Node::Identifier(Identifier { name, .. }) => ctx.str(*name),
+                    _ => unreachable!("labels must be identifiers"),
+                };
+                if let Some(prev) = self.find_label(name) {
+                    let diag = Diagnostic::error(
+                        *label.range(),
+                        format!("label '{}' is already defined", name),
+                    )
+                    .with_note(*prev.node.range(), "previous definition");
+                    self.errors.push(diag);
+                }
+                self.labels.push(Label {
+                    node: label,
+                    name,
+                    is_loop: is_loop(body),
+                });
+                body.visit(ctx, self, Some(node));
+                self.labels.pop();
+            }
+            Node::BreakStatement(BreakStatement { label, .. }) => match label {
+                Some(label @ Node::Identifier(Identifier { name, .. })) => {
+                    let name = ctx.str(*name);
+                    if self.find_label(name).is_none() {
+                        self.error(label, format!("label '{}' is not defined", name));
+                    }
+                }
+                _ => {
+                    if self.func.breakables == 0 {
+                        self.error(node, "'break' not within a loop or switch");
+                    }
+                }
+            },
+            Node::ContinueStatement(ContinueStatement { label, .. }) => match label {
+                Some(label @ Node::Identifier(Identifier { name, .. })) => {
+                    let name = ctx.str(*name);
+                    match self.find_label(name) {
+                        None => self.error(label, format!("label '{}' is not defined", name)),
+                        Some(target) if !target.is_loop => {
+                            self.error(label, format!("label '{}' does not denote a loop", name))
+                        }
+                        Some(_) => {}
+                    }
+                }
+                _ => {
+                    if self.func.loops == 0 {
+                        self.error(node, "'continue' not within a loop");
+                    }
+                }
+            },
+            Node::ReturnStatement(_) => {
+                if !self.func.is_function {
+                    self.error(node, "'return' not within a function");
+                }
+                node.visit_children(ctx, self);
+            }
+            Node::AwaitExpression(_) => {
+                if !self.func.is_async {
+                    self.error(node, "'await' not within an async function");
+                }
+                node.visit_children(ctx, self);
+            }
+            Node::YieldExpression(_) => {
+                if !self.func.is_generator {
+                    self.error(node, "'yield' not within a generator function");
+                }
+                node.visit_children(ctx, self);
+            }
+            Node::UnaryExpression(UnaryExpression {
+                operator: UnaryExpressionOperator::Delete,
+                argument: Node::Identifier(_),
+                ..
+            }) if self.strict => {
+                self.error(node, "'delete' of an unqualified identifier in strict mode");
+            }
+            Node::WithStatement(_) => {
+                if self.strict {
+                    self.error(node, "'with' not allowed in strict mode");
+                }
+                node.visit_children(ctx, self);
+            }
+            Node::Super(_) => match parent {
+                Some(Node::CallExpression(CallExpression { callee, .. }))
+                    if std::ptr::eq(*callee, node) =>
+                {
+                    if !self.func.super_call {
+                        self.error(
+                            node,
+                            "'super' call not within the constructor of a derived class",
+                        );
+                    }
+                }
+                _ => {
+                    if !self.func.super_property {
+                        self.error(node, "'super' property not within a method");
+                    }
+                }
+            },
+            Node::MetaProperty(MetaProperty { meta, property, .. }) => {
+                let is = |node: &Node, expected: &str| matches!(node, Node::Identifier(Identifier { name, .. }) if ctx.str(*name) == expected);
+                if is(meta, "new") && is(property, "target") && !self.func.new_target {
+                    self.error(node, "'new.target' not within a function");
+                }
+            }
+            Node::ObjectExpression(ObjectExpression { properties, .. }) => {
+                let mut proto = None;
+                for prop in properties.iter().filter(|prop| is_proto_setter(ctx, prop)) {
+                    match proto {
+                        None => proto = Some(prop),
+                        Some(prev) => {
+                            let diag =
+                                Diagnostic::error(*prop.range(), "duplicate '__proto__' property")
+                                    .with_note(*prev.range(), "previous definition");
+                            self.errors.push(diag);
+                        }
+                    }
+                }
+                node.visit_children(ctx, self);
+            }
+            _ => node.visit_children(ctx, self),
+        }
+    }
+}
@@ -0,0 +1,314 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Semantic analysis of the AST.
+//!
+//! [`resolve_program`] builds the lexical scopes of a program, declares the bindings of every
+//! scope and resolves each identifier to the binding it refers to or to a global variable.
+//!
+//! [`check_early_errors`] uses the results to detect the early errors of a program, such as
+//! conflicting declarations or a `return` outside of a function.
+//!
+//! The results refer to the nodes of the analyzed AST, so they are only valid while the
+//! `GCContext` used to compute them is alive, and must be recomputed after a transformation
+//! which rebuilds the AST.
+
+mod early_errors;
+mod resolve;
+
+pub use early_errors::{check_early_errors, find_early_errors, EarlyErrors};
+pub use resolve::{is_type, pattern_ids, resolve_program};
+
+use crate::ast::{Atom, Node, NodeRef, SourceRange};
+use std::collections::HashMap;
+
+/// Identifies a scope in a [`SemContext`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ScopeId(u32);
+
+impl ScopeId {
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Identifies a binding in a [`SemContext`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BindingId(u32);
+
+impl BindingId {
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// The construct introducing a scope.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScopeKind {
+    /// Top level of a script. Its `var` and function declarations are properties of the
+    /// global object.
+    Global,
+    /// Top level of a module.
+    Module,
+    /// Parameters and body of a function.
+    Function,
+    /// Block statement, `for` statement or body of a `switch` statement.
+    Block,
+    /// Parameter of a `catch` clause.
+    Catch,
+    /// Class, containing the name of a class expression.
+    Class,
+}
+
+impl ScopeKind {
+    /// Return true if `var` declarations are hoisted to scopes of this kind.
+    pub fn is_var_scope(self) -> bool {
+        matches!(
+            self,
+            ScopeKind::Global | ScopeKind::Module | ScopeKind::Function
+        )
+    }
+}
+
+/// A lexical scope.
+#[derive(Debug)]
+pub struct Scope<'gc> {
+    pub kind: ScopeKind,
+
+    /// The node introducing the scope.
+    pub node: &'gc Node<'gc>,
+
+    /// The enclosing scope, or `None` for the top level.
+    pub parent: Option<ScopeId>,
+
+    /// The nearest scope, possibly this one, to which `var` declarations are hoisted.
+    pub var_scope: ScopeId,
+
+    /// Bindings declared in this scope, in order of declaration.
+    pub bindings: Vec<BindingId>,
+
+    /// Whether the bindings visible in this scope may be accessed by name at runtime, because of
+    /// a direct `eval` or a `with` statement in this scope or a nested one.
+    /// Such bindings must not be renamed or removed.
+    pub dynamic: bool,
+
+    /// Binding of every name declared in this scope.
+    names: HashMap<Atom, BindingId>,
+}
+
+impl Scope<'_> {
+    /// Return the binding declared with `name` in this scope, if any.
+    pub fn binding(&self, name: Atom) -> Option<BindingId> {
+        self.names.get(&name).copied()
+    }
+}
+
+/// The construct declaring a binding.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BindingKind {
+    Var,
+    Let,
+    Const,
+    /// Function declaration.
+    Function,
+    /// Class declaration.
+    Class,
+    /// Function parameter.
+    Param,
+    /// Parameter of a `catch` clause.
+    CatchParam,
+    /// Local name of an import specifier.
+    Import,
+    /// Name of a function expression, visible in its body.
+    FunctionName,
+    /// Name of a class expression, visible in its body.
+    ClassName,
+}
+
+impl BindingKind {
+    /// Return true if the declaration is hoisted to the top of its scope, so the binding can be
+    /// accessed before the declaration.
+    pub fn is_hoisted(self) -> bool {
+        matches!(self, BindingKind::Var | BindingKind::Function)
+    }
+
+    /// Return true if the binding is uninitialized until its declaration is executed, i.e. it has
+    /// a temporal dead zone.
+    pub fn is_lexical(self) -> bool {
+        matches!(
+            self,
+            BindingKind::Let | BindingKind::Const | BindingKind::Class
+        )
+    }
+
+    /// Return true if the binding can't be assigned.
+    pub fn is_const(self) -> bool {
+        matches!(
+            self,
+            BindingKind::Const | BindingKind::Import | BindingKind::ClassName
+        )
+    }
+}
+
+/// A variable declared in a scope.
+#[derive(Debug)]
+pub struct Binding<'gc> {
+    pub name: Atom,
+    pub kind: BindingKind,
+
+    /// The scope the binding is declared in, after hoisting.
+    pub scope: ScopeId,
+
+    /// The identifiers declaring the binding. There may be several for `var` and function
+    /// declarations.
+    pub declarations: Vec<&'gc Node<'gc>>,
+
+    /// All references to the binding, excluding the declarations, in AST order.
+    pub references: Vec<Reference<'gc>>,
+
+    /// For lexical bindings, the range of the declaration. The binding is initialized at its end.
+    decl_range: Option<SourceRange>,
+}
+
+impl Binding<'_> {
+    /// Return true if the binding is assigned anywhere besides its declarations.
+    pub fn is_reassigned(&self) -> bool {
+        self.references
+            .iter()
+            .any(|reference| reference.kind != ReferenceKind::Read)
+    }
+}
+
+/// How a reference accesses a variable.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReferenceKind {
+    /// The value is read.
+    Read,
+    /// The variable is assigned, e.g. `x = 1` or `[x] = a`.
+    Write,
+    /// The value is read and the variable is assigned, e.g. `x += 1` or `x++`.
+    ReadWrite,
+}
+
+/// An identifier referring to a variable.
+#[derive(Debug, Copy, Clone)]
+pub struct Reference<'gc> {
+    /// The `Identifier` (or `JSXIdentifier`) node.
+    pub node: &'gc Node<'gc>,
+
+    pub kind: ReferenceKind,
+
+    /// Whether the reference may be evaluated before the binding is initialized, i.e. in its
+    /// temporal dead zone. This is conservative: it is true for references from closures which
+    /// may be called before the declaration. Always false for non-lexical bindings and globals.
+    pub maybe_in_tdz: bool,
+}
+
+/// What an identifier refers to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// A binding declared in the program.
+    Binding(BindingId),
+    /// A global variable which is not declared in the program.
+    Global(Atom),
+}
+
+/// The result of the semantic analysis of a program.
+#[derive(Debug, Default)]
+pub struct SemContext<'gc> {
+    scopes: Vec<Scope<'gc>>,
+    bindings: Vec<Binding<'gc>>,
+
+    /// The scope introduced by each node.
+    node_scopes: HashMap<NodeRef<'gc>, ScopeId>,
+
+    /// Resolution of every identifier declaring or referencing a variable.
+    resolutions: HashMap<NodeRef<'gc>, Resolution>,
+
+    /// References to undeclared global variables.
+    globals: HashMap<Atom, Vec<Reference<'gc>>>,
+
+    /// Declarations which are invalid because their name is already declared in the same scope,
+    /// with the conflicting previous declaration.
+    redeclarations: Vec<(&'gc Node<'gc>, &'gc Node<'gc>)>,
+
+    /// Parameters with the same name as a previous parameter of the same function, with the
+    /// previous one, keyed by the function. They are only valid in non-strict functions with
+    /// simple parameter lists.
+    duplicate_params: HashMap<NodeRef<'gc>, Vec<(&'gc Node<'gc>, &'gc Node<'gc>)>>,
+}
+
+impl<'gc> SemContext<'gc> {
+    /// Return the top level scope of the program.
+    pub fn root_scope(&self) -> ScopeId {
+        ScopeId(0)
+    }
+
+    pub fn scope(&self, id: ScopeId) -> &Scope<'gc> {
+        &self.scopes[id.index()]
+    }
+
+    pub fn binding(&self, id: BindingId) -> &Binding<'gc> {
+        &self.bindings[id.index()]
+    }
+
+    /// Return all scopes, parents before their children.
+    pub fn scopes(&self) -> impl Iterator<Item = (ScopeId, &Scope<'gc>)> {
+        (0u32..).map(ScopeId).zip(self.scopes.iter())
+    }
+
+    /// Return all bindings in order of declaration.
+    pub fn bindings(&self) -> impl Iterator<Item = (BindingId, &Binding<'gc>)> {
+        (0u32..).map(BindingId).zip(self.bindings.iter())
+    }
+
+    /// Return the scope introduced by `node`, if any.
+    /// The body of a function belongs to the scope introduced by the function.
+    pub fn node_scope(&self, node: &'gc Node<'gc>) -> Option<ScopeId> {
+        self.node_scopes.get(&NodeRef(node)).copied()
+    }
+
+    /// Return what the identifier `node` refers to, or `None` if it isn't a variable
+    /// (e.g. a property name or a label).
+    pub fn resolution(&self, node: &'gc Node<'gc>) -> Option<Resolution> {
+        self.resolutions.get(&NodeRef(node)).copied()
+    }
+
+    /// Return the binding declared or referenced by the identifier `node`, if any.
+    pub fn binding_of(&self, node: &'gc Node<'gc>) -> Option<BindingId> {
+        match self.resolution(node) {
+            Some(Resolution::Binding(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Return the binding visible with `name` in `scope`, if any.
+    pub fn lookup(&self, scope: ScopeId, name: Atom) -> Option<BindingId> {
+        let mut cur = Some(scope);
+        while let Some(id) = cur {
+            let scope = self.scope(id);
+            if let Some(binding) = scope.binding(name) {
+                return Some(binding);
+            }
+            cur = scope.parent;
+        }
+        None
+    }
+
+    /// Return the names of the undeclared global variables referenced by the program, with
+    /// their references.
+    pub fn globals(&self) -> impl Iterator<Item = (Atom, &[Reference<'gc>])> {
+        self.globals
+            .iter()
+            .map(|(name, references)| (*name, references.as_slice()))
+    }
+
+    /// Return the references to the undeclared global variable `name`.
+    pub fn global_references(&self, name: Atom) -> &[Reference<'gc>] {
+        self.globals.get(&name).map_or(&[], |refs| refs.as_slice())
+    }
+}
@@ -0,0 +1,814 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Construction of scopes and resolution of identifiers.
+//!
+//! The analysis runs in two traversals of the AST:
+//! * The [`Declarer`] creates the scopes and declares their bindings, hoisting `var`
+//!   declarations to the enclosing function.
+//! * The [`Resolver`] resolves the references. Since all declarations are known by then,
+//!   references preceding a hoisted declaration are resolved correctly.
+//!
+//! Function declarations in blocks are scoped to the block. In non-strict code, they are also
+//! visible in the enclosing function as specified in Annex B.3.3 of ECMAScript, unless that would
+//! conflict with another declaration: they are then declared in the function instead, so that
+//! renaming keeps both names the same. Type annotations are not analyzed.
+
+use super::early_errors::has_use_strict;
+use super::*;
+use crate::ast::{
+    ArrayPattern, ArrowFunctionExpression, AssignmentExpression, AssignmentExpressionOperator,
+    AssignmentPattern, BlockStatement, CallExpression, CatchClause, ClassDeclaration,
+    ClassExpression, ClassProperty, ExportNamedDeclaration, ForInStatement, ForOfStatement,
+    FunctionDeclaration, FunctionExpression, GCContext, Identifier, ImportDeclaration,
+    ImportDefaultSpecifier, ImportNamespaceSpecifier, ImportSpecifier, JSXIdentifier,
+    MemberExpression, MethodDefinition, NodeList, ObjectPattern, OptionalMemberExpression, Program,
+    Property, RestElement, SwitchStatement, UpdateExpression, VariableDeclaration,
+    VariableDeclarationKind, VariableDeclarator, Visitor,
+};
+use support::ScopedHashMap;
+
+/// Build the scopes of `program` and resolve all its identifiers.
+pub fn resolve_program<'gc>(ctx: &'gc GCContext, program: &'gc Node<'gc>) -> SemContext<'gc> {
+    let mut sem = SemContext::default();
+    Declarer {
+        sem: &mut sem,
+        scope: None,
+        hoisted: HashMap::new(),
+        strict: false,
+        block_functions: Vec::new(),
+    }
+    .call(ctx, program, None);
+    Resolver {
+        sem: &mut sem,
+        names: ScopedHashMap::new(),
+        scope: ScopeId(0),
+    }
+    .call(ctx, program, None);
+    sem
+}
+
+/// Return true if `node` is a type annotation or a type declaration, which don't contain
+/// references to variables.
+pub fn is_type(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::TypeAnnotation(_)
+            | Node::TSTypeAnnotation(_)
+            | Node::TypeParameterDeclaration(_)
+            | Node::TypeParameterInstantiation(_)
+            | Node::TSTypeParameterDeclaration(_)
+            | Node::TSTypeParameterInstantiation(_)
+            | Node::ClassImplements(_)
+            | Node::TSInterfaceHeritage(_)
+            | Node::Variance(_)
+            | Node::InferredPredicate(_)
+            | Node::DeclaredPredicate(_)
+            | Node::TypeAlias(_)
+            | Node::OpaqueType(_)
+            | Node::InterfaceDeclaration(_)
+            | Node::DeclareTypeAlias(_)
+            | Node::DeclareOpaqueType(_)
+            | Node::DeclareInterface(_)
+            | Node::DeclareClass(_)
+            | Node::DeclareFunction(_)
+            | Node::DeclareVariable(_)
+            | Node::DeclareExportDeclaration(_)
+            | Node::DeclareExportAllDeclaration(_)
+            | Node::DeclareModule(_)
+            | Node::DeclareModuleExports(_)
+            | Node::TSTypeAliasDeclaration(_)
+            | Node::TSInterfaceDeclaration(_)
+    )
+}
+
+/// Append the identifiers declared by `pattern` to `out`.
+pub fn pattern_ids<'gc>(pattern: &'gc Node<'gc>, out: &mut Vec<&'gc Node<'gc>>) {
+    match pattern {
+        Node::Identifier(_) => out.push(pattern),
+        Node::ObjectPattern(ObjectPattern { properties, .. }) => {
+            for prop in properties {
+                match prop {
+                    Node::Property(Property { value, .. }) => pattern_ids(value, out),
+                    _ => pattern_ids(prop, out),
+                }
+            }
+        }
+        Node::ArrayPattern(ArrayPattern { elements, .. }) => {
+            for elem in elements {
+                pattern_ids(elem, out);
+            }
+        }
+        Node::RestElement(RestElement { argument, .. }) => pattern_ids(argument, out),
+        Node::AssignmentPattern(AssignmentPattern { left, .. }) => pattern_ids(left, out),
+        _ => {}
+    }
+}
+
+/// Return true if the program contains import or export declarations.
+pub(super) fn is_module(body: &NodeList) -> bool {
+    body.iter().any(|stmt| {
+        matches!(
+            stmt,
+            Node::ImportDeclaration(_)
+                | Node::ExportNamedDeclaration(_)
+                | Node::ExportDefaultDeclaration(_)
+                | Node::ExportAllDeclaration(_)
+        )
+    })
+}
+
+/// Return true if the binding kinds `old` and `new` declare the same binding when they are
+/// declared with the same name in the same scope.
+fn can_redeclare(old: BindingKind, new: BindingKind) -> bool {
+    use BindingKind::*;
+    matches!(
+        (old, new),
+        (Var | Function | Param, Var | Function) | (Param, Param)
+    )
+}
+
+/// Return true if a binding of kind `kind` declared in a scope which isn't a var scope conflicts
+/// with a `var` declaration of the same name hoisted out of that scope.
+fn conflicts_with_hoisted_var(kind: BindingKind) -> bool {
+    kind.is_lexical() || kind == BindingKind::Function
+}
+
+/// Creates the scopes and declares the bindings.
+struct Declarer<'a, 'gc> {
+    sem: &'a mut SemContext<'gc>,
+
+    /// The current scope, `None` before entering the program.
+    scope: Option<ScopeId>,
+
+    /// The first `var` declaration of every name hoisted out of each block scope.
+    hoisted: HashMap<(ScopeId, Atom), &'gc Node<'gc>>,
+
+    /// Whether the current code is strict mode code.
+    strict: bool,
+
+    /// The names of the function declarations in blocks of non-strict code which haven't been
+    /// hoisted yet.
+    block_functions: Vec<&'gc Node<'gc>>,
+}
+
+impl<'a, 'gc> Declarer<'a, 'gc> {
+    fn cur_scope(&self) -> ScopeId {
+        self.scope.expect("no current scope")
+    }
+
+    /// Create a scope of the specified `kind` for `node` and run `f` in it.
+    fn in_new_scope<F: FnOnce(&mut Self)>(&mut self, kind: ScopeKind, node: &'gc Node<'gc>, f: F) {
+        let id = ScopeId(self.sem.scopes.len() as u32);
+        let var_scope = match self.scope {
+            Some(parent) if !kind.is_var_scope() => self.sem.scope(parent).var_scope,
+            _ => id,
+        };
+        self.sem.scopes.push(Scope {
+            kind,
+            node,
+            parent: self.scope,
+            var_scope,
+            bindings: Vec::new(),
+            dynamic: false,
+            names: HashMap::new(),
+        });
+        self.sem.node_scopes.insert(NodeRef(node), id);
+
+        let saved = self.scope.replace(id);
+        f(self);
+        self.scope = saved;
+    }
+
+    /// Declare the identifier `ident` as a binding of the specified `kind` in `scope`.
+    /// `decl` is the declaration containing the identifier.
+    fn declare(
+        &mut self,
+        scope: ScopeId,
+        ident: &'gc Node<'gc>,
+        kind: BindingKind,
+        decl: &'gc Node<'gc>,
+    ) {
+        let name = match ident {
+            Node::Identifier(Identifier { name, .. }) => *name,
+            _ => return,
+        };
+        if kind == BindingKind::Var {
+            self.hoist_var(scope, ident, name);
+        } else if conflicts_with_hoisted_var(kind) {
+            if let Some(&var) = self.hoisted.get(&(scope, name)) {
+                self.sem.redeclarations.push((ident, var));
+            }
+            if let Some(param) = self.catch_param(scope, name) {
+                self.sem.redeclarations.push((ident, param));
+            }
+        }
+        let existing = self.sem.scope(scope).binding(name);
+        let id = match existing {
+            Some(id) if can_redeclare(self.sem.binding(id).kind, kind) => {
+                if kind == BindingKind::Param {
+                    let function = self.sem.scope(scope).node;
+                    let prev = self.sem.binding(id).declarations[0];
+                    self.sem
+                        .duplicate_params
+                        .entry(NodeRef(function))
+                        .or_default()
+                        .push((ident, prev));
+                }
+                let binding = &mut self.sem.bindings[id.index()];
+                binding.declarations.push(ident);
+                if kind == BindingKind::Function && binding.kind == BindingKind::Var {
+                    binding.kind = kind;
+                }
+                id
+            }
+            _ => {
+                if let Some(prev) = existing {
+                    // The name of a function expression may be shadowed by its parameters and
+                    // body declarations.
+                    let prev = self.sem.binding(prev);
+                    if prev.kind != BindingKind::FunctionName {
+                        self.sem.redeclarations.push((ident, prev.declarations[0]));
+                    }
+                }
+                let id = BindingId(self.sem.bindings.len() as u32);
+                self.sem.bindings.push(Binding {
+                    name,
+                    kind,
+                    scope,
+                    declarations: vec![ident],
+                    references: Vec::new(),
+                    decl_range: if kind.is_lexical() {
+                        Some(*decl.range())
+                    } else {
+                        None
+                    },
+                });
+                let scope = &mut self.sem.scopes[scope.index()];
+                scope.bindings.push(id);
+                scope.names.insert(name, id);
+                id
+            }
+        };
+        self.sem
+            .resolutions
+            .insert(NodeRef(ident), Resolution::Binding(id));
+    }
+
+    /// Return the catch parameter named `name` if `scope` is the body of a catch clause,
+    /// whose lexical declarations can't redeclare its parameters.
+    fn catch_param(&self, scope: ScopeId, name: Atom) -> Option<&'gc Node<'gc>> {
+        let block = self.sem.scope(scope);
+        let catch = self.sem.scope(block.parent?);
+        match catch.node {
+            Node::CatchClause(CatchClause { body, .. }) if std::ptr::eq(*body, block.node) => catch
+                .binding(name)
+                .map(|id| self.sem.binding(id).declarations[0]),
+            _ => None,
+        }
+    }
+
+    /// Record the `var` declaration `ident` of `name`, which is hoisted from the current scope
+    /// to `var_scope`, in the block scopes in between, and check that it doesn't conflict with
+    /// their declarations.
+    fn hoist_var(&mut self, var_scope: ScopeId, ident: &'gc Node<'gc>, name: Atom) {
+        let mut cur = self.cur_scope();
+        while cur != var_scope {
+            let scope = self.sem.scope(cur);
+            let conflict = scope
+                .binding(name)
+                .map(|prev| self.sem.binding(prev))
+                .filter(|prev| conflicts_with_hoisted_var(prev.kind))
+                .map(|prev| prev.declarations[0]);
+            let parent = scope.parent.expect("var scope must be an enclosing scope");
+            if let Some(prev) = conflict {
+                self.sem.redeclarations.push((ident, prev));
+            }
+            self.hoisted.entry((cur, name)).or_insert(ident);
+            cur = parent;
+        }
+    }
+
+    /// Hoist the block functions declared from index `start` of `block_functions` to the current
+    /// var scope, unless a declaration of the same name in the var scope or in a block in between
+    /// would conflict with a `var` declaration, or it is a parameter.
+    fn hoist_block_functions(&mut self, start: usize) {
+        let var_scope = self.cur_scope();
+        for ident in self.block_functions.split_off(start) {
+            let id = match self.sem.resolution(ident) {
+                Some(Resolution::Binding(id)) => id,
+                _ => unreachable!("block functions must be declared"),
+            };
+            let name = self.sem.binding(id).name;
+            let block = self.sem.binding(id).scope;
+            let mut cur = self.sem.scope(block).parent;
+            let mut conflict = false;
+            while let Some(scope) = cur.filter(|&scope| scope != var_scope) {
+                conflict |= self.sem.scope(scope).binding(name).is_some();
+                cur = self.sem.scope(scope).parent;
+            }
+            // A `var` may shadow the name of a function expression.
+            let existing = self
+                .sem
+                .scope(var_scope)
+                .binding(name)
+                .filter(|&prev| self.sem.binding(prev).kind != BindingKind::FunctionName);
+            let redeclarable = existing.is_none_or(|prev| {
+                let kind = self.sem.binding(prev).kind;
+                kind != BindingKind::Param && can_redeclare(kind, BindingKind::Function)
+            });
+            if conflict || !redeclarable {
+                continue;
+            }
+
+            let block_scope = &mut self.sem.scopes[block.index()];
+            block_scope.bindings.retain(|&binding| binding != id);
+            block_scope.names.remove(&name);
+            match existing {
+                // The declarations are merged into the existing binding, and the block binding
+                // is left without declarations.
+                Some(prev) => {
+                    let declarations =
+                        std::mem::take(&mut self.sem.bindings[id.index()].declarations);
+                    for &decl in &declarations {
+                        self.sem
+                            .resolutions
+                            .insert(NodeRef(decl), Resolution::Binding(prev));
+                    }
+                    let binding = &mut self.sem.bindings[prev.index()];
+                    binding.declarations.extend(declarations);
+                    binding.kind = BindingKind::Function;
+                }
+                None => {
+                    self.sem.bindings[id.index()].scope = var_scope;
+                    let scope = &mut self.sem.scopes[var_scope.index()];
+                    scope.bindings.push(id);
+                    scope.names.insert(name, id);
+                }
+            }
+        }
+    }
+
+    /// Declare all identifiers in `pattern`.
+    fn declare_pattern(
+        &mut self,
+        scope: ScopeId,
+        pattern: &'gc Node<'gc>,
+        kind: BindingKind,
+        decl: &'gc Node<'gc>,
+    ) {
+        let mut ids = Vec::new();
+        pattern_ids(pattern, &mut ids);
+        for ident in ids {
+            self.declare(scope, ident, kind, decl);
+        }
+    }
+
+    /// Mark the current scope and its ancestors as dynamic.
+    fn mark_dynamic(&mut self) {
+        let mut cur = self.scope;
+        while let Some(id) = cur {
+            let scope = &mut self.sem.scopes[id.index()];
+            scope.dynamic = true;
+            cur = scope.parent;
+        }
+    }
+
+    /// Create the scope of a function and declare its name (for function expressions) and its
+    /// parameters. The body shares the scope of the parameters.
+    fn function(
+        &mut self,
+        ctx: &'gc GCContext,
+        node: &'gc Node<'gc>,
+        name: Option<&'gc Node<'gc>>,
+        params: &'gc NodeList<'gc>,
+        body: &'gc Node<'gc>,
+    ) {
+        let saved_strict = self.strict;
+        if let Node::BlockStatement(BlockStatement { body: stmts, .. }) = body {
+            self.strict |= has_use_strict(stmts);
+        }
+        let start = self.block_functions.len();
+        self.in_new_scope(ScopeKind::Function, node, |this| {
+            let scope = this.cur_scope();
+            if let Some(name) = name {
+                this.declare(scope, name, BindingKind::FunctionName, node);
+            }
+            for param in params {
+                this.declare_pattern(scope, param, BindingKind::Param, param);
+            }
+            for param in params {
+                param.visit(ctx, this, Some(node));
+            }
+            match body {
+                Node::BlockStatement(BlockStatement { body: stmts, .. }) => {
+                    for stmt in stmts {
+                        stmt.visit(ctx, this, Some(body));
+                    }
+                }
+                _ => body.visit(ctx, this, Some(node)),
+            }
+            this.hoist_block_functions(start);
+        });
+        self.strict = saved_strict;
+    }
+}
+
+impl<'a, 'gc> Visitor<'gc> for Declarer<'a, 'gc> {
+    fn call(&mut self, ctx: &'gc GCContext, node: &'gc Node<'gc>, _parent: Option<&'gc Node<'gc>>) {
+        match node {
+            _ if is_type(node) => {}
+            Node::Program(Program { body, .. }) => {
+                let kind = if is_module(body) {
+                    ScopeKind::Module
+                } else {
+                    ScopeKind::Global
+                };
+                self.strict =
+                    ctx.ctx().strict_mode() || kind == ScopeKind::Module || has_use_strict(body);
+                self.in_new_scope(kind, node, |this| {
+                    node.visit_children(ctx, this);
+                    this.hoist_block_functions(0);
+                });
+            }
+            Node::FunctionDeclaration(FunctionDeclaration {
+                id,
+                params,
+                body,
+                generator,
+                is_async,
+                ..
+            }) => {
+                if let Some(id) = id {
+                    let scope = self.cur_scope();
+                    self.declare(scope, id, BindingKind::Function, node);
+                    if !self.strict
+                        && !self.sem.scope(scope).kind.is_var_scope()
+                        && !generator
+                        && !is_async
+                    {
+                        self.block_functions.push(id);
+                    }
+                }
+                self.function(ctx, node, None, params, body);
+            }
+            Node::FunctionExpression(FunctionExpression {
+                id, params, body, ..
+            }) => self.function(ctx, node, *id, params, body),
+            Node::ArrowFunctionExpression(ArrowFunctionExpression { params, body, .. }) => {
+                self.function(ctx, node, None, params, body)
+            }
+            Node::ClassDeclaration(ClassDeclaration { id, .. }) => {
+                if let Some(id) = id {
+                    self.declare(self.cur_scope(), id, BindingKind::Class, node);
+                }
+                // All parts of a class are strict mode code.
+                let saved_strict = std::mem::replace(&mut self.strict, true);
+                self.in_new_scope(ScopeKind::Class, node, |this| {
+                    node.visit_children(ctx, this)
+                });
+                self.strict = saved_strict;
+            }
+            Node::ClassExpression(ClassExpression { id, .. }) => {
+                let saved_strict = std::mem::replace(&mut self.strict, true);
+                self.in_new_scope(ScopeKind::Class, node, |this| {
+                    if let Some(id) = id {
+                        this.declare(this.cur_scope(), id, BindingKind::ClassName, node);
+                    }
+                    node.visit_children(ctx, this)
+                });
+                self.strict = saved_strict;
+            }
+            Node::VariableDeclaration(VariableDeclaration {
+                kind, declarations, ..
+            }) => {
+                let cur = self.cur_scope();
+                let (scope, kind) = match kind {
+                    VariableDeclarationKind::Var => {
+                        (self.sem.scope(cur).var_scope, BindingKind::Var)
+                    }
+                    VariableDeclarationKind::Let => (cur, BindingKind::Let),
+                    VariableDeclarationKind::Const => (cur, BindingKind::Const),
+                };
+                for decl in declarations {
+                    if let Node::VariableDeclarator(VariableDeclarator { id, .. }) = decl {
+                        self.declare_pattern(scope, id, kind, decl);
+                    }
+                }
+                node.visit_children(ctx, self);
+            }
+            Node::CatchClause(CatchClause { param, .. }) => {
+                self.in_new_scope(ScopeKind::Catch, node, |this| {
+                    if let Some(param) = param {
+                        this.declare_pattern(
+                            this.cur_scope(),
+                            param,
+                            BindingKind::CatchParam,
+                            node,
+                        );
+                    }
+                    node.visit_children(ctx, this);
+                });
+            }
+            Node::BlockStatement(_)
+            | Node::ForStatement(_)
+            | Node::ForInStatement(_)
+            | Node::ForOfStatement(_) => {
+                self.in_new_scope(ScopeKind::Block, node, |this| {
+                    node.visit_children(ctx, this)
+                });
+            }
+            Node::SwitchStatement(SwitchStatement {
+                discriminant,
+                cases,
+                ..
+            }) => {
+                discriminant.visit(ctx, self, Some(node));
+                self.in_new_scope(ScopeKind::Block, node, |this| {
+                    for case in cases {
+                        case.visit(ctx, this, Some(node));
+                    }
+                });
+            }
+            Node::ImportDeclaration(ImportDeclaration { specifiers, .. }) => {
+                for spec in specifiers {
+                    match spec {
+                        Node::ImportSpecifier(ImportSpecifier { local, .. })
+                        | Node::ImportDefaultSpecifier(ImportDefaultSpecifier { local, .. })
+                        | Node::ImportNamespaceSpecifier(ImportNamespaceSpecifier {
+                            local, ..
+                        }) => self.declare(self.cur_scope(), local, BindingKind::Import, spec),
+                        _ => {}
+                    }
+                }
+            }
+            Node::WithStatement(_) => {
+                self.mark_dynamic();
+                node.visit_children(ctx, self);
+            }
+            Node::CallExpression(CallExpression {
+                callee: Node::Identifier(Identifier { name, .. }),
+                ..
+            }) if ctx.str(*name) == "eval" => {
+                // A direct eval can access all variables in scope.
+                self.mark_dynamic();
+                node.visit_children(ctx, self);
+            }
+            _ => node.visit_children(ctx, self),
+        }
+    }
+}
+
+/// Return true if the identifier `node`, which is a child of `parent`, may refer to a variable.
+/// Declarations are resolved by the [`Declarer`] and are not considered here.
+fn is_reference<'gc>(node: &'gc Node<'gc>, parent: Option<&'gc Node<'gc>>) -> bool {
+    let is = |child: &'gc Node<'gc>| std::ptr::eq(node, child);
+    match parent {
+        Some(Node::MemberExpression(MemberExpression {
+            property,
+            computed: false,
+            ..
+        }))
+        | Some(Node::OptionalMemberExpression(OptionalMemberExpression {
+            property,
+            computed: false,
+            ..
+        })) => !is(property),
+        // The value of a shorthand property may be the same node as the key.
+        Some(Node::Property(Property {
+            key,
+            value,
+            computed: false,
+            ..
+        })) => !is(key) || is(value),
+        Some(Node::MethodDefinition(MethodDefinition {
+            key,
+            computed: false,
+            ..
+        }))
+        | Some(Node::ClassProperty(ClassProperty {
+            key,
+            computed: false,
+            ..
+        })) => !is(key),
+        Some(Node::LabeledStatement(_))
+        | Some(Node::BreakStatement(_))
+        | Some(Node::ContinueStatement(_))
+        | Some(Node::MetaProperty(_))
+        | Some(Node::PrivateName(_))
+        | Some(Node::ImportSpecifier(_))
+        | Some(Node::ExportNamespaceSpecifier(_)) => false,
+        Some(Node::ExportSpecifier(spec)) => is(spec.local),
+        _ => true,
+    }
+}
+
+/// Return true if the JSX identifier `node`, which is a child of `parent`, refers to a variable:
+/// the name of a component, as opposed to an intrinsic element like `div` or an attribute.
+fn is_jsx_reference<'gc>(node: &'gc Node<'gc>, parent: Option<&'gc Node<'gc>>, name: &str) -> bool {
+    match parent {
+        Some(Node::JSXOpeningElement(_)) | Some(Node::JSXClosingElement(_)) => {
+            !name.starts_with(|c: char| c.is_ascii_lowercase())
+        }
+        Some(Node::JSXMemberExpression(expr)) => std::ptr::eq(node, expr.object),
+        _ => false,
+    }
+}
+
+/// Resolves the references once all bindings have been declared.
+struct Resolver<'a, 'gc> {
+    sem: &'a mut SemContext<'gc>,
+
+    /// The visible binding of every name.
+    names: ScopedHashMap<Atom, BindingId>,
+
+    /// The current scope.
+    scope: ScopeId,
+}
+
+impl<'a, 'gc> Resolver<'a, 'gc> {
+    /// If `node` introduces a scope, make its bindings visible while running `f`.
+    fn in_node_scope<F: FnOnce(&mut Self)>(&mut self, node: &'gc Node<'gc>, f: F) {
+        let id = match self.sem.node_scope(node) {
+            Some(id) => id,
+            None => return f(self),
+        };
+        self.names.push_scope();
+        for &binding in &self.sem.scope(id).bindings {
+            self.names.insert(self.sem.binding(binding).name, binding);
+        }
+        let saved = std::mem::replace(&mut self.scope, id);
+        f(self);
+        self.scope = saved;
+        self.names.pop_scope();
+    }
+
+    /// Resolve the reference `node` to the variable `name`.
+    fn reference(&mut self, node: &'gc Node<'gc>, name: Atom, kind: ReferenceKind) {
+        let mut reference = Reference {
+            node,
+            kind,
+            maybe_in_tdz: false,
+        };
+        let resolution = match self.names.get(&name).copied() {
+            Some(id) => {
+                reference.maybe_in_tdz = self.maybe_in_tdz(id, node);
+                self.sem.bindings[id.index()].references.push(reference);
+                Resolution::Binding(id)
+            }
+            None => {
+                self.sem.globals.entry(name).or_default().push(reference);
+                Resolution::Global(name)
+            }
+        };
+        self.sem.resolutions.insert(NodeRef(node), resolution);
+    }
+
+    /// Return true if the reference `node` to `binding` from the current scope may be evaluated
+    /// before the binding is initialized.
+    fn maybe_in_tdz(&self, binding: BindingId, node: &'gc Node<'gc>) -> bool {
+        let binding = self.sem.binding(binding);
+        let decl_range = match binding.decl_range {
+            Some(range) => range,
+            None => return false,
+        };
+        // Cases of a switch may be evaluated in any order.
+        if let Node::SwitchStatement(_) = self.sem.scope(binding.scope).node {
+            return true;
+        }
+        // Find the outermost function between the reference and the declaration.
+        let mut closure = None;
+        let mut cur = self.scope;
+        while cur != binding.scope {
+            let scope = self.sem.scope(cur);
+            if scope.kind == ScopeKind::Function {
+                closure = Some(scope.node);
+            }
+            cur = scope.parent.expect("binding must be in an enclosing scope");
+        }
+        let evaluated_at = match closure {
+            None => node,
+            // A function declaration can be called as soon as its scope is entered.
+            Some(Node::FunctionDeclaration(_)) => return true,
+            // A closure may be called as soon as it is created.
+            Some(closure) => closure,
+        };
+        let range = evaluated_at.range();
+        !range.file.is_valid() || range.file != decl_range.file || range.start <= decl_range.end
+    }
+
+    /// Visit the assignment target `node`, a child of `parent`, which is assigned as
+    /// specified by `kind`.
+    fn target(
+        &mut self,
+        ctx: &'gc GCContext,
+        node: &'gc Node<'gc>,
+        parent: &'gc Node<'gc>,
+        kind: ReferenceKind,
+    ) {
+        match node {
+            Node::Identifier(Identifier { name, .. }) => self.reference(node, *name, kind),
+            Node::ObjectPattern(ObjectPattern { properties, .. }) => {
+                for prop in properties {
+                    match prop {
+                        Node::Property(Property {
+                            key,
+                            value,
+                            computed,
+                            ..
+                        }) => {
+                            if *computed {
+                                key.visit(ctx, self, Some(prop));
+                            }
+                            self.target(ctx, value, prop, kind);
+                        }
+                        _ => self.target(ctx, prop, node, kind),
+                    }
+                }
+            }
+            Node::ArrayPattern(ArrayPattern { elements, .. }) => {
+                for elem in elements {
+                    self.target(ctx, elem, node, kind);
+                }
+            }
+            Node::RestElement(RestElement { argument, .. }) => {
+                self.target(ctx, argument, node, kind)
+            }
+            Node::AssignmentPattern(AssignmentPattern { left, right, .. }) => {
+                self.target(ctx, left, node, kind);
+                right.visit(ctx, self, Some(node));
+            }
+            _ => node.visit(ctx, self, Some(parent)),
+        }
+    }
+}
+
+impl<'a, 'gc> Visitor<'gc> for Resolver<'a, 'gc> {
+    fn call(&mut self, ctx: &'gc GCContext, node: &'gc Node<'gc>, parent: Option<&'gc Node<'gc>>) {
+        match node {
+            _ if is_type(node) => {}
+            Node::Identifier(Identifier { name, .. }) => {
+                if !self.sem.resolutions.contains_key(&NodeRef(node)) && is_reference(node, parent)
+                {
+                    self.reference(node, *name, ReferenceKind::Read);
+                }
+            }
+            Node::JSXIdentifier(JSXIdentifier { name, .. }) => {
+                if is_jsx_reference(node, parent, ctx.str(*name)) {
+                    self.reference(node, *name, ReferenceKind::Read);
+                }
+            }
+            Node::AssignmentExpression(AssignmentExpression {
+                operator,
+                left,
+                right,
+                ..
+            }) => {
+                let kind = if *operator == AssignmentExpressionOperator::Assign {
+                    ReferenceKind::Write
+                } else {
+                    ReferenceKind::ReadWrite
+                };
+                self.target(ctx, left, node, kind);
+                right.visit(ctx, self, Some(node));
+            }
+            Node::UpdateExpression(UpdateExpression { argument, .. }) => {
+                self.target(ctx, argument, node, ReferenceKind::ReadWrite)
+            }
+            Node::ForInStatement(ForInStatement {
+                left, right, body, ..
+            })
+            | Node::ForOfStatement(ForOfStatement {
+                left, right, body, ..
+            }) => self.in_node_scope(node, |this| {
+                match left {
+                    Node::VariableDeclaration(_) => left.visit(ctx, this, Some(node)),
+                    _ => this.target(ctx, left, node, ReferenceKind::Write),
+                }
+                right.visit(ctx, this, Some(node));
+                body.visit(ctx, this, Some(node));
+            }),
+            Node::SwitchStatement(SwitchStatement {
+                discriminant,
+                cases,
+                ..
+            }) => {
+                discriminant.visit(ctx, self, Some(node));
+                self.in_node_scope(node, |this| {
+                    for case in cases {
+                        case.visit(ctx, this, Some(node));
+                    }
+                });
+            }
+            // Re-exports don't refer to local variables.
+            Node::ExportNamedDeclaration(ExportNamedDeclaration {
+                source: Some(_), ..
+            }) => {}
+            _ => self.in_node_scope(node, |this| node.visit_children(ctx, this)),
+        }
+    }
+}
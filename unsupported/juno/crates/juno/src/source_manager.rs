@@ -6,7 +6,11 @@
  */
 
 use crate::ast::SourceRange;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::io::{self, Write};
 use std::rc::Rc;
+use support::json::{JSONEmitter, Pretty};
 use support::NullTerminatedBuf;
 
 /// An opaque value identifying a source registered with SourceManager.
@@ -25,11 +29,243 @@ impl SourceId {
     }
 }
 
+/// The severity of a diagnostic.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DiagLevel {
+    Error,
+    Warning,
+    Note,
+}
+
+impl DiagLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DiagLevel::Error => "error",
+            DiagLevel::Warning => "warning",
+            DiagLevel::Note => "note",
+        }
+    }
+}
+
+impl fmt::Display for DiagLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A single diagnostic message together with any notes attached to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub level: DiagLevel,
+    pub range: SourceRange,
+    pub message: String,
+    /// Additional notes elaborating on the diagnostic, for example pointing
+    /// to a previous declaration.
+    pub notes: Vec<Diagnostic>,
+}
+
+impl Diagnostic {
+    pub fn new<S: Into<String>>(level: DiagLevel, range: SourceRange, msg: S) -> Diagnostic {
+        Diagnostic {
+            level,
+            range,
+            message: msg.into(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn error<S: Into<String>>(range: SourceRange, msg: S) -> Diagnostic {
+        Self::new(DiagLevel::Error, range, msg)
+    }
+
+    pub fn warning<S: Into<String>>(range: SourceRange, msg: S) -> Diagnostic {
+        Self::new(DiagLevel::Warning, range, msg)
+    }
+
+    /// Attach a note at the specified range.
+    pub fn with_note<S: Into<String>>(mut self, range: SourceRange, msg: S) -> Diagnostic {
+        self.notes.push(Self::new(DiagLevel::Note, range, msg));
+        self
+    }
+}
+
+/// The destination of all diagnostics reported to a [`SourceManager`].
+pub trait DiagnosticSink {
+    /// Handle a single diagnostic, including its notes. `sm` is the reporting
+    /// source manager, which can be used to obtain source names and contents.
+    fn report(&mut self, sm: &SourceManager, diag: &Diagnostic);
+}
+
+/// A sink printing diagnostics in human readable form, followed by the
+/// relevant source line with the range underlined.
+#[derive(Debug)]
+pub struct TextSink<W: Write> {
+    out: W,
+}
+
+impl<W: Write> TextSink<W> {
+    pub fn new(out: W) -> Self {
+        TextSink { out }
+    }
+
+    fn print(&mut self, sm: &SourceManager, diag: &Diagnostic) -> io::Result<()> {
+        let range = &diag.range;
+        if !range.file.is_valid() {
+            return writeln!(self.out, "{}: {}", diag.level, diag.message);
+        }
+        if range.start.line == 0 {
+            return writeln!(
+                self.out,
+                "{}: {}: {}",
+                sm.source_name(range.file),
+                diag.level,
+                diag.message
+            );
+        }
+        writeln!(
+            self.out,
+            "{}:{}:{}: {}: {}",
+            sm.source_name(range.file),
+            range.start.line,
+            range.start.col,
+            diag.level,
+            diag.message
+        )?;
+        if let Some(line) = sm.source_line(range.file, range.start.line) {
+            let line = String::from_utf8_lossy(line);
+            writeln!(self.out, "{}", line)?;
+            writeln!(self.out, "{}", underline(&line, range))?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> DiagnosticSink for TextSink<W> {
+    fn report(&mut self, sm: &SourceManager, diag: &Diagnostic) {
+        // There isn't much we can do if printing a diagnostic fails.
+        let _ = self.print(sm, diag);
+        for note in &diag.notes {
+            let _ = self.print(sm, note);
+        }
+    }
+}
+
+/// A sink printing every diagnostic as a single line JSON object, suitable
+/// for consumption by tools.
+#[derive(Debug)]
+pub struct JSONSink<W: Write> {
+    out: W,
+}
+
+impl<W: Write> JSONSink<W> {
+    pub fn new(out: W) -> Self {
+        JSONSink { out }
+    }
+
+    fn emit_fields<O: Write>(emitter: &mut JSONEmitter<O>, sm: &SourceManager, diag: &Diagnostic) {
+        let range = &diag.range;
+        emitter.emit_key("severity");
+        emitter.emit_string(diag.level.as_str());
+        emitter.emit_key("file");
+        if range.file.is_valid() {
+            emitter.emit_string(sm.source_name(range.file));
+        } else {
+            emitter.emit_null();
+        }
+        emitter.emit_key("startLine");
+        emitter.emit_number(range.start.line.into());
+        emitter.emit_key("startColumn");
+        emitter.emit_number(range.start.col.into());
+        emitter.emit_key("endLine");
+        emitter.emit_number(range.end.line.into());
+        emitter.emit_key("endColumn");
+        emitter.emit_number(range.end.col.into());
+        emitter.emit_key("message");
+        emitter.emit_string(&diag.message);
+    }
+}
+
+impl<W: Write> DiagnosticSink for JSONSink<W> {
+    fn report(&mut self, sm: &SourceManager, diag: &Diagnostic) {
+        let mut emitter = JSONEmitter::new(&mut self.out, Pretty::No);
+        emitter.open_dict();
+        Self::emit_fields(&mut emitter, sm, diag);
+        emitter.emit_key("notes");
+        emitter.open_array();
+        for note in &diag.notes {
+            emitter.open_dict();
+            Self::emit_fields(&mut emitter, sm, note);
+            emitter.close_dict();
+        }
+        emitter.close_array();
+        emitter.close_dict();
+        // There isn't much we can do if printing a diagnostic fails.
+        let _ = emitter.end();
+    }
+}
+
+/// Build the line marking `range` within `line`: a caret at the start followed
+/// by tildes until the end of the range (or the end of the line if the range
+/// spans multiple lines). Columns are 1-based byte offsets. Tabs are
+/// preserved so the marker lines up with the source line.
+fn underline(line: &str, range: &SourceRange) -> String {
+    let start = (range.start.col as usize).saturating_sub(1);
+    let end = if range.end.line == range.start.line && range.end.col > range.start.col {
+        range.end.col as usize - 1
+    } else if range.end.line > range.start.line {
+        line.len().saturating_sub(1)
+    } else {
+        start
+    };
+
+    let mut res = String::new();
+    for (offset, ch) in line.char_indices() {
+        if offset > end {
+            break;
+        }
+        res.push(if offset < start {
+            if ch == '\t' {
+                '\t'
+            } else {
+                ' '
+            }
+        } else if offset == start {
+            '^'
+        } else {
+            '~'
+        });
+    }
+    // The location may be just past the end of the line (e.g. an unexpected
+    // end of input).
+    if start >= line.len() {
+        res.push('^');
+    }
+    res
+}
+
 /// SourceManager owns a collection of source buffers and their names and handles
 /// reporting errors.
-#[derive(Debug, Default)]
+///
+/// Diagnostics are forwarded to a [`DiagnosticSink`], which defaults to printing
+/// them to stderr. The number of errors and warnings is recorded and can be
+/// queried at any time.
+#[derive(Default)]
 pub struct SourceManager {
     sources: Vec<(String, Rc<NullTerminatedBuf>)>,
+    /// Where to send diagnostics. `None` means a `TextSink` writing to stderr.
+    sink: RefCell<Option<Box<dyn DiagnosticSink>>>,
+    num_errors: Cell<usize>,
+    num_warnings: Cell<usize>,
+}
+
+impl fmt::Debug for SourceManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SourceManager")
+            .field("sources", &self.sources)
+            .field("num_errors", &self.num_errors)
+            .field("num_warnings", &self.num_warnings)
+            .finish()
+    }
 }
 
 impl SourceManager {
@@ -62,49 +298,69 @@ impl SourceManager {
         Rc::clone(&self.sources[source_id.as_usize()].1)
     }
 
+    /// Return the contents of the specified 1-based line of a source buffer,
+    /// without the line terminator, or None if there is no such line.
+    pub fn source_line(&self, source_id: SourceId, line: u32) -> Option<&[u8]> {
+        let buf = self.source_buffer(source_id).as_bytes();
+        // Exclude the terminating zero.
+        let buf = &buf[..buf.len() - 1];
+        let line = buf
+            .split(|&c| c == b'\n')
+            .nth(line.checked_sub(1)? as usize)?;
+        Some(line.strip_suffix(b"\r").unwrap_or(line))
+    }
+
+    /// Replace the destination of all subsequently reported diagnostics.
+    pub fn set_diagnostic_sink(&mut self, sink: Box<dyn DiagnosticSink>) {
+        *self.sink.get_mut() = Some(sink);
+    }
+
+    /// Number of errors reported so far.
+    pub fn num_errors(&self) -> usize {
+        self.num_errors.get()
+    }
+
+    /// Number of warnings reported so far.
+    pub fn num_warnings(&self) -> usize {
+        self.num_warnings.get()
+    }
+
+    /// Report a diagnostic, together with its notes.
+    pub fn report(&self, diag: Diagnostic) {
+        // NOTE: this method deliberately takes immutable `self`. A SourceManager
+        // should be easily shareable, so the mutable state uses interior mutability.
+        match diag.level {
+            DiagLevel::Error => self.num_errors.set(self.num_errors.get() + 1),
+            DiagLevel::Warning => self.num_warnings.set(self.num_warnings.get() + 1),
+            DiagLevel::Note => {}
+        }
+        let mut sink = self.sink.borrow_mut();
+        match sink.as_mut() {
+            Some(sink) => sink.report(self, &diag),
+            None => TextSink::new(io::stderr()).report(self, &diag),
+        }
+    }
+
     /// Report an error at the specified range in the specified source buffer.
     pub fn error<S: Into<String>>(&self, range: SourceRange, msg: S) {
-        // NOTE: this method deliberately takes immutable `self`. A SourceManager
-        // should be easily shareable. It will be implemented using interior
-        // mutability.
-        eprintln!(
-            "{}:{}:{}: error: {}",
-            self.source_name(range.file),
-            range.start.line,
-            range.start.col,
-            msg.into()
-        );
+        self.report(Diagnostic::error(range, msg))
     }
+
+    /// Report a standalone note at the specified range in the specified source buffer.
     pub fn note<S: Into<String>>(&self, range: SourceRange, msg: S) {
-        // NOTE: this method deliberately takes immutable `self`. A SourceManager
-        // should be easily shareable. It will be implemented using interior
-        // mutability.
-        eprintln!(
-            "{}:{}:{}: note: {}",
-            self.source_name(range.file),
-            range.start.line,
-            range.start.col,
-            msg.into()
-        );
+        self.report(Diagnostic::new(DiagLevel::Note, range, msg))
     }
+
     /// Report a warning at the specified range in the specified source buffer.
     pub fn warning<S: Into<String>>(&self, range: SourceRange, msg: S) {
-        // NOTE: this method deliberately takes immutable `self`. A SourceManager
-        // should be easily shareable. It will be implemented using interior
-        // mutability.
-        eprintln!(
-            "{}:{}:{}: warning: {}",
-            self.source_name(range.file),
-            range.start.line,
-            range.start.col,
-            msg.into()
-        );
+        self.report(Diagnostic::warning(range, msg))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast::SourceLoc;
 
     #[test]
     fn smoke_test() {
@@ -123,4 +379,105 @@ mod tests {
         assert_eq!(2, buf1.len());
         assert_eq!(b"a\0", buf1.as_bytes());
     }
+
+    /// A sink which records the diagnostics in a shared vector.
+    struct Recorder(Rc<RefCell<Vec<Diagnostic>>>);
+
+    impl DiagnosticSink for Recorder {
+        fn report(&mut self, _sm: &SourceManager, diag: &Diagnostic) {
+            self.0.borrow_mut().push(diag.clone());
+        }
+    }
+
+    fn range(file: SourceId, line: u32, start: u32, end: u32) -> SourceRange {
+        SourceRange {
+            file,
+            start: SourceLoc { line, col: start },
+            end: SourceLoc { line, col: end },
+        }
+    }
+
+    #[test]
+    fn counts_and_sink() {
+        let mut sm = SourceManager::new();
+        let id = sm.add_source("buf", NullTerminatedBuf::from_str_copy("var x;"));
+        let diags = Rc::new(RefCell::new(Vec::new()));
+        sm.set_diagnostic_sink(Box::new(Recorder(Rc::clone(&diags))));
+
+        sm.error(range(id, 1, 1, 3), "e1");
+        sm.warning(range(id, 1, 5, 5), "w1");
+        sm.report(Diagnostic::error(range(id, 1, 5, 5), "e2").with_note(range(id, 1, 1, 3), "n1"));
+        sm.note(range(id, 1, 1, 1), "n2");
+
+        assert_eq!(2, sm.num_errors());
+        assert_eq!(1, sm.num_warnings());
+
+        let diags = diags.borrow();
+        assert_eq!(4, diags.len());
+        assert_eq!(DiagLevel::Warning, diags[1].level);
+        assert_eq!("e2", diags[2].message);
+        assert_eq!(1, diags[2].notes.len());
+        assert_eq!(DiagLevel::Note, diags[2].notes[0].level);
+        assert_eq!(DiagLevel::Note, diags[3].level);
+    }
+
+    #[test]
+    fn text_rendering() {
+        let mut sm = SourceManager::new();
+        let id = sm.add_source(
+            "buf",
+            NullTerminatedBuf::from_str_copy("first line\r\n\tvar x = 1;\nlast"),
+        );
+        assert_eq!(b"first line", sm.source_line(id, 1).unwrap());
+        assert_eq!(b"last", sm.source_line(id, 3).unwrap());
+        assert!(sm.source_line(id, 4).is_none());
+
+        let mut out: Vec<u8> = Vec::new();
+        TextSink::new(&mut out).report(
+            &sm,
+            &Diagnostic::error(range(id, 2, 6, 10), "bad").with_note(range(id, 3, 1, 1), "here"),
+        );
+        assert_eq!(
+            "buf:2:6: error: bad\n\tvar x = 1;\n\t    ^~~~~\nbuf:3:1: note: here\nlast\n^\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn underline_synthesized_range() {
+        // Ranges synthesized by passes can start at column 0.
+        let id = SourceId(0);
+        assert_eq!("^~", underline("abc", &range(id, 1, 0, 2)));
+        assert_eq!("^", underline("abc", &range(id, 1, 0, 0)));
+    }
+
+    #[test]
+    fn json_rendering() {
+        let mut sm = SourceManager::new();
+        let id = sm.add_source("a\"b.js", NullTerminatedBuf::from_str_copy("var x;"));
+
+        let mut out: Vec<u8> = Vec::new();
+        let mut sink = JSONSink::new(&mut out);
+        sink.report(
+            &sm,
+            &Diagnostic::error(range(id, 1, 5, 5), "bad").with_note(range(id, 1, 1, 3), "here"),
+        );
+        sink.report(
+            &sm,
+            &Diagnostic::warning(range(SourceId::INVALID, 0, 0, 0), "w"),
+        );
+        assert_eq!(
+            concat!(
+                r#"{"severity":"error","file":"a\"b.js","startLine":1,"startColumn":5,"#,
+                r#""endLine":1,"endColumn":5,"message":"bad","notes":[{"severity":"note","#,
+                r#""file":"a\"b.js","startLine":1,"startColumn":1,"endLine":1,"endColumn":3,"#,
+                r#""message":"here"}]}"#,
+                "\n",
+                r#"{"severity":"warning","file":null,"startLine":0,"startColumn":0,"#,
+                r#""endLine":0,"endColumn":0,"message":"w","notes":[]}"#,
+                "\n",
+            ),
+            String::from_utf8(out).unwrap()
+        );
+    }
 }
@@ -5,44 +5,538 @@
  * LICENSE file in the root directory of this source tree.
  */
 
-use sourcemap::{SourceMap, SourceMapBuilder};
-
-/// Return a merged version of the `input` and `output` source maps.
-/// Currently this only supports a single input source map.
-/// The function works by iterating through every `Token` in `output` and attempting
-/// to look it up in `input` (use the `lookup_token` method to get the closest token near it).
-/// If `input` does not contain the token, use the `output` token.
-/// Currently the only metadata retained from the `input` map is `names` and `sources`.
-/// TODO: Add support for other sourcemap metadata fields such as `metadata` and `sourcesContent`.
-pub fn merge_sourcemaps(input: &SourceMap, output: &SourceMap) -> SourceMap {
-    let mut merged = SourceMapBuilder::new(output.get_file());
+//! Source map composition.
+//!
+//! A file is often processed by several tools in sequence (e.g. Babel, then Metro, then Juno),
+//! each of which produces a source map from its input to its output. Composing those maps
+//! yields a single map from the final output directly to the original sources.
+
+use crate::source_manager::{SourceId, SourceManager};
+use serde_json::Value;
+use sourcemap::{DecodedMap, Error, SourceMap, SourceMapBuilder, Token};
+use std::collections::HashMap;
+use std::io::Write;
+
+/// The name of the source map field listing the indices of sources which debuggers should
+/// ignore (typically library or generated code).
+const IGNORE_LIST_KEY: &str = "x_google_ignoreList";
+
+/// A source map together with the metadata which `sourcemap::SourceMap` does not retain.
+#[derive(Debug, Clone)]
+pub struct SourceMapInfo {
+    /// The actual mappings, sources, names and source contents.
+    pub map: SourceMap,
+    /// Indices into the sources of `map` which are listed in `x_google_ignoreList`.
+    pub ignore_list: Vec<u32>,
+}
+
+impl From<SourceMap> for SourceMapInfo {
+    fn from(map: SourceMap) -> Self {
+        SourceMapInfo {
+            map,
+            ignore_list: Vec::new(),
+        }
+    }
+}
 
-    for name in input.names() {
-        merged.add_name(name);
+impl SourceMapInfo {
+    /// Parse a source map from JSON. Indexed (sectioned) maps are flattened into a regular map,
+    /// so every section must embed its map rather than refer to it by URL.
+    pub fn from_slice(slice: &[u8]) -> Result<SourceMapInfo, Error> {
+        let json: Value = serde_json::from_slice(slice)?;
+        match sourcemap::decode_slice(slice)? {
+            DecodedMap::Regular(map) => Ok(SourceMapInfo {
+                ignore_list: parse_ignore_list(&json),
+                map,
+            }),
+            decoded => flatten(&decoded, &json),
+        }
     }
 
-    let mut output_src_id = 0;
-    for src in input.sources() {
-        output_src_id = merged.add_source(src) + 1;
+    /// Serialize the source map as JSON, including the ignore list.
+    /// The ignore list is appended as the last key, so that the order of the other keys is the
+    /// same whether there is one or not.
+    pub fn to_writer<W: Write>(&self, mut w: W) -> Result<(), Error> {
+        let mut data = Vec::new();
+        self.map.to_writer(&mut data)?;
+        if !self.ignore_list.is_empty() {
+            let end = data
+                .iter()
+                .rposition(|&c| c == b'}')
+                .expect("source map is not a JSON object");
+            data.truncate(end);
+            data.extend_from_slice(format!(",\"{}\":", IGNORE_LIST_KEY).as_bytes());
+            serde_json::to_writer(&mut data, &self.ignore_list)?;
+            data.push(b'}');
+        }
+        w.write_all(&data)?;
+        Ok(())
     }
-    for output_token in output.tokens() {
-        let (merged_token, src_id) =
-            match input.lookup_token(output_token.get_src_line(), output_token.get_src_col()) {
-                Some(input_token) => (input_token, input_token.get_src_id()),
-                None => (output_token, output_src_id),
+}
+
+/// Return the valid entries of the ignore list of the regular source map `json`.
+fn parse_ignore_list(json: &Value) -> Vec<u32> {
+    let num_sources = json
+        .get("sources")
+        .and_then(Value::as_array)
+        .map_or(0, |sources| sources.len());
+    json.get(IGNORE_LIST_KEY)
+        .and_then(Value::as_array)
+        .map(|list| {
+            list.iter()
+                .filter_map(Value::as_u64)
+                .filter(|&idx| idx < num_sources as u64)
+                .map(|idx| idx as u32)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Convert a decoded map to a regular one. `json` is the JSON the map was decoded from, which
+/// is needed to recover the ignore lists of nested maps.
+fn flatten(decoded: &DecodedMap, json: &Value) -> Result<SourceMapInfo, Error> {
+    let index = match decoded {
+        DecodedMap::Regular(map) => {
+            return Ok(SourceMapInfo {
+                map: map.clone(),
+                ignore_list: parse_ignore_list(json),
+            })
+        }
+        DecodedMap::Hermes(map) => {
+            return Ok(SourceMapInfo {
+                map: (**map).clone(),
+                ignore_list: parse_ignore_list(json),
+            })
+        }
+        DecodedMap::Index(index) => index,
+    };
+
+    let json_sections = json.get("sections").and_then(Value::as_array);
+    let mut builder = SourceMapBuilder::new(index.get_file());
+    let mut ignore_list = Vec::new();
+    for (i, section) in index.sections().enumerate() {
+        let nested = match section.get_sourcemap() {
+            Some(nested) => nested,
+            None => {
+                return Err(Error::CannotFlatten(format!(
+                    "Section has an unresolved sourcemap: {}",
+                    section.get_url().unwrap_or("<unknown url>")
+                )))
+            }
+        };
+        let nested_json = json_sections
+            .and_then(|sections| sections.get(i))
+            .and_then(|s| s.get("map"))
+            .unwrap_or(&Value::Null);
+        let nested = flatten(nested, nested_json)?;
+
+        let (off_line, off_col) = section.get_offset();
+        for token in nested.map.tokens() {
+            // The column offset only applies to the first line of the section.
+            let dst_col = if token.get_dst_line() == 0 {
+                token.get_dst_col() + off_col
+            } else {
+                token.get_dst_col()
             };
+            let raw = builder.add(
+                token.get_dst_line() + off_line,
+                dst_col,
+                token.get_src_line(),
+                token.get_src_col(),
+                token.get_source(),
+                token.get_name(),
+            );
+            if !token.has_source() {
+                continue;
+            }
+            if !builder.has_source_contents(raw.src_id) {
+                builder.set_source_contents(
+                    raw.src_id,
+                    nested.map.get_source_contents(token.get_src_id()),
+                );
+            }
+            if nested.ignore_list.contains(&token.get_src_id())
+                && !ignore_list.contains(&raw.src_id)
+            {
+                ignore_list.push(raw.src_id);
+            }
+        }
+    }
 
-        merged.add_raw(
-            output_token.get_dst_line(),
-            output_token.get_dst_col(),
-            merged_token.get_src_line(),
-            merged_token.get_src_col(),
-            Some(src_id),
-            match merged_token.get_name() {
-                Some(_) => Some(merged_token.get_name_id()),
-                _ => None,
+    Ok(SourceMapInfo {
+        map: builder.into_sourcemap(),
+        ignore_list,
+    })
+}
+
+/// A location in the source of one of the maps being composed.
+struct Traced<'a> {
+    /// The map whose source contains the location.
+    info: &'a SourceMapInfo,
+    /// Source id in that map.
+    src_id: u32,
+    line: u32,
+    col: u32,
+    name: Option<&'a str>,
+}
+
+/// Find the token of `map` which maps the generated location `line`:`col`.
+/// If there is no token starting exactly at `col`, the token covering it on the same line is
+/// returned, together with `false` to indicate that the match was not exact.
+/// Tokens on other lines are never used, because they don't describe the location.
+fn lookup_same_line(map: &SourceMap, line: u32, col: u32) -> Option<(Token<'_>, bool)> {
+    map.lookup_token(line, col)
+        .filter(|token| token.get_dst_line() == line)
+        .map(|token| (token, token.get_dst_col() == col))
+}
+
+/// Trace `token` of `last` back through the maps in `chain`, which precede `last`, as far as
+/// possible.
+/// Names are only retained as long as every match is exact, since a name describes the token
+/// starting at a location rather than every location inside it.
+/// Returns None if the token doesn't map to any source.
+fn trace<'a>(
+    chain: &[&'a SourceMapInfo],
+    last: &'a SourceMapInfo,
+    token: Token<'a>,
+) -> Option<Traced<'a>> {
+    if !token.has_source() {
+        return None;
+    }
+    let mut cur = Traced {
+        info: last,
+        src_id: token.get_src_id(),
+        line: token.get_src_line(),
+        col: token.get_src_col(),
+        name: token.get_name(),
+    };
+    for &prev_info in chain.iter().rev() {
+        let (prev, exact) = match lookup_same_line(&prev_info.map, cur.line, cur.col) {
+            Some((prev, exact)) if prev.has_source() => (prev, exact),
+            // The location isn't mapped by the previous map, so this is as far as we can go.
+            _ => break,
+        };
+        cur = Traced {
+            info: prev_info,
+            src_id: prev.get_src_id(),
+            line: prev.get_src_line(),
+            col: prev.get_src_col(),
+            name: if exact {
+                prev.get_name().or(cur.name)
+            } else {
+                None
             },
+        };
+    }
+    Some(cur)
+}
+
+/// Compose every token of `last` with the chain of maps returned by `chain_for` for the
+/// source id of the token. See `compose_sourcemaps` for details.
+fn compose_tokens<'a, 'b, F>(last: &'a SourceMapInfo, chain_for: F) -> SourceMapInfo
+where
+    'a: 'b,
+    F: Fn(u32) -> &'b [&'a SourceMapInfo],
+{
+    let mut builder = SourceMapBuilder::new(last.map.get_file());
+    let mut ignore_list = Vec::new();
+
+    for token in last.map.tokens() {
+        let traced = match trace(chain_for(token.get_src_id()), last, token) {
+            Some(traced) => traced,
+            None => {
+                builder.add_raw(token.get_dst_line(), token.get_dst_col(), 0, 0, None, None);
+                continue;
+            }
+        };
+        let info = traced.info;
+        // Sources are deduplicated by name.
+        let src_id = builder.add_source(info.map.get_source(traced.src_id).unwrap_or(""));
+        if !builder.has_source_contents(src_id) {
+            builder.set_source_contents(src_id, info.map.get_source_contents(traced.src_id));
+        }
+        if info.ignore_list.contains(&traced.src_id) && !ignore_list.contains(&src_id) {
+            ignore_list.push(src_id);
+        }
+        let name_id = traced.name.map(|name| builder.add_name(name));
+        builder.add_raw(
+            token.get_dst_line(),
+            token.get_dst_col(),
+            traced.line,
+            traced.col,
+            Some(src_id),
+            name_id,
+        );
+    }
+
+    SourceMapInfo {
+        map: builder.into_sourcemap(),
+        ignore_list,
+    }
+}
+
+/// Compose a chain of source maps into a single one.
+/// `maps` are ordered from the first transformation applied to the original sources to the
+/// last one, so the sources of every map refer to the output described by the previous map,
+/// e.g. `[babel_map, metro_map, juno_map]`.
+///
+/// The result maps every generated location of the last map to the earliest source it can be
+/// traced back to. Locations which a previous map doesn't cover keep referring to the source of
+/// the latest map which does. Source contents and ignore list entries are carried over from the
+/// maps defining the respective sources.
+pub fn compose_sourcemaps(maps: &[&SourceMapInfo]) -> SourceMapInfo {
+    let (last, chain) = maps
+        .split_last()
+        .expect("at least one source map is required");
+    compose_tokens(last, |_| chain)
+}
+
+/// The input source maps of the files being compiled, keyed by the `SourceId` of each file.
+/// When the generated code contains segments from several files, every segment is composed
+/// with the input map of the file it came from.
+#[derive(Debug, Default)]
+pub struct InputSourceMaps {
+    maps: HashMap<SourceId, SourceMapInfo>,
+}
+
+impl InputSourceMaps {
+    pub fn new() -> InputSourceMaps {
+        Default::default()
+    }
+
+    /// Record `map` as the input source map of the file `id`, replacing any previous one.
+    pub fn insert(&mut self, id: SourceId, map: SourceMapInfo) {
+        self.maps.insert(id, map);
+    }
+
+    /// Return the input source map of the file `id`, if it has one.
+    pub fn get(&self, id: SourceId) -> Option<&SourceMapInfo> {
+        self.maps.get(&id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.maps.is_empty()
+    }
+
+    /// Compose `output`, which was generated from files registered in `sm`, with the input maps
+    /// of those files. The sources of `output` are matched to files by name, which is how the
+    /// JS generator names them.
+    pub fn compose(&self, sm: &SourceManager, output: &SourceMapInfo) -> SourceMapInfo {
+        let by_name: HashMap<&str, &SourceMapInfo> = self
+            .maps
+            .iter()
+            .map(|(&id, map)| (sm.source_name(id), map))
+            .collect();
+        // Maps source ids of `output` to the input map of the corresponding file.
+        let inputs: HashMap<u32, &SourceMapInfo> = output
+            .map
+            .sources()
+            .enumerate()
+            .filter_map(|(src_id, name)| by_name.get(name).map(|&map| (src_id as u32, map)))
+            .collect();
+        compose_tokens(output, |src_id| {
+            inputs.get(&src_id).map_or(&[][..], std::slice::from_ref)
+        })
+    }
+}
+
+/// Return a merged version of the `input` and `output` source maps, where `output` maps the
+/// result of a transformation of the file described by `input`.
+/// This is a convenience wrapper around `compose_sourcemaps` for maps without ignore lists.
+pub fn merge_sourcemaps(input: &SourceMap, output: &SourceMap) -> SourceMap {
+    compose_sourcemaps(&[
+        &SourceMapInfo::from(input.clone()),
+        &SourceMapInfo::from(output.clone()),
+    ])
+    .map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> SourceMapInfo {
+        SourceMapInfo::from_slice(json.as_bytes()).unwrap()
+    }
+
+    /// Return the tokens of `map` as (dst, src, source, name) tuples.
+    #[allow(clippy::type_complexity)]
+    fn tokens(map: &SourceMap) -> Vec<((u32, u32), (u32, u32), Option<&str>, Option<&str>)> {
+        map.tokens()
+            .map(|t| (t.get_dst(), t.get_src(), t.get_source(), t.get_name()))
+            .collect()
+    }
+
+    #[test]
+    fn test_compose_chain() {
+        // a.js -> b.js: "foo" moves from 0:4 to 0:0, "bar" from 1:0 to 0:4.
+        let first = parse(
+            r#"{"version":3,"sources":["a.js"],"sourcesContent":["var foo;\nbar"],
+                "names":["foo","bar"],"mappings":"AAAIA,IACJC"}"#,
+        );
+        // b.js -> c.js: shifted right by 2 columns.
+        let second = parse(r#"{"version":3,"sources":["b.js"],"names":[],"mappings":"EAAA,IAAI"}"#);
+        // c.js -> d.js: moved down one line.
+        let third = parse(
+            r#"{"version":3,"file":"d.js","sources":["c.js"],"names":[],"mappings":";AAAA,EAAE,IAAI"}"#,
+        );
+
+        let composed = compose_sourcemaps(&[&first, &second, &third]);
+        assert_eq!(composed.map.get_file(), Some("d.js"));
+        assert_eq!(
+            tokens(&composed.map),
+            [
+                // 0:0 of c.js isn't mapped by the second map, so it stays in c.js.
+                ((1, 0), (0, 0), Some("c.js"), None),
+                ((1, 2), (0, 4), Some("a.js"), Some("foo")),
+                ((1, 6), (1, 0), Some("a.js"), Some("bar")),
+            ]
+        );
+        let a_id = composed.map.sources().position(|s| s == "a.js").unwrap();
+        assert_eq!(
+            composed.map.get_source_contents(a_id as u32),
+            Some("var foo;\nbar")
+        );
+    }
+
+    #[test]
+    fn test_compose_inexact() {
+        // A single token at 0:0 covers the whole line, a second one starts at 1:0.
+        let input =
+            parse(r#"{"version":3,"sources":["in.js"],"names":["x"],"mappings":"AAAAA;AACA"}"#);
+        let output = parse(
+            r#"{"version":3,"sources":["mid.js"],"names":["y"],"mappings":"AAAAA,EAAE;;AACA"}"#,
+        );
+        let composed = compose_sourcemaps(&[&input, &output]);
+        assert_eq!(
+            tokens(&composed.map),
+            [
+                ((0, 0), (0, 0), Some("in.js"), Some("x")),
+                // The column doesn't line up with a token: use the covering one without a name.
+                ((0, 2), (0, 0), Some("in.js"), None),
+                // Line 1 of mid.js is mapped by the input map.
+                ((2, 0), (1, 0), Some("in.js"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compose_unmapped_source() {
+        // Only the second line of the intermediate file is mapped.
+        let input = parse(r#"{"version":3,"sources":["in.js"],"names":[],"mappings":";AAAA"}"#);
+        let output = parse(
+            r#"{"version":3,"sources":["mid.js"],"sourcesContent":["x\ny"],
+                "names":[],"mappings":"AAAA,C;AACA"}"#,
+        );
+        let composed = compose_sourcemaps(&[&input, &output]);
+        assert_eq!(
+            tokens(&composed.map),
+            [
+                ((0, 0), (0, 0), Some("mid.js"), None),
+                ((0, 1), (0, 0), None, None),
+                ((1, 0), (0, 0), Some("in.js"), None),
+            ]
+        );
+        let mid_id = composed.map.sources().position(|s| s == "mid.js").unwrap();
+        assert_eq!(
+            composed.map.get_source_contents(mid_id as u32),
+            Some("x\ny")
+        );
+    }
+
+    #[test]
+    fn test_ignore_list() {
+        let input = parse(
+            r#"{"version":3,"sources":["app.js","lib.js"],"names":[],
+                "mappings":"AAAA,CCAA","x_google_ignoreList":[1,7]}"#,
+        );
+        assert_eq!(input.ignore_list, [1]);
+        let output =
+            parse(r#"{"version":3,"sources":["mid.js"],"names":[],"mappings":"AAAA,CAAC"}"#);
+        let composed = compose_sourcemaps(&[&input, &output]);
+        let lib_id = composed.map.sources().position(|s| s == "lib.js").unwrap();
+        assert_eq!(composed.ignore_list, [lib_id as u32]);
+
+        let mut out = Vec::new();
+        composed.to_writer(&mut out).unwrap();
+        let reparsed = SourceMapInfo::from_slice(&out).unwrap();
+        assert_eq!(reparsed.ignore_list, composed.ignore_list);
+        assert_eq!(tokens(&reparsed.map), tokens(&composed.map));
+    }
+
+    #[test]
+    fn test_key_order() {
+        let json = r#"{"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA"}"#;
+        let mut plain = Vec::new();
+        parse(json).to_writer(&mut plain).unwrap();
+        let mut ignored = Vec::new();
+        let mut info = parse(json);
+        info.ignore_list = vec![0];
+        info.to_writer(&mut ignored).unwrap();
+        // The ignore list is the only difference between the two.
+        let plain = String::from_utf8(plain).unwrap();
+        let ignored = String::from_utf8(ignored).unwrap();
+        assert_eq!(plain, ignored.replace(r#","x_google_ignoreList":[0]"#, ""));
+    }
+
+    #[test]
+    fn test_input_source_maps() {
+        use support::NullTerminatedBuf;
+        let mut sm = SourceManager::new();
+        let a_id = sm.add_source("a.js", NullTerminatedBuf::from_str_check("a"));
+        let b_id = sm.add_source("b.js", NullTerminatedBuf::from_str_check("b"));
+
+        let mut inputs = InputSourceMaps::new();
+        inputs.insert(
+            a_id,
+            parse(r#"{"version":3,"sources":["a.ts"],"names":[],"mappings":"AAAA"}"#),
+        );
+        inputs.insert(
+            b_id,
+            parse(r#"{"version":3,"sources":["b.ts"],"names":[],"mappings":"AACA"}"#),
+        );
+        assert!(inputs.get(a_id).is_some());
+
+        // A bundle of both files, each on its own line, plus a file without an input map.
+        let output = parse(
+            r#"{"version":3,"sources":["b.js","a.js","c.js"],"names":[],"mappings":"AAAA;ACAA;ACAA"}"#,
+        );
+        let composed = inputs.compose(&sm, &output);
+        assert_eq!(
+            tokens(&composed.map),
+            [
+                ((0, 0), (1, 0), Some("b.ts"), None),
+                ((1, 0), (0, 0), Some("a.ts"), None),
+                ((2, 0), (0, 0), Some("c.js"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_indexed_map() {
+        let index = parse(
+            r#"{"version":3,"file":"bundle.js","sections":[
+                {"offset":{"line":0,"column":0},"map":{"version":3,"sources":["a.js"],
+                    "sourcesContent":["a()"],"names":["a"],"mappings":"AAAAA"}},
+                {"offset":{"line":1,"column":4},"map":{"version":3,"sources":["b.js"],
+                    "names":[],"mappings":"AAAA;AACA","x_google_ignoreList":[0]}}
+            ]}"#,
+        );
+        assert_eq!(index.map.get_file(), Some("bundle.js"));
+        assert_eq!(
+            tokens(&index.map),
+            [
+                ((0, 0), (0, 0), Some("a.js"), Some("a")),
+                ((1, 4), (0, 0), Some("b.js"), None),
+                ((2, 0), (1, 0), Some("b.js"), None),
+            ]
+        );
+        assert_eq!(index.map.get_source_contents(0), Some("a()"));
+        assert_eq!(index.ignore_list, [1]);
+
+        let unresolved = SourceMapInfo::from_slice(
+            br#"{"version":3,"sections":[{"offset":{"line":0,"column":0},"url":"a.map"}]}"#,
         );
+        assert!(unresolved.is_err());
     }
-    merged.into_sourcemap()
 }
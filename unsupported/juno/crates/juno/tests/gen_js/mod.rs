@@ -13,7 +13,16 @@ use juno::sourcemap::merge_sourcemaps;
 fn do_gen<'ast>(ctx: &mut Context<'ast>, node: &NodePtr, pretty: gen_js::Pretty) -> String {
     use juno::gen_js::*;
     let mut out: Vec<u8> = vec![];
-    generate(&mut out, ctx, node, pretty).unwrap();
+    generate(
+        &mut out,
+        ctx,
+        node,
+        &Opt {
+            pretty,
+            ..Default::default()
+        },
+    )
+    .unwrap();
     String::from_utf8(out).expect("Invalid UTF-8 output in test")
 }
 
@@ -197,10 +206,12 @@ fn test_calls() {
     test_roundtrip("f();");
     test_roundtrip("f(1);");
     test_roundtrip("f(1, 2);");
+    test_roundtrip("f(1, ...a, ...(b, c));");
     test_roundtrip("(f?.(1, 2))(3);");
     test_roundtrip("f?.(1, 2)?.(3)(5);");
     test_roundtrip("new f();");
     test_roundtrip("new f(1);");
+    test_roundtrip("new f(...a);");
     test_roundtrip("new(a.b);");
     test_roundtrip("new(a.b());");
     test_roundtrip("new(a.b())();");
@@ -488,13 +499,117 @@ fn test_jsx() {
     );
 }
 
+#[test]
+fn test_comments() {
+    use juno::gen_js::*;
+
+    test_roundtrip("/* a */ x; // b");
+    test_roundtrip("function f() {\n  // only\n}");
+    test_roundtrip("var x = /*#__PURE__*/ f(1, /* two */ 2);");
+    test_roundtrip("class C {\n  /** doc */\n  m() {}\n}");
+
+    let src = "/*! License */\n\
+               // @flow\n\
+               \n\
+               /**\n * Doc.\n */\n\
+               function foo() {\n\
+                 // Leading.\n\
+                 return /*#__PURE__*/ bar(); // Trailing.\n\
+               }\n\
+               function empty() {\n\
+                 // Inner.\n\
+               }\n";
+    let mut ctx = Context::new();
+    let ast = hparser::parse(&mut ctx, src).unwrap();
+    assert_eq!(
+        do_gen(&mut ctx, &ast, Pretty::Yes),
+        "/*! License */\n\
+         // @flow\n\
+         /**\n * Doc.\n */\n\
+         function foo() {\n  \
+           // Leading.\n  \
+           return /*#__PURE__*/ bar(); // Trailing.\n\
+         }\n\
+         function empty() {\n  \
+           // Inner.\n\
+         }\n"
+    );
+    assert_eq!(
+        do_gen(&mut ctx, &ast, Pretty::No),
+        "/*! License */// @flow\n\
+         /**\n * Doc.\n */function foo(){// Leading.\n\
+         return /*#__PURE__*/bar();// Trailing.\n\
+         }function empty(){// Inner.\n\
+         }\n"
+    );
+
+    let mut out: Vec<u8> = vec![];
+    generate(
+        &mut out,
+        &mut ctx,
+        &ast,
+        &Opt {
+            comments: Comments::None,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "function foo() {\n  return bar();\n}\nfunction empty() {}\n"
+    );
+}
+
+#[test]
+fn test_legal_comments() {
+    use juno::gen_js::*;
+
+    let src = "/*! License */\n\
+               // Regular.\n\
+               function foo() {\n\
+                 /** @license MIT */\n\
+                 return 1; // Regular.\n\
+               }\n\
+               /*! License */\n\
+               /* @preserve */ bar();\n";
+    let mut ctx = Context::new();
+    let ast = hparser::parse(&mut ctx, src).unwrap();
+    let mut gen = |comments| {
+        let mut out: Vec<u8> = vec![];
+        generate(
+            &mut out,
+            &mut ctx,
+            &ast,
+            &Opt {
+                pretty: Pretty::No,
+                comments,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(
+        gen(Comments::Legal),
+        "/*! License */function foo(){/** @license MIT */return 1;}\
+         /*! License *//* @preserve */bar();\n"
+    );
+    assert_eq!(
+        gen(Comments::LegalHoisted),
+        "/*! License */\n\
+         /** @license MIT */\n\
+         /* @preserve */\n\
+         function foo(){return 1;}bar();\n"
+    );
+}
+
 #[test]
 fn test_sourcemap() {
     use juno::gen_js::*;
     let mut ctx = Context::new();
     let ast1: NodePtr = hparser::parse(&mut ctx, "function foo() { return 1 }").unwrap();
     let mut out: Vec<u8> = vec![];
-    let sourcemap = generate(&mut out, &mut ctx, &ast1, Pretty::Yes).unwrap();
+    let sourcemap = generate(&mut out, &mut ctx, &ast1, &Opt::default()).unwrap();
     let string = String::from_utf8(out).expect("Invalid UTF-8 output in test");
     assert_eq!(
         string,
@@ -524,9 +639,10 @@ fn test_sourcemap() {
             src_line: 0,
             src_col: 9,
             src_id: 0,
-            name_id: !0,
+            name_id: 0,
         }
     );
+    assert_eq!(sourcemap.get_token(1).unwrap().get_name(), Some("foo"));
 
     assert_eq!(
         sourcemap.get_token(2).unwrap().get_raw_token(),
@@ -553,6 +669,68 @@ fn test_sourcemap() {
     );
 }
 
+#[test]
+fn test_sourcemap_sources() {
+    use juno::gen_js::*;
+    let mut ctx = Context::new();
+    let src = "var x = 1;";
+    let ast = hparser::parse(&mut ctx, src).unwrap();
+    let mut out: Vec<u8> = vec![];
+    let sourcemap = generate(
+        &mut out,
+        &mut ctx,
+        &ast,
+        &Opt {
+            file: Some("out.js".to_string()),
+            sources_content: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(sourcemap.get_file(), Some("out.js"));
+    assert_eq!(sourcemap.get_source_count(), 1);
+    assert_eq!(sourcemap.get_source(0), Some("<input>"));
+    assert_eq!(sourcemap.get_source_contents(0), Some(src));
+    for token in sourcemap.tokens() {
+        assert_eq!(token.get_source(), Some("<input>"));
+    }
+
+    // Without sources content.
+    let mut out: Vec<u8> = vec![];
+    let sourcemap = generate(&mut out, &mut ctx, &ast, &Opt::default()).unwrap();
+    assert_eq!(sourcemap.get_file(), None);
+    assert_eq!(sourcemap.get_source_contents(0), None);
+}
+
+#[test]
+fn test_sourcemap_names() {
+    use juno::gen_js::*;
+    let mut ctx = Context::new();
+    let ast = hparser::parse(
+        &mut ctx,
+        "class Cls { #priv = 1; meth(arg) { return this.#priv + arg; } }",
+    )
+    .unwrap();
+    let mut out: Vec<u8> = vec![];
+    let sourcemap = generate(&mut out, &mut ctx, &ast, &Opt::default()).unwrap();
+    let names: Vec<&str> = sourcemap.names().collect();
+    assert_eq!(names, ["Cls", "priv", "meth", "arg"]);
+
+    // Every occurrence of a name is mapped to its original location.
+    let arg_tokens: Vec<(u32, u32)> = sourcemap
+        .tokens()
+        .filter(|token| token.get_name() == Some("arg"))
+        .map(|token| token.get_src())
+        .collect();
+    assert_eq!(arg_tokens, [(0, 28), (0, 55)]);
+    let priv_tokens: Vec<(u32, u32)> = sourcemap
+        .tokens()
+        .filter(|token| token.get_name() == Some("priv"))
+        .map(|token| token.get_src())
+        .collect();
+    assert_eq!(priv_tokens, [(0, 12), (0, 47)]);
+}
+
 #[test]
 fn test_sourcemap_merged() {
     use juno::gen_js::*;
@@ -576,7 +754,7 @@ fn test_sourcemap_merged() {
     .unwrap();
     let mut out: Vec<u8> = vec![];
     let node = hparser::parse_with_flags(Default::default(), input_src, ctx).unwrap();
-    let output_map = generate(&mut out, ctx, &node, Pretty::Yes).unwrap();
+    let output_map = generate(&mut out, ctx, &node, &Opt::default()).unwrap();
     let output = String::from_utf8(out).expect("Invalid UTF-8 output in test");
     assert_eq!(output, "function foo() {\n  1;\n}\n",);
 
@@ -10,4 +10,6 @@ mod ast;
 
 mod gen_js;
 
+mod sema;
+
 mod lit;
@@ -0,0 +1,444 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use juno::ast::*;
+use juno::hparser;
+use juno::sema::*;
+
+/// Collects the `Identifier` and `JSXIdentifier` nodes of an AST in order.
+struct Identifiers<'gc> {
+    nodes: Vec<(&'gc str, &'gc Node<'gc>)>,
+}
+
+impl<'gc> Visitor<'gc> for Identifiers<'gc> {
+    fn call(&mut self, ctx: &'gc GCContext, node: &'gc Node<'gc>, _parent: Option<&'gc Node<'gc>>) {
+        match node {
+            Node::Identifier(Identifier { name, .. })
+            | Node::JSXIdentifier(JSXIdentifier { name, .. }) => {
+                self.nodes.push((ctx.str(*name), node))
+            }
+            _ => {}
+        }
+        node.visit_children(ctx, self);
+    }
+}
+
+/// Parse `src`, resolve it and call `f` with the results and the identifiers of the AST.
+fn with_sema<F>(src: &str, f: F)
+where
+    F: for<'gc> FnOnce(&'gc GCContext, &SemContext<'gc>, &dyn Fn(&str, usize) -> &'gc Node<'gc>),
+{
+    let mut ctx = Context::new();
+    let ast = hparser::parse_with_flags(
+        hparser::ParserFlags {
+            strict_mode: false,
+            enable_jsx: true,
+            dialect: hparser::ParserDialect::JavaScript,
+        },
+        src,
+        &mut ctx,
+    )
+    .unwrap();
+    let gc = GCContext::new(&mut ctx);
+    let root = ast.node(&gc);
+    let sem = resolve_program(&gc, root);
+    let mut idents = Identifiers { nodes: Vec::new() };
+    root.visit(&gc, &mut idents, None);
+    // Return the `n`-th identifier named `name`.
+    let ident = |name: &str, n: usize| {
+        idents
+            .nodes
+            .iter()
+            .filter(|(s, _)| *s == name)
+            .nth(n)
+            .unwrap_or_else(|| panic!("identifier {} #{} not found", name, n))
+            .1
+    };
+    f(&gc, &sem, &ident);
+}
+
+#[test]
+fn test_resolve() {
+    with_sema(
+        "var x = 1;\n\
+         function f(y) { return x + y + z; }",
+        |_, sem, ident| {
+            let x = sem.binding_of(ident("x", 0)).unwrap();
+            assert_eq!(sem.binding(x).kind, BindingKind::Var);
+            assert_eq!(sem.binding(x).scope, sem.root_scope());
+            assert_eq!(sem.binding_of(ident("x", 1)), Some(x));
+            assert_eq!(sem.binding(x).references.len(), 1);
+
+            let y = sem.binding_of(ident("y", 1)).unwrap();
+            assert_eq!(sem.binding(y).kind, BindingKind::Param);
+            assert_eq!(sem.binding_of(ident("y", 0)), Some(y));
+            assert_eq!(sem.scope(sem.binding(y).scope).kind, ScopeKind::Function);
+
+            let z = ident("z", 0);
+            assert!(matches!(sem.resolution(z), Some(Resolution::Global(_))));
+            assert_eq!(sem.globals().count(), 1);
+            assert_eq!(sem.scope(sem.root_scope()).kind, ScopeKind::Global);
+        },
+    );
+}
+
+#[test]
+fn test_hoisting() {
+    with_sema(
+        "f(x);\n\
+         function f() {}\n\
+         { var x; let y; }\n\
+         y;",
+        |_, sem, ident| {
+            let f = sem.binding_of(ident("f", 0)).unwrap();
+            assert_eq!(sem.binding(f).kind, BindingKind::Function);
+            assert!(sem.binding(f).kind.is_hoisted());
+            let x = sem.binding_of(ident("x", 0)).unwrap();
+            assert_eq!(sem.binding(x).scope, sem.root_scope());
+            assert!(!sem.binding(x).references[0].maybe_in_tdz);
+            // `y` is scoped to the block.
+            assert!(matches!(
+                sem.resolution(ident("y", 1)),
+                Some(Resolution::Global(_))
+            ));
+        },
+    );
+}
+
+#[test]
+fn test_shadowing() {
+    with_sema(
+        "let a = 1;\n\
+         { let a = 2; a; }\n\
+         try {} catch (a) { a; }\n\
+         a;",
+        |_, sem, ident| {
+            let outer = sem.binding_of(ident("a", 0)).unwrap();
+            let inner = sem.binding_of(ident("a", 1)).unwrap();
+            assert_ne!(outer, inner);
+            assert_eq!(sem.binding_of(ident("a", 2)), Some(inner));
+            assert_eq!(sem.scope(sem.binding(inner).scope).kind, ScopeKind::Block);
+            let param = sem.binding_of(ident("a", 3)).unwrap();
+            assert_eq!(sem.binding(param).kind, BindingKind::CatchParam);
+            assert_eq!(sem.binding_of(ident("a", 4)), Some(param));
+            assert_eq!(sem.binding_of(ident("a", 5)), Some(outer));
+        },
+    );
+}
+
+#[test]
+fn test_tdz() {
+    with_sema(
+        "x;\n\
+         let x = x;\n\
+         x;\n\
+         function g() { x; }\n\
+         const h = () => x;",
+        |_, sem, ident| {
+            let x = sem.binding(sem.binding_of(ident("x", 1)).unwrap());
+            assert!(x.kind.is_lexical());
+            let tdz: Vec<bool> = x.references.iter().map(|r| r.maybe_in_tdz).collect();
+            assert_eq!(tdz, [true, true, false, true, false]);
+        },
+    );
+}
+
+#[test]
+fn test_reference_kinds() {
+    with_sema(
+        "let a, b;\n\
+         a = 1; a += 2; a++; [a, ...b] = []; ({a} = {}); for (a in b);",
+        |_, sem, ident| {
+            let a = sem.binding(sem.binding_of(ident("a", 0)).unwrap());
+            let kinds: Vec<ReferenceKind> = a.references.iter().map(|r| r.kind).collect();
+            use ReferenceKind::*;
+            assert_eq!(kinds, [Write, ReadWrite, ReadWrite, Write, Write, Write]);
+            assert!(a.is_reassigned());
+            let b = sem.binding(sem.binding_of(ident("b", 0)).unwrap());
+            let kinds: Vec<ReferenceKind> = b.references.iter().map(|r| r.kind).collect();
+            assert_eq!(kinds, [Write, Read]);
+        },
+    );
+}
+
+#[test]
+fn test_non_references() {
+    with_sema(
+        "var p, l;\n\
+         o.p; ({p: 1}); ({p}); class C { p() {} }\n\
+         l: for (;;) break l;",
+        |_, sem, ident| {
+            let p = sem.binding_of(ident("p", 0)).unwrap();
+            assert_eq!(sem.resolution(ident("p", 1)), None);
+            assert_eq!(sem.resolution(ident("p", 2)), None);
+            // Shorthand property.
+            assert_eq!(sem.binding(p).references.len(), 1);
+            assert_eq!(sem.resolution(ident("p", 5)), None);
+            assert_eq!(sem.resolution(ident("l", 1)), None);
+            assert_eq!(sem.resolution(ident("l", 2)), None);
+            assert_eq!(sem.globals().count(), 1);
+        },
+    );
+}
+
+#[test]
+fn test_module() {
+    with_sema(
+        "import d, {a as b} from 'm';\n\
+         export {b as c};\n\
+         export {e} from 'n';\n\
+         d(b);",
+        |_, sem, ident| {
+            assert_eq!(sem.scope(sem.root_scope()).kind, ScopeKind::Module);
+            let d = sem.binding_of(ident("d", 0)).unwrap();
+            assert_eq!(sem.binding(d).kind, BindingKind::Import);
+            assert!(sem.binding(d).kind.is_const());
+            assert_eq!(sem.resolution(ident("a", 0)), None);
+            let b = sem.binding_of(ident("b", 0)).unwrap();
+            assert_eq!(sem.binding(b).references.len(), 2);
+            assert_eq!(sem.resolution(ident("c", 0)), None);
+            assert_eq!(sem.resolution(ident("e", 0)), None);
+        },
+    );
+}
+
+#[test]
+fn test_functions_and_classes() {
+    with_sema(
+        "var f = function g(g2) { return g; };\n\
+         var K = class L { m() { return L; } };\n\
+         class M {}\n\
+         <M.N x={K} />; <div />;",
+        |_, sem, ident| {
+            let g = sem.binding_of(ident("g", 0)).unwrap();
+            assert_eq!(sem.binding(g).kind, BindingKind::FunctionName);
+            assert_eq!(sem.binding_of(ident("g", 1)), Some(g));
+            let l = sem.binding_of(ident("L", 0)).unwrap();
+            assert_eq!(sem.binding(l).kind, BindingKind::ClassName);
+            assert_eq!(sem.scope(sem.binding(l).scope).kind, ScopeKind::Class);
+            assert_eq!(sem.binding_of(ident("L", 1)), Some(l));
+            let m = sem.binding_of(ident("M", 0)).unwrap();
+            assert_eq!(sem.binding(m).kind, BindingKind::Class);
+            assert_eq!(sem.binding_of(ident("M", 1)), Some(m));
+            assert_eq!(sem.resolution(ident("N", 0)), None);
+            assert_eq!(sem.resolution(ident("x", 0)), None);
+            assert_eq!(sem.resolution(ident("div", 0)), None);
+        },
+    );
+}
+
+#[test]
+fn test_block_functions() {
+    with_sema(
+        "{ function f() {} }\n\
+         f();\n\
+         function g(h) { { function h() {} } let i; { function i() {} } return h; }\n\
+         function j() { 'use strict'; { function k() {} } k(); }",
+        |_, sem, ident| {
+            let f = sem.binding_of(ident("f", 0)).unwrap();
+            assert_eq!(sem.binding(f).scope, sem.root_scope());
+            assert_eq!(sem.binding_of(ident("f", 1)), Some(f));
+            // Parameters and lexical declarations aren't redeclared.
+            let h = sem.binding_of(ident("h", 0)).unwrap();
+            assert_eq!(sem.binding_of(ident("h", 2)), Some(h));
+            let h2 = sem.binding_of(ident("h", 1)).unwrap();
+            assert_eq!(sem.scope(sem.binding(h2).scope).kind, ScopeKind::Block);
+            let i = sem.binding_of(ident("i", 1)).unwrap();
+            assert_eq!(sem.scope(sem.binding(i).scope).kind, ScopeKind::Block);
+            let k = sem.binding_of(ident("k", 0)).unwrap();
+            assert_eq!(sem.scope(sem.binding(k).scope).kind, ScopeKind::Block);
+            assert_eq!(sem.binding_of(ident("k", 1)), None);
+        },
+    );
+}
+
+#[test]
+fn test_dynamic() {
+    with_sema(
+        "function f() { var a; function g() { eval('a'); } }\n\
+         function h() { var b; }",
+        |_, sem, ident| {
+            let a = sem.binding(sem.binding_of(ident("a", 0)).unwrap());
+            assert!(sem.scope(a.scope).dynamic);
+            let b = sem.binding(sem.binding_of(ident("b", 0)).unwrap());
+            assert!(!sem.scope(b.scope).dynamic);
+        },
+    );
+}
+
+/// An early error as `(line, col, message, note)`, where `note` is the location of the note
+/// attached to the error, if any.
+type EarlyError = (u32, u32, String, Option<(u32, u32)>);
+
+/// Parse `src` and return its early errors.
+fn early_errors(src: &str) -> Vec<EarlyError> {
+    let mut ctx = Context::new();
+    let ast = hparser::parse(&mut ctx, src).unwrap();
+    let gc = GCContext::new(&mut ctx);
+    find_early_errors(&gc, ast.node(&gc))
+        .into_iter()
+        .map(|diag| {
+            let note = diag
+                .notes
+                .first()
+                .map(|n| (n.range.start.line, n.range.start.col));
+            (
+                diag.range.start.line,
+                diag.range.start.col,
+                diag.message,
+                note,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_early_errors_declarations() {
+    assert_eq!(
+        early_errors(
+            "let a; var a;\n\
+             { var b; const b = 1; }\n\
+             function f(c) { let c; }\n\
+             { function g() {} var g; }\n\
+             try {} catch (e) { let e; }"
+        ),
+        vec![
+            (1, 12, "'a' is already declared".to_string(), Some((1, 5))),
+            (2, 16, "'b' is already declared".to_string(), Some((2, 7))),
+            (3, 21, "'c' is already declared".to_string(), Some((3, 12))),
+            (4, 23, "'g' is already declared".to_string(), Some((4, 12))),
+            (5, 24, "'e' is already declared".to_string(), Some((5, 15))),
+        ]
+    );
+    assert!(early_errors(
+        "var a; var a; function a() {}\n\
+         (function f(f) { var f; })\n\
+         try {} catch (e) { var e; }\n\
+         try {} catch (e) { { let e; } }\n\
+         { let a; } { let a; }"
+    )
+    .is_empty());
+}
+
+#[test]
+fn test_early_errors_duplicate_params() {
+    assert_eq!(
+        early_errors(
+            "function f(a, a) { 'use strict'; }\n\
+             (b, b) => 1;\n\
+             function g(c, c = 1) {}\n\
+             ({ m(d, d) {} });"
+        ),
+        vec![
+            (1, 15, "'a' is already declared".to_string(), Some((1, 12))),
+            (2, 5, "'b' is already declared".to_string(), Some((2, 2))),
+            (3, 15, "'c' is already declared".to_string(), Some((3, 12))),
+            (4, 9, "'d' is already declared".to_string(), Some((4, 6))),
+        ]
+    );
+    assert!(early_errors("function f(a, a) { var a; }").is_empty());
+}
+
+#[test]
+fn test_early_errors_control_flow() {
+    assert_eq!(
+        early_errors(
+            "return;\n\
+             break;\n\
+             a: { continue a; }\n\
+             b: while (1) { b: ; break c; }\n\
+             while (1) { (function () { break; }); }"
+        ),
+        vec![
+            (1, 1, "'return' not within a function".to_string(), None),
+            (
+                2,
+                1,
+                "'break' not within a loop or switch".to_string(),
+                None
+            ),
+            (3, 15, "label 'a' does not denote a loop".to_string(), None),
+            (
+                4,
+                16,
+                "label 'b' is already defined".to_string(),
+                Some((4, 1))
+            ),
+            (4, 27, "label 'c' is not defined".to_string(), None),
+            (
+                5,
+                28,
+                "'break' not within a loop or switch".to_string(),
+                None
+            ),
+        ]
+    );
+    assert!(early_errors(
+        "a: b: for (;;) { switch (1) { case 1: break; } continue a; }\n\
+         c: { break c; }\n\
+         function f() { d: do { continue d; } while (0); return; }"
+    )
+    .is_empty());
+}
+
+#[test]
+fn test_early_errors_functions() {
+    assert_eq!(
+        early_errors(
+            "function f() { super.x; new.target; }\n\
+             class A { constructor() { super(); } }\n\
+             new.target;\n\
+             ({ __proto__: 1, '__proto__': 2 });\n\
+             'use strict'; delete x;"
+        ),
+        vec![
+            (
+                1,
+                16,
+                "'super' property not within a method".to_string(),
+                None
+            ),
+            (
+                2,
+                27,
+                "'super' call not within the constructor of a derived class".to_string(),
+                None
+            ),
+            (3, 1, "'new.target' not within a function".to_string(), None),
+            (
+                4,
+                18,
+                "duplicate '__proto__' property".to_string(),
+                Some((4, 4))
+            ),
+        ]
+    );
+    assert_eq!(
+        early_errors("'use strict'; delete x; with (x) {}"),
+        vec![
+            (
+                1,
+                15,
+                "'delete' of an unqualified identifier in strict mode".to_string(),
+                None
+            ),
+            (1, 25, "'with' not allowed in strict mode".to_string(), None),
+        ]
+    );
+    assert!(early_errors(
+        "class A extends B {\n\
+           constructor() { super(); () => super.x; }\n\
+           m() { return super.m(); }\n\
+           x = super.x;\n\
+         }\n\
+         ({ m() { super.m(); }, get g() { return super.g; }, __proto__: 1, ['__proto__']: 2 });\n\
+         async function f() { await 1; }\n\
+         function* g() { yield 1; }\n\
+         delete x;"
+    )
+    .is_empty());
+}
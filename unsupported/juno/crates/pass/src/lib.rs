@@ -10,6 +10,23 @@
 //! Provides transformation traits and the ability to compose them in a pipeline.
 
 mod manager;
-pub use manager::{Pass, PassManager};
+pub use manager::{Lowering, Pass, PassManager};
 
 mod passes;
+pub use passes::add_negative::AddNegative;
+pub use passes::arrow_functions::ArrowFunctions;
+pub use passes::async_functions::AsyncFunctions;
+pub use passes::block_scoping::BlockScoping;
+pub use passes::class_fields::ClassFields;
+pub use passes::classes::Classes;
+pub use passes::computed_properties::ComputedProperties;
+pub use passes::define::{Define, Definition};
+pub use passes::destructuring::Destructuring;
+pub use passes::generators::Generators;
+pub use passes::jsx::{JsxOptions, JsxRuntime, LowerJsx};
+pub use passes::mangle::Mangle;
+pub use passes::optional_chaining::OptionalChaining;
+pub use passes::shorthand_properties::ShorthandProperties;
+pub use passes::spread::Spread;
+pub use passes::strip_types::StripTypes;
+pub use passes::template_literals::TemplateLiterals;
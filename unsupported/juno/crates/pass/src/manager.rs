@@ -27,8 +27,59 @@ impl PassManager {
 
     /// Pipeline containing a list of standard passes.
     pub fn standard() -> Self {
-        Self {
-            passes: vec![Box::new(add_negative::AddNegative::new())],
+        let mut pm = Self::new();
+        pm.add_standard_passes();
+        pm
+    }
+
+    /// Add the standard passes to the end of the pipeline.
+    pub fn add_standard_passes(&mut self) {
+        self.add_pass(Box::new(const_fold::ConstantFold::new()));
+        self.add_pass(Box::new(dce::DeadCodeElimination::new()));
+    }
+
+    /// Pipeline minifying the output: the standard passes followed by compaction and mangling.
+    pub fn minify() -> Self {
+        let mut pm = Self::new();
+        pm.add_minify_passes();
+        pm
+    }
+
+    /// Add the minification passes to the end of the pipeline.
+    pub fn add_minify_passes(&mut self) {
+        self.add_standard_passes();
+        self.add_pass(Box::new(compact_statements::CompactStatements::new()));
+        self.add_pass(Box::new(merge_declarations::MergeDeclarations::new()));
+        self.add_pass(Box::new(compact_literals::CompactLiterals::new()));
+        self.add_pass(Box::new(mangle::Mangle::new()));
+    }
+
+    /// Add the passes compiling the `lowerings` to the end of the pipeline, in the order
+    /// required for them to compose, e.g. arrow functions are lowered before their rest
+    /// parameters. Duplicates are ignored.
+    pub fn add_lowering_passes(&mut self, lowerings: &[Lowering]) {
+        for &lowering in Lowering::ALL {
+            if !lowerings.contains(&lowering) {
+                continue;
+            }
+            self.add_pass(match lowering {
+                Lowering::OptionalChaining => Box::new(optional_chaining::OptionalChaining::new()),
+                Lowering::ClassFields => Box::new(class_fields::ClassFields::new()),
+                Lowering::Classes => Box::new(classes::Classes::new()),
+                Lowering::TemplateLiterals => Box::new(template_literals::TemplateLiterals::new()),
+                Lowering::ArrowFunctions => Box::new(arrow_functions::ArrowFunctions::new()),
+                Lowering::Spread => Box::new(spread::Spread::new()),
+                Lowering::Destructuring => Box::new(destructuring::Destructuring::new()),
+                Lowering::ShorthandProperties => {
+                    Box::new(shorthand_properties::ShorthandProperties::new())
+                }
+                Lowering::ComputedProperties => {
+                    Box::new(computed_properties::ComputedProperties::new())
+                }
+                Lowering::BlockScoping => Box::new(block_scoping::BlockScoping::new()),
+                Lowering::AsyncFunctions => Box::new(async_functions::AsyncFunctions::new()),
+                Lowering::Generators => Box::new(generators::Generators::new()),
+            });
         }
     }
 
@@ -49,6 +100,49 @@ impl PassManager {
     }
 }
 
+/// A language feature which can be compiled to older JS by a lowering pass.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Lowering {
+    /// Optional chaining and nullish coalescing.
+    OptionalChaining,
+    /// Public and private class fields.
+    ClassFields,
+    /// Classes, whose fields must be lowered first.
+    Classes,
+    TemplateLiterals,
+    ArrowFunctions,
+    /// Spread elements and properties, and rest parameters.
+    Spread,
+    /// Destructuring patterns and default values.
+    Destructuring,
+    ShorthandProperties,
+    ComputedProperties,
+    /// `let` and `const` declarations.
+    BlockScoping,
+    /// Async functions, compiled to generators.
+    AsyncFunctions,
+    /// Generator functions, compiled to state machines.
+    Generators,
+}
+
+impl Lowering {
+    /// All the features, in the order their passes run.
+    pub const ALL: &'static [Lowering] = &[
+        Lowering::OptionalChaining,
+        Lowering::ClassFields,
+        Lowering::Classes,
+        Lowering::TemplateLiterals,
+        Lowering::ArrowFunctions,
+        Lowering::Spread,
+        Lowering::Destructuring,
+        Lowering::ShorthandProperties,
+        Lowering::ComputedProperties,
+        Lowering::BlockScoping,
+        Lowering::AsyncFunctions,
+        Lowering::Generators,
+    ];
+}
+
 /// A single pass over the AST.
 pub trait Pass {
     /// Short name of the pass.
@@ -0,0 +1,302 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Arrow function lowering.
+//!
+//! Compiles arrow functions to function expressions. `this`, `arguments` and `new.target` in
+//! arrow functions refer to those of the enclosing function, so they are saved in variables
+//! declared at the top of it.
+//! In class field initializers and derived class constructors, where `this` can't be read
+//! before the arrow function is created, the function is bound to `this` instead.
+//! Arrow functions using `super` can't be lowered and are reported as errors.
+//!
+//! Transforms
+//! ```js
+//! function f() {
+//!   return () => this.x + arguments[0];
+//! }
+//! ```
+//! into
+//! ```js
+//! function f() {
+//!   var _this = this, _arguments = arguments;
+//!   return function () {
+//!     return _this.x + _arguments[0];
+//!   };
+//! }
+//! ```
+
+use crate::passes::util::*;
+use crate::Pass;
+use juno::ast::*;
+use juno::sema::resolve_program;
+use std::collections::HashSet;
+
+#[derive(Default)]
+pub struct ArrowFunctions {}
+
+impl ArrowFunctions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Pass for ArrowFunctions {
+    fn name(&self) -> &'static str {
+        "Lower arrow functions"
+    }
+    fn description(&self) -> &'static str {
+        "Compiles arrow functions to function expressions"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let sem = resolve_program(gc, node);
+        let mut lowerer = Lowerer {
+            names: UniqueNames::new(gc, node),
+            frames: Vec::new(),
+            derived: Vec::new(),
+            arguments: sem
+                .global_references(gc.atom("arguments"))
+                .iter()
+                .map(|reference| NodeRef(reference.node))
+                .collect(),
+        };
+        VisitorMut::call(&mut lowerer, gc, node, None)
+    }
+}
+
+/// A function, program or class field initializer, providing the `this` of the arrow
+/// functions it contains.
+struct Frame {
+    /// Whether `this` can be saved in a variable. Otherwise arrow functions using `this` are
+    /// bound to it.
+    capture_this: bool,
+    /// Whether the frame is a function, which has `arguments` and `new.target`.
+    is_function: bool,
+    this: Option<Atom>,
+    arguments: Option<Atom>,
+    new_target: Option<Atom>,
+    /// Whether each arrow function being visited in the frame, innermost last, uses `this`
+    /// and must be bound to it.
+    arrows: Vec<bool>,
+}
+
+struct Lowerer<'gc> {
+    names: UniqueNames,
+    /// Frames being visited, innermost last.
+    frames: Vec<Frame>,
+    /// Whether each class being visited, innermost last, has a superclass.
+    derived: Vec<bool>,
+    /// Identifiers referring to the `arguments` object of a function.
+    arguments: HashSet<NodeRef<'gc>>,
+}
+
+impl<'gc> VisitorMut<'gc> for Lowerer<'gc> {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let in_arrow = self
+            .frames
+            .last()
+            .is_some_and(|frame| !frame.arrows.is_empty());
+        match node {
+            Node::Program(_) | Node::FunctionDeclaration(_) | Node::FunctionExpression(_) => {
+                let derived_constructor = matches!(
+                    parent,
+                    Some(Node::MethodDefinition(MethodDefinition {
+                        kind: MethodDefinitionKind::Constructor,
+                        ..
+                    }))
+                ) && self.derived.last() == Some(&true);
+                self.in_frame(
+                    gc,
+                    node,
+                    !derived_constructor,
+                    !matches!(node, Node::Program(_)),
+                )
+            }
+            Node::ClassDeclaration(ClassDeclaration { super_class, .. })
+            | Node::ClassExpression(ClassExpression { super_class, .. }) => {
+                self.derived.push(super_class.is_some());
+                let result = node.visit_children_mut(NodeBuilder::from_node(node), gc, self);
+                self.derived.pop();
+                result
+            }
+            Node::ClassProperty(_) | Node::ClassPrivateProperty(_) => {
+                self.in_frame(gc, node, false, false)
+            }
+            Node::ArrowFunctionExpression(_) => {
+                self.frames.last_mut().unwrap().arrows.push(false);
+                let node = match node.visit_children_mut(NodeBuilder::from_node(node), gc, self) {
+                    TransformResult::Changed(new_node) => new_node,
+                    TransformResult::Unchanged => node,
+                };
+                let frame = self.frames.last_mut().unwrap();
+                let bind = frame.arrows.pop().unwrap();
+                let mut function = to_function(gc, node);
+                if bind {
+                    // The `this` passed to `bind` belongs to the enclosing arrow function.
+                    if let Some(outer) = frame.arrows.last_mut() {
+                        *outer = true;
+                    }
+                    let range = *node.range();
+                    function = call(
+                        gc,
+                        member(gc, function, gc.atom("bind"), range),
+                        vec![this_expression(gc, range)],
+                        range,
+                    );
+                }
+                TransformResult::Changed(function)
+            }
+            Node::ThisExpression(ThisExpression { metadata }) if in_arrow => {
+                let Lowerer { frames, names, .. } = self;
+                let frame = frames.last_mut().unwrap();
+                if !frame.capture_this {
+                    *frame.arrows.last_mut().unwrap() = true;
+                    return TransformResult::Unchanged;
+                }
+                let name = *frame
+                    .this
+                    .get_or_insert_with(|| names.generate(gc, "_this"));
+                TransformResult::Changed(identifier(gc, name, metadata.range))
+            }
+            Node::Identifier(Identifier { metadata, .. })
+                if in_arrow
+                    && self.frames.last().unwrap().is_function
+                    && self.arguments.contains(&NodeRef(node)) =>
+            {
+                let Lowerer { frames, names, .. } = self;
+                let frame = frames.last_mut().unwrap();
+                let name = *frame
+                    .arguments
+                    .get_or_insert_with(|| names.generate(gc, "_arguments"));
+                TransformResult::Changed(identifier(gc, name, metadata.range))
+            }
+            Node::MetaProperty(MetaProperty {
+                metadata,
+                meta: Node::Identifier(Identifier { name: meta, .. }),
+                ..
+            }) if in_arrow && self.frames.last().unwrap().is_function && gc.str(*meta) == "new" => {
+                let Lowerer { frames, names, .. } = self;
+                let frame = frames.last_mut().unwrap();
+                let name = *frame
+                    .new_target
+                    .get_or_insert_with(|| names.generate(gc, "_newTarget"));
+                TransformResult::Changed(identifier(gc, name, metadata.range))
+            }
+            Node::Super(Super { metadata }) if in_arrow => {
+                gc.sm().error(
+                    metadata.range,
+                    "arrow functions using 'super' can't be lowered",
+                );
+                TransformResult::Unchanged
+            }
+            _ => node.visit_children_mut(NodeBuilder::from_node(node), gc, self),
+        }
+    }
+}
+
+impl<'gc> Lowerer<'gc> {
+    /// Visit `node` in a new frame, and declare the variables saving its `this`, `arguments`
+    /// and `new.target` if they are used by arrow functions.
+    fn in_frame(
+        &mut self,
+        gc: &'gc GCContext,
+        node: &'gc Node<'gc>,
+        capture_this: bool,
+        is_function: bool,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        self.frames.push(Frame {
+            capture_this,
+            is_function,
+            this: None,
+            arguments: None,
+            new_target: None,
+            arrows: Vec::new(),
+        });
+        let result = node.visit_children_mut(NodeBuilder::from_node(node), gc, self);
+        let frame = self.frames.pop().unwrap();
+
+        let range = *node.range();
+        let mut declarators = Vec::new();
+        if let Some(name) = frame.this {
+            declarators.push((identifier(gc, name, range), this_expression(gc, range)));
+        }
+        if let Some(name) = frame.arguments {
+            declarators.push((
+                identifier(gc, name, range),
+                identifier(gc, gc.atom("arguments"), range),
+            ));
+        }
+        if let Some(name) = frame.new_target {
+            let new_target = MetaPropertyBuilder::build_template(
+                gc,
+                MetaPropertyTemplate {
+                    metadata: template_metadata(range),
+                    meta: identifier(gc, gc.atom("new"), range),
+                    property: identifier(gc, gc.atom("target"), range),
+                },
+            );
+            declarators.push((identifier(gc, name, range), new_target));
+        }
+        if declarators.is_empty() {
+            return result;
+        }
+        let node = match result {
+            TransformResult::Changed(new_node) => new_node,
+            TransformResult::Unchanged => node,
+        };
+        let decl = declaration(
+            gc,
+            VariableDeclarationKind::Var,
+            declarators.into_iter().map(|(id, init)| (id, Some(init))),
+            range,
+        );
+        TransformResult::Changed(prepend_to_body(gc, node, vec![decl]))
+    }
+}
+
+/// Return the function expression equivalent to the arrow function `node`, whose `this`,
+/// `arguments` and `new.target` have already been replaced.
+fn to_function<'gc>(gc: &'gc GCContext, node: &'gc Node<'gc>) -> &'gc Node<'gc> {
+    let arrow = match node {
+        Node::ArrowFunctionExpression(arrow) => arrow,
+        _ => unreachable!("{} isn't an arrow function", node.name()),
+    };
+    let body = match arrow.body {
+        Node::BlockStatement(_) => arrow.body,
+        expression => {
+            let range = *expression.range();
+            block(
+                gc,
+                vec![return_statement(gc, Some(expression), range)],
+                range,
+            )
+        }
+    };
+    FunctionExpressionBuilder::build_template(
+        gc,
+        FunctionExpressionTemplate {
+            metadata: template_metadata(arrow.metadata.range),
+            id: None,
+            params: arrow.params.clone(),
+            body,
+            type_parameters: arrow.type_parameters,
+            return_type: arrow.return_type,
+            predicate: arrow.predicate,
+            generator: false,
+            is_async: arrow.is_async,
+        },
+    )
+}
@@ -0,0 +1,475 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Async function lowering.
+//!
+//! Compiles async functions to generator functions, with `await` compiled to `yield`, which are
+//! run by a helper declared at the top of the program. The helper resumes the generator with the
+//! result of each yielded promise, and returns a promise of its result. The generator is called
+//! with the `this` and `arguments` of the async function, which keeps its parameters.
+//! Async generators, `for await` loops, async functions using `super` and async arrow functions
+//! using `arguments` can't be lowered and are reported as errors.
+//!
+//! Transforms
+//! ```js
+//! async function f(x) {
+//!   return await g(x);
+//! }
+//! ```
+//! into
+//! ```js
+//! function _async(fn, self, args) {
+//!   return new Promise(function (resolve, reject) {
+//!     var gen = fn.apply(self, args);
+//!     function step(method, arg) {
+//!       try {
+//!         var result = gen[method](arg);
+//!       } catch (error) {
+//!         reject(error);
+//!         return;
+//!       }
+//!       if (result.done) {
+//!         resolve(result.value);
+//!       } else {
+//!         Promise.resolve(result.value).then(function (value) {
+//!           step("next", value);
+//!         }, function (error) {
+//!           step("throw", error);
+//!         });
+//!       }
+//!     }
+//!     step("next");
+//!   });
+//! }
+//! function f(x) {
+//!   return _async(function* () {
+//!     return yield g(x);
+//!   }, this, arguments);
+//! }
+//! ```
+
+use crate::passes::util::*;
+use crate::Pass;
+use juno::ast::*;
+
+#[derive(Default)]
+pub struct AsyncFunctions {}
+
+impl AsyncFunctions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Pass for AsyncFunctions {
+    fn name(&self) -> &'static str {
+        "Lower async functions"
+    }
+    fn description(&self) -> &'static str {
+        "Compiles async functions to generator functions run by a helper"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let mut lowerer = Lowerer {
+            names: UniqueNames::new(gc, node),
+            helper: None,
+        };
+        VisitorMut::call(&mut lowerer, gc, node, None)
+    }
+}
+
+struct Lowerer {
+    names: UniqueNames,
+    /// Name of the helper running the generators, once it is used.
+    helper: Option<Atom>,
+}
+
+impl<'gc> VisitorMut<'gc> for Lowerer {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        _parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let (node, changed) = match node.visit_children_mut(NodeBuilder::from_node(node), gc, self)
+        {
+            TransformResult::Changed(new_node) => (new_node, true),
+            TransformResult::Unchanged => (node, false),
+        };
+        let lowered = match node {
+            Node::FunctionDeclaration(FunctionDeclaration {
+                is_async: true,
+                generator,
+                body,
+                ..
+            })
+            | Node::FunctionExpression(FunctionExpression {
+                is_async: true,
+                generator,
+                body,
+                ..
+            }) => {
+                if *generator {
+                    gc.sm()
+                        .error(*node.range(), "async generators can't be lowered");
+                    None
+                } else {
+                    self.lower_function(gc, node, body)
+                }
+            }
+            Node::ArrowFunctionExpression(ArrowFunctionExpression {
+                is_async: true,
+                body,
+                ..
+            }) => self.lower_function(gc, node, body),
+            Node::Program(_) => self
+                .helper
+                .map(|name| prepend_to_body(gc, node, vec![helper(gc, name, *node.range())])),
+            _ => None,
+        };
+        match lowered {
+            Some(new_node) => TransformResult::Changed(new_node),
+            None if changed => TransformResult::Changed(node),
+            None => TransformResult::Unchanged,
+        }
+    }
+}
+
+impl Lowerer {
+    /// Return the async function `node` with its `body` compiled to a generator run by the
+    /// helper, or `None` if it can't be lowered, which is reported.
+    fn lower_function<'gc>(
+        &mut self,
+        gc: &'gc GCContext,
+        node: &'gc Node<'gc>,
+        body: &'gc Node<'gc>,
+    ) -> Option<&'gc Node<'gc>> {
+        let is_arrow = matches!(node, Node::ArrowFunctionExpression(_));
+        let mut checker = Checker {
+            is_arrow,
+            failed: false,
+        };
+        checker.call(gc, body, None);
+        if checker.failed {
+            return None;
+        }
+
+        let range = *body.range();
+        // Directives stay in the async function.
+        let (directives, stmts) = match body {
+            Node::BlockStatement(BlockStatement { body, .. }) => {
+                let count = directive_count(body);
+                (body[..count].to_vec(), body[count..].to_vec())
+            }
+            _ => (vec![], vec![return_statement(gc, Some(body), range)]),
+        };
+        let stmts = stmts
+            .into_iter()
+            .map(|stmt| match AwaitToYield.call(gc, stmt, None) {
+                TransformResult::Changed(new_stmt) => new_stmt,
+                TransformResult::Unchanged => stmt,
+            })
+            .collect();
+        let mut generator =
+            FunctionExpressionBuilder::from_node(match function(gc, vec![], stmts, range) {
+                Node::FunctionExpression(func) => func,
+                _ => unreachable!(),
+            });
+        generator.generator(true);
+        let generator = generator.build(gc).unwrap();
+
+        let helper = match self.helper {
+            Some(name) => name,
+            None => *self.helper.insert(self.names.generate(gc, "_async")),
+        };
+        let mut args = vec![generator, this_expression(gc, range)];
+        if !is_arrow {
+            args.push(identifier(gc, gc.atom("arguments"), range));
+        }
+        let run = call(gc, identifier(gc, helper, range), args, range);
+
+        Some(match node {
+            Node::FunctionDeclaration(func) => {
+                let mut builder = FunctionDeclarationBuilder::from_node(func);
+                let mut body = directives;
+                body.push(return_statement(gc, Some(run), range));
+                builder.body(block(gc, body, range));
+                builder.is_async(false);
+                builder.build(gc).unwrap()
+            }
+            Node::FunctionExpression(func) => {
+                let mut builder = FunctionExpressionBuilder::from_node(func);
+                let mut body = directives;
+                body.push(return_statement(gc, Some(run), range));
+                builder.body(block(gc, body, range));
+                builder.is_async(false);
+                builder.build(gc).unwrap()
+            }
+            Node::ArrowFunctionExpression(func) => {
+                let mut builder = ArrowFunctionExpressionBuilder::from_node(func);
+                if directives.is_empty() {
+                    builder.body(run);
+                    builder.expression(true);
+                } else {
+                    let mut body = directives;
+                    body.push(return_statement(gc, Some(run), range));
+                    builder.body(block(gc, body, range));
+                    builder.expression(false);
+                }
+                builder.is_async(false);
+                builder.build(gc).unwrap()
+            }
+            _ => unreachable!("invalid function {}", node.name()),
+        })
+    }
+}
+
+/// Reports the parts of the body of an async function which can't be moved to a generator.
+struct Checker {
+    is_arrow: bool,
+    failed: bool,
+}
+
+impl<'gc> Visitor<'gc> for Checker {
+    fn call(&mut self, gc: &'gc GCContext, node: &'gc Node<'gc>, parent: Option<&'gc Node<'gc>>) {
+        match node {
+            Node::FunctionDeclaration(_) | Node::FunctionExpression(_) => return,
+            Node::Super(_) => {
+                gc.sm().error(
+                    *node.range(),
+                    "async functions using 'super' can't be lowered",
+                );
+                self.failed = true;
+            }
+            Node::Identifier(Identifier { name, .. })
+                if self.is_arrow
+                    && gc.str(*name) == "arguments"
+                    && !is_property_name(node, parent) =>
+            {
+                gc.sm().error(
+                    *node.range(),
+                    "async arrow functions using 'arguments' can't be lowered",
+                );
+                self.failed = true;
+            }
+            Node::ForOfStatement(ForOfStatement { is_await: true, .. }) => {
+                gc.sm()
+                    .error(*node.range(), "'for await' loops can't be lowered");
+                self.failed = true;
+            }
+            _ => {}
+        }
+        node.visit_children(gc, self);
+    }
+}
+
+/// Compiles `await` to `yield`, outside of nested functions.
+struct AwaitToYield;
+
+impl<'gc> VisitorMut<'gc> for AwaitToYield {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        _parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        if matches!(
+            node,
+            Node::FunctionDeclaration(_)
+                | Node::FunctionExpression(_)
+                | Node::ArrowFunctionExpression(_)
+        ) {
+            return TransformResult::Unchanged;
+        }
+        let (node, changed) = match node.visit_children_mut(NodeBuilder::from_node(node), gc, self)
+        {
+            TransformResult::Changed(new_node) => (new_node, true),
+            TransformResult::Unchanged => (node, false),
+        };
+        match node {
+            Node::AwaitExpression(AwaitExpression { metadata, argument }) => {
+                TransformResult::Changed(YieldExpressionBuilder::build_template(
+                    gc,
+                    YieldExpressionTemplate {
+                        metadata: template_metadata(metadata.range),
+                        argument: Some(argument),
+                        delegate: false,
+                    },
+                ))
+            }
+            _ if changed => TransformResult::Changed(node),
+            _ => TransformResult::Unchanged,
+        }
+    }
+}
+
+/// Return the declaration of the helper `name`, which runs the generator returned by
+/// `fn.apply(self, args)` and returns a promise of its result.
+fn helper<'gc>(gc: &'gc GCContext, name: Atom, range: SourceRange) -> &'gc Node<'gc> {
+    let id = |name: &str| identifier(gc, gc.atom(name), range);
+    let str = |value: &str| string(gc, value, range);
+    let stmt = |expression| expression_statement(gc, expression);
+    let func = |params: &[&str], body| {
+        function(
+            gc,
+            params.iter().map(|param| id(param)).collect(),
+            body,
+            range,
+        )
+    };
+
+    // Resume the generator with `step("next", value)` or `step("throw", error)`.
+    let resume = |method: &str, param: &str| {
+        func(
+            &[param],
+            vec![stmt(call(
+                gc,
+                id("step"),
+                vec![str(method), id(param)],
+                range,
+            ))],
+        )
+    };
+    let result_member = |name: &str| member(gc, id("result"), gc.atom(name), range);
+    let try_step = TryStatementBuilder::build_template(
+        gc,
+        TryStatementTemplate {
+            metadata: template_metadata(range),
+            block: block(
+                gc,
+                vec![declaration(
+                    gc,
+                    VariableDeclarationKind::Var,
+                    [(
+                        id("result"),
+                        Some(call(
+                            gc,
+                            computed_member(gc, id("gen"), id("method"), range),
+                            vec![id("arg")],
+                            range,
+                        )),
+                    )],
+                    range,
+                )],
+                range,
+            ),
+            handler: Some(CatchClauseBuilder::build_template(
+                gc,
+                CatchClauseTemplate {
+                    metadata: template_metadata(range),
+                    param: Some(id("error")),
+                    body: block(
+                        gc,
+                        vec![
+                            stmt(call(gc, id("reject"), vec![id("error")], range)),
+                            return_statement(gc, None, range),
+                        ],
+                        range,
+                    ),
+                },
+            )),
+            finalizer: None,
+        },
+    );
+    let mut finish = IfStatementBuilder::from_node(
+        match if_statement(
+            gc,
+            result_member("done"),
+            block(
+                gc,
+                vec![stmt(call(
+                    gc,
+                    id("resolve"),
+                    vec![result_member("value")],
+                    range,
+                ))],
+                range,
+            ),
+            range,
+        ) {
+            Node::IfStatement(if_stmt) => if_stmt,
+            _ => unreachable!(),
+        },
+    );
+    let then = stmt(call(
+        gc,
+        member(
+            gc,
+            call(
+                gc,
+                dotted_name(gc, "Promise.resolve", range),
+                vec![result_member("value")],
+                range,
+            ),
+            gc.atom("then"),
+            range,
+        ),
+        vec![resume("next", "value"), resume("throw", "error")],
+        range,
+    ));
+    finish.alternate(Some(block(gc, vec![then], range)));
+    let step = FunctionDeclarationBuilder::build_template(
+        gc,
+        FunctionDeclarationTemplate {
+            metadata: template_metadata(range),
+            id: Some(id("step")),
+            params: vec![id("method"), id("arg")],
+            body: block(gc, vec![try_step, finish.build(gc).unwrap()], range),
+            type_parameters: None,
+            return_type: None,
+            predicate: None,
+            generator: false,
+            is_async: false,
+        },
+    );
+    let executor = func(
+        &["resolve", "reject"],
+        vec![
+            declaration(
+                gc,
+                VariableDeclarationKind::Var,
+                [(
+                    id("gen"),
+                    Some(call(
+                        gc,
+                        dotted_name(gc, "fn.apply", range),
+                        vec![id("self"), id("args")],
+                        range,
+                    )),
+                )],
+                range,
+            ),
+            step,
+            stmt(call(gc, id("step"), vec![str("next")], range)),
+        ],
+    );
+    FunctionDeclarationBuilder::build_template(
+        gc,
+        FunctionDeclarationTemplate {
+            metadata: template_metadata(range),
+            id: Some(identifier(gc, name, range)),
+            params: vec![id("fn"), id("self"), id("args")],
+            body: block(
+                gc,
+                vec![return_statement(
+                    gc,
+                    Some(new_expression(gc, id("Promise"), vec![executor], range)),
+                    range,
+                )],
+                range,
+            ),
+            type_parameters: None,
+            return_type: None,
+            predicate: None,
+            generator: false,
+            is_async: false,
+        },
+    )
+}
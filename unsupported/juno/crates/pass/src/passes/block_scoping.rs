@@ -0,0 +1,1013 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Block scoping lowering.
+//!
+//! Compiles `let` and `const` declarations to `var`. Bindings declared in blocks are renamed
+//! when hoisting them to the enclosing function could conflict with another variable of the
+//! same name, and `let` declarations in loops without an initializer are initialized with
+//! `undefined` on every iteration.
+//!
+//! Each iteration of a loop has its own copy of the bindings declared in the loop, which may be
+//! captured by closures. The body of such a loop is moved to a function called on every
+//! iteration with the loop variables as arguments. `break`, `continue` and `return` in the body
+//! are compiled to returns from the function, whose result is checked after the call, which
+//! also performs the jumps to the labels of outer statements. The variables of a `for` loop
+//! assigned in its body are copied back to the variables of the loop after every iteration.
+//! Loops whose bodies yield, await or use `arguments` can't be lowered this way and are reported
+//! as errors.
+//!
+//! Assignments of constants are compiled to calls of a helper throwing a `TypeError`, after the
+//! assigned value is evaluated.
+//!
+//! Transforms
+//! ```js
+//! for (let i = 0; i < n; i++) {
+//!   let x = i;
+//!   fns.push(() => x);
+//! }
+//! { let i = 1; }
+//! ```
+//! into
+//! ```js
+//! {
+//!   var _loop = function (i) {
+//!     var x = i;
+//!     fns.push(() => x);
+//!   };
+//!   for (var i = 0; i < n; i++) {
+//!     _loop(i);
+//!   }
+//! }
+//! { var _i = 1; }
+//! ```
+
+use crate::passes::util::*;
+use crate::Pass;
+use juno::ast::*;
+use juno::sema::{resolve_program, BindingKind, ReferenceKind, Resolution, ScopeId, SemContext};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Default)]
+pub struct BlockScoping {}
+
+impl BlockScoping {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Pass for BlockScoping {
+    fn name(&self) -> &'static str {
+        "Lower block scoping"
+    }
+    fn description(&self) -> &'static str {
+        "Compiles let and const declarations to var"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let sem = resolve_program(gc, node);
+        let mut names = UniqueNames::new(gc, node);
+        let renames = renames(gc, &sem, &mut names);
+
+        let mut analyzer = Analyzer {
+            sem: &sem,
+            frames: vec![Vec::new()],
+            scopes: HashMap::new(),
+            captured: HashSet::new(),
+            uses_arguments: HashSet::new(),
+        };
+        Visitor::call(&mut analyzer, gc, node, None);
+        let (outer_names, copies) = loop_copies(gc, &sem, &analyzer.captured, &renames, &mut names);
+
+        let mut lowerer = Lowerer {
+            sem: &sem,
+            renames: &renames,
+            outer_names,
+            captured: analyzer.captured,
+            uses_arguments: analyzer.uses_arguments,
+            copies,
+            const_writes: const_writes(&sem),
+            names,
+            loop_depths: vec![0],
+            pending: None,
+            helper: None,
+        };
+        VisitorMut::call(&mut lowerer, gc, node, None)
+    }
+}
+
+/// Return the new names of the identifiers declaring and referencing the lexical bindings
+/// which must be renamed before being hoisted.
+/// A binding declared in a block is kept if no other variable with the same name exists,
+/// except other lexical bindings declared in blocks which are renamed.
+fn renames<'gc>(
+    gc: &'gc GCContext,
+    sem: &SemContext<'gc>,
+    names: &mut UniqueNames,
+) -> HashMap<NodeRef<'gc>, Atom> {
+    let in_block = |kind: BindingKind, scope: ScopeId| {
+        matches!(kind, BindingKind::Let | BindingKind::Const)
+            && !sem.scope(scope).kind.is_var_scope()
+    };
+    let mut used: HashSet<Atom> = sem
+        .bindings()
+        .filter(|(_, binding)| !in_block(binding.kind, binding.scope))
+        .map(|(_, binding)| binding.name)
+        .chain(sem.globals().map(|(name, _)| name))
+        .collect();
+    let mut result = HashMap::new();
+    for (_, binding) in sem.bindings() {
+        if !in_block(binding.kind, binding.scope) || used.insert(binding.name) {
+            continue;
+        }
+        let name = names.generate(gc, &format!("_{}", gc.str(binding.name)));
+        for &decl in &binding.declarations {
+            result.insert(NodeRef(decl), name);
+        }
+        for reference in &binding.references {
+            result.insert(NodeRef(reference.node), name);
+        }
+    }
+    result
+}
+
+/// Return the new names of the identifiers in the heads of the `for` loops moved to functions
+/// whose variables are assigned in their bodies, and the names of these variables in each loop
+/// and in its function.
+/// The variables of the loop are renamed, so they can be assigned in the function.
+#[allow(clippy::type_complexity)]
+fn loop_copies<'gc>(
+    gc: &'gc GCContext,
+    sem: &SemContext<'gc>,
+    captured: &HashSet<NodeRef<'gc>>,
+    renames: &HashMap<NodeRef<'gc>, Atom>,
+    names: &mut UniqueNames,
+) -> (
+    HashMap<NodeRef<'gc>, Atom>,
+    HashMap<NodeRef<'gc>, Vec<(Atom, Atom)>>,
+) {
+    let mut outer_names = HashMap::new();
+    let mut copies: HashMap<NodeRef, Vec<(Atom, Atom)>> = HashMap::new();
+    for (_, binding) in sem.bindings() {
+        let node = sem.scope(binding.scope).node;
+        let body = match node {
+            Node::ForStatement(ForStatement { body, .. })
+                if binding.kind == BindingKind::Let && captured.contains(&NodeRef(node)) =>
+            {
+                *body.range()
+            }
+            _ => continue,
+        };
+        let in_body = |ident: &Node| {
+            let range = ident.range();
+            range.start >= body.start && range.end <= body.end
+        };
+        if !binding
+            .references
+            .iter()
+            .any(|reference| reference.kind != ReferenceKind::Read && in_body(reference.node))
+        {
+            continue;
+        }
+        let inner = renames
+            .get(&NodeRef(binding.declarations[0]))
+            .copied()
+            .unwrap_or(binding.name);
+        let outer = names.generate(gc, &format!("_{}", gc.str(binding.name)));
+        let head_refs = binding
+            .references
+            .iter()
+            .map(|reference| reference.node)
+            .filter(|&ident| !in_body(ident));
+        for ident in binding.declarations.iter().copied().chain(head_refs) {
+            outer_names.insert(NodeRef(ident), outer);
+        }
+        copies
+            .entry(NodeRef(node))
+            .or_default()
+            .push((outer, inner));
+    }
+    (outer_names, copies)
+}
+
+/// Return the identifiers assigning constants.
+fn const_writes<'gc>(sem: &SemContext<'gc>) -> HashSet<NodeRef<'gc>> {
+    sem.bindings()
+        .filter(|(_, binding)| binding.kind == BindingKind::Const)
+        .flat_map(|(_, binding)| binding.references.iter())
+        .filter(|reference| reference.kind != ReferenceKind::Read)
+        .map(|reference| NodeRef(reference.node))
+        .collect()
+}
+
+fn is_loop(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::ForStatement(_)
+            | Node::ForInStatement(_)
+            | Node::ForOfStatement(_)
+            | Node::WhileStatement(_)
+            | Node::DoWhileStatement(_)
+    )
+}
+
+fn is_function(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::FunctionDeclaration(_)
+            | Node::FunctionExpression(_)
+            | Node::ArrowFunctionExpression(_)
+    )
+}
+
+/// Finds the loops whose bindings are captured by closures.
+struct Analyzer<'a, 'gc> {
+    sem: &'a SemContext<'gc>,
+    /// Loops being visited in each function, innermost last.
+    frames: Vec<Vec<&'gc Node<'gc>>>,
+    /// Depth of the function of each visited scope, and innermost loop containing it.
+    scopes: HashMap<ScopeId, (usize, Option<&'gc Node<'gc>>)>,
+    /// Loops declaring lexical bindings referenced in nested functions.
+    captured: HashSet<NodeRef<'gc>>,
+    /// Loops using `arguments` in their function.
+    uses_arguments: HashSet<NodeRef<'gc>>,
+}
+
+impl<'gc> Visitor<'gc> for Analyzer<'_, 'gc> {
+    fn call(&mut self, gc: &'gc GCContext, node: &'gc Node<'gc>, _parent: Option<&'gc Node<'gc>>) {
+        if is_function(node) {
+            self.frames.push(Vec::new());
+        }
+        if is_loop(node) {
+            self.frames.last_mut().unwrap().push(node);
+        }
+        if let Some(scope) = self.sem.node_scope(node) {
+            self.scopes.insert(
+                scope,
+                (
+                    self.frames.len(),
+                    self.frames.last().unwrap().last().copied(),
+                ),
+            );
+        }
+        if let Node::Identifier(Identifier { name, .. }) = node {
+            match self.sem.resolution(node) {
+                Some(Resolution::Binding(id)) => {
+                    let binding = self.sem.binding(id);
+                    if let (BindingKind::Let | BindingKind::Const, Some(&(depth, Some(owner)))) =
+                        (binding.kind, self.scopes.get(&binding.scope))
+                    {
+                        if self.frames.len() > depth {
+                            self.captured.insert(NodeRef(owner));
+                        }
+                    }
+                }
+                Some(Resolution::Global(_)) if gc.str(*name) == "arguments" => {
+                    for &owner in self.frames.last().unwrap() {
+                        self.uses_arguments.insert(NodeRef(owner));
+                    }
+                }
+                _ => {}
+            }
+        }
+        node.visit_children(gc, self);
+        if is_loop(node) {
+            self.frames.last_mut().unwrap().pop();
+        }
+        if is_function(node) {
+            self.frames.pop();
+        }
+    }
+}
+
+struct Lowerer<'a, 'gc> {
+    sem: &'a SemContext<'gc>,
+    renames: &'a HashMap<NodeRef<'gc>, Atom>,
+    /// New names of the identifiers in the heads of loops whose variables are copied back.
+    outer_names: HashMap<NodeRef<'gc>, Atom>,
+    captured: HashSet<NodeRef<'gc>>,
+    uses_arguments: HashSet<NodeRef<'gc>>,
+    /// Names of the variables of each loop and of its function, copied back after every
+    /// iteration.
+    copies: HashMap<NodeRef<'gc>, Vec<(Atom, Atom)>>,
+    /// Identifiers assigning constants.
+    const_writes: HashSet<NodeRef<'gc>>,
+    names: UniqueNames,
+    /// Number of loops being visited in each function, innermost last.
+    loop_depths: Vec<usize>,
+    /// Declaration of the body function of a labeled loop, to be inserted before the label.
+    pending: Option<&'gc Node<'gc>>,
+    /// Name of the helper throwing on assignments of constants, once it is used.
+    helper: Option<Atom>,
+}
+
+impl<'gc> VisitorMut<'gc> for Lowerer<'_, 'gc> {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        if let Node::Identifier(ident) = node {
+            if self.const_writes.contains(&NodeRef(node)) && !is_assignment_target(node, parent) {
+                gc.sm().error(
+                    *node.range(),
+                    "constants assigned by destructuring or for-in/for-of loops can't be lowered",
+                );
+            }
+            let name = self
+                .outer_names
+                .get(&NodeRef(node))
+                .or_else(|| self.renames.get(&NodeRef(node)));
+            return match name {
+                Some(&name) => {
+                    let mut builder = IdentifierBuilder::from_node(ident);
+                    builder.name(name);
+                    TransformResult::Changed(builder.build(gc).unwrap())
+                }
+                None => TransformResult::Unchanged,
+            };
+        }
+
+        // The loop variables are passed to the function containing the body.
+        // The analysis refers to the original loop.
+        let wrap_params = if is_loop(node) && self.captured.contains(&NodeRef(node)) {
+            if self.uses_arguments.contains(&NodeRef(node)) {
+                gc.sm().error(
+                    *node.range(),
+                    "loops using 'arguments' and capturing their variables can't be lowered",
+                );
+                None
+            } else {
+                let copies = self.copies.remove(&NodeRef(node)).unwrap_or_default();
+                Some((self.loop_params(node), copies))
+            }
+        } else {
+            None
+        };
+        let const_target = match node {
+            Node::AssignmentExpression(AssignmentExpression { left: target, .. })
+            | Node::UpdateExpression(UpdateExpression {
+                argument: target, ..
+            }) if self.const_writes.contains(&NodeRef(target)) => match target {
+                Node::Identifier(Identifier { name, .. }) => Some(*name),
+                _ => None,
+            },
+            _ => None,
+        };
+        // The key of a shorthand property is the same node as its value.
+        let shorthand_key = match node {
+            Node::Property(Property {
+                key: key @ Node::Identifier(Identifier { name, .. }),
+                shorthand: true,
+                ..
+            }) if self.renames.contains_key(&NodeRef(key)) => Some((*name, *key.range())),
+            _ => None,
+        };
+
+        if is_function(node) {
+            self.loop_depths.push(0);
+        }
+        if is_loop(node) {
+            *self.loop_depths.last_mut().unwrap() += 1;
+        }
+        let (node, changed) = match node.visit_children_mut(NodeBuilder::from_node(node), gc, self)
+        {
+            TransformResult::Changed(new_node) => (new_node, true),
+            TransformResult::Unchanged => (node, false),
+        };
+        if is_loop(node) {
+            *self.loop_depths.last_mut().unwrap() -= 1;
+        }
+        if is_function(node) {
+            self.loop_depths.pop();
+        }
+
+        match node {
+            _ if const_target.is_some() => {
+                TransformResult::Changed(self.assign_constant(gc, node, const_target.unwrap()))
+            }
+            Node::Program(_) if self.helper.is_some() => {
+                let helper = read_only_helper(gc, self.helper.unwrap(), *node.range());
+                TransformResult::Changed(prepend_to_body(gc, node, vec![helper]))
+            }
+            Node::Property(prop) if shorthand_key.is_some() => {
+                let (name, range) = shorthand_key.unwrap();
+                let mut builder = PropertyBuilder::from_node(prop);
+                builder.key(identifier(gc, name, range));
+                builder.shorthand(false);
+                TransformResult::Changed(builder.build(gc).unwrap())
+            }
+            Node::VariableDeclaration(
+                decl @ VariableDeclaration {
+                    kind: kind @ (VariableDeclarationKind::Let | VariableDeclarationKind::Const),
+                    declarations,
+                    ..
+                },
+            ) => {
+                let mut builder = VariableDeclarationBuilder::from_node(decl);
+                builder.kind(VariableDeclarationKind::Var);
+                // A `let` without initializer is reset on every iteration.
+                if *kind == VariableDeclarationKind::Let
+                    && *self.loop_depths.last().unwrap() > 0
+                    && !matches!(
+                        parent,
+                        Some(Node::ForInStatement(_) | Node::ForOfStatement(_))
+                    )
+                {
+                    builder.declarations(
+                        declarations
+                            .iter()
+                            .map(|&declarator| match declarator {
+                                Node::VariableDeclarator(
+                                    declarator @ VariableDeclarator {
+                                        metadata,
+                                        init: None,
+                                        ..
+                                    },
+                                ) => {
+                                    let mut builder =
+                                        VariableDeclaratorBuilder::from_node(declarator);
+                                    builder.init(Some(undefined(gc, metadata.range)));
+                                    builder.build(gc).unwrap()
+                                }
+                                _ => declarator,
+                            })
+                            .collect(),
+                    );
+                }
+                TransformResult::Changed(builder.build(gc).unwrap())
+            }
+            _ if wrap_params.is_some() => {
+                let label = match parent {
+                    Some(Node::LabeledStatement(LabeledStatement {
+                        label: Node::Identifier(Identifier { name, .. }),
+                        ..
+                    })) => Some(*name),
+                    _ => None,
+                };
+                let (params, copies) = wrap_params.unwrap();
+                match self.wrap_loop(gc, node, &params, &copies, label) {
+                    Some((decl, new_loop)) if label.is_some() => {
+                        self.pending = Some(decl);
+                        TransformResult::Changed(new_loop)
+                    }
+                    Some((decl, new_loop)) => {
+                        TransformResult::Changed(block(gc, vec![decl, new_loop], *node.range()))
+                    }
+                    None if changed => TransformResult::Changed(node),
+                    None => TransformResult::Unchanged,
+                }
+            }
+            Node::LabeledStatement(_) if self.pending.is_some() => {
+                let decl = self.pending.take().unwrap();
+                TransformResult::Changed(block(gc, vec![decl, node], *node.range()))
+            }
+            _ if changed => TransformResult::Changed(node),
+            _ => TransformResult::Unchanged,
+        }
+    }
+}
+
+impl<'gc> Lowerer<'_, 'gc> {
+    /// Return the names of the lexical bindings declared by the head of the loop `node`, after
+    /// renaming.
+    fn loop_params(&self, node: &'gc Node<'gc>) -> Vec<Atom> {
+        let scope = match self.sem.node_scope(node) {
+            Some(scope) => scope,
+            None => return Vec::new(),
+        };
+        self.sem
+            .scope(scope)
+            .bindings
+            .iter()
+            .map(|&id| {
+                let binding = self.sem.binding(id);
+                binding
+                    .declarations
+                    .first()
+                    .and_then(|&decl| self.renames.get(&NodeRef(decl)).copied())
+                    .unwrap_or(binding.name)
+            })
+            .collect()
+    }
+
+    /// Return the expression replacing the assignment `node` of the constant `name`, which
+    /// throws after evaluating the assigned value.
+    fn assign_constant(
+        &mut self,
+        gc: &'gc GCContext,
+        node: &'gc Node<'gc>,
+        name: Atom,
+    ) -> &'gc Node<'gc> {
+        let range = *node.range();
+        let helper = match self.helper {
+            Some(helper) => helper,
+            None => *self
+                .helper
+                .insert(self.names.generate(gc, "_readOnlyError")),
+        };
+        let error = call(
+            gc,
+            identifier(gc, helper, range),
+            vec![string(gc, gc.str(name), range)],
+            range,
+        );
+        match node {
+            Node::AssignmentExpression(AssignmentExpression {
+                operator,
+                left,
+                right,
+                ..
+            }) => {
+                let assign = sequence(gc, vec![right, error], range);
+                // Logical assignments only assign if the test fails.
+                let operator = match operator {
+                    AssignmentExpressionOperator::LogicalOrAssign => LogicalExpressionOperator::Or,
+                    AssignmentExpressionOperator::LogicalAndAssign => {
+                        LogicalExpressionOperator::And
+                    }
+                    AssignmentExpressionOperator::NullishCoalesceAssign => {
+                        LogicalExpressionOperator::NullishCoalesce
+                    }
+                    _ => return assign,
+                };
+                logical(gc, operator, left, assign, range)
+            }
+            _ => error,
+        }
+    }
+
+    /// Return the declaration of the function containing the body of the loop `node`, and
+    /// the loop calling it, or `None` if the body can't be moved to a function.
+    /// `copies` are the names of the variables of the loop and of the function which are copied
+    /// back at the end of every iteration.
+    fn wrap_loop(
+        &mut self,
+        gc: &'gc GCContext,
+        node: &'gc Node<'gc>,
+        params: &[Atom],
+        copies: &[(Atom, Atom)],
+        label: Option<Atom>,
+    ) -> Option<(&'gc Node<'gc>, &'gc Node<'gc>)> {
+        let range = *node.range();
+        let body = match node {
+            Node::ForStatement(ForStatement { body, .. })
+            | Node::ForInStatement(ForInStatement { body, .. })
+            | Node::ForOfStatement(ForOfStatement { body, .. })
+            | Node::WhileStatement(WhileStatement { body, .. })
+            | Node::DoWhileStatement(DoWhileStatement { body, .. }) => body,
+            _ => unreachable!("{} isn't a loop", node.name()),
+        };
+        let mut rewriter = BodyRewriter {
+            label,
+            copies,
+            loops: 0,
+            switches: 0,
+            labels: Vec::new(),
+            functions: 0,
+            arrows: 0,
+            has_break: false,
+            has_return: false,
+            jumps: Vec::new(),
+            uses_this: false,
+            failed: false,
+        };
+        let body = match VisitorMut::call(&mut rewriter, gc, body, Some(node)) {
+            TransformResult::Changed(new_body) => new_body,
+            TransformResult::Unchanged => body,
+        };
+        if rewriter.failed {
+            return None;
+        }
+
+        let loop_name = self.names.generate(gc, "_loop");
+        let mut stmts = match body {
+            Node::BlockStatement(BlockStatement { body, .. }) => body.clone(),
+            _ => vec![body],
+        };
+        stmts.extend(copy_back(gc, copies, range));
+        let param_ids = params
+            .iter()
+            .map(|&name| identifier(gc, name, range))
+            .collect();
+        let decl = declaration(
+            gc,
+            VariableDeclarationKind::Var,
+            [(
+                identifier(gc, loop_name, range),
+                Some(function(gc, param_ids, stmts, range)),
+            )],
+            range,
+        );
+
+        let callee = identifier(gc, loop_name, range);
+        let arguments = params.iter().map(|&name| {
+            let outer = copies.iter().find(|&&(_, inner)| inner == name);
+            identifier(gc, outer.map_or(name, |&(outer, _)| outer), range)
+        });
+        let result = if rewriter.uses_this {
+            let arguments = std::iter::once(this_expression(gc, range))
+                .chain(arguments)
+                .collect();
+            call(
+                gc,
+                member(gc, callee, gc.atom("call"), range),
+                arguments,
+                range,
+            )
+        } else {
+            call(gc, callee, arguments.collect(), range)
+        };
+        // `if (value === "kind|label") kind label;`, or without the label for the loop.
+        let jump = |value: &'gc Node<'gc>, kind: &str, label: Option<Atom>| {
+            let result = match label {
+                Some(label) => format!("{}|{}", kind, gc.str(label)),
+                None => kind.to_string(),
+            };
+            let metadata = template_metadata(range);
+            let label = label.map(|label| identifier(gc, label, range));
+            let stmt = if kind == "break" {
+                BreakStatementBuilder::build_template(
+                    gc,
+                    BreakStatementTemplate { metadata, label },
+                )
+            } else {
+                ContinueStatementBuilder::build_template(
+                    gc,
+                    ContinueStatementTemplate { metadata, label },
+                )
+            };
+            let test = binary(
+                gc,
+                BinaryExpressionOperator::StrictEquals,
+                value,
+                string(gc, &result, range),
+                range,
+            );
+            if_statement(gc, test, stmt, range)
+        };
+        let mut new_body = NodeList::new();
+        if rewriter.has_return || !rewriter.jumps.is_empty() {
+            // var _ret = _loop(); if (_ret === "break") break;
+            // if (_ret === "continue|outer") continue outer;
+            // if (typeof _ret === "object") return _ret.v;
+            let ret = self.names.generate(gc, "_ret");
+            new_body.push(declaration(
+                gc,
+                VariableDeclarationKind::Var,
+                [(identifier(gc, ret, range), Some(result))],
+                range,
+            ));
+            if rewriter.has_break {
+                new_body.push(jump(identifier(gc, ret, range), "break", None));
+            }
+            for &(kind, label) in &rewriter.jumps {
+                new_body.push(jump(identifier(gc, ret, range), kind, Some(label)));
+            }
+            if rewriter.has_return {
+                let type_of = UnaryExpressionBuilder::build_template(
+                    gc,
+                    UnaryExpressionTemplate {
+                        metadata: template_metadata(range),
+                        operator: UnaryExpressionOperator::Typeof,
+                        argument: identifier(gc, ret, range),
+                        prefix: true,
+                    },
+                );
+                new_body.push(if_statement(
+                    gc,
+                    binary(
+                        gc,
+                        BinaryExpressionOperator::StrictEquals,
+                        type_of,
+                        string(gc, "object", range),
+                        range,
+                    ),
+                    return_statement(
+                        gc,
+                        Some(member(gc, identifier(gc, ret, range), gc.atom("v"), range)),
+                        range,
+                    ),
+                    range,
+                ));
+            }
+        } else if rewriter.has_break {
+            new_body.push(jump(result, "break", None));
+        } else {
+            new_body.push(expression_statement(gc, result));
+        }
+        let new_body = block(gc, new_body, range);
+
+        let new_loop = match node {
+            Node::ForStatement(stmt) => {
+                let mut builder = ForStatementBuilder::from_node(stmt);
+                builder.body(new_body);
+                builder.build(gc).unwrap()
+            }
+            Node::ForInStatement(stmt) => {
+                let mut builder = ForInStatementBuilder::from_node(stmt);
+                builder.body(new_body);
+                builder.build(gc).unwrap()
+            }
+            Node::ForOfStatement(stmt) => {
+                let mut builder = ForOfStatementBuilder::from_node(stmt);
+                builder.body(new_body);
+                builder.build(gc).unwrap()
+            }
+            Node::WhileStatement(stmt) => {
+                let mut builder = WhileStatementBuilder::from_node(stmt);
+                builder.body(new_body);
+                builder.build(gc).unwrap()
+            }
+            Node::DoWhileStatement(stmt) => {
+                let mut builder = DoWhileStatementBuilder::from_node(stmt);
+                builder.body(new_body);
+                builder.build(gc).unwrap()
+            }
+            _ => unreachable!(),
+        };
+        Some((decl, new_loop))
+    }
+}
+
+/// Rewrites the jumps out of the body of a loop moved to a function.
+struct BodyRewriter<'a> {
+    /// Label of the loop.
+    label: Option<Atom>,
+    /// Names of the variables of the loop and of the function, copied back before continuing.
+    copies: &'a [(Atom, Atom)],
+    /// Number of nested loops and switch statements being visited, which are the targets of
+    /// unlabeled jumps.
+    loops: usize,
+    switches: usize,
+    /// Labels declared in the body being visited.
+    labels: Vec<Atom>,
+    /// Number of nested functions, other than arrow functions, being visited.
+    functions: usize,
+    /// Number of nested arrow functions being visited.
+    arrows: usize,
+    has_break: bool,
+    has_return: bool,
+    /// Kinds and labels of the jumps to outer statements, in order of appearance.
+    jumps: Vec<(&'static str, Atom)>,
+    uses_this: bool,
+    failed: bool,
+}
+
+/// Target of a jump in the body of a loop moved to a function.
+enum JumpTarget {
+    /// The loop itself.
+    Loop,
+    /// A statement in the body.
+    Body,
+    /// The outer statement with the label.
+    Outer(Atom),
+}
+
+impl<'gc> VisitorMut<'gc> for BodyRewriter<'_> {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        _parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let range = *node.range();
+        match node {
+            Node::FunctionDeclaration(_) | Node::FunctionExpression(_) => {
+                self.functions += 1;
+                let result = node.visit_children_mut(NodeBuilder::from_node(node), gc, self);
+                self.functions -= 1;
+                return result;
+            }
+            Node::ArrowFunctionExpression(_) => {
+                self.arrows += 1;
+                let result = node.visit_children_mut(NodeBuilder::from_node(node), gc, self);
+                self.arrows -= 1;
+                return result;
+            }
+            _ if is_loop(node) => {
+                self.loops += 1;
+                let result = node.visit_children_mut(NodeBuilder::from_node(node), gc, self);
+                self.loops -= 1;
+                return result;
+            }
+            Node::SwitchStatement(_) => {
+                self.switches += 1;
+                let result = node.visit_children_mut(NodeBuilder::from_node(node), gc, self);
+                self.switches -= 1;
+                return result;
+            }
+            Node::LabeledStatement(LabeledStatement {
+                label: Node::Identifier(Identifier { name, .. }),
+                ..
+            }) => {
+                self.labels.push(*name);
+                let result = node.visit_children_mut(NodeBuilder::from_node(node), gc, self);
+                self.labels.pop();
+                return result;
+            }
+            _ => {}
+        }
+        if self.functions > 0 {
+            return node.visit_children_mut(NodeBuilder::from_node(node), gc, self);
+        }
+
+        match node {
+            Node::ThisExpression(_) => {
+                self.uses_this = true;
+                TransformResult::Unchanged
+            }
+            Node::YieldExpression(_) | Node::AwaitExpression(_) if self.arrows == 0 => {
+                self.fail(
+                    gc,
+                    range,
+                    "loops capturing their variables can't contain 'yield' or 'await'",
+                );
+                TransformResult::Unchanged
+            }
+            Node::ContinueStatement(ContinueStatement { label, .. }) => {
+                match self.jump_target(*label, self.loops == 0) {
+                    JumpTarget::Loop if self.copies.is_empty() => {
+                        TransformResult::Changed(return_statement(gc, None, range))
+                    }
+                    JumpTarget::Loop => {
+                        let mut stmts = copy_back(gc, self.copies, range);
+                        stmts.push(return_statement(gc, None, range));
+                        TransformResult::Changed(block(gc, stmts, range))
+                    }
+                    JumpTarget::Body => TransformResult::Unchanged,
+                    JumpTarget::Outer(label) => {
+                        TransformResult::Changed(self.outer_jump(gc, "continue", label, range))
+                    }
+                }
+            }
+            Node::BreakStatement(BreakStatement { label, .. }) => {
+                match self.jump_target(*label, self.loops == 0 && self.switches == 0) {
+                    JumpTarget::Loop => {
+                        self.has_break = true;
+                        TransformResult::Changed(return_statement(
+                            gc,
+                            Some(string(gc, "break", range)),
+                            range,
+                        ))
+                    }
+                    JumpTarget::Body => TransformResult::Unchanged,
+                    JumpTarget::Outer(label) => {
+                        TransformResult::Changed(self.outer_jump(gc, "break", label, range))
+                    }
+                }
+            }
+            // `return {v: value};`
+            Node::ReturnStatement(ReturnStatement { argument, .. }) if self.arrows == 0 => {
+                self.has_return = true;
+                let value = match argument {
+                    Some(argument) => match VisitorMut::call(self, gc, argument, Some(node)) {
+                        TransformResult::Changed(new_argument) => new_argument,
+                        TransformResult::Unchanged => argument,
+                    },
+                    None => undefined(gc, range),
+                };
+                TransformResult::Changed(return_statement(
+                    gc,
+                    Some(object(gc, vec![property(gc, "v", value, range)], range)),
+                    range,
+                ))
+            }
+            _ => node.visit_children_mut(NodeBuilder::from_node(node), gc, self),
+        }
+    }
+}
+
+impl BodyRewriter<'_> {
+    fn fail(&mut self, gc: &GCContext, range: SourceRange, msg: &str) {
+        gc.sm().error(range, msg);
+        self.failed = true;
+    }
+
+    /// Return the target of the jump with `label`.
+    /// `unlabeled_exits` is whether an unlabeled jump targets the loop.
+    fn jump_target(&self, label: Option<&Node>, unlabeled_exits: bool) -> JumpTarget {
+        match label {
+            None if unlabeled_exits => JumpTarget::Loop,
+            None => JumpTarget::Body,
+            Some(Node::Identifier(Identifier { name, .. })) => {
+                if self.labels.contains(name) {
+                    JumpTarget::Body
+                } else if self.label == Some(*name) {
+                    JumpTarget::Loop
+                } else {
+                    JumpTarget::Outer(*name)
+                }
+            }
+            Some(label) => unreachable!("invalid label {}", label.name()),
+        }
+    }
+
+    /// Return `return "kind|label";`, performing the jump of `kind` to the outer `label` after
+    /// the call.
+    fn outer_jump<'gc>(
+        &mut self,
+        gc: &'gc GCContext,
+        kind: &'static str,
+        label: Atom,
+        range: SourceRange,
+    ) -> &'gc Node<'gc> {
+        if !self.jumps.contains(&(kind, label)) {
+            self.jumps.push((kind, label));
+        }
+        let result = format!("{}|{}", kind, gc.str(label));
+        return_statement(gc, Some(string(gc, &result, range)), range)
+    }
+}
+
+/// Return the statements assigning the variables of the function to the variables of the loop,
+/// for each pair of names in `copies`.
+fn copy_back<'gc>(
+    gc: &'gc GCContext,
+    copies: &[(Atom, Atom)],
+    range: SourceRange,
+) -> NodeList<'gc> {
+    copies
+        .iter()
+        .map(|&(outer, inner)| {
+            expression_statement(
+                gc,
+                assign(
+                    gc,
+                    identifier(gc, outer, range),
+                    identifier(gc, inner, range),
+                    range,
+                ),
+            )
+        })
+        .collect()
+}
+
+/// Return true if `node` is the identifier assigned by its `parent` assignment or update.
+fn is_assignment_target<'gc>(node: &'gc Node<'gc>, parent: Option<&'gc Node<'gc>>) -> bool {
+    match parent {
+        Some(Node::AssignmentExpression(AssignmentExpression { left: target, .. }))
+        | Some(Node::UpdateExpression(UpdateExpression {
+            argument: target, ..
+        })) => std::ptr::eq(*target, node),
+        _ => false,
+    }
+}
+
+/// Return the declaration of the helper `name` throwing on the assignment of a constant:
+/// ```js
+/// function _readOnlyError(name) {
+///   throw new TypeError('"' + name + '" is read-only');
+/// }
+/// ```
+fn read_only_helper<'gc>(gc: &'gc GCContext, name: Atom, range: SourceRange) -> &'gc Node<'gc> {
+    let param = || identifier(gc, gc.atom("name"), range);
+    let message = binary(
+        gc,
+        BinaryExpressionOperator::Plus,
+        binary(
+            gc,
+            BinaryExpressionOperator::Plus,
+            string(gc, "\"", range),
+            param(),
+            range,
+        ),
+        string(gc, "\" is read-only", range),
+        range,
+    );
+    let throw = ThrowStatementBuilder::build_template(
+        gc,
+        ThrowStatementTemplate {
+            metadata: template_metadata(range),
+            argument: new_expression(
+                gc,
+                identifier(gc, gc.atom("TypeError"), range),
+                vec![message],
+                range,
+            ),
+        },
+    );
+    FunctionDeclarationBuilder::build_template(
+        gc,
+        FunctionDeclarationTemplate {
+            metadata: template_metadata(range),
+            id: Some(identifier(gc, name, range)),
+            params: vec![param()],
+            body: block(gc, vec![throw], range),
+            type_parameters: None,
+            return_type: None,
+            predicate: None,
+            generator: false,
+            is_async: false,
+        },
+    )
+}
@@ -0,0 +1,1404 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Class field lowering.
+//!
+//! Compiles class fields for engines which support classes but not fields. Instance fields are
+//! assigned at the start of the constructor, or after the `super` call in derived classes, and
+//! static fields are assigned to the class after its definition. Fields are assigned rather than
+//! defined, so setters inherited from the superclass are called.
+//! Private fields are stored in a `WeakMap` per field, keyed by the instances, and private
+//! member accesses are compiled to calls of its methods, after a helper declared at the top of
+//! the program has checked that the object has the field. Private methods are moved out of the
+//! class into variables, and the objects which have them are added to a `WeakSet` per class.
+//! Private accessors can't be lowered and are reported as errors.
+//! Computed keys are evaluated before the class, in variables.
+//!
+//! Transforms
+//! ```js
+//! class A extends B {
+//!   #x = 1;
+//!   y = this.#x;
+//!   static z = 2;
+//!   m() { return this.#x++; }
+//! }
+//! ```
+//! into
+//! ```js
+//! function _checkPrivate(set, obj) { ... }
+//! var _x = new WeakMap();
+//! class A extends B {
+//!   constructor(...args) {
+//!     super(...args);
+//!     _x.set(this, 1);
+//!     this.y = _x.get(_checkPrivate(_x, this));
+//!   }
+//!   m() {
+//!     var _value;
+//!     return _x.set(_checkPrivate(_x, this), (_value = +_x.get(this)) + 1), _value;
+//!   }
+//! }
+//! A.z = 2;
+//! ```
+
+use crate::passes::util::*;
+use crate::Pass;
+use juno::ast::*;
+use juno::sema::{resolve_program, BindingId, SemContext};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+
+#[derive(Default)]
+pub struct ClassFields {}
+
+impl ClassFields {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Pass for ClassFields {
+    fn name(&self) -> &'static str {
+        "Lower class fields"
+    }
+    fn description(&self) -> &'static str {
+        "Compiles class fields to assignments and private fields to WeakMaps"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let sem = resolve_program(gc, node);
+        let mut lowerer = Lowerer {
+            sem: &sem,
+            temps: Temporaries::new(UniqueNames::new(gc, node)),
+            classes: Vec::new(),
+            splices: Splices::new(),
+            helper: None,
+        };
+        VisitorMut::call(&mut lowerer, gc, node, None)
+    }
+}
+
+/// Where a private member of a class is stored.
+#[derive(Copy, Clone)]
+enum Private {
+    /// A field, stored in a `WeakMap`.
+    Field(Atom),
+    /// A method, stored in a variable, with the `WeakSet` of the objects which have it.
+    Method { function: Atom, brand: Atom },
+}
+
+/// A class being visited.
+struct Class {
+    /// The storage of each private field and method.
+    privates: HashMap<Atom, Private>,
+    /// The `WeakSet`s of the instances and of the class, if they have private methods.
+    brand: Option<Atom>,
+    static_brand: Option<Atom>,
+    /// Names declared in the scope of the constructor, which must not be referenced by the
+    /// instance field initializers moved into it.
+    constructor_names: HashSet<Atom>,
+    /// The binding of the name of a class expression, visible in its body.
+    name_binding: Option<BindingId>,
+}
+
+struct Lowerer<'a, 'gc> {
+    sem: &'a SemContext<'gc>,
+    temps: Temporaries,
+    /// Classes being visited, innermost last.
+    classes: Vec<Class>,
+    /// Declarations of the `WeakMap`s and computed keys of class declarations, and assignments
+    /// of their static fields.
+    splices: Splices<'gc>,
+    /// Name of the helper checking that objects have a private member, once it is used.
+    helper: Option<Atom>,
+}
+
+/// Return the object and the private name of the private member expression `node`.
+fn private_member<'gc>(node: &'gc Node<'gc>) -> Option<(&'gc Node<'gc>, &'gc Node<'gc>)> {
+    match node {
+        Node::MemberExpression(MemberExpression {
+            object,
+            property: property @ Node::PrivateName(_),
+            ..
+        }) => Some((object, property)),
+        _ => None,
+    }
+}
+
+/// Return the name of the private name `node`, or of the key of a private property, which is
+/// an identifier without the `#`.
+fn private_name(node: &Node) -> Atom {
+    match node {
+        Node::PrivateName(PrivateName {
+            id: Node::Identifier(Identifier { name, .. }),
+            ..
+        })
+        | Node::Identifier(Identifier { name, .. }) => *name,
+        _ => unreachable!("invalid private name {}", node.name()),
+    }
+}
+
+impl<'gc> VisitorMut<'gc> for Lowerer<'_, 'gc> {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let is_scope = is_var_scope(node);
+        if is_scope {
+            self.temps.enter();
+        }
+        let is_class = matches!(node, Node::ClassDeclaration(_) | Node::ClassExpression(_));
+        if is_class {
+            let class = self.enter_class(gc, node);
+            self.classes.push(class);
+        }
+        // Private members which are called or assigned are lowered with their parent.
+        let mut lower_member = false;
+        if private_member(node).is_some() {
+            lower_member = match parent {
+                Some(Node::CallExpression(CallExpression { callee, .. })) => {
+                    !std::ptr::eq(*callee, node)
+                }
+                Some(Node::AssignmentExpression(AssignmentExpression { left, .. })) => {
+                    !std::ptr::eq(*left, node)
+                }
+                Some(Node::UpdateExpression(_)) => false,
+                Some(
+                    Node::ArrayPattern(_)
+                    | Node::RestElement(_)
+                    | Node::AssignmentPattern(_)
+                    | Node::ForInStatement(_)
+                    | Node::ForOfStatement(_),
+                ) => {
+                    gc.sm().error(
+                        *node.range(),
+                        "private fields can't be lowered in destructuring targets",
+                    );
+                    false
+                }
+                _ => true,
+            };
+        }
+        if let (
+            Some(Node::ObjectPattern(_)),
+            Node::Property(Property {
+                value: value @ Node::MemberExpression(_),
+                ..
+            }),
+        ) = (parent, node)
+        {
+            if private_member(value).is_some() {
+                gc.sm().error(
+                    *value.range(),
+                    "private fields can't be lowered in destructuring targets",
+                );
+            }
+        }
+
+        let (mut node, mut changed) =
+            match node.visit_children_mut(NodeBuilder::from_node(node), gc, self) {
+                TransformResult::Changed(new_node) => (new_node, true),
+                TransformResult::Unchanged => (node, false),
+            };
+        let is_statement = matches!(parent, Some(Node::ExpressionStatement(_)));
+        let lowered = match node {
+            _ if lower_member => self.lower_private_get(gc, node),
+            Node::CallExpression(CallExpression {
+                metadata,
+                callee,
+                arguments,
+                ..
+            }) => private_member(callee).and_then(|(object, name)| {
+                let range = metadata.range;
+                let (function, this) = match self.private(gc, name)? {
+                    // `_x.get(_checkPrivate(_x, _obj = o)).call(_obj, args)`
+                    Private::Field(map) => {
+                        let (object, this) = self.reuse(gc, object);
+                        let object = self.check(gc, map, object, range);
+                        (map_call(gc, map, "get", vec![object], range), this)
+                    }
+                    // `_m.call(_checkPrivate(_brand, o), args)`
+                    Private::Method { function, brand } => (
+                        identifier(gc, function, range),
+                        self.check(gc, brand, object, range),
+                    ),
+                };
+                let mut call_arguments = vec![this];
+                call_arguments.extend(arguments);
+                Some(call(
+                    gc,
+                    member(gc, function, gc.atom("call"), range),
+                    call_arguments,
+                    range,
+                ))
+            }),
+            Node::AssignmentExpression(AssignmentExpression {
+                metadata,
+                operator,
+                left,
+                right,
+            }) => private_member(left).and_then(|(object, name)| {
+                let map = self.private_field(gc, name)?;
+                self.lower_private_assignment(
+                    gc,
+                    map,
+                    object,
+                    *operator,
+                    right,
+                    is_statement,
+                    metadata.range,
+                )
+            }),
+            Node::UpdateExpression(UpdateExpression {
+                metadata,
+                operator,
+                argument,
+                prefix,
+            }) => private_member(argument).and_then(|(object, name)| {
+                let map = self.private_field(gc, name)?;
+                Some(self.lower_private_update(
+                    gc,
+                    map,
+                    object,
+                    *operator,
+                    *prefix || is_statement,
+                    metadata.range,
+                ))
+            }),
+            // `#x in o`
+            Node::BinaryExpression(BinaryExpression {
+                metadata,
+                left: name @ Node::PrivateName(_),
+                right,
+                operator: BinaryExpressionOperator::In,
+            }) => self.private(gc, name).map(|private| {
+                let set = match private {
+                    Private::Field(map) => map,
+                    Private::Method { brand, .. } => brand,
+                };
+                map_call(gc, set, "has", vec![right], metadata.range)
+            }),
+            Node::ClassDeclaration(_) | Node::ClassExpression(_) => self.lower_class(gc, node),
+            Node::ExportNamedDeclaration(ExportNamedDeclaration {
+                declaration: Some(decl),
+                ..
+            })
+            | Node::ExportDefaultDeclaration(ExportDefaultDeclaration {
+                declaration: decl, ..
+            }) => {
+                // The statements around an exported class are inserted around the export.
+                if let Some(splice) = self.splices.remove(&NodeRef(decl)) {
+                    self.splices.insert(NodeRef(node), splice);
+                }
+                None
+            }
+            _ => splice_statements(gc, node, &mut self.splices),
+        };
+        let lowered = match (node, self.helper) {
+            (Node::Program(_), Some(helper)) => {
+                let program = lowered.unwrap_or(node);
+                Some(prepend_to_body(
+                    gc,
+                    program,
+                    vec![check_helper(gc, helper, *program.range())],
+                ))
+            }
+            _ => lowered,
+        };
+        if is_class {
+            self.classes.pop();
+        }
+        if let Some(new_node) = lowered {
+            node = new_node;
+            changed = true;
+        }
+        if is_scope {
+            if let Some(new_node) = self.temps.exit(gc, node) {
+                node = new_node;
+                changed = true;
+            }
+        }
+        if changed {
+            TransformResult::Changed(node)
+        } else {
+            TransformResult::Unchanged
+        }
+    }
+}
+
+impl<'gc> Lowerer<'_, 'gc> {
+    /// Return the information about the class `node` needed while visiting it.
+    fn enter_class(&mut self, gc: &'gc GCContext, node: &'gc Node<'gc>) -> Class {
+        let (id, body, is_decl) = match node {
+            Node::ClassDeclaration(ClassDeclaration { id, body, .. }) => (*id, *body, true),
+            Node::ClassExpression(ClassExpression { id, body, .. }) => (*id, *body, false),
+            _ => unreachable!(),
+        };
+        let mut class = Class {
+            privates: HashMap::new(),
+            brand: None,
+            static_brand: None,
+            constructor_names: HashSet::new(),
+            name_binding: if is_decl {
+                None
+            } else {
+                id.and_then(|id| self.sem.binding_of(id))
+            },
+        };
+        for &member in class_members(body) {
+            match member {
+                Node::ClassPrivateProperty(ClassPrivateProperty { key, .. }) => {
+                    let name = private_name(key);
+                    let map = self.class_temp(gc, &format!("_{}", gc.str(name)), is_decl);
+                    class.privates.insert(name, Private::Field(map));
+                }
+                Node::MethodDefinition(MethodDefinition {
+                    key: key @ Node::PrivateName(_),
+                    kind: MethodDefinitionKind::Method,
+                    is_static,
+                    ..
+                }) => {
+                    let name = private_name(key);
+                    let (slot, base) = if *is_static {
+                        (&mut class.static_brand, "_staticBrand")
+                    } else {
+                        (&mut class.brand, "_brand")
+                    };
+                    let brand = match *slot {
+                        Some(brand) => brand,
+                        None => *slot.insert(self.class_temp(gc, base, is_decl)),
+                    };
+                    let function = self.class_temp(gc, &format!("_{}", gc.str(name)), is_decl);
+                    class
+                        .privates
+                        .insert(name, Private::Method { function, brand });
+                }
+                Node::MethodDefinition(MethodDefinition {
+                    kind: MethodDefinitionKind::Constructor,
+                    value,
+                    ..
+                }) => {
+                    if let Some(scope) = self.sem.node_scope(value) {
+                        class.constructor_names = self
+                            .sem
+                            .scope(scope)
+                            .bindings
+                            .iter()
+                            .map(|&id| self.sem.binding(id).name)
+                            .collect();
+                    }
+                }
+                _ => {}
+            }
+        }
+        class
+    }
+
+    /// Return where the private member `name` is stored, or `None` if it isn't declared by an
+    /// enclosing class, which is reported.
+    fn private(&self, gc: &'gc GCContext, name: &'gc Node<'gc>) -> Option<Private> {
+        let atom = private_name(name);
+        let private = self
+            .classes
+            .iter()
+            .rev()
+            .find_map(|class| class.privates.get(&atom).copied());
+        if private.is_none() {
+            gc.sm().error(
+                *name.range(),
+                format!(
+                    "private name #{} isn't a member of an enclosing class",
+                    gc.str(atom)
+                ),
+            );
+        }
+        private
+    }
+
+    /// Return the `WeakMap` storing the private field `name`, or `None` if it isn't a field of
+    /// an enclosing class, which is reported.
+    fn private_field(&self, gc: &'gc GCContext, name: &'gc Node<'gc>) -> Option<Atom> {
+        match self.private(gc, name)? {
+            Private::Field(map) => Some(map),
+            Private::Method { .. } => {
+                gc.sm().error(
+                    *name.range(),
+                    format!(
+                        "private method #{} can't be assigned",
+                        gc.str(private_name(name))
+                    ),
+                );
+                None
+            }
+        }
+    }
+
+    /// Return `_checkPrivate(set, object)`, which throws a `TypeError` if `object` isn't in the
+    /// `WeakMap` or `WeakSet` `set`, and evaluates to `object` otherwise.
+    fn check(
+        &mut self,
+        gc: &'gc GCContext,
+        set: Atom,
+        object: &'gc Node<'gc>,
+        range: SourceRange,
+    ) -> &'gc Node<'gc> {
+        let helper = match self.helper {
+            Some(helper) => helper,
+            None => *self
+                .helper
+                .insert(self.temps.names.generate(gc, "_checkPrivate")),
+        };
+        call(
+            gc,
+            identifier(gc, helper, range),
+            vec![identifier(gc, set, range), object],
+            range,
+        )
+    }
+
+    /// Return the expression evaluating `object` and the expression evaluating to the same
+    /// object afterwards, saving it in a temporary if needed.
+    fn reuse(
+        &mut self,
+        gc: &'gc GCContext,
+        object: &'gc Node<'gc>,
+    ) -> (&'gc Node<'gc>, &'gc Node<'gc>) {
+        match object {
+            Node::ThisExpression(ThisExpression { metadata }) => {
+                (object, this_expression(gc, metadata.range))
+            }
+            Node::Identifier(Identifier { metadata, name, .. }) => {
+                (object, identifier(gc, *name, metadata.range))
+            }
+            _ => {
+                let range = *object.range();
+                let temp = self.temps.generate(gc, "_obj");
+                (
+                    assign(gc, identifier(gc, temp, range), object, range),
+                    identifier(gc, temp, range),
+                )
+            }
+        }
+    }
+
+    /// Return `_x.get(_checkPrivate(_x, o))` for the private member `o.#x`.
+    fn lower_private_get(
+        &mut self,
+        gc: &'gc GCContext,
+        node: &'gc Node<'gc>,
+    ) -> Option<&'gc Node<'gc>> {
+        let (object, name) = private_member(node)?;
+        let range = *node.range();
+        Some(match self.private(gc, name)? {
+            Private::Field(map) => {
+                let object = self.check(gc, map, object, range);
+                map_call(gc, map, "get", vec![object], range)
+            }
+            // `(_checkPrivate(_brand, o), _m)`
+            Private::Method { function, brand } => sequence(
+                gc,
+                vec![
+                    self.check(gc, brand, object, range),
+                    identifier(gc, function, range),
+                ],
+                range,
+            ),
+        })
+    }
+
+    /// Return the expression storing `value` in the private field `map` of `object`, and
+    /// evaluating to it unless `is_statement`.
+    fn set_private(
+        &mut self,
+        gc: &'gc GCContext,
+        map: Atom,
+        object: &'gc Node<'gc>,
+        value: &'gc Node<'gc>,
+        is_statement: bool,
+        range: SourceRange,
+    ) -> &'gc Node<'gc> {
+        if is_statement {
+            return map_call(gc, map, "set", vec![object, value], range);
+        }
+        // `(_x.set(o, _value = value), _value)`
+        let temp = self.temps.generate(gc, "_value");
+        let set = map_call(
+            gc,
+            map,
+            "set",
+            vec![
+                object,
+                assign(gc, identifier(gc, temp, range), value, range),
+            ],
+            range,
+        );
+        sequence(gc, vec![set, identifier(gc, temp, range)], range)
+    }
+
+    /// Return the lowered assignment `o.#x op value`.
+    #[allow(clippy::too_many_arguments)]
+    fn lower_private_assignment(
+        &mut self,
+        gc: &'gc GCContext,
+        map: Atom,
+        object: &'gc Node<'gc>,
+        operator: AssignmentExpressionOperator,
+        value: &'gc Node<'gc>,
+        is_statement: bool,
+        range: SourceRange,
+    ) -> Option<&'gc Node<'gc>> {
+        if operator == AssignmentExpressionOperator::Assign {
+            let object = self.check(gc, map, object, range);
+            return Some(self.set_private(gc, map, object, value, is_statement, range));
+        }
+        let (first, second) = self.reuse(gc, object);
+        let first = self.check(gc, map, first, range);
+        // The operator without the trailing `=`.
+        let op = operator.as_str().strip_suffix('=').unwrap();
+        if let Ok(op) = BinaryExpressionOperator::try_from(op) {
+            // `_x.set(_checkPrivate(_x, _obj = o), _x.get(_obj) op value)`
+            let get = map_call(gc, map, "get", vec![second], range);
+            let value = binary(gc, op, get, value, range);
+            Some(self.set_private(gc, map, first, value, is_statement, range))
+        } else if let Ok(op) = LogicalExpressionOperator::try_from(op) {
+            // `_x.get(_checkPrivate(_x, _obj = o)) op (_x.set(_obj, _value = value), _value)`
+            let get = map_call(gc, map, "get", vec![first], range);
+            let set = self.set_private(gc, map, second, value, false, range);
+            Some(logical(gc, op, get, set, range))
+        } else {
+            unreachable!("invalid assignment operator {}", operator.as_str())
+        }
+    }
+
+    /// Return the lowered update `o.#x++`, evaluating to the new value if `new_value`.
+    fn lower_private_update(
+        &mut self,
+        gc: &'gc GCContext,
+        map: Atom,
+        object: &'gc Node<'gc>,
+        operator: UpdateExpressionOperator,
+        new_value: bool,
+        range: SourceRange,
+    ) -> &'gc Node<'gc> {
+        let (first, second) = self.reuse(gc, object);
+        let first = self.check(gc, map, first, range);
+        let op = match operator {
+            UpdateExpressionOperator::Increment => BinaryExpressionOperator::Plus,
+            UpdateExpressionOperator::Decrement => BinaryExpressionOperator::Minus,
+        };
+        let old = UnaryExpressionBuilder::build_template(
+            gc,
+            UnaryExpressionTemplate {
+                metadata: template_metadata(range),
+                operator: UnaryExpressionOperator::Plus,
+                argument: map_call(gc, map, "get", vec![second], range),
+                prefix: true,
+            },
+        );
+        if new_value {
+            // `_x.set(_checkPrivate(_x, _obj = o), +_x.get(_obj) + 1)`
+            let value = binary(gc, op, old, number(gc, 1.0, range), range);
+            return self.set_private(gc, map, first, value, false, range);
+        }
+        // `(_x.set(_checkPrivate(_x, _obj = o), (_value = +_x.get(_obj)) + 1), _value)`
+        let temp = self.temps.generate(gc, "_value");
+        let value = binary(
+            gc,
+            op,
+            assign(gc, identifier(gc, temp, range), old, range),
+            number(gc, 1.0, range),
+            range,
+        );
+        let set = map_call(gc, map, "set", vec![first, value], range);
+        sequence(gc, vec![set, identifier(gc, temp, range)], range)
+    }
+
+    /// Return the class `node`, whose members have already been lowered, without its fields,
+    /// or `None` if it doesn't have any.
+    fn lower_class(&mut self, gc: &'gc GCContext, node: &'gc Node<'gc>) -> Option<&'gc Node<'gc>> {
+        let (id, super_class, body, is_decl) = match node {
+            Node::ClassDeclaration(ClassDeclaration {
+                id,
+                super_class,
+                body,
+                ..
+            }) => (*id, *super_class, *body, true),
+            Node::ClassExpression(ClassExpression {
+                id,
+                super_class,
+                body,
+                ..
+            }) => (*id, *super_class, *body, false),
+            _ => unreachable!(),
+        };
+        let members = class_members(body);
+        let is_lowered = |member: &Node| {
+            matches!(
+                member,
+                Node::ClassProperty(_)
+                    | Node::ClassPrivateProperty(_)
+                    | Node::MethodDefinition(MethodDefinition {
+                        key: Node::PrivateName(_),
+                        ..
+                    })
+            )
+        };
+        if !members.iter().any(|member| is_lowered(member)) {
+            return None;
+        }
+        let range = *node.range();
+        let has_statics = members.iter().any(|member| {
+            matches!(
+                member,
+                Node::ClassProperty(ClassProperty {
+                    is_static: true,
+                    declare: false,
+                    ..
+                }) | Node::ClassPrivateProperty(ClassPrivateProperty {
+                    is_static: true,
+                    ..
+                }) | Node::MethodDefinition(MethodDefinition {
+                    key: Node::PrivateName(_),
+                    is_static: true,
+                    ..
+                })
+            )
+        });
+
+        // The class referenced by the static fields.
+        let mut id = id;
+        let class_ref = match id {
+            _ if !has_statics => None,
+            Some(Node::Identifier(Identifier { name, .. })) if is_decl => Some(*name),
+            None if is_decl => {
+                // An anonymous default export.
+                let name = self.temps.names.generate(gc, "_class");
+                id = Some(identifier(gc, name, range));
+                Some(name)
+            }
+            _ => Some(self.temps.generate(gc, "_class")),
+        };
+
+        let class = self.classes.last().unwrap();
+        let name_binding = class.name_binding;
+        let constructor_names = class.constructor_names.clone();
+        // Variables initialized before the class.
+        let mut prefix: Vec<(Atom, &'gc Node<'gc>)> = Vec::new();
+        let (brand, static_brand) = (class.brand, class.static_brand);
+        for brand in brand.into_iter().chain(static_brand) {
+            let weak_set = identifier(gc, gc.atom("WeakSet"), range);
+            prefix.push((brand, new_expression(gc, weak_set, vec![], range)));
+        }
+        for &member in members {
+            match member {
+                Node::ClassPrivateProperty(ClassPrivateProperty { key, .. }) => {
+                    if let Private::Field(map) = class.privates[&private_name(key)] {
+                        let weak_map = identifier(gc, gc.atom("WeakMap"), *key.range());
+                        prefix.push((map, new_expression(gc, weak_map, vec![], *key.range())));
+                    }
+                }
+                Node::MethodDefinition(MethodDefinition {
+                    key: key @ Node::PrivateName(_),
+                    kind: MethodDefinitionKind::Method,
+                    value,
+                    ..
+                }) => {
+                    if let Private::Method { function, .. } = class.privates[&private_name(key)] {
+                        check_private_method(gc, self.sem, name_binding, value);
+                        prefix.push((function, value));
+                    }
+                }
+                _ => {}
+            }
+        }
+        let privates = class.privates.clone();
+
+        let mut instance_inits = NodeList::new();
+        let mut static_inits = NodeList::new();
+        let mut new_members = NodeList::new();
+        for &member in members {
+            let member_range = *member.range();
+            match member {
+                Node::ClassProperty(ClassProperty { declare: true, .. }) => {}
+                Node::ClassProperty(ClassProperty {
+                    key,
+                    value,
+                    computed,
+                    is_static,
+                    ..
+                }) => {
+                    let key = match key {
+                        Node::StringLiteral(_) | Node::NumericLiteral(_) => *key,
+                        _ if *computed => {
+                            let temp = self.class_temp(gc, "_key", is_decl);
+                            prefix.push((temp, key));
+                            identifier(gc, temp, *key.range())
+                        }
+                        _ => *key,
+                    };
+                    let value = value.unwrap_or_else(|| undefined(gc, member_range));
+                    if *is_static {
+                        let class_ref = class_ref.unwrap();
+                        let target = key_member(
+                            gc,
+                            identifier(gc, class_ref, member_range),
+                            key,
+                            *computed,
+                            member_range,
+                        );
+                        let value = self.static_value(gc, value, class_ref, name_binding);
+                        static_inits.push(assign(gc, target, value, member_range));
+                    } else {
+                        check_shadowing(gc, self.sem, &constructor_names, value);
+                        let target = key_member(
+                            gc,
+                            this_expression(gc, member_range),
+                            key,
+                            *computed,
+                            member_range,
+                        );
+                        instance_inits.push(assign(gc, target, value, member_range));
+                    }
+                }
+                Node::ClassPrivateProperty(ClassPrivateProperty {
+                    key,
+                    value,
+                    is_static,
+                    ..
+                }) => {
+                    let map = match privates[&private_name(key)] {
+                        Private::Field(map) => map,
+                        Private::Method { .. } => unreachable!("private field declared as method"),
+                    };
+                    let value = value.unwrap_or_else(|| undefined(gc, member_range));
+                    if *is_static {
+                        let class_ref = class_ref.unwrap();
+                        let value = self.static_value(gc, value, class_ref, name_binding);
+                        static_inits.push(map_call(
+                            gc,
+                            map,
+                            "set",
+                            vec![identifier(gc, class_ref, member_range), value],
+                            member_range,
+                        ));
+                    } else {
+                        check_shadowing(gc, self.sem, &constructor_names, value);
+                        instance_inits.push(map_call(
+                            gc,
+                            map,
+                            "set",
+                            vec![this_expression(gc, member_range), value],
+                            member_range,
+                        ));
+                    }
+                }
+                // Moved before the class.
+                Node::MethodDefinition(MethodDefinition {
+                    key: Node::PrivateName(_),
+                    kind: MethodDefinitionKind::Method,
+                    ..
+                }) => {}
+                Node::MethodDefinition(MethodDefinition {
+                    key: key @ Node::PrivateName(_),
+                    ..
+                }) => {
+                    gc.sm()
+                        .error(*key.range(), "private accessors can't be lowered");
+                    new_members.push(member);
+                }
+                _ => new_members.push(member),
+            }
+        }
+
+        // The methods are added before the fields are initialized.
+        if let Some(brand) = brand {
+            instance_inits.insert(
+                0,
+                map_call(gc, brand, "add", vec![this_expression(gc, range)], range),
+            );
+        }
+        if let Some(brand) = static_brand {
+            let class_ref = identifier(gc, class_ref.unwrap(), range);
+            static_inits.insert(0, map_call(gc, brand, "add", vec![class_ref], range));
+        }
+
+        if !instance_inits.is_empty() {
+            let derived = super_class.is_some();
+            match new_members.iter().position(|member| {
+                matches!(
+                    member,
+                    Node::MethodDefinition(MethodDefinition {
+                        kind: MethodDefinitionKind::Constructor,
+                        ..
+                    })
+                )
+            }) {
+                Some(index) => {
+                    new_members[index] =
+                        self.add_to_constructor(gc, new_members[index], derived, instance_inits);
+                }
+                None => {
+                    let constructor = self.default_constructor(gc, derived, instance_inits, range);
+                    new_members.insert(0, constructor);
+                }
+            }
+        }
+
+        let new_body = match body {
+            Node::ClassBody(class_body) => {
+                let mut builder = ClassBodyBuilder::from_node(class_body);
+                builder.body(new_members);
+                builder.build(gc).unwrap()
+            }
+            _ => unreachable!(),
+        };
+        let new_class = match node {
+            Node::ClassDeclaration(class) => {
+                let mut builder = ClassDeclarationBuilder::from_node(class);
+                builder.id(id);
+                builder.body(new_body);
+                builder.build(gc).unwrap()
+            }
+            Node::ClassExpression(class) => {
+                let mut builder = ClassExpressionBuilder::from_node(class);
+                builder.body(new_body);
+                builder.build(gc).unwrap()
+            }
+            _ => unreachable!(),
+        };
+
+        if is_decl {
+            let mut before = NodeList::new();
+            if !prefix.is_empty() {
+                before.push(declaration(
+                    gc,
+                    VariableDeclarationKind::Var,
+                    prefix
+                        .into_iter()
+                        .map(|(name, init)| (identifier(gc, name, *init.range()), Some(init))),
+                    range,
+                ));
+            }
+            let after = static_inits
+                .into_iter()
+                .map(|init| expression_statement(gc, init))
+                .collect();
+            self.splices.insert(NodeRef(new_class), (before, after));
+            return Some(new_class);
+        }
+
+        // `(_x = new WeakMap(), _class = class {...}, _class.y = 1, _class)`
+        let mut expressions: NodeList = prefix
+            .into_iter()
+            .map(|(name, init)| {
+                let init_range = *init.range();
+                assign(gc, identifier(gc, name, init_range), init, init_range)
+            })
+            .collect();
+        match class_ref {
+            Some(class_ref) => {
+                expressions.push(assign(
+                    gc,
+                    identifier(gc, class_ref, range),
+                    new_class,
+                    range,
+                ));
+                expressions.extend(static_inits);
+                expressions.push(identifier(gc, class_ref, range));
+            }
+            None => expressions.push(new_class),
+        }
+        Some(sequence(gc, expressions, range))
+    }
+
+    /// Return a new variable initialized before the class, declared with the class if
+    /// `is_decl`, or assigned in the class expression otherwise.
+    fn class_temp(&mut self, gc: &'gc GCContext, base: &str, is_decl: bool) -> Atom {
+        if is_decl {
+            self.temps.names.generate(gc, base)
+        } else {
+            self.temps.generate(gc, base)
+        }
+    }
+
+    /// Return the initializer `value` of a static field moved after the class, with `this` and
+    /// the name of the class expression replaced by `class_ref`.
+    fn static_value(
+        &mut self,
+        gc: &'gc GCContext,
+        value: &'gc Node<'gc>,
+        class_ref: Atom,
+        name_binding: Option<BindingId>,
+    ) -> &'gc Node<'gc> {
+        let mut rewriter = StaticRewriter {
+            sem: self.sem,
+            class_ref,
+            name_binding,
+            functions: 0,
+        };
+        match VisitorMut::call(&mut rewriter, gc, value, None) {
+            TransformResult::Changed(new_value) => new_value,
+            TransformResult::Unchanged => value,
+        }
+    }
+
+    /// Return the constructor `method` with the instance field initializers `inits` evaluated
+    /// at its start, or after the `super` calls of a `derived` class.
+    fn add_to_constructor(
+        &mut self,
+        gc: &'gc GCContext,
+        method: &'gc Node<'gc>,
+        derived: bool,
+        inits: NodeList<'gc>,
+    ) -> &'gc Node<'gc> {
+        let (method_def, constructor) = match method {
+            Node::MethodDefinition(method_def @ MethodDefinition { value, .. }) => {
+                (method_def, *value)
+            }
+            _ => unreachable!(),
+        };
+        let new_constructor = if !derived {
+            prepend_to_body(
+                gc,
+                constructor,
+                inits
+                    .into_iter()
+                    .map(|init| expression_statement(gc, init))
+                    .collect(),
+            )
+        } else {
+            let mut counter = SuperCallCounter { count: 0 };
+            constructor.visit_children(gc, &mut counter);
+            let range = *constructor.range();
+            // With several `super` calls, the initializers are moved to a function called after
+            // each of them.
+            let (helper, inits) = if counter.count > 1 {
+                let name = self.temps.names.generate(gc, "_initFields");
+                let body = inits
+                    .into_iter()
+                    .map(|init| expression_statement(gc, init))
+                    .collect();
+                (Some((name, function(gc, vec![], body, range))), None)
+            } else {
+                (None, Some(inits))
+            };
+            let mut rewriter = SuperCallRewriter {
+                helper: helper.map(|(name, _)| name),
+                inits,
+                splices: Splices::new(),
+            };
+            let new_constructor = match constructor.visit_children_mut(
+                NodeBuilder::from_node(constructor),
+                gc,
+                &mut rewriter,
+            ) {
+                TransformResult::Changed(new_constructor) => new_constructor,
+                TransformResult::Unchanged => constructor,
+            };
+            match helper {
+                Some((name, helper)) => {
+                    let decl = declaration(
+                        gc,
+                        VariableDeclarationKind::Var,
+                        [(identifier(gc, name, range), Some(helper))],
+                        range,
+                    );
+                    prepend_to_body(gc, new_constructor, vec![decl])
+                }
+                None => new_constructor,
+            }
+        };
+        let mut builder = MethodDefinitionBuilder::from_node(method_def);
+        builder.value(new_constructor);
+        builder.build(gc).unwrap()
+    }
+
+    /// Return the constructor evaluating the instance field initializers `inits`, calling the
+    /// superclass constructor with its arguments if `derived`.
+    fn default_constructor(
+        &mut self,
+        gc: &'gc GCContext,
+        derived: bool,
+        inits: NodeList<'gc>,
+        range: SourceRange,
+    ) -> &'gc Node<'gc> {
+        let mut params = NodeList::new();
+        let mut body = NodeList::new();
+        if derived {
+            // `constructor(...args) { super(...args); }`
+            let args = self.temps.names.generate(gc, "args");
+            params.push(RestElementBuilder::build_template(
+                gc,
+                RestElementTemplate {
+                    metadata: template_metadata(range),
+                    argument: identifier(gc, args, range),
+                },
+            ));
+            let spread = SpreadElementBuilder::build_template(
+                gc,
+                SpreadElementTemplate {
+                    metadata: template_metadata(range),
+                    argument: identifier(gc, args, range),
+                },
+            );
+            let super_node = SuperBuilder::build_template(
+                gc,
+                SuperTemplate {
+                    metadata: template_metadata(range),
+                },
+            );
+            body.push(expression_statement(
+                gc,
+                call(gc, super_node, vec![spread], range),
+            ));
+        }
+        body.extend(inits.into_iter().map(|init| expression_statement(gc, init)));
+        MethodDefinitionBuilder::build_template(
+            gc,
+            MethodDefinitionTemplate {
+                metadata: template_metadata(range),
+                key: identifier(gc, gc.atom("constructor"), range),
+                value: function(gc, params, body, range),
+                kind: MethodDefinitionKind::Constructor,
+                computed: false,
+                is_static: false,
+            },
+        )
+    }
+}
+
+/// Return the members of the class body `body`.
+fn class_members<'a, 'gc>(body: &'a Node<'gc>) -> &'a NodeList<'gc> {
+    match body {
+        Node::ClassBody(ClassBody { body, .. }) => body,
+        _ => unreachable!("invalid class body {}", body.name()),
+    }
+}
+
+/// Return `map.method(arguments)`.
+fn map_call<'gc>(
+    gc: &'gc GCContext,
+    map: Atom,
+    method: &str,
+    arguments: NodeList<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    call(
+        gc,
+        member(gc, identifier(gc, map, range), gc.atom(method), range),
+        arguments,
+        range,
+    )
+}
+
+/// Report the variables referenced by the instance field initializer `value` which are
+/// shadowed by the declarations `names` of the constructor it is moved into.
+fn check_shadowing<'gc>(
+    gc: &'gc GCContext,
+    sem: &SemContext<'gc>,
+    names: &HashSet<Atom>,
+    value: &'gc Node<'gc>,
+) {
+    struct Checker<'a, 'gc> {
+        sem: &'a SemContext<'gc>,
+        names: &'a HashSet<Atom>,
+    }
+    impl<'gc> Visitor<'gc> for Checker<'_, 'gc> {
+        fn call(
+            &mut self,
+            gc: &'gc GCContext,
+            node: &'gc Node<'gc>,
+            _parent: Option<&'gc Node<'gc>>,
+        ) {
+            if let Node::Identifier(Identifier { metadata, name, .. }) = node {
+                if self.names.contains(name) && self.sem.resolution(node).is_some() {
+                    gc.sm().error(
+                        metadata.range,
+                        format!(
+                            "class field initializers can't refer to '{}', which is declared in the constructor",
+                            gc.str(*name)
+                        ),
+                    );
+                }
+            }
+            node.visit_children(gc, self);
+        }
+    }
+    if !names.is_empty() {
+        Visitor::call(&mut Checker { sem, names }, gc, value, None);
+    }
+}
+
+/// Report the `super` properties and the references to the name of the class expression
+/// `name_binding` in the private method `value`, which are invalid once it is moved out of the
+/// class.
+fn check_private_method<'gc>(
+    gc: &'gc GCContext,
+    sem: &SemContext<'gc>,
+    name_binding: Option<BindingId>,
+    value: &'gc Node<'gc>,
+) {
+    struct Checker<'a, 'gc> {
+        sem: &'a SemContext<'gc>,
+        name_binding: Option<BindingId>,
+        /// Number of nested functions, other than arrow functions, being visited.
+        functions: usize,
+    }
+    impl<'gc> Visitor<'gc> for Checker<'_, 'gc> {
+        fn call(
+            &mut self,
+            gc: &'gc GCContext,
+            node: &'gc Node<'gc>,
+            _parent: Option<&'gc Node<'gc>>,
+        ) {
+            match node {
+                Node::Super(Super { metadata }) if self.functions == 0 => {
+                    gc.sm().error(
+                        metadata.range,
+                        "private methods using 'super' can't be lowered",
+                    );
+                }
+                Node::Identifier(Identifier { metadata, .. })
+                    if self.name_binding.is_some()
+                        && self.sem.binding_of(node) == self.name_binding =>
+                {
+                    gc.sm().error(
+                        metadata.range,
+                        "private methods referring to the name of a class expression can't be lowered",
+                    );
+                }
+                Node::FunctionDeclaration(_) | Node::FunctionExpression(_) => {
+                    self.functions += 1;
+                    node.visit_children(gc, self);
+                    self.functions -= 1;
+                }
+                _ => node.visit_children(gc, self),
+            }
+        }
+    }
+    value.visit_children(
+        gc,
+        &mut Checker {
+            sem,
+            name_binding,
+            functions: 0,
+        },
+    );
+}
+
+/// Return the declaration of the helper `name` checking that an object is in a `WeakMap` or a
+/// `WeakSet`:
+/// ```js
+/// function _checkPrivate(set, obj) {
+///   if (!set.has(obj)) throw new TypeError("...");
+///   return obj;
+/// }
+/// ```
+fn check_helper<'gc>(gc: &'gc GCContext, name: Atom, range: SourceRange) -> &'gc Node<'gc> {
+    let id = |name: &str| identifier(gc, gc.atom(name), range);
+    let missing = UnaryExpressionBuilder::build_template(
+        gc,
+        UnaryExpressionTemplate {
+            metadata: template_metadata(range),
+            operator: UnaryExpressionOperator::Not,
+            argument: call(
+                gc,
+                dotted_name(gc, "set.has", range),
+                vec![id("obj")],
+                range,
+            ),
+            prefix: true,
+        },
+    );
+    let check = if_statement(
+        gc,
+        missing,
+        throw_type_error(
+            gc,
+            "Cannot access a private member of an object whose class did not declare it",
+            range,
+        ),
+        range,
+    );
+    FunctionDeclarationBuilder::build_template(
+        gc,
+        FunctionDeclarationTemplate {
+            metadata: template_metadata(range),
+            id: Some(identifier(gc, name, range)),
+            params: vec![id("set"), id("obj")],
+            body: block(
+                gc,
+                vec![check, return_statement(gc, Some(id("obj")), range)],
+                range,
+            ),
+            type_parameters: None,
+            return_type: None,
+            predicate: None,
+            generator: false,
+            is_async: false,
+        },
+    )
+}
+
+/// Replaces `this` and references to the name of the class in static field initializers.
+struct StaticRewriter<'a, 'gc> {
+    sem: &'a SemContext<'gc>,
+    class_ref: Atom,
+    name_binding: Option<BindingId>,
+    /// Number of nested functions, other than arrow functions, being visited.
+    functions: usize,
+}
+
+impl<'gc> VisitorMut<'gc> for StaticRewriter<'_, 'gc> {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        _parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        match node {
+            Node::ThisExpression(ThisExpression { metadata }) if self.functions == 0 => {
+                TransformResult::Changed(identifier(gc, self.class_ref, metadata.range))
+            }
+            Node::Super(Super { metadata }) if self.functions == 0 => {
+                gc.sm().error(
+                    metadata.range,
+                    "static fields using 'super' can't be lowered",
+                );
+                TransformResult::Unchanged
+            }
+            Node::Identifier(Identifier { metadata, .. })
+                if self.name_binding.is_some()
+                    && self.sem.binding_of(node) == self.name_binding =>
+            {
+                TransformResult::Changed(identifier(gc, self.class_ref, metadata.range))
+            }
+            Node::FunctionDeclaration(_) | Node::FunctionExpression(_) => {
+                self.functions += 1;
+                let result = node.visit_children_mut(NodeBuilder::from_node(node), gc, self);
+                self.functions -= 1;
+                result
+            }
+            _ => node.visit_children_mut(NodeBuilder::from_node(node), gc, self),
+        }
+    }
+}
+
+fn is_super_call(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::CallExpression(CallExpression {
+            callee: Node::Super(_),
+            ..
+        })
+    )
+}
+
+/// Counts the `super` calls of a constructor.
+struct SuperCallCounter {
+    count: usize,
+}
+
+impl<'gc> Visitor<'gc> for SuperCallCounter {
+    fn call(&mut self, gc: &'gc GCContext, node: &'gc Node<'gc>, _parent: Option<&'gc Node<'gc>>) {
+        match node {
+            Node::FunctionDeclaration(_) | Node::FunctionExpression(_) => {}
+            _ => {
+                if is_super_call(node) {
+                    self.count += 1;
+                }
+                node.visit_children(gc, self);
+            }
+        }
+    }
+}
+
+/// Evaluates the instance field initializers after each `super` call of a constructor.
+struct SuperCallRewriter<'gc> {
+    /// The function evaluating the initializers, if there are several `super` calls.
+    helper: Option<Atom>,
+    /// The initializers, if there is a single `super` call.
+    inits: Option<NodeList<'gc>>,
+    /// The initializers to insert after the `super` calls in statement lists.
+    splices: Splices<'gc>,
+}
+
+impl<'gc> SuperCallRewriter<'gc> {
+    /// Return the expressions initializing the fields after a `super` call.
+    fn inits(&mut self, gc: &'gc GCContext, range: SourceRange) -> NodeList<'gc> {
+        match self.helper {
+            // `_initFields.call(this)`
+            Some(helper) => vec![call(
+                gc,
+                member(gc, identifier(gc, helper, range), gc.atom("call"), range),
+                vec![this_expression(gc, range)],
+                range,
+            )],
+            None => self.inits.take().expect("multiple super calls"),
+        }
+    }
+}
+
+impl<'gc> VisitorMut<'gc> for SuperCallRewriter<'gc> {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        if let Node::FunctionDeclaration(_) | Node::FunctionExpression(_) = node {
+            return TransformResult::Unchanged;
+        }
+        let (node, changed) = match node.visit_children_mut(NodeBuilder::from_node(node), gc, self)
+        {
+            TransformResult::Changed(new_node) => (new_node, true),
+            TransformResult::Unchanged => (node, false),
+        };
+        let range = *node.range();
+        match node {
+            Node::ExpressionStatement(ExpressionStatement { expression, .. })
+                if is_super_call(expression) =>
+            {
+                let inits: NodeList = self
+                    .inits(gc, range)
+                    .into_iter()
+                    .map(|init| expression_statement(gc, init))
+                    .collect();
+                if parent.and_then(statement_list).is_some() {
+                    self.splices.insert(NodeRef(node), (vec![], inits));
+                    if changed {
+                        TransformResult::Changed(node)
+                    } else {
+                        TransformResult::Unchanged
+                    }
+                } else {
+                    // `{ super(); inits; }`
+                    let mut body = vec![node];
+                    body.extend(inits);
+                    TransformResult::Changed(block(gc, body, range))
+                }
+            }
+            // `(super(), inits, this)`
+            _ if is_super_call(node) && !matches!(parent, Some(Node::ExpressionStatement(_))) => {
+                let mut expressions = vec![node];
+                expressions.extend(self.inits(gc, range));
+                expressions.push(this_expression(gc, range));
+                TransformResult::Changed(sequence(gc, expressions, range))
+            }
+            _ => match splice_statements(gc, node, &mut self.splices) {
+                Some(new_node) => TransformResult::Changed(new_node),
+                None if changed => TransformResult::Changed(node),
+                None => TransformResult::Unchanged,
+            },
+        }
+    }
+}
@@ -0,0 +1,741 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Class lowering.
+//!
+//! Compiles classes to constructor functions, created by a function called immediately with the
+//! superclass. The constructors throw when called without `new`. Methods and accessors are
+//! defined on the prototype or on the constructor with `Object.defineProperty`, so that they
+//! aren't enumerable. Derived classes inherit with `Object.create` and `Object.setPrototypeOf`.
+//! `super` calls are compiled to calls of the superclass, and `super` properties are read from
+//! its prototype with a helper declared at the top of the program, which calls getters with
+//! `this`.
+//! Class fields must be lowered first. Assignments to `super` properties can't be lowered and
+//! are reported as errors.
+//!
+//! Transforms
+//! ```js
+//! class A extends B {
+//!   constructor(x) { super(x); }
+//!   m() { return super.m(); }
+//!   get x() { return 1; }
+//! }
+//! ```
+//! into
+//! ```js
+//! function _get(object, key, receiver) { ... }
+//! let A = function (_super) {
+//!   function A(x) {
+//!     if (!(this instanceof A)) throw new TypeError("Cannot call a class as a function");
+//!     _super.call(this, x);
+//!   }
+//!   A.prototype = Object.create(_super.prototype,
+//!     {constructor: {value: A, writable: true, configurable: true}});
+//!   Object.setPrototypeOf(A, _super);
+//!   Object.defineProperty(A.prototype, "m", {value: function () {
+//!     return _get(_super.prototype, "m", this).call(this);
+//!   }, writable: true, configurable: true});
+//!   Object.defineProperty(A.prototype, "x", {get: function () {
+//!     return 1;
+//!   }, configurable: true});
+//!   return A;
+//! }(B);
+//! ```
+
+use crate::passes::const_fold::{literal, Value};
+use crate::passes::util::*;
+use crate::Pass;
+use juno::ast::*;
+
+#[derive(Default)]
+pub struct Classes {}
+
+impl Classes {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Pass for Classes {
+    fn name(&self) -> &'static str {
+        "Lower classes"
+    }
+    fn description(&self) -> &'static str {
+        "Compiles classes to constructor functions and prototype assignments"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let mut lowerer = Lowerer {
+            names: UniqueNames::new(gc, node),
+            splices: Splices::new(),
+            helper: None,
+        };
+        VisitorMut::call(&mut lowerer, gc, node, None)
+    }
+}
+
+struct Lowerer<'gc> {
+    names: UniqueNames,
+    /// Declarations of the default exported classes, inserted before the exports.
+    splices: Splices<'gc>,
+    /// Name of the helper reading `super` properties, once it is used.
+    helper: Option<Atom>,
+}
+
+impl<'gc> VisitorMut<'gc> for Lowerer<'gc> {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        _parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let (node, changed) = match node.visit_children_mut(NodeBuilder::from_node(node), gc, self)
+        {
+            TransformResult::Changed(new_node) => (new_node, true),
+            TransformResult::Unchanged => (node, false),
+        };
+        let lowered = match node {
+            Node::ClassDeclaration(_) | Node::ClassExpression(_) => self.lower_class(gc, node),
+            // `export default A;`, after the declaration of `A`.
+            Node::ExportDefaultDeclaration(
+                export @ ExportDefaultDeclaration {
+                    declaration:
+                        decl @ Node::VariableDeclaration(VariableDeclaration { declarations, .. }),
+                    ..
+                },
+            ) => {
+                let id = match declarations[0] {
+                    Node::VariableDeclarator(VariableDeclarator {
+                        id: Node::Identifier(Identifier { metadata, name, .. }),
+                        ..
+                    }) => identifier(gc, *name, metadata.range),
+                    _ => unreachable!("invalid class declaration"),
+                };
+                let mut builder = ExportDefaultDeclarationBuilder::from_node(export);
+                builder.declaration(id);
+                let new_export = builder.build(gc).unwrap();
+                self.splices
+                    .insert(NodeRef(new_export), (vec![decl], vec![]));
+                Some(new_export)
+            }
+            _ => splice_statements(gc, node, &mut self.splices),
+        };
+        let lowered = match (node, self.helper) {
+            (Node::Program(_), Some(helper)) => {
+                let program = lowered.unwrap_or(node);
+                Some(prepend_to_body(
+                    gc,
+                    program,
+                    vec![get_helper(gc, helper, *program.range())],
+                ))
+            }
+            _ => lowered,
+        };
+        match lowered {
+            Some(new_node) => TransformResult::Changed(new_node),
+            None if changed => TransformResult::Changed(node),
+            None => TransformResult::Unchanged,
+        }
+    }
+}
+
+/// Accessors of a property, defined by a single `Object.defineProperty` call.
+struct Accessor<'gc> {
+    is_static: bool,
+    key: &'gc Node<'gc>,
+    /// The name of a non-computed key, to merge the getter and setter of a property.
+    name: Option<String>,
+    get: Option<&'gc Node<'gc>>,
+    set: Option<&'gc Node<'gc>>,
+}
+
+impl<'gc> Lowerer<'gc> {
+    /// Return the constructor function equivalent to the class `node`, whose members have
+    /// already been lowered, or `None` if it can't be lowered, which is reported.
+    fn lower_class(&mut self, gc: &'gc GCContext, node: &'gc Node<'gc>) -> Option<&'gc Node<'gc>> {
+        let (id, super_class, body, is_decl) = match node {
+            Node::ClassDeclaration(ClassDeclaration {
+                id,
+                super_class,
+                body,
+                ..
+            }) => (*id, *super_class, *body, true),
+            Node::ClassExpression(ClassExpression {
+                id,
+                super_class,
+                body,
+                ..
+            }) => (*id, *super_class, *body, false),
+            _ => unreachable!(),
+        };
+        let members = match body {
+            Node::ClassBody(ClassBody { body, .. }) => body,
+            _ => unreachable!("invalid class body {}", body.name()),
+        };
+        if let Some(field) = members.iter().find(|member| {
+            matches!(
+                member,
+                Node::ClassProperty(_) | Node::ClassPrivateProperty(_)
+            )
+        }) {
+            gc.sm().error(
+                *field.range(),
+                "class fields must be lowered before classes",
+            );
+            return None;
+        }
+
+        let range = *node.range();
+        let name = match id {
+            Some(Node::Identifier(Identifier { name, .. })) => *name,
+            _ => self.names.generate(gc, "_class"),
+        };
+        let super_name = super_class.map(|_| self.names.generate(gc, "_super"));
+        let class_id = || identifier(gc, name, range);
+        let prototype = || member(gc, class_id(), gc.atom("prototype"), range);
+        let t = || literal(gc, Value::Bool(true), range).unwrap();
+
+        let mut stmts = NodeList::new();
+        let constructor = members.iter().find_map(|member| match member {
+            Node::MethodDefinition(MethodDefinition {
+                kind: MethodDefinitionKind::Constructor,
+                value,
+                ..
+            }) => Some(*value),
+            _ => None,
+        });
+        stmts.push(self.constructor(gc, name, constructor, super_name, range));
+        if let Some(super_name) = super_name {
+            // `A.prototype = Object.create(_super.prototype, {constructor: {...}});`
+            let descriptor = object(
+                gc,
+                vec![
+                    property(gc, "value", class_id(), range),
+                    property(gc, "writable", t(), range),
+                    property(gc, "configurable", t(), range),
+                ],
+                range,
+            );
+            let create = call(
+                gc,
+                dotted_name(gc, "Object.create", range),
+                vec![
+                    member(
+                        gc,
+                        identifier(gc, super_name, range),
+                        gc.atom("prototype"),
+                        range,
+                    ),
+                    object(
+                        gc,
+                        vec![property(gc, "constructor", descriptor, range)],
+                        range,
+                    ),
+                ],
+                range,
+            );
+            stmts.push(expression_statement(
+                gc,
+                assign(gc, prototype(), create, range),
+            ));
+            stmts.push(expression_statement(
+                gc,
+                call(
+                    gc,
+                    dotted_name(gc, "Object.setPrototypeOf", range),
+                    vec![class_id(), identifier(gc, super_name, range)],
+                    range,
+                ),
+            ));
+        }
+
+        let mut pending: Option<Accessor> = None;
+        let define = |accessor: Accessor<'gc>| {
+            // `Object.defineProperty(A.prototype, key, {get: ..., set: ..., configurable: true})`
+            let mut descriptor = NodeList::new();
+            if let Some(get) = accessor.get {
+                descriptor.push(property(gc, "get", get, range));
+            }
+            if let Some(set) = accessor.set {
+                descriptor.push(property(gc, "set", set, range));
+            }
+            descriptor.push(property(gc, "configurable", t(), range));
+            let target = if accessor.is_static {
+                class_id()
+            } else {
+                prototype()
+            };
+            expression_statement(
+                gc,
+                call(
+                    gc,
+                    dotted_name(gc, "Object.defineProperty", range),
+                    vec![target, accessor.key, object(gc, descriptor, range)],
+                    range,
+                ),
+            )
+        };
+        for &member in members {
+            let (key, value, kind, computed, is_static) = match member {
+                Node::MethodDefinition(MethodDefinition {
+                    kind: MethodDefinitionKind::Constructor,
+                    ..
+                }) => continue,
+                Node::MethodDefinition(MethodDefinition {
+                    key,
+                    value,
+                    kind,
+                    computed,
+                    is_static,
+                    ..
+                }) => (*key, *value, *kind, *computed, *is_static),
+                _ => unreachable!("invalid class member {}", member.name()),
+            };
+            let member_range = *member.range();
+            let value = self.rewrite_super(gc, value, super_name, is_static);
+            if kind == MethodDefinitionKind::Method {
+                if let Some(accessor) = pending.take() {
+                    stmts.push(define(accessor));
+                }
+                // `Object.defineProperty(A.prototype, key, {value: ..., writable: true,
+                // configurable: true})`
+                let target = if is_static { class_id() } else { prototype() };
+                let descriptor = object(
+                    gc,
+                    vec![
+                        property(gc, "value", value, member_range),
+                        property(gc, "writable", t(), member_range),
+                        property(gc, "configurable", t(), member_range),
+                    ],
+                    member_range,
+                );
+                stmts.push(expression_statement(
+                    gc,
+                    call(
+                        gc,
+                        dotted_name(gc, "Object.defineProperty", member_range),
+                        vec![target, key_value(gc, key, computed), descriptor],
+                        member_range,
+                    ),
+                ));
+                continue;
+            }
+
+            let name = match key {
+                Node::Identifier(Identifier { name, .. }) if !computed => {
+                    Some(gc.str(*name).to_string())
+                }
+                Node::StringLiteral(StringLiteral { value, .. }) => {
+                    Some(String::from_utf16_lossy(&value.str))
+                }
+                _ => None,
+            };
+            let is_get = kind == MethodDefinitionKind::Get;
+            // Merge the getter and setter of a property declared next to each other.
+            if let Some(accessor) = &mut pending {
+                let slot = if is_get {
+                    &mut accessor.get
+                } else {
+                    &mut accessor.set
+                };
+                if accessor.is_static == is_static
+                    && name.is_some()
+                    && accessor.name == name
+                    && slot.is_none()
+                {
+                    *slot = Some(value);
+                    continue;
+                }
+            }
+            if let Some(accessor) = pending.take() {
+                stmts.push(define(accessor));
+            }
+            pending = Some(Accessor {
+                is_static,
+                key: key_value(gc, key, computed),
+                name,
+                get: if is_get { Some(value) } else { None },
+                set: if is_get { None } else { Some(value) },
+            });
+        }
+        if let Some(accessor) = pending.take() {
+            stmts.push(define(accessor));
+        }
+        stmts.push(return_statement(gc, Some(class_id()), range));
+
+        // `function (_super) { ... }(B)`
+        let params = super_name
+            .map(|super_name| identifier(gc, super_name, range))
+            .into_iter()
+            .collect();
+        let iife = call(
+            gc,
+            function(gc, params, stmts, range),
+            super_class.into_iter().collect(),
+            range,
+        );
+        if is_decl {
+            Some(declaration(
+                gc,
+                VariableDeclarationKind::Let,
+                [(class_id(), Some(iife))],
+                range,
+            ))
+        } else {
+            Some(iife)
+        }
+    }
+
+    /// Return the declaration of the constructor function `name`, with the body of the class
+    /// `constructor` if any, preceded by a check that it is called with `new`.
+    fn constructor(
+        &mut self,
+        gc: &'gc GCContext,
+        name: Atom,
+        constructor: Option<&'gc Node<'gc>>,
+        super_name: Option<Atom>,
+        range: SourceRange,
+    ) -> &'gc Node<'gc> {
+        let (params, body) = match constructor {
+            Some(constructor) => match self.rewrite_super(gc, constructor, super_name, false) {
+                Node::FunctionExpression(FunctionExpression { params, body, .. }) => {
+                    (params.clone(), *body)
+                }
+                _ => unreachable!("invalid constructor"),
+            },
+            None => {
+                let mut body = NodeList::new();
+                if let Some(super_name) = super_name {
+                    // `_super.apply(this, arguments);`
+                    body.push(expression_statement(
+                        gc,
+                        call(
+                            gc,
+                            member(
+                                gc,
+                                identifier(gc, super_name, range),
+                                gc.atom("apply"),
+                                range,
+                            ),
+                            vec![
+                                this_expression(gc, range),
+                                identifier(gc, gc.atom("arguments"), range),
+                            ],
+                            range,
+                        ),
+                    ));
+                }
+                (vec![], block(gc, body, range))
+            }
+        };
+        let decl = FunctionDeclarationBuilder::build_template(
+            gc,
+            FunctionDeclarationTemplate {
+                metadata: template_metadata(range),
+                id: Some(identifier(gc, name, range)),
+                params,
+                body,
+                type_parameters: None,
+                return_type: None,
+                predicate: None,
+                generator: false,
+                is_async: false,
+            },
+        );
+        // `if (!(this instanceof A)) throw new TypeError("Cannot call a class as a function");`
+        let not_instance = UnaryExpressionBuilder::build_template(
+            gc,
+            UnaryExpressionTemplate {
+                metadata: template_metadata(range),
+                operator: UnaryExpressionOperator::Not,
+                argument: binary(
+                    gc,
+                    BinaryExpressionOperator::Instanceof,
+                    this_expression(gc, range),
+                    identifier(gc, name, range),
+                    range,
+                ),
+                prefix: true,
+            },
+        );
+        let throw = throw_type_error(gc, "Cannot call a class as a function", range);
+        prepend_to_body(gc, decl, vec![if_statement(gc, not_instance, throw, range)])
+    }
+
+    /// Return the method `function` with its `super` calls and property accesses replaced by
+    /// accesses of the superclass `super_name`, or of `Object` for base classes.
+    fn rewrite_super(
+        &mut self,
+        gc: &'gc GCContext,
+        function: &'gc Node<'gc>,
+        super_name: Option<Atom>,
+        is_static: bool,
+    ) -> &'gc Node<'gc> {
+        let mut rewriter = SuperRewriter {
+            super_name,
+            is_static,
+            names: &mut self.names,
+            helper: &mut self.helper,
+        };
+        match function.visit_children_mut(NodeBuilder::from_node(function), gc, &mut rewriter) {
+            TransformResult::Changed(new_function) => new_function,
+            TransformResult::Unchanged => function,
+        }
+    }
+}
+
+struct SuperRewriter<'a> {
+    super_name: Option<Atom>,
+    is_static: bool,
+    names: &'a mut UniqueNames,
+    /// Name of the helper reading `super` properties, once it is used.
+    helper: &'a mut Option<Atom>,
+}
+
+impl SuperRewriter<'_> {
+    /// Return the object on which `super` properties are looked up.
+    fn home<'gc>(&self, gc: &'gc GCContext, range: SourceRange) -> &'gc Node<'gc> {
+        match (self.super_name, self.is_static) {
+            (Some(super_name), true) => identifier(gc, super_name, range),
+            (Some(super_name), false) => member(
+                gc,
+                identifier(gc, super_name, range),
+                gc.atom("prototype"),
+                range,
+            ),
+            (None, true) => dotted_name(gc, "Function.prototype", range),
+            (None, false) => dotted_name(gc, "Object.prototype", range),
+        }
+    }
+
+    /// Return the value of the `super` property `key` for `this`, e.g.
+    /// `_get(_super.prototype, "x", this)`.
+    fn get<'gc>(
+        &mut self,
+        gc: &'gc GCContext,
+        key: &'gc Node<'gc>,
+        computed: bool,
+        range: SourceRange,
+    ) -> &'gc Node<'gc> {
+        let helper = match *self.helper {
+            Some(name) => name,
+            None => *self.helper.insert(self.names.generate(gc, "_get")),
+        };
+        call(
+            gc,
+            identifier(gc, helper, range),
+            vec![
+                self.home(gc, range),
+                key_value(gc, key, computed),
+                this_expression(gc, range),
+            ],
+            range,
+        )
+    }
+}
+
+fn is_super_member(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::MemberExpression(MemberExpression {
+            object: Node::Super(_),
+            ..
+        })
+    )
+}
+
+impl<'gc> VisitorMut<'gc> for SuperRewriter<'_> {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        // `super` in nested functions refers to their object, if any.
+        if let Node::FunctionDeclaration(_) | Node::FunctionExpression(_) = node {
+            return TransformResult::Unchanged;
+        }
+        // The methods called on `super` are lowered with the call.
+        let is_callee = matches!(
+            parent,
+            Some(Node::CallExpression(CallExpression { callee, .. })) if std::ptr::eq(*callee, node)
+        );
+        if is_super_member(node) {
+            match parent {
+                Some(Node::AssignmentExpression(AssignmentExpression { left, .. }))
+                    if std::ptr::eq(*left, node) =>
+                {
+                    gc.sm().error(
+                        *node.range(),
+                        "assignments to 'super' properties can't be lowered",
+                    );
+                }
+                Some(Node::UpdateExpression(_)) => {
+                    gc.sm().error(
+                        *node.range(),
+                        "assignments to 'super' properties can't be lowered",
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        let (node, changed) = match node.visit_children_mut(NodeBuilder::from_node(node), gc, self)
+        {
+            TransformResult::Changed(new_node) => (new_node, true),
+            TransformResult::Unchanged => (node, false),
+        };
+        let range = *node.range();
+        match node {
+            // `_super.call(this, args)`
+            Node::CallExpression(CallExpression {
+                callee: Node::Super(_),
+                arguments,
+                ..
+            }) if self.super_name.is_some() => {
+                let mut call_arguments = vec![this_expression(gc, range)];
+                call_arguments.extend(arguments);
+                TransformResult::Changed(call(
+                    gc,
+                    member(
+                        gc,
+                        identifier(gc, self.super_name.unwrap(), range),
+                        gc.atom("call"),
+                        range,
+                    ),
+                    call_arguments,
+                    range,
+                ))
+            }
+            // `_get(_super.prototype, "m", this).call(this, args)`
+            Node::CallExpression(CallExpression {
+                callee:
+                    Node::MemberExpression(MemberExpression {
+                        object: Node::Super(_),
+                        property,
+                        computed,
+                        ..
+                    }),
+                arguments,
+                ..
+            }) => {
+                let method = self.get(gc, property, *computed, range);
+                let mut call_arguments = vec![this_expression(gc, range)];
+                call_arguments.extend(arguments);
+                TransformResult::Changed(call(
+                    gc,
+                    member(gc, method, gc.atom("call"), range),
+                    call_arguments,
+                    range,
+                ))
+            }
+            // `_get(_super.prototype, "x", this)`
+            Node::MemberExpression(MemberExpression {
+                object: Node::Super(_),
+                property,
+                computed,
+                ..
+            }) if !is_callee => TransformResult::Changed(self.get(gc, property, *computed, range)),
+            _ if changed => TransformResult::Changed(node),
+            _ => TransformResult::Unchanged,
+        }
+    }
+}
+
+/// Return the declaration of the helper `name`, which reads the property `key` of `object` or
+/// of its prototypes, calling a getter with `receiver`.
+fn get_helper<'gc>(gc: &'gc GCContext, name: Atom, range: SourceRange) -> &'gc Node<'gc> {
+    let id = |name: &str| identifier(gc, gc.atom(name), range);
+
+    // if (desc) return desc.get ? desc.get.call(receiver) : desc.value;
+    let found = if_statement(
+        gc,
+        id("desc"),
+        return_statement(
+            gc,
+            Some(conditional(
+                gc,
+                dotted_name(gc, "desc.get", range),
+                call(
+                    gc,
+                    dotted_name(gc, "desc.get.call", range),
+                    vec![id("receiver")],
+                    range,
+                ),
+                dotted_name(gc, "desc.value", range),
+                range,
+            )),
+            range,
+        ),
+        range,
+    );
+    let lookup = WhileStatementBuilder::build_template(
+        gc,
+        WhileStatementTemplate {
+            metadata: template_metadata(range),
+            test: binary(
+                gc,
+                BinaryExpressionOperator::StrictNotEquals,
+                id("object"),
+                literal(gc, Value::Null, range).unwrap(),
+                range,
+            ),
+            body: block(
+                gc,
+                vec![
+                    declaration(
+                        gc,
+                        VariableDeclarationKind::Var,
+                        [(
+                            id("desc"),
+                            Some(call(
+                                gc,
+                                dotted_name(gc, "Object.getOwnPropertyDescriptor", range),
+                                vec![id("object"), id("key")],
+                                range,
+                            )),
+                        )],
+                        range,
+                    ),
+                    found,
+                    expression_statement(
+                        gc,
+                        assign(
+                            gc,
+                            id("object"),
+                            call(
+                                gc,
+                                dotted_name(gc, "Object.getPrototypeOf", range),
+                                vec![id("object")],
+                                range,
+                            ),
+                            range,
+                        ),
+                    ),
+                ],
+                range,
+            ),
+        },
+    );
+    FunctionDeclarationBuilder::build_template(
+        gc,
+        FunctionDeclarationTemplate {
+            metadata: template_metadata(range),
+            id: Some(identifier(gc, name, range)),
+            params: vec![id("object"), id("key"), id("receiver")],
+            body: block(gc, vec![lookup], range),
+            type_parameters: None,
+            return_type: None,
+            predicate: None,
+            generator: false,
+            is_async: false,
+        },
+    )
+}
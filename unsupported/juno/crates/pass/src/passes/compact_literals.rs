@@ -0,0 +1,110 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Shorter forms of literals for minification.
+//!
+//! Replaces `true` and `false` with `!0` and `!1`, and reads of the global `undefined` with
+//! `void 0`. Variables named `undefined` declared in the program aren't replaced.
+//! The shortest forms of numbers are chosen when printing.
+//!
+//! Transforms
+//! ```js
+//! x = [true, false, undefined];
+//! ```
+//! into
+//! ```js
+//! x = [!0, !1, void 0];
+//! ```
+
+use crate::passes::const_fold::{literal, Value};
+use crate::Pass;
+use juno::ast::*;
+use juno::sema::{resolve_program, ReferenceKind};
+use std::collections::HashSet;
+
+#[derive(Default)]
+pub struct CompactLiterals {}
+
+impl CompactLiterals {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Pass for CompactLiterals {
+    fn name(&self) -> &'static str {
+        "Compact literals"
+    }
+    fn description(&self) -> &'static str {
+        "Replaces booleans and undefined with shorter expressions"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let sem = resolve_program(gc, node);
+        let undefined = sem
+            .global_references(gc.atom("undefined"))
+            .iter()
+            .filter(|reference| reference.kind == ReferenceKind::Read)
+            .map(|reference| NodeRef(reference.node))
+            .collect();
+        VisitorMut::call(&mut Compactor { undefined }, gc, node, None)
+    }
+}
+
+struct Compactor<'gc> {
+    /// Identifiers reading the global `undefined`.
+    undefined: HashSet<NodeRef<'gc>>,
+}
+
+impl<'gc> VisitorMut<'gc> for Compactor<'gc> {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        match node {
+            Node::BooleanLiteral(BooleanLiteral { metadata, value }) => {
+                let range = metadata.range;
+                let number = literal(gc, Value::Number(!*value as u8 as f64), range).unwrap();
+                TransformResult::Changed(UnaryExpressionBuilder::build_template(
+                    gc,
+                    UnaryExpressionTemplate {
+                        metadata: TemplateMetadata {
+                            range,
+                            ..Default::default()
+                        },
+                        operator: UnaryExpressionOperator::Not,
+                        argument: number,
+                        prefix: true,
+                    },
+                ))
+            }
+            // `delete undefined` is false, unlike `delete void 0`.
+            Node::Identifier(_)
+                if self.undefined.contains(&NodeRef(node))
+                    && !matches!(
+                        parent,
+                        Some(Node::UnaryExpression(UnaryExpression {
+                            operator: UnaryExpressionOperator::Delete,
+                            ..
+                        }))
+                    ) =>
+            {
+                TransformResult::Changed(literal(gc, Value::Undefined, *node.range()).unwrap())
+            }
+            // The key of a shorthand property is the same node as its value.
+            Node::Property(Property {
+                shorthand: true, ..
+            }) => TransformResult::Unchanged,
+            _ => node.visit_children_mut(NodeBuilder::from_node(node), gc, self),
+        }
+    }
+}
@@ -0,0 +1,390 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Statement compaction for minification.
+//!
+//! Rewrites `if` statements containing single expressions, `return` or `throw` statements
+//! into logical and conditional expressions, and joins consecutive expression statements into
+//! sequence expressions, prepending them to a following `return`, `throw` or `if`.
+//!
+//! Transforms
+//! ```js
+//! if (a) b();
+//! if (!c) d(); else e();
+//! f();
+//! if (g) return h;
+//! return i;
+//! ```
+//! into
+//! ```js
+//! return a && b(), c ? e() : d(), f(), g ? h : i;
+//! ```
+
+use crate::passes::const_fold::{literal, Value};
+use crate::passes::util::{statement_list, with_statement_list};
+use crate::Pass;
+use juno::ast::*;
+
+#[derive(Default)]
+pub struct CompactStatements {}
+
+impl CompactStatements {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Pass for CompactStatements {
+    fn name(&self) -> &'static str {
+        "Compact statements"
+    }
+    fn description(&self) -> &'static str {
+        "Rewrites if statements and expression statements as expressions"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        VisitorMut::call(self, gc, node, None)
+    }
+}
+
+impl<'gc> VisitorMut<'gc> for CompactStatements {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        _parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let (node, changed) = match node.visit_children_mut(NodeBuilder::from_node(node), gc, self)
+        {
+            TransformResult::Changed(new_node) => (new_node, true),
+            TransformResult::Unchanged => (node, false),
+        };
+        let replacement = match node {
+            Node::IfStatement(stmt) => compact_if(gc, stmt),
+            _ => statement_list(node)
+                .and_then(|list| compact_list(gc, list))
+                .map(|list| with_statement_list(gc, node, list)),
+        };
+        match replacement {
+            Some(replacement) => TransformResult::Changed(replacement),
+            None if changed => TransformResult::Changed(node),
+            None => TransformResult::Unchanged,
+        }
+    }
+}
+
+/// Return the expression of an expression statement which isn't a directive.
+fn expression<'gc>(stmt: &'gc Node<'gc>) -> Option<&'gc Node<'gc>> {
+    match stmt {
+        Node::ExpressionStatement(ExpressionStatement {
+            expression,
+            directive: None,
+            ..
+        }) => Some(expression),
+        _ => None,
+    }
+}
+
+/// Return the only statement of the block statement `stmt`, or `stmt` if it isn't a block.
+/// Blocks containing declarations or statements which may capture a following `else` are
+/// kept.
+fn unwrap_block<'gc>(stmt: &'gc Node<'gc>) -> &'gc Node<'gc> {
+    match stmt {
+        Node::BlockStatement(BlockStatement { body, .. }) if body.len() == 1 => match body[0] {
+            inner @ Node::ExpressionStatement(ExpressionStatement {
+                directive: None, ..
+            })
+            | inner @ Node::ReturnStatement(_)
+            | inner @ Node::ThrowStatement(_)
+            | inner @ Node::BreakStatement(_)
+            | inner @ Node::ContinueStatement(_) => inner,
+            _ => stmt,
+        },
+        _ => stmt,
+    }
+}
+
+/// Return the replacement of the `if` statement `stmt`, if it can be compacted.
+fn compact_if<'gc>(gc: &'gc GCContext, stmt: &'gc IfStatement<'gc>) -> Option<&'gc Node<'gc>> {
+    let IfStatement {
+        metadata,
+        test,
+        consequent,
+        alternate,
+    } = stmt;
+    let range = metadata.range;
+    let consequent = unwrap_block(consequent);
+    let alternate = alternate.map(unwrap_block);
+    // `if (!a) b; else c;` is `if (a) c; else b;`.
+    let (positive_test, negated) = match test {
+        Node::UnaryExpression(UnaryExpression {
+            operator: UnaryExpressionOperator::Not,
+            argument,
+            ..
+        }) => (*argument, true),
+        _ => (*test, false),
+    };
+    let conditional = |consequent, alternate| {
+        if negated {
+            build_conditional(gc, range, positive_test, alternate, consequent)
+        } else {
+            build_conditional(gc, range, test, consequent, alternate)
+        }
+    };
+    match (consequent, alternate) {
+        (consequent, None) if expression(consequent).is_some() => {
+            let operator = if negated {
+                LogicalExpressionOperator::Or
+            } else {
+                LogicalExpressionOperator::And
+            };
+            let logical = LogicalExpressionBuilder::build_template(
+                gc,
+                LogicalExpressionTemplate {
+                    metadata: template_metadata(range),
+                    operator,
+                    left: positive_test,
+                    right: expression(consequent)?,
+                },
+            );
+            Some(build_expression_statement(gc, range, logical))
+        }
+        (consequent, Some(alternate)) => {
+            if let (Some(consequent), Some(alternate)) =
+                (expression(consequent), expression(alternate))
+            {
+                let expression = conditional(consequent, alternate);
+                return Some(build_expression_statement(gc, range, expression));
+            }
+            match (consequent, alternate) {
+                (
+                    Node::ReturnStatement(ReturnStatement {
+                        argument: consequent,
+                        ..
+                    }),
+                    Node::ReturnStatement(ReturnStatement {
+                        argument: alternate,
+                        ..
+                    }),
+                ) if consequent.is_some() || alternate.is_some() => {
+                    let consequent = consequent.unwrap_or_else(|| undefined(gc, range));
+                    let alternate = alternate.unwrap_or_else(|| undefined(gc, range));
+                    Some(build_return(gc, range, conditional(consequent, alternate)))
+                }
+                (
+                    Node::ThrowStatement(ThrowStatement {
+                        argument: consequent,
+                        ..
+                    }),
+                    Node::ThrowStatement(ThrowStatement {
+                        argument: alternate,
+                        ..
+                    }),
+                ) => Some(ThrowStatementBuilder::build_template(
+                    gc,
+                    ThrowStatementTemplate {
+                        metadata: template_metadata(range),
+                        argument: conditional(consequent, alternate),
+                    },
+                )),
+                _ => unwrap_branches(gc, stmt),
+            }
+        }
+        _ => unwrap_branches(gc, stmt),
+    }
+}
+
+/// Return the `if` statement `stmt` with the blocks around its branches removed, if any.
+fn unwrap_branches<'gc>(gc: &'gc GCContext, stmt: &'gc IfStatement<'gc>) -> Option<&'gc Node<'gc>> {
+    let mut builder = IfStatementBuilder::from_node(stmt);
+    let consequent = unwrap_block(stmt.consequent);
+    if !std::ptr::eq(consequent, stmt.consequent) {
+        builder.consequent(consequent);
+    }
+    if let Some(alternate) = stmt.alternate {
+        let unwrapped = unwrap_block(alternate);
+        if !std::ptr::eq(unwrapped, alternate) {
+            builder.alternate(Some(unwrapped));
+        }
+    }
+    match builder.build(gc) {
+        TransformResult::Changed(new_node) => Some(new_node),
+        TransformResult::Unchanged => None,
+    }
+}
+
+/// Return the compacted statement list `list`, or `None` if it can't be compacted.
+fn compact_list<'gc>(gc: &'gc GCContext, list: &NodeList<'gc>) -> Option<NodeList<'gc>> {
+    let mut result: NodeList = Vec::with_capacity(list.len());
+    let mut changed = false;
+    for &stmt in list {
+        let mut stmt = stmt;
+        let range = *stmt.range();
+
+        // `if (a) return b; return c;` is `return a ? b : c;`.
+        if let (
+            Node::ReturnStatement(ReturnStatement {
+                argument: alternate,
+                ..
+            }),
+            Some(Node::IfStatement(IfStatement {
+                test,
+                consequent,
+                alternate: None,
+                ..
+            })),
+        ) = (stmt, result.last())
+        {
+            if let Node::ReturnStatement(ReturnStatement {
+                argument: consequent,
+                ..
+            }) = unwrap_block(consequent)
+            {
+                if consequent.is_some() || alternate.is_some() {
+                    let consequent = consequent.unwrap_or_else(|| undefined(gc, range));
+                    let alternate = alternate.unwrap_or_else(|| undefined(gc, range));
+                    let conditional = build_conditional(gc, range, test, consequent, alternate);
+                    stmt = build_return(gc, range, conditional);
+                    result.pop();
+                    changed = true;
+                }
+            }
+        }
+
+        // Prepend the preceding expression statement.
+        if let Some(previous) = result.last().and_then(|last| expression(last)) {
+            let joined = match stmt {
+                Node::ExpressionStatement(ExpressionStatement {
+                    expression,
+                    directive: None,
+                    ..
+                }) => Some(build_expression_statement(
+                    gc,
+                    range,
+                    build_sequence(gc, previous, expression),
+                )),
+                Node::ReturnStatement(ReturnStatement {
+                    argument: Some(argument),
+                    ..
+                }) => Some(build_return(
+                    gc,
+                    range,
+                    build_sequence(gc, previous, argument),
+                )),
+                Node::ThrowStatement(throw @ ThrowStatement { argument, .. }) => {
+                    let mut builder = ThrowStatementBuilder::from_node(throw);
+                    builder.argument(build_sequence(gc, previous, argument));
+                    Some(builder.build(gc).unwrap())
+                }
+                Node::IfStatement(if_stmt @ IfStatement { test, .. }) => {
+                    let mut builder = IfStatementBuilder::from_node(if_stmt);
+                    builder.test(build_sequence(gc, previous, test));
+                    Some(builder.build(gc).unwrap())
+                }
+                _ => None,
+            };
+            if let Some(joined) = joined {
+                stmt = joined;
+                result.pop();
+                changed = true;
+            }
+        }
+
+        result.push(stmt);
+    }
+    if changed {
+        Some(result)
+    } else {
+        None
+    }
+}
+
+/// Return `void 0`.
+fn undefined<'gc>(gc: &'gc GCContext, range: SourceRange) -> &'gc Node<'gc> {
+    literal(gc, Value::Undefined, range).unwrap()
+}
+
+fn template_metadata<'gc>(range: SourceRange) -> TemplateMetadata<'gc> {
+    TemplateMetadata {
+        range,
+        ..Default::default()
+    }
+}
+
+fn build_expression_statement<'gc>(
+    gc: &'gc GCContext,
+    range: SourceRange,
+    expression: &'gc Node<'gc>,
+) -> &'gc Node<'gc> {
+    ExpressionStatementBuilder::build_template(
+        gc,
+        ExpressionStatementTemplate {
+            metadata: template_metadata(range),
+            expression,
+            directive: None,
+        },
+    )
+}
+
+fn build_return<'gc>(
+    gc: &'gc GCContext,
+    range: SourceRange,
+    argument: &'gc Node<'gc>,
+) -> &'gc Node<'gc> {
+    ReturnStatementBuilder::build_template(
+        gc,
+        ReturnStatementTemplate {
+            metadata: template_metadata(range),
+            argument: Some(argument),
+        },
+    )
+}
+
+fn build_conditional<'gc>(
+    gc: &'gc GCContext,
+    range: SourceRange,
+    test: &'gc Node<'gc>,
+    consequent: &'gc Node<'gc>,
+    alternate: &'gc Node<'gc>,
+) -> &'gc Node<'gc> {
+    ConditionalExpressionBuilder::build_template(
+        gc,
+        ConditionalExpressionTemplate {
+            metadata: template_metadata(range),
+            test,
+            consequent,
+            alternate,
+        },
+    )
+}
+
+/// Return the sequence expression `first, second`, flattening nested sequences.
+fn build_sequence<'gc>(
+    gc: &'gc GCContext,
+    first: &'gc Node<'gc>,
+    second: &'gc Node<'gc>,
+) -> &'gc Node<'gc> {
+    let mut expressions = NodeList::new();
+    for expr in [first, second] {
+        match expr {
+            Node::SequenceExpression(SequenceExpression {
+                expressions: inner, ..
+            }) => expressions.extend(inner),
+            _ => expressions.push(expr),
+        }
+    }
+    SequenceExpressionBuilder::build_template(
+        gc,
+        SequenceExpressionTemplate {
+            metadata: template_metadata(*first.range()),
+            expressions,
+        },
+    )
+}
@@ -0,0 +1,189 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Computed property lowering.
+//!
+//! Compiles object literals with computed keys to a temporary object, created with the
+//! properties preceding the first computed key, to which the following properties are added
+//! in order. Values are assigned and accessors are defined with `Object.defineProperty`.
+//!
+//! Transforms
+//! ```js
+//! x = {a: 1, [k]: 2, get b() {}};
+//! ```
+//! into
+//! ```js
+//! var _obj;
+//! x = (_obj = {a: 1}, _obj[k] = 2, Object.defineProperty(_obj, "b",
+//!   {get: function () {}, configurable: true, enumerable: true}), _obj);
+//! ```
+
+use crate::passes::const_fold::{literal, Value};
+use crate::passes::util::*;
+use crate::Pass;
+use juno::ast::*;
+
+#[derive(Default)]
+pub struct ComputedProperties {}
+
+impl ComputedProperties {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Pass for ComputedProperties {
+    fn name(&self) -> &'static str {
+        "Lower computed properties"
+    }
+    fn description(&self) -> &'static str {
+        "Compiles object literals with computed keys to property assignments"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let mut lowerer = Lowerer {
+            temps: Temporaries::new(UniqueNames::new(gc, node)),
+        };
+        VisitorMut::call(&mut lowerer, gc, node, None)
+    }
+}
+
+struct Lowerer {
+    temps: Temporaries,
+}
+
+impl<'gc> VisitorMut<'gc> for Lowerer {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        _parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let is_scope = is_var_scope(node);
+        if is_scope {
+            self.temps.enter();
+        }
+        let (mut node, mut changed) =
+            match node.visit_children_mut(NodeBuilder::from_node(node), gc, self) {
+                TransformResult::Changed(new_node) => (new_node, true),
+                TransformResult::Unchanged => (node, false),
+            };
+        if is_scope {
+            if let Some(new_node) = self.temps.exit(gc, node) {
+                node = new_node;
+                changed = true;
+            }
+        }
+        match node {
+            Node::ObjectExpression(ObjectExpression {
+                metadata,
+                properties,
+            }) => match properties.iter().position(|prop| is_computed(prop)) {
+                Some(first) => {
+                    TransformResult::Changed(self.lower(gc, properties, first, metadata.range))
+                }
+                None if changed => TransformResult::Changed(node),
+                None => TransformResult::Unchanged,
+            },
+            _ if changed => TransformResult::Changed(node),
+            _ => TransformResult::Unchanged,
+        }
+    }
+}
+
+impl Lowerer {
+    /// Return the expression creating the object with `properties`, the first computed one
+    /// being at index `first`.
+    fn lower<'gc>(
+        &mut self,
+        gc: &'gc GCContext,
+        properties: &[&'gc Node<'gc>],
+        first: usize,
+        range: SourceRange,
+    ) -> &'gc Node<'gc> {
+        let temp = self.temps.generate(gc, "_obj");
+        let obj = || identifier(gc, temp, range);
+        let mut expressions = vec![assign(
+            gc,
+            obj(),
+            object(gc, properties[..first].to_vec(), range),
+            range,
+        )];
+        for &prop in &properties[first..] {
+            let prop_range = *prop.range();
+            let expression = match prop {
+                Node::SpreadElement(SpreadElement { argument, .. }) => call(
+                    gc,
+                    dotted_name(gc, "Object.assign", prop_range),
+                    vec![obj(), argument],
+                    prop_range,
+                ),
+                Node::Property(Property {
+                    key,
+                    value,
+                    kind: PropertyKind::Init,
+                    computed,
+                    ..
+                }) => {
+                    let target = match key {
+                        Node::Identifier(Identifier { name, .. }) if !computed => {
+                            member(gc, obj(), *name, prop_range)
+                        }
+                        _ => computed_member(gc, obj(), key, prop_range),
+                    };
+                    assign(gc, target, value, prop_range)
+                }
+                Node::Property(Property {
+                    key,
+                    value,
+                    kind,
+                    computed,
+                    ..
+                }) => {
+                    let key = match key {
+                        Node::Identifier(Identifier { name, .. }) if !computed => {
+                            string(gc, gc.str(*name), *key.range())
+                        }
+                        _ => key,
+                    };
+                    let accessor = if *kind == PropertyKind::Get {
+                        "get"
+                    } else {
+                        "set"
+                    };
+                    let t = || literal(gc, Value::Bool(true), prop_range).unwrap();
+                    let descriptor = object(
+                        gc,
+                        vec![
+                            property(gc, accessor, value, prop_range),
+                            property(gc, "configurable", t(), prop_range),
+                            property(gc, "enumerable", t(), prop_range),
+                        ],
+                        prop_range,
+                    );
+                    call(
+                        gc,
+                        dotted_name(gc, "Object.defineProperty", prop_range),
+                        vec![obj(), key, descriptor],
+                        prop_range,
+                    )
+                }
+                _ => unreachable!("invalid object property {}", prop.name()),
+            };
+            expressions.push(expression);
+        }
+        expressions.push(obj());
+        sequence(gc, expressions, range)
+    }
+}
+
+fn is_computed(prop: &Node) -> bool {
+    matches!(prop, Node::Property(Property { computed: true, .. }))
+}
@@ -0,0 +1,513 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Constant folding.
+//!
+//! Evaluates unary, binary, logical and conditional expressions whose operands are literals,
+//! following the JS semantics exactly, and replaces them with their result.
+//! Numbers are only folded when the result isn't longer than the expression, so `1 / 3` is
+//! kept, and results without a literal representation like `NaN` are never folded, since the
+//! corresponding globals may be shadowed.
+//!
+//! Transforms
+//! ```js
+//! x = 60 * 60 * 24;
+//! y = "v" + 1.5;
+//! z = 1 < 2 ? a : b;
+//! ```
+//! into
+//! ```js
+//! x = 86400;
+//! y = "v1.5";
+//! z = a;
+//! ```
+
+use crate::Pass;
+use juno::ast::*;
+use support::convert::{number_to_string, string_to_number, to_int32, to_uint32};
+
+/// A JS primitive value.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(f64),
+    /// UTF-16 code units.
+    String(Vec<u16>),
+}
+
+impl Value {
+    /// ES2022 7.1.2 ToBoolean.
+    pub(crate) fn to_boolean(&self) -> bool {
+        match self {
+            Value::Undefined | Value::Null => false,
+            Value::Bool(b) => *b,
+            Value::Number(n) => !(*n == 0.0 || n.is_nan()),
+            Value::String(s) => !s.is_empty(),
+        }
+    }
+
+    /// ES2022 7.1.4 ToNumber.
+    fn to_number(&self) -> f64 {
+        match self {
+            Value::Undefined => f64::NAN,
+            Value::Null => 0.0,
+            Value::Bool(b) => *b as u8 as f64,
+            Value::Number(n) => *n,
+            Value::String(s) => string_to_number(&String::from_utf16_lossy(s)),
+        }
+    }
+
+    /// ES2022 7.1.17 ToString.
+    fn to_js_string(&self) -> Vec<u16> {
+        match self {
+            Value::Undefined => "undefined".encode_utf16().collect(),
+            Value::Null => "null".encode_utf16().collect(),
+            Value::Bool(b) => b.to_string().encode_utf16().collect(),
+            // -0 is converted to "0".
+            Value::Number(n) if *n == 0.0 => vec![b'0' as u16],
+            Value::Number(n) => number_to_string(*n).encode_utf16().collect(),
+            Value::String(s) => s.clone(),
+        }
+    }
+
+    /// The result of `typeof`.
+    fn type_of(&self) -> &'static str {
+        match self {
+            Value::Undefined => "undefined",
+            Value::Null => "object",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+        }
+    }
+}
+
+/// Return true if the logical expression `left op right` evaluates to `left` without evaluating
+/// `right`.
+pub(crate) fn short_circuits(op: LogicalExpressionOperator, left: &Value) -> bool {
+    match op {
+        LogicalExpressionOperator::And => !left.to_boolean(),
+        LogicalExpressionOperator::Or => left.to_boolean(),
+        LogicalExpressionOperator::NullishCoalesce => {
+            !matches!(left, Value::Undefined | Value::Null)
+        }
+    }
+}
+
+/// Return the value of `node` if it is a constant expression, which has no side effects.
+pub(crate) fn evaluate(node: &Node) -> Option<Value> {
+    match node {
+        Node::NullLiteral(_) => Some(Value::Null),
+        Node::BooleanLiteral(BooleanLiteral { value, .. }) => Some(Value::Bool(*value)),
+        Node::NumericLiteral(NumericLiteral { value, .. }) => Some(Value::Number(*value)),
+        Node::StringLiteral(StringLiteral { value, .. }) => Some(Value::String(value.str.clone())),
+        Node::UnaryExpression(UnaryExpression {
+            operator, argument, ..
+        }) => unary(*operator, evaluate(argument)?),
+        Node::BinaryExpression(BinaryExpression {
+            operator,
+            left,
+            right,
+            ..
+        }) => binary(*operator, evaluate(left)?, evaluate(right)?),
+        Node::LogicalExpression(LogicalExpression {
+            operator,
+            left,
+            right,
+            ..
+        }) => {
+            let left = evaluate(left)?;
+            if short_circuits(*operator, &left) {
+                Some(left)
+            } else {
+                evaluate(right)
+            }
+        }
+        Node::ConditionalExpression(ConditionalExpression {
+            test,
+            consequent,
+            alternate,
+            ..
+        }) => {
+            if evaluate(test)?.to_boolean() {
+                evaluate(consequent)
+            } else {
+                evaluate(alternate)
+            }
+        }
+        _ => None,
+    }
+}
+
+fn unary(op: UnaryExpressionOperator, arg: Value) -> Option<Value> {
+    use UnaryExpressionOperator::*;
+    Some(match op {
+        Minus => Value::Number(-arg.to_number()),
+        Plus => Value::Number(arg.to_number()),
+        Not => Value::Bool(!arg.to_boolean()),
+        BitNot => Value::Number(!to_int32(arg.to_number()) as f64),
+        Typeof => Value::String(arg.type_of().encode_utf16().collect()),
+        Void => Value::Undefined,
+        Delete => return None,
+    })
+}
+
+fn binary(op: BinaryExpressionOperator, left: Value, right: Value) -> Option<Value> {
+    use BinaryExpressionOperator::*;
+    let num = |f: fn(f64, f64) -> f64| Value::Number(f(left.to_number(), right.to_number()));
+    let int = |f: fn(i32, i32) -> i32| {
+        Value::Number(f(to_int32(left.to_number()), to_int32(right.to_number())) as f64)
+    };
+    // Only the low 5 bits of the shift count are used.
+    let shift = to_uint32(right.to_number()) & 31;
+    Some(match op {
+        Plus => match (&left, &right) {
+            (Value::String(_), _) | (_, Value::String(_)) => {
+                let mut s = left.to_js_string();
+                s.extend(right.to_js_string());
+                Value::String(s)
+            }
+            _ => num(|a, b| a + b),
+        },
+        Minus => num(|a, b| a - b),
+        Mult => num(|a, b| a * b),
+        Div => num(|a, b| a / b),
+        // Like fmod, the sign of the result is the sign of the dividend.
+        Mod => num(|a, b| a % b),
+        Exp => num(exponentiate),
+        BitAnd => int(|a, b| a & b),
+        BitOr => int(|a, b| a | b),
+        BitXor => int(|a, b| a ^ b),
+        LShift => Value::Number(to_int32(left.to_number()).wrapping_shl(shift) as f64),
+        RShift => Value::Number((to_int32(left.to_number()) >> shift) as f64),
+        RShift3 => Value::Number((to_uint32(left.to_number()) >> shift) as f64),
+        // Numbers compare with IEEE semantics, like JS.
+        StrictEquals => Value::Bool(left == right),
+        StrictNotEquals => Value::Bool(left != right),
+        LooseEquals => Value::Bool(loose_equals(&left, &right)),
+        LooseNotEquals => Value::Bool(!loose_equals(&left, &right)),
+        Less => Value::Bool(less_than(&left, &right) == Some(true)),
+        Greater => Value::Bool(less_than(&right, &left) == Some(true)),
+        LessEquals => Value::Bool(less_than(&right, &left) == Some(false)),
+        GreaterEquals => Value::Bool(less_than(&left, &right) == Some(false)),
+        In | Instanceof => return None,
+    })
+}
+
+/// ES2022 6.1.6.1.3 Number::exponentiate.
+fn exponentiate(base: f64, exponent: f64) -> f64 {
+    // Unlike `powf`, the result is NaN if the exponent is NaN, or if the base is 1 or -1 and the
+    // exponent is infinite.
+    if exponent.is_nan() || (base.abs() == 1.0 && exponent.is_infinite()) {
+        f64::NAN
+    } else {
+        base.powf(exponent)
+    }
+}
+
+/// ES2022 7.2.14 IsLooselyEqual.
+fn loose_equals(x: &Value, y: &Value) -> bool {
+    match (x, y) {
+        (Value::Undefined | Value::Null, Value::Undefined | Value::Null) => true,
+        (Value::Undefined | Value::Null, _) | (_, Value::Undefined | Value::Null) => false,
+        (Value::Number(_), Value::String(_)) | (Value::String(_), Value::Number(_)) => {
+            x.to_number() == y.to_number()
+        }
+        (Value::Bool(_), _) => loose_equals(&Value::Number(x.to_number()), y),
+        (_, Value::Bool(_)) => loose_equals(x, &Value::Number(y.to_number())),
+        _ => x == y,
+    }
+}
+
+/// ES2022 7.2.13 IsLessThan. Return `None` for undefined, if either operand is NaN.
+fn less_than(x: &Value, y: &Value) -> Option<bool> {
+    match (x, y) {
+        // Strings are compared by code units.
+        (Value::String(x), Value::String(y)) => Some(x < y),
+        _ => {
+            let (x, y) = (x.to_number(), y.to_number());
+            if x.is_nan() || y.is_nan() {
+                None
+            } else {
+                Some(x < y)
+            }
+        }
+    }
+}
+
+/// Return the length of the constant expression `node` when printed without spaces.
+fn printed_len(node: &Node) -> usize {
+    match node {
+        Node::NumericLiteral(NumericLiteral { value, .. }) => number_to_string(*value).len(),
+        Node::StringLiteral(StringLiteral { value, .. }) => value.str.len() + 2,
+        Node::BooleanLiteral(BooleanLiteral { value, .. }) => value.to_string().len(),
+        Node::NullLiteral(_) => 4,
+        Node::UnaryExpression(UnaryExpression {
+            operator, argument, ..
+        }) => {
+            let op = operator.as_str();
+            // Keywords are followed by a space.
+            let space = op.starts_with(char::is_alphabetic) as usize;
+            op.len() + space + printed_len(argument)
+        }
+        Node::BinaryExpression(BinaryExpression {
+            operator,
+            left,
+            right,
+            ..
+        }) => printed_len(left) + operator.as_str().len() + printed_len(right),
+        Node::LogicalExpression(LogicalExpression {
+            operator,
+            left,
+            right,
+            ..
+        }) => printed_len(left) + operator.as_str().len() + printed_len(right),
+        Node::ConditionalExpression(ConditionalExpression {
+            test,
+            consequent,
+            alternate,
+            ..
+        }) => printed_len(test) + printed_len(consequent) + printed_len(alternate) + 2,
+        _ => 0,
+    }
+}
+
+/// Return true if `node` is the literal representation of its value, which can't be folded
+/// further, like `-1` or `void 0`.
+fn is_literal_form(node: &Node) -> bool {
+    match node {
+        Node::UnaryExpression(UnaryExpression {
+            operator: UnaryExpressionOperator::Minus,
+            argument: Node::NumericLiteral(_),
+            ..
+        }) => true,
+        Node::UnaryExpression(UnaryExpression {
+            operator: UnaryExpressionOperator::Void,
+            argument: Node::NumericLiteral(NumericLiteral { value, .. }),
+            ..
+        }) => *value == 0.0,
+        _ => false,
+    }
+}
+
+/// Return a new node evaluating to `value`, located at `range`, or `None` if `value` doesn't
+/// have a literal representation.
+pub(crate) fn literal<'gc>(
+    gc: &'gc GCContext,
+    value: Value,
+    range: SourceRange,
+) -> Option<&'gc Node<'gc>> {
+    let metadata = || TemplateMetadata {
+        range,
+        ..Default::default()
+    };
+    let number = |value: f64| {
+        NumericLiteralBuilder::build_template(
+            gc,
+            NumericLiteralTemplate {
+                metadata: metadata(),
+                value,
+            },
+        )
+    };
+    let unary = |operator: UnaryExpressionOperator, argument: &'gc Node<'gc>| {
+        UnaryExpressionBuilder::build_template(
+            gc,
+            UnaryExpressionTemplate {
+                metadata: metadata(),
+                operator,
+                argument,
+                prefix: true,
+            },
+        )
+    };
+    Some(match value {
+        Value::Undefined => unary(UnaryExpressionOperator::Void, number(0.0)),
+        Value::Null => NullLiteralBuilder::build_template(
+            gc,
+            NullLiteralTemplate {
+                metadata: metadata(),
+            },
+        ),
+        Value::Bool(value) => BooleanLiteralBuilder::build_template(
+            gc,
+            BooleanLiteralTemplate {
+                metadata: metadata(),
+                value,
+            },
+        ),
+        Value::Number(value) if !value.is_finite() => return None,
+        Value::Number(value) if value.is_sign_negative() => {
+            unary(UnaryExpressionOperator::Minus, number(-value))
+        }
+        Value::Number(value) => number(value),
+        Value::String(str) => StringLiteralBuilder::build_template(
+            gc,
+            StringLiteralTemplate {
+                metadata: metadata(),
+                value: NodeString { str },
+            },
+        ),
+    })
+}
+
+/// Return true if `node` is in a position where replacing it with a reference changes the
+/// meaning of the code: `(0, a.f)()` calls `f` with `this` undefined unlike `a.f()`, `(0, eval)`
+/// is an indirect eval, and `delete` and `typeof` treat references specially.
+fn is_reference_position<'gc>(node: &'gc Node<'gc>, parent: Option<&'gc Node<'gc>>) -> bool {
+    let is = |child: &'gc Node<'gc>| std::ptr::eq(node, child);
+    match parent {
+        Some(Node::CallExpression(CallExpression { callee, .. }))
+        | Some(Node::OptionalCallExpression(OptionalCallExpression { callee, .. })) => is(callee),
+        Some(Node::TaggedTemplateExpression(TaggedTemplateExpression { tag, .. })) => is(tag),
+        Some(Node::UnaryExpression(UnaryExpression {
+            operator: UnaryExpressionOperator::Delete | UnaryExpressionOperator::Typeof,
+            argument,
+            ..
+        })) => is(argument),
+        _ => false,
+    }
+}
+
+/// Return the operand which a logical or conditional expression `node` evaluates to, if it is
+/// known because the test is constant.
+pub(crate) fn constant_branch<'gc>(node: &'gc Node<'gc>) -> Option<&'gc Node<'gc>> {
+    match node {
+        Node::LogicalExpression(LogicalExpression {
+            operator,
+            left,
+            right,
+            ..
+        }) => {
+            if short_circuits(*operator, &evaluate(left)?) {
+                Some(left)
+            } else {
+                Some(right)
+            }
+        }
+        Node::ConditionalExpression(ConditionalExpression {
+            test,
+            consequent,
+            alternate,
+            ..
+        }) => {
+            if evaluate(test)?.to_boolean() {
+                Some(consequent)
+            } else {
+                Some(alternate)
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Return the node replacing the expression `node`, a child of `parent`, with `replacement`,
+/// which evaluates to the same value.
+pub(crate) fn replace_value<'gc>(
+    gc: &'gc GCContext,
+    node: &'gc Node<'gc>,
+    parent: Option<&'gc Node<'gc>>,
+    replacement: &'gc Node<'gc>,
+) -> &'gc Node<'gc> {
+    match replacement {
+        Node::Identifier(_) | Node::MemberExpression(_) | Node::OptionalMemberExpression(_)
+            if is_reference_position(node, parent) =>
+        {
+            // Keep the value without the reference as `(0, replacement)`.
+            let metadata = TemplateMetadata {
+                range: *node.range(),
+                ..Default::default()
+            };
+            let zero = NumericLiteralBuilder::build_template(
+                gc,
+                NumericLiteralTemplate {
+                    metadata: metadata.clone(),
+                    value: 0.0,
+                },
+            );
+            SequenceExpressionBuilder::build_template(
+                gc,
+                SequenceExpressionTemplate {
+                    metadata,
+                    expressions: vec![zero, replacement],
+                },
+            )
+        }
+        _ => replacement,
+    }
+}
+
+#[derive(Default)]
+pub struct ConstantFold {}
+
+impl ConstantFold {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Return the folded replacement of `node`, whose operands have already been folded.
+    fn fold<'gc>(&self, gc: &'gc GCContext, node: &'gc Node<'gc>) -> Option<&'gc Node<'gc>> {
+        match node {
+            Node::UnaryExpression(_) | Node::BinaryExpression(_) => {
+                let value = evaluate(node)?;
+                if is_literal_form(node) {
+                    return None;
+                }
+                if let Value::Number(n) = value {
+                    let len = number_to_string(n.abs()).len() + n.is_sign_negative() as usize;
+                    if len > printed_len(node) {
+                        return None;
+                    }
+                }
+                literal(gc, value, *node.range())
+            }
+            Node::LogicalExpression(_) | Node::ConditionalExpression(_) => constant_branch(node),
+            _ => None,
+        }
+    }
+}
+
+impl Pass for ConstantFold {
+    fn name(&self) -> &'static str {
+        "Constant folding"
+    }
+    fn description(&self) -> &'static str {
+        "Evaluates operators applied to literals"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        VisitorMut::call(self, gc, node, None)
+    }
+}
+
+impl<'gc> VisitorMut<'gc> for ConstantFold {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        // Fold the operands first.
+        let (folded, changed) =
+            match node.visit_children_mut(NodeBuilder::from_node(node), gc, self) {
+                TransformResult::Changed(new_node) => (new_node, true),
+                TransformResult::Unchanged => (node, false),
+            };
+        match self.fold(gc, folded) {
+            Some(replacement) => {
+                TransformResult::Changed(replace_value(gc, node, parent, replacement))
+            }
+            None if changed => TransformResult::Changed(folded),
+            None => TransformResult::Unchanged,
+        }
+    }
+}
@@ -0,0 +1,400 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Dead code elimination.
+//!
+//! Removes the statements following a `return`, `throw`, `break` or `continue`, the branches of
+//! `if` statements, conditional and logical expressions which are never taken, empty statements
+//! and blocks, and the declarations of local functions and variables which are never referenced
+//! and whose initializer has no side effects.
+//! Removed `var` and function declarations which may still be referenced because they are
+//! hoisted are kept, without their initializers.
+//!
+//! Transforms
+//! ```js
+//! function f(x) {
+//!   var unused = 1;
+//!   if (false) {
+//!     log(x);
+//!   }
+//!   return x;
+//!   x++;
+//! }
+//! ```
+//! into
+//! ```js
+//! function f(x) {
+//!   return x;
+//! }
+//! ```
+
+use crate::passes::const_fold::{constant_branch, evaluate, replace_value};
+use crate::passes::util::{statement_list, with_statement_list};
+use crate::Pass;
+use juno::ast::*;
+use juno::sema::{pattern_ids, resolve_program, BindingKind, ScopeKind, SemContext};
+
+#[derive(Default)]
+pub struct DeadCodeElimination {}
+
+impl DeadCodeElimination {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Pass for DeadCodeElimination {
+    fn name(&self) -> &'static str {
+        "Dead code elimination"
+    }
+    fn description(&self) -> &'static str {
+        "Removes unreachable code and unused declarations"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let mut result = TransformResult::Unchanged;
+        let mut root = node;
+        // Removing a declaration may leave the declarations it refers to unused, so repeat until
+        // nothing changes.
+        loop {
+            let sem = resolve_program(gc, root);
+            let mut eliminator = Eliminator { sem: &sem };
+            match VisitorMut::call(&mut eliminator, gc, root, None) {
+                TransformResult::Changed(new_root) => {
+                    root = new_root;
+                    result = TransformResult::Changed(new_root);
+                }
+                TransformResult::Unchanged => return result,
+            }
+        }
+    }
+}
+
+struct Eliminator<'a, 'gc> {
+    /// Scopes of the AST being transformed.
+    sem: &'a SemContext<'gc>,
+}
+
+impl<'gc> Eliminator<'_, 'gc> {
+    /// Return the replacement of `node`, whose children have already been transformed.
+    fn eliminate(&self, gc: &'gc GCContext, node: &'gc Node<'gc>) -> Option<&'gc Node<'gc>> {
+        match node {
+            Node::LogicalExpression(_) | Node::ConditionalExpression(_) => constant_branch(node),
+            Node::IfStatement(IfStatement {
+                metadata,
+                test,
+                consequent,
+                alternate,
+            }) => {
+                let (taken, dropped) = if evaluate(test)?.to_boolean() {
+                    (Some(*consequent), *alternate)
+                } else {
+                    (*alternate, Some(*consequent))
+                };
+                let hoisted = dropped.and_then(|dropped| self.hoisted_vars(gc, dropped));
+                let range = metadata.range;
+                Some(match (taken, hoisted) {
+                    (Some(taken), Some(hoisted)) => block(gc, range, vec![taken, hoisted]),
+                    (Some(stmt), None) | (None, Some(stmt)) => stmt,
+                    (None, None) => EmptyStatementBuilder::build_template(
+                        gc,
+                        EmptyStatementTemplate {
+                            metadata: TemplateMetadata {
+                                range,
+                                ..Default::default()
+                            },
+                        },
+                    ),
+                })
+            }
+            _ => {
+                let list = self.statements(gc, statement_list(node)?)?;
+                Some(with_statement_list(gc, node, list))
+            }
+        }
+    }
+
+    /// Return the statements remaining from the statement list `list`, or `None` if it is
+    /// unchanged.
+    fn statements(&self, gc: &'gc GCContext, list: &NodeList<'gc>) -> Option<NodeList<'gc>> {
+        let mut result = NodeList::with_capacity(list.len());
+        let mut changed = false;
+        let mut reachable = true;
+        for &stmt in list {
+            if !reachable {
+                match stmt {
+                    Node::FunctionDeclaration(FunctionDeclaration { id: Some(id), .. })
+                        if !self.is_unused(id) =>
+                    {
+                        result.push(stmt)
+                    }
+                    Node::VariableDeclaration(VariableDeclaration {
+                        kind: VariableDeclarationKind::Var,
+                        declarations,
+                        ..
+                    }) if declarations.iter().all(|&decl| {
+                        matches!(
+                            decl,
+                            Node::VariableDeclarator(VariableDeclarator {
+                                init: None,
+                                id: Node::Identifier(_),
+                                ..
+                            })
+                        ) && !self.is_removable(decl)
+                    }) =>
+                    {
+                        result.push(stmt)
+                    }
+                    _ => {
+                        result.extend(self.hoisted_vars(gc, stmt));
+                        changed = true;
+                    }
+                }
+                continue;
+            }
+            match stmt {
+                Node::EmptyStatement(_) => changed = true,
+                Node::ExpressionStatement(ExpressionStatement {
+                    expression,
+                    directive: None,
+                    ..
+                }) if evaluate(expression).is_some() => changed = true,
+                Node::BlockStatement(BlockStatement { body, .. })
+                    if !body.iter().any(|stmt| is_lexical_declaration(stmt)) =>
+                {
+                    reachable = !body.iter().any(|stmt| is_abrupt(stmt));
+                    result.extend(body);
+                    changed = true;
+                }
+                Node::FunctionDeclaration(FunctionDeclaration { id: Some(id), .. })
+                    if self.is_unused(id) =>
+                {
+                    changed = true
+                }
+                Node::VariableDeclaration(decl @ VariableDeclaration { declarations, .. }) => {
+                    let used: NodeList = declarations
+                        .iter()
+                        .copied()
+                        .filter(|&declarator| !self.is_removable(declarator))
+                        .collect();
+                    if used.is_empty() {
+                        changed = true;
+                    } else if used.len() < declarations.len() {
+                        let mut builder = VariableDeclarationBuilder::from_node(decl);
+                        builder.declarations(used);
+                        result.push(builder.build(gc).unwrap());
+                        changed = true;
+                    } else {
+                        result.push(stmt);
+                    }
+                }
+                _ => {
+                    reachable = !is_abrupt(stmt);
+                    result.push(stmt);
+                }
+            }
+        }
+        if changed {
+            Some(result)
+        } else {
+            None
+        }
+    }
+
+    /// Return a declaration without initializers of the variables declared with `var` in the
+    /// statement `stmt` which are hoisted out of it and used, or `None` if there are none.
+    fn hoisted_vars(&self, gc: &'gc GCContext, stmt: &'gc Node<'gc>) -> Option<&'gc Node<'gc>> {
+        let mut collector = VarCollector::default();
+        Visitor::call(&mut collector, gc, stmt, None);
+        collector.ids.retain(|id| !self.is_unused(id));
+        if collector.ids.is_empty() {
+            return None;
+        }
+        let metadata = || TemplateMetadata {
+            range: *stmt.range(),
+            ..Default::default()
+        };
+        let declarations = collector
+            .ids
+            .into_iter()
+            .map(|id| {
+                VariableDeclaratorBuilder::build_template(
+                    gc,
+                    VariableDeclaratorTemplate {
+                        metadata: metadata(),
+                        init: None,
+                        id,
+                    },
+                )
+            })
+            .collect();
+        Some(VariableDeclarationBuilder::build_template(
+            gc,
+            VariableDeclarationTemplate {
+                metadata: metadata(),
+                kind: VariableDeclarationKind::Var,
+                declarations,
+            },
+        ))
+    }
+
+    /// Return true if the variable declarator `node` declares a single unused variable and has
+    /// no side effects.
+    fn is_removable(&self, node: &'gc Node<'gc>) -> bool {
+        match node {
+            Node::VariableDeclarator(VariableDeclarator {
+                id: id @ Node::Identifier(_),
+                init,
+                ..
+            }) => self.is_unused(id) && init.is_none_or(is_pure),
+            _ => false,
+        }
+    }
+
+    /// Return true if the identifier `id` declares a local function or variable which is never
+    /// referenced, so its declaration can be removed.
+    fn is_unused(&self, id: &'gc Node<'gc>) -> bool {
+        let binding = match self.sem.binding_of(id) {
+            Some(binding) => self.sem.binding(binding),
+            None => return false,
+        };
+        let scope = self.sem.scope(binding.scope);
+        let removable_kind = match binding.kind {
+            BindingKind::Var | BindingKind::Let | BindingKind::Const => true,
+            // Functions declared in blocks may be visible outside of them in sloppy mode.
+            BindingKind::Function => scope.kind.is_var_scope(),
+            _ => false,
+        };
+        // Top level declarations of scripts are properties of the global object.
+        removable_kind
+            && scope.kind != ScopeKind::Global
+            && !scope.dynamic
+            && binding.references.is_empty()
+    }
+}
+
+impl<'gc> VisitorMut<'gc> for Eliminator<'_, 'gc> {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let (transformed, changed) =
+            match node.visit_children_mut(NodeBuilder::from_node(node), gc, self) {
+                TransformResult::Changed(new_node) => (new_node, true),
+                TransformResult::Unchanged => (node, false),
+            };
+        match self.eliminate(gc, transformed) {
+            Some(replacement) => {
+                TransformResult::Changed(replace_value(gc, node, parent, replacement))
+            }
+            None if changed => TransformResult::Changed(transformed),
+            None => TransformResult::Unchanged,
+        }
+    }
+}
+
+/// Return true if control never reaches the end of the statement `node`.
+fn is_abrupt(node: &Node) -> bool {
+    match node {
+        Node::ReturnStatement(_)
+        | Node::ThrowStatement(_)
+        | Node::BreakStatement(_)
+        | Node::ContinueStatement(_) => true,
+        Node::BlockStatement(BlockStatement { body, .. }) => {
+            body.iter().any(|stmt| is_abrupt(stmt))
+        }
+        Node::IfStatement(IfStatement {
+            consequent,
+            alternate: Some(alternate),
+            ..
+        }) => is_abrupt(consequent) && is_abrupt(alternate),
+        _ => false,
+    }
+}
+
+/// Return true if `node` declares a binding scoped to the enclosing block.
+fn is_lexical_declaration(node: &Node) -> bool {
+    match node {
+        Node::VariableDeclaration(VariableDeclaration { kind, .. }) => {
+            *kind != VariableDeclarationKind::Var
+        }
+        Node::FunctionDeclaration(_) | Node::ClassDeclaration(_) => true,
+        _ => false,
+    }
+}
+
+/// Return true if evaluating the expression `node` has no side effects.
+fn is_pure(node: &Node) -> bool {
+    match node {
+        Node::FunctionExpression(_) | Node::ArrowFunctionExpression(_) => true,
+        Node::ArrayExpression(ArrayExpression { elements, .. }) => {
+            elements.iter().all(|elem| is_pure(elem))
+        }
+        Node::ObjectExpression(ObjectExpression { properties, .. }) => {
+            properties.iter().all(|prop| match prop {
+                Node::Property(Property {
+                    computed: false,
+                    value,
+                    ..
+                }) => is_pure(value),
+                _ => false,
+            })
+        }
+        Node::TemplateLiteral(TemplateLiteral { expressions, .. }) => expressions.is_empty(),
+        _ => evaluate(node).is_some(),
+    }
+}
+
+/// Collects the identifiers declared by the `var` declarations in a statement.
+#[derive(Default)]
+struct VarCollector<'gc> {
+    ids: Vec<&'gc Node<'gc>>,
+}
+
+impl<'gc> Visitor<'gc> for VarCollector<'gc> {
+    fn call(&mut self, gc: &'gc GCContext, node: &'gc Node<'gc>, _parent: Option<&'gc Node<'gc>>) {
+        match node {
+            Node::VariableDeclaration(VariableDeclaration {
+                kind: VariableDeclarationKind::Var,
+                declarations,
+                ..
+            }) => {
+                for decl in declarations {
+                    if let Node::VariableDeclarator(VariableDeclarator { id, .. }) = decl {
+                        pattern_ids(id, &mut self.ids);
+                    }
+                }
+            }
+            // Declarations in functions and class bodies aren't hoisted out of them.
+            Node::FunctionDeclaration(_)
+            | Node::FunctionExpression(_)
+            | Node::ArrowFunctionExpression(_)
+            | Node::ClassDeclaration(_)
+            | Node::ClassExpression(_) => {}
+            _ => node.visit_children(gc, self),
+        }
+    }
+}
+
+/// Return a new block statement located at `range` containing `body`.
+fn block<'gc>(gc: &'gc GCContext, range: SourceRange, body: NodeList<'gc>) -> &'gc Node<'gc> {
+    BlockStatementBuilder::build_template(
+        gc,
+        BlockStatementTemplate {
+            metadata: TemplateMetadata {
+                range,
+                ..Default::default()
+            },
+            body,
+        },
+    )
+}
@@ -0,0 +1,368 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Compile time definitions.
+//!
+//! Replaces reads of global variables and of property chains rooted at global variables with
+//! constant values, so the following passes can fold the code depending on them.
+//! Variables declared in the program with the same name aren't replaced.
+//!
+//! With `__DEV__=false` and `process.env.NODE_ENV="production"`, transforms
+//! ```js
+//! if (__DEV__) check();
+//! mode = process.env.NODE_ENV;
+//! ```
+//! into
+//! ```js
+//! if (false) check();
+//! mode = "production";
+//! ```
+
+use crate::Pass;
+use juno::ast::*;
+use juno::sema::{resolve_program, ReferenceKind};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// A replacement of a global variable or a property chain by a value.
+#[derive(Debug, Clone)]
+pub struct Definition {
+    /// The name of the global variable followed by the property names.
+    path: Vec<String>,
+    value: Value,
+}
+
+/// Parses `KEY=VALUE`, where `KEY` is a global variable name optionally followed by property
+/// names separated by dots, and `VALUE` is JSON.
+impl FromStr for Definition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("'{}': expected KEY=VALUE", s))?;
+        let path: Vec<String> = key.split('.').map(String::from).collect();
+        if !path.iter().all(|name| is_identifier_name(name)) {
+            return Err(format!("'{}': invalid name", key));
+        }
+        let value = serde_json::from_str(value)
+            .map_err(|err| format!("'{}': invalid value: {}", key, err))?;
+        Ok(Definition { path, value })
+    }
+}
+
+/// Return true if `name` is an ASCII identifier name.
+fn is_identifier_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+pub struct Define {
+    definitions: Vec<Definition>,
+}
+
+impl Define {
+    pub fn new(definitions: Vec<Definition>) -> Self {
+        Self { definitions }
+    }
+}
+
+impl Pass for Define {
+    fn name(&self) -> &'static str {
+        "Define"
+    }
+    fn description(&self) -> &'static str {
+        "Replaces global variables and properties with constant values"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let sem = resolve_program(gc, node);
+        let mut replacer = Replacer {
+            definitions: &self.definitions,
+            globals: HashSet::new(),
+            targets: HashSet::new(),
+        };
+        for def in &self.definitions {
+            let name = gc.atom(&def.path[0]);
+            replacer.globals.extend(
+                sem.global_references(name)
+                    .iter()
+                    .filter(|reference| reference.kind == ReferenceKind::Read)
+                    .map(|reference| NodeRef(reference.node)),
+            );
+        }
+        Visitor::call(
+            &mut TargetCollector {
+                targets: &mut replacer.targets,
+            },
+            gc,
+            node,
+            None,
+        );
+        VisitorMut::call(&mut replacer, gc, node, None)
+    }
+}
+
+/// Collects the member expressions which are assigned or deleted.
+struct TargetCollector<'a, 'gc> {
+    targets: &'a mut HashSet<NodeRef<'gc>>,
+}
+
+impl<'gc> TargetCollector<'_, 'gc> {
+    /// Add the member expressions assigned by the assignment target `node`.
+    fn add(&mut self, node: &'gc Node<'gc>) {
+        match node {
+            Node::MemberExpression(_) => {
+                self.targets.insert(NodeRef(node));
+            }
+            Node::ObjectPattern(ObjectPattern { properties, .. }) => {
+                for prop in properties {
+                    match prop {
+                        Node::Property(Property { value, .. }) => self.add(value),
+                        _ => self.add(prop),
+                    }
+                }
+            }
+            Node::ArrayPattern(ArrayPattern { elements, .. }) => {
+                for elem in elements {
+                    self.add(elem);
+                }
+            }
+            Node::RestElement(RestElement { argument, .. }) => self.add(argument),
+            Node::AssignmentPattern(AssignmentPattern { left, .. }) => self.add(left),
+            _ => {}
+        }
+    }
+}
+
+impl<'gc> Visitor<'gc> for TargetCollector<'_, 'gc> {
+    fn call(&mut self, gc: &'gc GCContext, node: &'gc Node<'gc>, _parent: Option<&'gc Node<'gc>>) {
+        match node {
+            Node::AssignmentExpression(AssignmentExpression { left, .. })
+            | Node::ForInStatement(ForInStatement { left, .. })
+            | Node::ForOfStatement(ForOfStatement { left, .. }) => self.add(left),
+            Node::UpdateExpression(UpdateExpression { argument, .. })
+            | Node::UnaryExpression(UnaryExpression {
+                operator: UnaryExpressionOperator::Delete,
+                argument,
+                ..
+            }) => self.add(argument),
+            _ => {}
+        }
+        node.visit_children(gc, self);
+    }
+}
+
+struct Replacer<'a, 'gc> {
+    definitions: &'a [Definition],
+
+    /// Identifiers reading global variables which have a definition.
+    globals: HashSet<NodeRef<'gc>>,
+
+    /// Member expressions which are assigned or deleted.
+    targets: HashSet<NodeRef<'gc>>,
+}
+
+impl<'gc> Replacer<'_, 'gc> {
+    /// Return the definition replacing `node`, if any.
+    fn definition(&self, gc: &'gc GCContext, node: &'gc Node<'gc>) -> Option<&Definition> {
+        if self.targets.contains(&NodeRef(node)) {
+            return None;
+        }
+        // Collect the property names in reverse order.
+        let mut names = Vec::new();
+        let mut cur = node;
+        while let Node::MemberExpression(MemberExpression {
+            object, property, ..
+        }) = cur
+        {
+            names.push(property_name(gc, cur, property)?);
+            cur = object;
+        }
+        if !self.globals.contains(&NodeRef(cur)) {
+            return None;
+        }
+        match cur {
+            Node::Identifier(Identifier { name, .. }) => names.push(gc.str(*name)),
+            _ => return None,
+        }
+        self.definitions.iter().find(|def| {
+            def.path.len() == names.len()
+                && def.path.iter().zip(names.iter().rev()).all(|(a, b)| a == b)
+        })
+    }
+}
+
+/// Return the name of the property accessed by the member expression `node`.
+fn property_name<'gc>(
+    gc: &'gc GCContext,
+    node: &'gc Node<'gc>,
+    property: &'gc Node<'gc>,
+) -> Option<&'gc str> {
+    match (node, property) {
+        (
+            Node::MemberExpression(MemberExpression {
+                computed: false, ..
+            }),
+            Node::Identifier(Identifier { name, .. }),
+        ) => Some(gc.str(*name)),
+        (
+            Node::MemberExpression(MemberExpression { computed: true, .. }),
+            Node::StringLiteral(StringLiteral { value, .. }),
+        ) => {
+            let name = String::from_utf16(&value.str).ok()?;
+            Some(gc.str(gc.atom(name)))
+        }
+        _ => None,
+    }
+}
+
+impl<'gc> VisitorMut<'gc> for Replacer<'_, 'gc> {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        match node {
+            // The key of a shorthand property is the same node as its value.
+            Node::Property(
+                prop @ Property {
+                    value,
+                    shorthand: true,
+                    ..
+                },
+            ) => {
+                if let Some(def) = self.definition(gc, value) {
+                    let mut builder = PropertyBuilder::from_node(prop);
+                    builder.value(json_to_node(gc, &def.value, *value.range()));
+                    builder.shorthand(false);
+                    return builder.build(gc);
+                }
+            }
+            Node::Identifier(_) => {
+                if let Some(Node::Property(Property {
+                    key,
+                    computed: false,
+                    ..
+                })) = parent
+                {
+                    if std::ptr::eq(node, *key) {
+                        return TransformResult::Unchanged;
+                    }
+                }
+                if let Some(def) = self.definition(gc, node) {
+                    return TransformResult::Changed(json_to_node(gc, &def.value, *node.range()));
+                }
+            }
+            Node::MemberExpression(_) => {
+                if let Some(def) = self.definition(gc, node) {
+                    return TransformResult::Changed(json_to_node(gc, &def.value, *node.range()));
+                }
+            }
+            _ => {}
+        }
+        node.visit_children_mut(NodeBuilder::from_node(node), gc, self)
+    }
+}
+
+/// Return a new expression evaluating to the JSON `value`, located at `range`.
+fn json_to_node<'gc>(gc: &'gc GCContext, value: &Value, range: SourceRange) -> &'gc Node<'gc> {
+    let metadata = || TemplateMetadata {
+        range,
+        ..Default::default()
+    };
+    let string = |s: &str| {
+        StringLiteralBuilder::build_template(
+            gc,
+            StringLiteralTemplate {
+                metadata: metadata(),
+                value: NodeString {
+                    str: s.encode_utf16().collect(),
+                },
+            },
+        )
+    };
+    match value {
+        Value::Null => NullLiteralBuilder::build_template(
+            gc,
+            NullLiteralTemplate {
+                metadata: metadata(),
+            },
+        ),
+        Value::Bool(value) => BooleanLiteralBuilder::build_template(
+            gc,
+            BooleanLiteralTemplate {
+                metadata: metadata(),
+                value: *value,
+            },
+        ),
+        Value::Number(n) => {
+            let value = n.as_f64().unwrap_or(f64::NAN);
+            let literal = NumericLiteralBuilder::build_template(
+                gc,
+                NumericLiteralTemplate {
+                    metadata: metadata(),
+                    value: value.abs(),
+                },
+            );
+            if value.is_sign_negative() {
+                UnaryExpressionBuilder::build_template(
+                    gc,
+                    UnaryExpressionTemplate {
+                        metadata: metadata(),
+                        operator: UnaryExpressionOperator::Minus,
+                        argument: literal,
+                        prefix: true,
+                    },
+                )
+            } else {
+                literal
+            }
+        }
+        Value::String(s) => string(s),
+        Value::Array(elements) => ArrayExpressionBuilder::build_template(
+            gc,
+            ArrayExpressionTemplate {
+                metadata: metadata(),
+                elements: elements
+                    .iter()
+                    .map(|elem| json_to_node(gc, elem, range))
+                    .collect(),
+                trailing_comma: false,
+            },
+        ),
+        Value::Object(properties) => ObjectExpressionBuilder::build_template(
+            gc,
+            ObjectExpressionTemplate {
+                metadata: metadata(),
+                properties: properties
+                    .iter()
+                    .map(|(key, value)| {
+                        PropertyBuilder::build_template(
+                            gc,
+                            PropertyTemplate {
+                                metadata: metadata(),
+                                key: string(key),
+                                value: json_to_node(gc, value, range),
+                                kind: PropertyKind::Init,
+                                computed: false,
+                                method: false,
+                                shorthand: false,
+                            },
+                        )
+                    })
+                    .collect(),
+            },
+        ),
+    }
+}
@@ -0,0 +1,572 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Destructuring lowering.
+//!
+//! Compiles object and array patterns and default values in declarations, assignments,
+//! parameters, `catch` clauses and `for-in`/`for-of` loops to assignments of each target.
+//! Values destructured more than once are saved in temporaries.
+//! Arrays are indexed, so the destructured values are assumed to be array-like, as in engines
+//! without iterators. Object rest elements copy the object with `Object.assign` and delete the
+//! destructured properties from the copy.
+//!
+//! Transforms
+//! ```js
+//! var {a, b: [c = 1, ...d]} = obj;
+//! function f({x}, y = 2) {}
+//! ```
+//! into
+//! ```js
+//! var _ref = obj, a = _ref.a, _ref2 = _ref.b, _ref3 = _ref2[0],
+//!   c = _ref3 === void 0 ? 1 : _ref3, d = Array.prototype.slice.call(_ref2, 1);
+//! function f(_ref4, y) {
+//!   var x = _ref4.x;
+//!   if (y === void 0) y = 2;
+//! }
+//! ```
+
+use crate::passes::const_fold::{literal, Value};
+use crate::passes::util::*;
+use crate::Pass;
+use juno::ast::*;
+
+#[derive(Default)]
+pub struct Destructuring {}
+
+impl Destructuring {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Pass for Destructuring {
+    fn name(&self) -> &'static str {
+        "Lower destructuring"
+    }
+    fn description(&self) -> &'static str {
+        "Compiles destructuring patterns and default values to assignments"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let mut lowerer = Lowerer {
+            temps: Temporaries::new(UniqueNames::new(gc, node)),
+        };
+        VisitorMut::call(&mut lowerer, gc, node, None)
+    }
+}
+
+struct Lowerer {
+    temps: Temporaries,
+}
+
+impl<'gc> VisitorMut<'gc> for Lowerer {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let is_scope = is_var_scope(node);
+        if is_scope {
+            self.temps.enter();
+        }
+        let (mut node, mut changed) =
+            match node.visit_children_mut(NodeBuilder::from_node(node), gc, self) {
+                TransformResult::Changed(new_node) => (new_node, true),
+                TransformResult::Unchanged => (node, false),
+            };
+        // Parameters are lowered before the temporaries are declared, so that they are declared
+        // in the function.
+        if is_scope {
+            if let Some(new_node) = self.lower_params(gc, node) {
+                node = new_node;
+                changed = true;
+            }
+            if let Some(new_node) = self.temps.exit(gc, node) {
+                node = new_node;
+                changed = true;
+            }
+        }
+        match self.lower(gc, node, parent) {
+            Some(new_node) => TransformResult::Changed(new_node),
+            None if changed => TransformResult::Changed(node),
+            None => TransformResult::Unchanged,
+        }
+    }
+}
+
+impl Lowerer {
+    /// Return the lowered `node`, whose children have already been lowered, or `None` if it
+    /// doesn't contain patterns.
+    fn lower<'gc>(
+        &mut self,
+        gc: &'gc GCContext,
+        node: &'gc Node<'gc>,
+        parent: Option<&'gc Node<'gc>>,
+    ) -> Option<&'gc Node<'gc>> {
+        match node {
+            Node::VariableDeclaration(decl @ VariableDeclaration { declarations, .. })
+                if declarations.iter().any(|declarator| {
+                    matches!(
+                        declarator,
+                        Node::VariableDeclarator(VariableDeclarator {
+                            id: Node::ObjectPattern(_) | Node::ArrayPattern(_),
+                            init: Some(_),
+                            ..
+                        })
+                    )
+                }) =>
+            {
+                let mut new_declarations = NodeList::new();
+                for &declarator in declarations {
+                    match declarator {
+                        Node::VariableDeclarator(VariableDeclarator {
+                            id: id @ (Node::ObjectPattern(_) | Node::ArrayPattern(_)),
+                            init: Some(init),
+                            ..
+                        }) => {
+                            let mut flattener = Flattener::new(&mut self.temps, true);
+                            flattener.flatten(gc, id, init);
+                            new_declarations.extend(flattener.steps.into_iter().map(
+                                |(id, init)| {
+                                    VariableDeclaratorBuilder::build_template(
+                                        gc,
+                                        VariableDeclaratorTemplate {
+                                            metadata: template_metadata(*id.range()),
+                                            init: Some(init),
+                                            id,
+                                        },
+                                    )
+                                },
+                            ));
+                        }
+                        _ => new_declarations.push(declarator),
+                    }
+                }
+                let mut builder = VariableDeclarationBuilder::from_node(decl);
+                builder.declarations(new_declarations);
+                Some(builder.build(gc).unwrap())
+            }
+            Node::AssignmentExpression(AssignmentExpression {
+                metadata,
+                operator: AssignmentExpressionOperator::Assign,
+                left: left @ (Node::ObjectPattern(_) | Node::ArrayPattern(_)),
+                right,
+            }) => {
+                let range = metadata.range;
+                let needs_value = !matches!(
+                    parent,
+                    Some(Node::ExpressionStatement(_) | Node::ForStatement(_))
+                );
+                let mut flattener = Flattener::new(&mut self.temps, false);
+                let mut result = None;
+                if needs_value {
+                    let temp = flattener.temp(gc, right);
+                    flattener.flatten(gc, left, identifier(gc, temp, range));
+                    result = Some(identifier(gc, temp, range));
+                } else {
+                    flattener.flatten(gc, left, right);
+                }
+                let mut expressions = flattener.assignments(gc);
+                expressions.extend(result);
+                Some(sequence(gc, expressions, range))
+            }
+            Node::CatchClause(
+                clause @ CatchClause {
+                    param: Some(param @ (Node::ObjectPattern(_) | Node::ArrayPattern(_))),
+                    body,
+                    ..
+                },
+            ) => {
+                let range = *param.range();
+                let temp = self.temps.names.generate(gc, "_ref");
+                let mut flattener = Flattener::new(&mut self.temps, true);
+                flattener.flatten(gc, param, identifier(gc, temp, range));
+                let decl = flattener.declaration(gc, VariableDeclarationKind::Let, range);
+                let mut builder = CatchClauseBuilder::from_node(clause);
+                builder.param(Some(identifier(gc, temp, range)));
+                builder.body(prepend_to_block(gc, body, decl));
+                Some(builder.build(gc).unwrap())
+            }
+            Node::ForInStatement(ForInStatement { left, body, .. })
+            | Node::ForOfStatement(ForOfStatement { left, body, .. }) => {
+                let (new_left, stmt) = match left {
+                    Node::VariableDeclaration(VariableDeclaration {
+                        metadata,
+                        kind,
+                        declarations,
+                    }) => match declarations.as_slice() {
+                        [Node::VariableDeclarator(VariableDeclarator {
+                            id: id @ (Node::ObjectPattern(_) | Node::ArrayPattern(_)),
+                            ..
+                        })] => {
+                            let range = metadata.range;
+                            let temp = self.temps.names.generate(gc, "_ref");
+                            let mut flattener = Flattener::new(&mut self.temps, true);
+                            flattener.flatten(gc, id, identifier(gc, temp, range));
+                            (
+                                declaration(
+                                    gc,
+                                    *kind,
+                                    [(identifier(gc, temp, range), None)],
+                                    range,
+                                ),
+                                flattener.declaration(gc, *kind, range),
+                            )
+                        }
+                        _ => return None,
+                    },
+                    Node::ObjectPattern(_) | Node::ArrayPattern(_) => {
+                        let range = *left.range();
+                        let temp = self.temps.generate(gc, "_ref");
+                        let mut flattener = Flattener::new(&mut self.temps, false);
+                        flattener.flatten(gc, left, identifier(gc, temp, range));
+                        let assignments = flattener.assignments(gc);
+                        (
+                            identifier(gc, temp, range),
+                            expression_statement(gc, sequence(gc, assignments, range)),
+                        )
+                    }
+                    _ => return None,
+                };
+                let body = prepend_to_block(gc, body, stmt);
+                Some(match node {
+                    Node::ForInStatement(stmt) => {
+                        let mut builder = ForInStatementBuilder::from_node(stmt);
+                        builder.left(new_left);
+                        builder.body(body);
+                        builder.build(gc).unwrap()
+                    }
+                    Node::ForOfStatement(stmt) => {
+                        let mut builder = ForOfStatementBuilder::from_node(stmt);
+                        builder.left(new_left);
+                        builder.body(body);
+                        builder.build(gc).unwrap()
+                    }
+                    _ => unreachable!(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the function `node` with its destructured parameters and default values
+    /// replaced by statements at the top of its body, or `None` if there are none.
+    fn lower_params<'gc>(
+        &mut self,
+        gc: &'gc GCContext,
+        node: &'gc Node<'gc>,
+    ) -> Option<&'gc Node<'gc>> {
+        let params = match node {
+            Node::FunctionDeclaration(FunctionDeclaration { params, .. })
+            | Node::FunctionExpression(FunctionExpression { params, .. })
+            | Node::ArrowFunctionExpression(ArrowFunctionExpression { params, .. }) => params,
+            _ => return None,
+        };
+        if params
+            .iter()
+            .all(|param| matches!(param, Node::Identifier(_) | Node::RestElement(_)))
+        {
+            return None;
+        }
+
+        let mut new_params = NodeList::with_capacity(params.len());
+        let mut stmts = NodeList::new();
+        for &param in params {
+            let range = *param.range();
+            match param {
+                Node::Identifier(_) | Node::RestElement(_) => new_params.push(param),
+                // `if (a === void 0) a = value;`
+                Node::AssignmentPattern(AssignmentPattern {
+                    left: left @ Node::Identifier(Identifier { name, .. }),
+                    right,
+                    ..
+                }) => {
+                    new_params.push(left);
+                    let test = binary(
+                        gc,
+                        BinaryExpressionOperator::StrictEquals,
+                        identifier(gc, *name, range),
+                        undefined(gc, range),
+                        range,
+                    );
+                    let assignment = assign(gc, identifier(gc, *name, range), right, range);
+                    stmts.push(if_statement(
+                        gc,
+                        test,
+                        expression_statement(gc, assignment),
+                        range,
+                    ));
+                }
+                _ => {
+                    let temp = self.temps.names.generate(gc, "_ref");
+                    new_params.push(identifier(gc, temp, range));
+                    let mut flattener = Flattener::new(&mut self.temps, true);
+                    flattener.flatten(gc, param, identifier(gc, temp, range));
+                    stmts.push(flattener.declaration(gc, VariableDeclarationKind::Var, range));
+                }
+            }
+        }
+
+        let node = match node {
+            Node::FunctionDeclaration(func) => {
+                let mut builder = FunctionDeclarationBuilder::from_node(func);
+                builder.params(new_params);
+                builder.build(gc).unwrap()
+            }
+            Node::FunctionExpression(func) => {
+                let mut builder = FunctionExpressionBuilder::from_node(func);
+                builder.params(new_params);
+                builder.build(gc).unwrap()
+            }
+            Node::ArrowFunctionExpression(func) => {
+                let mut builder = ArrowFunctionExpressionBuilder::from_node(func);
+                builder.params(new_params);
+                builder.build(gc).unwrap()
+            }
+            _ => unreachable!(),
+        };
+        Some(prepend_to_body(gc, node, stmts))
+    }
+}
+
+/// Return `body` with `stmt` inserted at its start, wrapping it in a block if needed.
+fn prepend_to_block<'gc>(
+    gc: &'gc GCContext,
+    body: &'gc Node<'gc>,
+    stmt: &'gc Node<'gc>,
+) -> &'gc Node<'gc> {
+    match body {
+        Node::BlockStatement(BlockStatement { body: stmts, .. }) => {
+            let mut new_stmts = vec![stmt];
+            new_stmts.extend(stmts);
+            with_statement_list(gc, body, new_stmts)
+        }
+        _ => block(gc, vec![stmt, body], *body.range()),
+    }
+}
+
+/// A key of a destructured property, which can be used again by an object rest element.
+#[derive(Clone)]
+enum Key {
+    Name(Atom),
+    String(Vec<u16>),
+    Number(f64),
+    /// Temporary holding a computed key.
+    Temp(Atom),
+}
+
+impl Key {
+    /// Return the member expression accessing the property of `object`.
+    fn member<'gc>(
+        &self,
+        gc: &'gc GCContext,
+        object: &'gc Node<'gc>,
+        range: SourceRange,
+    ) -> &'gc Node<'gc> {
+        match self {
+            Key::Name(name) => member(gc, object, *name, range),
+            Key::String(str) => computed_member(
+                gc,
+                object,
+                literal(gc, Value::String(str.clone()), range).unwrap(),
+                range,
+            ),
+            Key::Number(value) => computed_member(gc, object, number(gc, *value, range), range),
+            Key::Temp(name) => computed_member(gc, object, identifier(gc, *name, range), range),
+        }
+    }
+}
+
+/// Flattening of a pattern into a list of assignments to its targets.
+struct Flattener<'a, 'gc> {
+    temps: &'a mut Temporaries,
+    /// Whether the targets are declared, in which case the temporaries are declared along them.
+    /// Otherwise they are declared in the enclosing function.
+    declare: bool,
+    /// The targets and their values, in evaluation order.
+    steps: Vec<(&'gc Node<'gc>, &'gc Node<'gc>)>,
+}
+
+impl<'a, 'gc> Flattener<'a, 'gc> {
+    fn new(temps: &'a mut Temporaries, declare: bool) -> Self {
+        Flattener {
+            temps,
+            declare,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Return the name of a new temporary assigned `value`.
+    fn temp(&mut self, gc: &'gc GCContext, value: &'gc Node<'gc>) -> Atom {
+        let name = if self.declare {
+            self.temps.names.generate(gc, "_ref")
+        } else {
+            self.temps.generate(gc, "_ref")
+        };
+        self.steps
+            .push((identifier(gc, name, *value.range()), value));
+        name
+    }
+
+    /// Add the steps assigning `value` to `pattern`.
+    fn flatten(&mut self, gc: &'gc GCContext, pattern: &'gc Node<'gc>, value: &'gc Node<'gc>) {
+        let range = *pattern.range();
+        match pattern {
+            // `value === void 0 ? right : value`
+            Node::AssignmentPattern(AssignmentPattern { left, right, .. }) => {
+                let temp = self.temp(gc, value);
+                let test = binary(
+                    gc,
+                    BinaryExpressionOperator::StrictEquals,
+                    identifier(gc, temp, range),
+                    undefined(gc, range),
+                    range,
+                );
+                let value = conditional(gc, test, right, identifier(gc, temp, range), range);
+                self.flatten(gc, left, value);
+            }
+            Node::ObjectPattern(ObjectPattern { properties, .. }) => {
+                let has_rest = matches!(properties.last(), Some(Node::RestElement(_)));
+                let temp = if properties.len() == 1 && !has_rest {
+                    None
+                } else {
+                    Some(self.temp(gc, value))
+                };
+                let source = || match temp {
+                    Some(temp) => identifier(gc, temp, range),
+                    None => value,
+                };
+                let mut keys = Vec::new();
+                for &prop in properties {
+                    match prop {
+                        Node::Property(Property {
+                            key,
+                            value: target,
+                            computed,
+                            ..
+                        }) => {
+                            let key = match key {
+                                Node::Identifier(Identifier { name, .. }) if !computed => {
+                                    Key::Name(*name)
+                                }
+                                Node::StringLiteral(StringLiteral {
+                                    value: NodeString { str },
+                                    ..
+                                }) => Key::String(str.clone()),
+                                Node::NumericLiteral(NumericLiteral { value, .. }) => {
+                                    Key::Number(*value)
+                                }
+                                // A computed key is evaluated once, even if it is deleted
+                                // from the rest.
+                                _ if has_rest => Key::Temp(self.temp(gc, key)),
+                                _ => {
+                                    self.flatten(
+                                        gc,
+                                        target,
+                                        computed_member(gc, source(), key, *prop.range()),
+                                    );
+                                    continue;
+                                }
+                            };
+                            self.flatten(gc, target, key.member(gc, source(), *prop.range()));
+                            keys.push(key);
+                        }
+                        // `(_rest = Object.assign({}, source), delete _rest.key, _rest)`
+                        Node::RestElement(RestElement { argument, .. }) => {
+                            let copy = call(
+                                gc,
+                                dotted_name(gc, "Object.assign", range),
+                                vec![object(gc, vec![], range), source()],
+                                range,
+                            );
+                            if keys.is_empty() {
+                                self.flatten(gc, argument, copy);
+                                continue;
+                            }
+                            let rest = self.temp(gc, copy);
+                            let mut expressions: NodeList = keys
+                                .iter()
+                                .map(|key| {
+                                    UnaryExpressionBuilder::build_template(
+                                        gc,
+                                        UnaryExpressionTemplate {
+                                            metadata: template_metadata(range),
+                                            operator: UnaryExpressionOperator::Delete,
+                                            argument: key.member(
+                                                gc,
+                                                identifier(gc, rest, range),
+                                                range,
+                                            ),
+                                            prefix: true,
+                                        },
+                                    )
+                                })
+                                .collect();
+                            expressions.push(identifier(gc, rest, range));
+                            self.flatten(gc, argument, sequence(gc, expressions, range));
+                        }
+                        _ => unreachable!("invalid object pattern property {}", prop.name()),
+                    }
+                }
+            }
+            Node::ArrayPattern(ArrayPattern { elements, .. }) => {
+                let temp = match elements.as_slice() {
+                    [elem] if !matches!(elem, Node::RestElement(_)) => None,
+                    _ => Some(self.temp(gc, value)),
+                };
+                let source = || match temp {
+                    Some(temp) => identifier(gc, temp, range),
+                    None => value,
+                };
+                for (i, &elem) in elements.iter().enumerate() {
+                    match elem {
+                        Node::Empty(_) => {}
+                        Node::RestElement(RestElement { argument, .. }) => {
+                            self.flatten(gc, argument, array_slice(gc, source(), i, range))
+                        }
+                        _ => {
+                            let index = number(gc, i as f64, *elem.range());
+                            self.flatten(
+                                gc,
+                                elem,
+                                computed_member(gc, source(), index, *elem.range()),
+                            );
+                        }
+                    }
+                }
+            }
+            _ => self.steps.push((pattern, value)),
+        }
+    }
+
+    /// Return the steps as a variable declaration of `kind`.
+    fn declaration(
+        self,
+        gc: &'gc GCContext,
+        kind: VariableDeclarationKind,
+        range: SourceRange,
+    ) -> &'gc Node<'gc> {
+        declaration(
+            gc,
+            kind,
+            self.steps.into_iter().map(|(id, init)| (id, Some(init))),
+            range,
+        )
+    }
+
+    /// Return the steps as assignment expressions.
+    fn assignments(self, gc: &'gc GCContext) -> NodeList<'gc> {
+        self.steps
+            .into_iter()
+            .map(|(target, value)| assign(gc, target, value, *target.range()))
+            .collect()
+    }
+}
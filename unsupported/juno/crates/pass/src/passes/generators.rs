@@ -0,0 +1,2079 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Generator and async function lowering.
+//!
+//! Compiles generator and async functions to state machines run by the regenerator runtime,
+//! which must be available as the global `regeneratorRuntime`. The body of the function is split
+//! at each `yield` and `await` into the cases of a `switch` on the location to resume at, which
+//! is kept with the other state of the machine in a context object. `try` statements are
+//! described to the runtime by their locations, and it dispatches exceptions and completions
+//! through `catch` and `finally` blocks.
+//! Variables and functions declared in the body are hoisted to the enclosing function, and the
+//! values of expressions which must survive a `yield` are kept in the context. Block scoped
+//! bindings are renamed first if their names are used by other variables. Loops whose block
+//! scoped bindings are captured by closures must be lowered by block scoping first, and are
+//! reported as errors otherwise. `for of` loops
+//! containing `yield` are compiled to calls of the iterator, without closing it on early exits.
+//! Async generators, generators and async functions using `super`, `for await` loops and `with`
+//! statements containing `yield` can't be lowered and are reported as errors.
+//!
+//! Transforms
+//! ```js
+//! function* f(x) {
+//!   var y = yield x;
+//!   return y + 1;
+//! }
+//! ```
+//! into
+//! ```js
+//! regeneratorRuntime.mark(f);
+//! function f(x) {
+//!   var y;
+//!   return regeneratorRuntime.wrap(function f$(_context) {
+//!     while (1) switch (_context.prev = _context.next) {
+//!       case 0:
+//!         _context.next = 2;
+//!         return x;
+//!       case 2:
+//!         y = _context.sent;
+//!         return _context.abrupt("return", y + 1);
+//!       case "end":
+//!         return _context.stop();
+//!     }
+//!   }, f, this);
+//! }
+//! ```
+
+use crate::passes::const_fold::{literal, Value};
+use crate::passes::util::*;
+use crate::Pass;
+use juno::ast::*;
+use juno::sema::{pattern_ids, resolve_program, BindingKind, Resolution, ScopeId, SemContext};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::convert::TryFrom;
+
+#[derive(Default)]
+pub struct Generators {}
+
+impl Generators {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Pass for Generators {
+    fn name(&self) -> &'static str {
+        "Lower generators"
+    }
+    fn description(&self) -> &'static str {
+        "Compiles generator and async functions to state machines"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let sem = resolve_program(gc, node);
+        let mut names = UniqueNames::new(gc, node);
+        let mut checker = CaptureChecker {
+            sem: &sem,
+            frames: vec![(false, Vec::new())],
+            scopes: HashMap::new(),
+            reported: HashSet::new(),
+        };
+        Visitor::call(&mut checker, gc, node, None);
+
+        let renames = renames(gc, &sem, &mut names);
+        let (node, renamed) = if renames.is_empty() {
+            (node, false)
+        } else {
+            match VisitorMut::call(&mut Renamer { renames: &renames }, gc, node, None) {
+                TransformResult::Changed(new_node) => (new_node, true),
+                TransformResult::Unchanged => (node, false),
+            }
+        };
+
+        let mut lowerer = Lowerer {
+            names,
+            marks: HashMap::new(),
+        };
+        match VisitorMut::call(&mut lowerer, gc, node, None) {
+            TransformResult::Unchanged if renamed => TransformResult::Changed(node),
+            result => result,
+        }
+    }
+}
+
+/// Return true if `node` is a function lowered by this pass.
+fn is_lowered(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::FunctionDeclaration(FunctionDeclaration {
+            generator: true,
+            ..
+        }) | Node::FunctionDeclaration(FunctionDeclaration { is_async: true, .. })
+            | Node::FunctionExpression(FunctionExpression {
+                generator: true,
+                ..
+            })
+            | Node::FunctionExpression(FunctionExpression { is_async: true, .. })
+            | Node::ArrowFunctionExpression(ArrowFunctionExpression { is_async: true, .. })
+    )
+}
+
+fn is_function(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::FunctionDeclaration(_)
+            | Node::FunctionExpression(_)
+            | Node::ArrowFunctionExpression(_)
+    )
+}
+
+fn is_loop(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::ForStatement(_)
+            | Node::ForInStatement(_)
+            | Node::ForOfStatement(_)
+            | Node::WhileStatement(_)
+            | Node::DoWhileStatement(_)
+    )
+}
+
+/// Return whether the binding `kind` declared in `scope` is hoisted to a lowered function from
+/// one of its blocks, where it may be shadowing or shadowed by another variable.
+fn is_hoisted_from_block(sem: &SemContext, kind: BindingKind, scope: ScopeId) -> bool {
+    let scope = sem.scope(scope);
+    matches!(
+        kind,
+        BindingKind::Let | BindingKind::Const | BindingKind::Class | BindingKind::CatchParam
+    ) && !scope.kind.is_var_scope()
+        && is_lowered(sem.scope(scope.var_scope).node)
+}
+
+/// Return the new names of the identifiers declaring and referencing the block scoped bindings
+/// of the lowered functions which must be renamed before being hoisted, because another
+/// variable has the same name.
+fn renames<'gc>(
+    gc: &'gc GCContext,
+    sem: &SemContext<'gc>,
+    names: &mut UniqueNames,
+) -> HashMap<NodeRef<'gc>, Atom> {
+    let mut used: HashSet<Atom> = sem
+        .bindings()
+        .filter(|(_, binding)| !is_hoisted_from_block(sem, binding.kind, binding.scope))
+        .map(|(_, binding)| binding.name)
+        .chain(sem.globals().map(|(name, _)| name))
+        .collect();
+    let mut result = HashMap::new();
+    for (_, binding) in sem.bindings() {
+        if !is_hoisted_from_block(sem, binding.kind, binding.scope) || used.insert(binding.name) {
+            continue;
+        }
+        let name = names.generate(gc, &format!("_{}", gc.str(binding.name)));
+        for &decl in &binding.declarations {
+            result.insert(NodeRef(decl), name);
+        }
+        for reference in &binding.references {
+            result.insert(NodeRef(reference.node), name);
+        }
+    }
+    result
+}
+
+/// Renames identifiers.
+struct Renamer<'a, 'gc> {
+    renames: &'a HashMap<NodeRef<'gc>, Atom>,
+}
+
+impl<'gc> VisitorMut<'gc> for Renamer<'_, 'gc> {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        _parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        match node {
+            Node::Identifier(ident) => match self.renames.get(&NodeRef(node)) {
+                Some(&name) => {
+                    let mut builder = IdentifierBuilder::from_node(ident);
+                    builder.name(name);
+                    builder.build(gc)
+                }
+                None => TransformResult::Unchanged,
+            },
+            // The key of a shorthand property is the same node as its value.
+            Node::Property(
+                prop @ Property {
+                    key: key @ Node::Identifier(Identifier { name, .. }),
+                    shorthand: true,
+                    ..
+                },
+            ) if self.renames.contains_key(&NodeRef(key)) => {
+                let key = identifier(gc, *name, *key.range());
+                let node = match node.visit_children_mut(NodeBuilder::from_node(node), gc, self) {
+                    TransformResult::Changed(Node::Property(new_prop)) => new_prop,
+                    _ => prop,
+                };
+                let mut builder = PropertyBuilder::from_node(node);
+                builder.key(key);
+                builder.shorthand(false);
+                builder.build(gc)
+            }
+            _ => node.visit_children_mut(NodeBuilder::from_node(node), gc, self),
+        }
+    }
+}
+
+/// Reports the loops of lowered functions whose block scoped bindings are captured by closures,
+/// which would share a single hoisted variable instead of one per iteration.
+struct CaptureChecker<'a, 'gc> {
+    sem: &'a SemContext<'gc>,
+    /// Whether each function being visited is lowered, with its loops being visited, innermost
+    /// last.
+    frames: Vec<(bool, Vec<&'gc Node<'gc>>)>,
+    /// Depth of the function of each visited scope of a lowered function, and innermost loop
+    /// containing it.
+    scopes: HashMap<ScopeId, (usize, Option<&'gc Node<'gc>>)>,
+    /// Loops which have been reported.
+    reported: HashSet<NodeRef<'gc>>,
+}
+
+impl<'gc> Visitor<'gc> for CaptureChecker<'_, 'gc> {
+    fn call(&mut self, gc: &'gc GCContext, node: &'gc Node<'gc>, _parent: Option<&'gc Node<'gc>>) {
+        if is_function(node) {
+            self.frames.push((is_lowered(node), Vec::new()));
+        }
+        if is_loop(node) {
+            self.frames.last_mut().unwrap().1.push(node);
+        }
+        if let Some(scope) = self.sem.node_scope(node) {
+            let (lowered, loops) = self.frames.last().unwrap();
+            if *lowered {
+                self.scopes
+                    .insert(scope, (self.frames.len(), loops.last().copied()));
+            }
+        }
+        if let Some(Resolution::Binding(id)) = self.sem.resolution(node) {
+            let binding = self.sem.binding(id);
+            if let (
+                BindingKind::Let | BindingKind::Const | BindingKind::Class,
+                Some(&(depth, Some(owner))),
+            ) = (binding.kind, self.scopes.get(&binding.scope))
+            {
+                if self.frames.len() > depth && self.reported.insert(NodeRef(owner)) {
+                    gc.sm().error(
+                        *owner.range(),
+                        "loops of generators and async functions capturing their variables must be lowered by BlockScoping first",
+                    );
+                }
+            }
+        }
+        node.visit_children(gc, self);
+        if is_loop(node) {
+            self.frames.last_mut().unwrap().1.pop();
+        }
+        if is_function(node) {
+            self.frames.pop();
+        }
+    }
+}
+
+struct Lowerer<'gc> {
+    names: UniqueNames,
+    /// Statements marking the lowered generator function declarations, inserted at the top of
+    /// the statement lists containing them so that they are marked before being called.
+    marks: HashMap<NodeRef<'gc>, &'gc Node<'gc>>,
+}
+
+impl<'gc> VisitorMut<'gc> for Lowerer<'gc> {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let is_method = parent.is_some_and(|parent| is_method_of(parent, node));
+        let (node, changed) = match node.visit_children_mut(NodeBuilder::from_node(node), gc, self)
+        {
+            TransformResult::Changed(new_node) => (new_node, true),
+            TransformResult::Unchanged => (node, false),
+        };
+        let lowered = match node {
+            Node::FunctionDeclaration(FunctionDeclaration {
+                generator,
+                is_async,
+                ..
+            })
+            | Node::FunctionExpression(FunctionExpression {
+                generator,
+                is_async,
+                ..
+            }) if *generator || *is_async => {
+                if *generator && *is_async {
+                    gc.sm()
+                        .error(*node.range(), "async generators can't be lowered");
+                    None
+                } else {
+                    self.lower_function(gc, node, is_method)
+                }
+            }
+            Node::ArrowFunctionExpression(ArrowFunctionExpression { is_async: true, .. }) => {
+                self.lower_function(gc, node, false)
+            }
+            _ => self.insert_marks(gc, node),
+        };
+        match lowered {
+            Some(new_node) => TransformResult::Changed(new_node),
+            None if changed => TransformResult::Changed(node),
+            None => TransformResult::Unchanged,
+        }
+    }
+}
+
+impl<'gc> Lowerer<'gc> {
+    /// Return the generator or async function `node` compiled to a function running a state
+    /// machine, or `None` if it can't be lowered, which is reported.
+    fn lower_function(
+        &mut self,
+        gc: &'gc GCContext,
+        node: &'gc Node<'gc>,
+        is_method: bool,
+    ) -> Option<&'gc Node<'gc>> {
+        let (id, body, is_generator) = match node {
+            Node::FunctionDeclaration(FunctionDeclaration {
+                id,
+                body,
+                generator,
+                ..
+            })
+            | Node::FunctionExpression(FunctionExpression {
+                id,
+                body,
+                generator,
+                ..
+            }) => (*id, *body, *generator),
+            Node::ArrowFunctionExpression(ArrowFunctionExpression { body, .. }) => {
+                (None, *body, false)
+            }
+            _ => unreachable!("invalid function {}", node.name()),
+        };
+        let mut finder = UnsupportedFinder {
+            found: None,
+            arrows: 0,
+        };
+        finder.call(gc, body, None);
+        if let Some((range, message)) = finder.found {
+            gc.sm().error(range, message);
+            return None;
+        }
+
+        let range = *body.range();
+        let (directives, stmts) = match body {
+            Node::BlockStatement(BlockStatement { body, .. }) => {
+                let count = directive_count(body);
+                (body[..count].to_vec(), body[count..].to_vec())
+            }
+            _ => (vec![], vec![return_statement(gc, Some(body), range)]),
+        };
+
+        let mut hoister = Hoister {
+            names: &mut self.names,
+            vars: Vec::new(),
+            functions: Vec::new(),
+            arguments: None,
+            removed: HashSet::new(),
+            arrows: 0,
+        };
+        let stmts: NodeList<'gc> = stmts
+            .into_iter()
+            .filter_map(|stmt| hoister.hoist(gc, stmt))
+            .collect();
+        let Hoister {
+            vars,
+            functions,
+            arguments,
+            ..
+        } = hoister;
+
+        let context = self.names.generate(gc, "_context");
+        let mut emitter = Emitter {
+            names: &mut self.names,
+            context,
+            listing: Vec::new(),
+            marks: HashMap::new(),
+            try_entries: Vec::new(),
+            targets: Vec::new(),
+            temps: 0,
+        };
+        for stmt in stmts {
+            emitter.explode_statement(gc, stmt, None);
+        }
+        let (cases, try_locs) = emitter.finish(gc, range);
+
+        // function f$(_context) { while (1) switch (_context.prev = _context.next) {...} }
+        let context_member =
+            |name: &str| member(gc, identifier(gc, context, range), gc.atom(name), range);
+        let switch = SwitchStatementBuilder::build_template(
+            gc,
+            SwitchStatementTemplate {
+                metadata: template_metadata(range),
+                discriminant: assign(gc, context_member("prev"), context_member("next"), range),
+                cases,
+            },
+        );
+        let machine = WhileStatementBuilder::build_template(
+            gc,
+            WhileStatementTemplate {
+                metadata: template_metadata(range),
+                body: switch,
+                test: number(gc, 1.0, range),
+            },
+        );
+        let name = match id {
+            Some(Node::Identifier(Identifier { name, .. })) => Some(*name),
+            _ => None,
+        };
+        let base = match name {
+            Some(name) => gc.str(name).to_string(),
+            None => "_callee".to_string(),
+        };
+        let inner_name = self.names.generate(gc, &format!("{}$", base));
+        let mut inner = FunctionExpressionBuilder::from_node(
+            match function(
+                gc,
+                vec![identifier(gc, context, range)],
+                vec![machine],
+                range,
+            ) {
+                Node::FunctionExpression(func) => func,
+                _ => unreachable!(),
+            },
+        );
+        inner.id(Some(identifier(gc, inner_name, range)));
+        let inner = inner.build(gc).unwrap();
+
+        // The generator function itself is passed to the runtime to be the constructor of the
+        // generator objects, except for methods which can't refer to themselves.
+        let outer_name = if is_generator && !is_method {
+            Some(name.unwrap_or_else(|| self.names.generate(gc, "_callee")))
+        } else {
+            None
+        };
+        let mut args = vec![
+            inner,
+            match outer_name {
+                Some(outer_name) => identifier(gc, outer_name, range),
+                None => literal(gc, Value::Null, range).unwrap(),
+            },
+            this_expression(gc, range),
+        ];
+        if !try_locs.is_empty() {
+            args.push(array(gc, try_locs, range));
+        }
+        let run = call(
+            gc,
+            dotted_name(
+                gc,
+                if is_generator {
+                    "regeneratorRuntime.wrap"
+                } else {
+                    "regeneratorRuntime.async"
+                },
+                range,
+            ),
+            args,
+            range,
+        );
+
+        let mut new_body = directives;
+        let mut declarators: Vec<(&'gc Node<'gc>, Option<&'gc Node<'gc>>)> = vars
+            .into_iter()
+            .map(|var| (identifier(gc, var, range), None))
+            .collect();
+        if let Some(arguments) = arguments {
+            declarators.push((
+                identifier(gc, arguments, range),
+                Some(identifier(gc, gc.atom("arguments"), range)),
+            ));
+        }
+        if !declarators.is_empty() {
+            new_body.push(declaration(
+                gc,
+                VariableDeclarationKind::Var,
+                declarators,
+                range,
+            ));
+        }
+        new_body.extend(functions);
+        new_body.push(return_statement(gc, Some(run), range));
+        let new_body = block(gc, new_body, range);
+
+        let mark = |function| {
+            call(
+                gc,
+                dotted_name(gc, "regeneratorRuntime.mark", range),
+                vec![function],
+                range,
+            )
+        };
+        Some(match node {
+            Node::FunctionDeclaration(func) => {
+                let mut builder = FunctionDeclarationBuilder::from_node(func);
+                builder.body(new_body);
+                builder.generator(false);
+                builder.is_async(false);
+                if let Some(outer_name) = outer_name {
+                    builder.id(Some(identifier(gc, outer_name, *node.range())));
+                }
+                let new_node = builder.build(gc).unwrap();
+                if let Some(outer_name) = outer_name {
+                    self.marks.insert(
+                        NodeRef(new_node),
+                        expression_statement(gc, mark(identifier(gc, outer_name, range))),
+                    );
+                }
+                new_node
+            }
+            Node::FunctionExpression(func) => {
+                let mut builder = FunctionExpressionBuilder::from_node(func);
+                builder.body(new_body);
+                builder.generator(false);
+                builder.is_async(false);
+                if let Some(outer_name) = outer_name {
+                    builder.id(Some(identifier(gc, outer_name, *node.range())));
+                }
+                let new_node = builder.build(gc).unwrap();
+                match outer_name {
+                    Some(_) => mark(new_node),
+                    None => new_node,
+                }
+            }
+            Node::ArrowFunctionExpression(func) => {
+                let mut builder = ArrowFunctionExpressionBuilder::from_node(func);
+                builder.body(new_body);
+                builder.expression(false);
+                builder.is_async(false);
+                builder.build(gc).unwrap()
+            }
+            _ => unreachable!("invalid function {}", node.name()),
+        })
+    }
+
+    /// Return `node` with the marks of the generator functions declared in its statement list
+    /// inserted at the top, or `None` if it doesn't declare any.
+    fn insert_marks(&mut self, gc: &'gc GCContext, node: &'gc Node<'gc>) -> Option<&'gc Node<'gc>> {
+        if self.marks.is_empty() {
+            return None;
+        }
+        let list = statement_list(node)?;
+        let marks: NodeList<'gc> = list
+            .iter()
+            .filter_map(|stmt| {
+                let decl = match stmt {
+                    Node::ExportNamedDeclaration(ExportNamedDeclaration {
+                        declaration: Some(decl),
+                        ..
+                    })
+                    | Node::ExportDefaultDeclaration(ExportDefaultDeclaration {
+                        declaration: decl,
+                        ..
+                    }) => decl,
+                    _ => stmt,
+                };
+                self.marks.remove(&NodeRef(decl))
+            })
+            .collect();
+        if marks.is_empty() {
+            return None;
+        }
+        let directives = directive_count(list);
+        let mut new_list = list[..directives].to_vec();
+        new_list.extend(marks);
+        new_list.extend_from_slice(&list[directives..]);
+        Some(with_statement_list(gc, node, new_list))
+    }
+}
+
+/// Hoists the declarations of the body of a generator or async function to the function, so
+/// that they are shared by all the cases of the state machine.
+/// Variable, `let`, `const` and class declarations are replaced by assignments, function
+/// declarations are moved to the function, and `arguments` is replaced by a variable.
+struct Hoister<'n, 'gc> {
+    names: &'n mut UniqueNames,
+    /// Names of the hoisted variables.
+    vars: Vec<Atom>,
+    /// Hoisted function declarations.
+    functions: NodeList<'gc>,
+    /// Name of the variable holding `arguments`, if it is used.
+    arguments: Option<Atom>,
+    /// Empty statements replacing the removed declarations, to be dropped from statement lists.
+    removed: HashSet<NodeRef<'gc>>,
+    /// Depth of the arrow functions being visited, which have their own declarations.
+    arrows: usize,
+}
+
+impl<'n, 'gc> Hoister<'n, 'gc> {
+    /// Return the statement `stmt` with its declarations hoisted, or `None` if nothing is left.
+    fn hoist(&mut self, gc: &'gc GCContext, stmt: &'gc Node<'gc>) -> Option<&'gc Node<'gc>> {
+        let stmt = match self.call(gc, stmt, None) {
+            TransformResult::Changed(new_stmt) => new_stmt,
+            TransformResult::Unchanged => stmt,
+        };
+        if self.removed.contains(&NodeRef(stmt)) {
+            None
+        } else {
+            Some(stmt)
+        }
+    }
+
+    fn declare(&mut self, pattern: &'gc Node<'gc>) {
+        let mut ids = Vec::new();
+        pattern_ids(pattern, &mut ids);
+        for id in ids {
+            if let Node::Identifier(Identifier { name, .. }) = id {
+                if !self.vars.contains(name) {
+                    self.vars.push(*name);
+                }
+            }
+        }
+    }
+
+    /// Return an empty statement, removed from the statement list containing it.
+    fn removed(&mut self, gc: &'gc GCContext, range: SourceRange) -> &'gc Node<'gc> {
+        let empty = EmptyStatementBuilder::build_template(
+            gc,
+            EmptyStatementTemplate {
+                metadata: template_metadata(range),
+            },
+        );
+        self.removed.insert(NodeRef(empty));
+        empty
+    }
+}
+
+impl<'n, 'gc> VisitorMut<'gc> for Hoister<'n, 'gc> {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let range = *node.range();
+        match node {
+            Node::FunctionExpression(_) => return TransformResult::Unchanged,
+            Node::FunctionDeclaration(_) => {
+                if self.arrows > 0 {
+                    return TransformResult::Unchanged;
+                }
+                self.functions.push(node);
+                return TransformResult::Changed(self.removed(gc, range));
+            }
+            Node::Identifier(Identifier { name, .. })
+                if gc.str(*name) == "arguments" && !is_property_name(node, parent) =>
+            {
+                let names = &mut self.names;
+                let arguments = *self
+                    .arguments
+                    .get_or_insert_with(|| names.generate(gc, "_args"));
+                return TransformResult::Changed(identifier(gc, arguments, range));
+            }
+            _ => {}
+        }
+
+        let original = node;
+        let is_arrow = matches!(node, Node::ArrowFunctionExpression(_));
+        if is_arrow {
+            self.arrows += 1;
+        }
+        let (node, changed) = match node.visit_children_mut(NodeBuilder::from_node(node), gc, self)
+        {
+            TransformResult::Changed(new_node) => (new_node, true),
+            TransformResult::Unchanged => (node, false),
+        };
+        if is_arrow {
+            self.arrows -= 1;
+        }
+        if self.arrows > 0 {
+            return if changed {
+                TransformResult::Changed(node)
+            } else {
+                TransformResult::Unchanged
+            };
+        }
+
+        let hoisted = match node {
+            Node::VariableDeclaration(VariableDeclaration {
+                kind, declarations, ..
+            }) => {
+                let mut assignments = NodeList::new();
+                for decl in declarations {
+                    if let Node::VariableDeclarator(VariableDeclarator { id, init, .. }) = decl {
+                        self.declare(id);
+                        match init {
+                            Some(init) => assignments.push(assign(gc, id, init, *decl.range())),
+                            // `let` and `const` declarations in loops are reinitialized.
+                            None if *kind != VariableDeclarationKind::Var => {
+                                assignments.push(assign(gc, id, undefined(gc, range), range))
+                            }
+                            None => {}
+                        }
+                    }
+                }
+                Some(match parent {
+                    Some(Node::ForInStatement(ForInStatement { left, .. }))
+                    | Some(Node::ForOfStatement(ForOfStatement { left, .. }))
+                        if std::ptr::eq(*left, original) =>
+                    {
+                        match declarations[0] {
+                            Node::VariableDeclarator(VariableDeclarator { id, .. }) => *id,
+                            _ => unreachable!("invalid declarator"),
+                        }
+                    }
+                    Some(Node::ForStatement(ForStatement {
+                        init: Some(init), ..
+                    })) if std::ptr::eq(*init, original) && !assignments.is_empty() => {
+                        sequence(gc, assignments, range)
+                    }
+                    _ if assignments.is_empty() => self.removed(gc, range),
+                    _ => expression_statement(gc, sequence(gc, assignments, range)),
+                })
+            }
+            Node::ClassDeclaration(class) => {
+                let id = class.id.expect("class declaration without a name");
+                self.declare(id);
+                let expression = ClassExpressionBuilder::build_template(
+                    gc,
+                    ClassExpressionTemplate {
+                        metadata: template_metadata(range),
+                        id: class.id,
+                        type_parameters: class.type_parameters,
+                        super_class: class.super_class,
+                        super_type_parameters: class.super_type_parameters,
+                        implements: class.implements.clone(),
+                        decorators: class.decorators.clone(),
+                        body: class.body,
+                    },
+                );
+                let name = match id {
+                    Node::Identifier(Identifier { name, .. }) => *name,
+                    _ => unreachable!("invalid class name"),
+                };
+                Some(expression_statement(
+                    gc,
+                    assign(gc, identifier(gc, name, range), expression, range),
+                ))
+            }
+            Node::CatchClause(CatchClause {
+                param: Some(param), ..
+            }) => {
+                self.declare(param);
+                None
+            }
+            Node::ForStatement(
+                for_stmt @ ForStatement {
+                    init: Some(init), ..
+                },
+            ) if self.removed.contains(&NodeRef(init)) => {
+                let mut builder = ForStatementBuilder::from_node(for_stmt);
+                builder.init(None);
+                Some(builder.build(gc).unwrap())
+            }
+            _ => statement_list(node).and_then(|list| {
+                if list
+                    .iter()
+                    .any(|stmt| self.removed.contains(&NodeRef(stmt)))
+                {
+                    let list = list
+                        .iter()
+                        .copied()
+                        .filter(|stmt| !self.removed.contains(&NodeRef(stmt)))
+                        .collect();
+                    Some(with_statement_list(gc, node, list))
+                } else {
+                    None
+                }
+            }),
+        };
+        match hoisted {
+            Some(new_node) => TransformResult::Changed(new_node),
+            None if changed => TransformResult::Changed(node),
+            None => TransformResult::Unchanged,
+        }
+    }
+}
+
+/// A `try` statement, described to the runtime by its locations.
+struct TryEntry {
+    try_loc: Atom,
+    catch_loc: Option<Atom>,
+    finally_loc: Option<Atom>,
+    after_loc: Atom,
+}
+
+/// A statement which can be the target of `break` or `continue`.
+struct JumpTarget {
+    label: Option<Atom>,
+    break_loc: Atom,
+    continue_loc: Option<Atom>,
+    /// Whether `break` without a label targets the statement: it is a loop or a `switch`.
+    unlabeled: bool,
+}
+
+/// Emits the listing of the state machine.
+/// Locations in the listing are referred to by identifiers with unique names, which are
+/// replaced by the index of the statement they mark once the listing is complete.
+struct Emitter<'n, 'gc> {
+    names: &'n mut UniqueNames,
+    /// Name of the context parameter of the state machine.
+    context: Atom,
+    listing: NodeList<'gc>,
+    /// Index in the listing of each marked location.
+    marks: HashMap<Atom, usize>,
+    try_entries: Vec<TryEntry>,
+    /// Enclosing jump targets, innermost last.
+    targets: Vec<JumpTarget>,
+    /// Number of temporaries stored in the context.
+    temps: usize,
+}
+
+impl<'n, 'gc> Emitter<'n, 'gc> {
+    /// Return the cases of the `switch` of the state machine, and the list of the locations of
+    /// the `try` statements.
+    fn finish(&mut self, gc: &'gc GCContext, range: SourceRange) -> (NodeList<'gc>, NodeList<'gc>) {
+        let mut positions: BTreeSet<usize> = self.marks.values().copied().collect();
+        positions.insert(0);
+        let positions: Vec<usize> = positions.into_iter().collect();
+        let mut cases = NodeList::new();
+        for (i, &start) in positions.iter().enumerate() {
+            let end = positions.get(i + 1).copied().unwrap_or(self.listing.len());
+            cases.push(switch_case(
+                gc,
+                number(gc, start as f64, range),
+                self.listing[start..end].to_vec(),
+                range,
+            ));
+        }
+        cases.push(switch_case(
+            gc,
+            string(gc, "end", range),
+            vec![return_statement(
+                gc,
+                Some(call(
+                    gc,
+                    self.context_member(gc, "stop", range),
+                    vec![],
+                    range,
+                )),
+                range,
+            )],
+            range,
+        ));
+        let mut replacer = LocReplacer { marks: &self.marks };
+        let cases = cases
+            .into_iter()
+            .map(|case| match replacer.call(gc, case, None) {
+                TransformResult::Changed(new_case) => new_case,
+                TransformResult::Unchanged => case,
+            })
+            .collect();
+
+        let position = |loc: Atom| number(gc, self.marks[&loc] as f64, range);
+        let try_locs = self
+            .try_entries
+            .iter()
+            .map(|entry| {
+                let mut locs = vec![position(entry.try_loc)];
+                if let Some(catch_loc) = entry.catch_loc {
+                    locs.push(position(catch_loc));
+                }
+                if let Some(finally_loc) = entry.finally_loc {
+                    // A missing `catch` is a null location, which is never entered.
+                    if entry.catch_loc.is_none() {
+                        locs.push(literal(gc, Value::Null, range).unwrap());
+                    }
+                    locs.push(position(finally_loc));
+                    locs.push(position(entry.after_loc));
+                }
+                array(gc, locs, range)
+            })
+            .collect();
+        (cases, try_locs)
+    }
+
+    /// Return a new location, to be marked.
+    fn loc(&mut self, gc: &'gc GCContext) -> Atom {
+        self.names.generate(gc, "_loc")
+    }
+
+    /// Mark `loc` at the current end of the listing.
+    fn mark(&mut self, loc: Atom) {
+        self.marks.insert(loc, self.listing.len());
+    }
+
+    fn emit(&mut self, stmt: &'gc Node<'gc>) {
+        self.listing.push(stmt);
+    }
+
+    fn emit_assign(&mut self, gc: &'gc GCContext, left: &'gc Node<'gc>, right: &'gc Node<'gc>) {
+        let range = *right.range();
+        self.emit(expression_statement(gc, assign(gc, left, right, range)));
+    }
+
+    /// Return `_context.name`.
+    fn context_member(&self, gc: &'gc GCContext, name: &str, range: SourceRange) -> &'gc Node<'gc> {
+        member(
+            gc,
+            identifier(gc, self.context, range),
+            gc.atom(name),
+            range,
+        )
+    }
+
+    /// Return a new temporary stored in the context.
+    fn temp(&mut self) -> usize {
+        self.temps += 1;
+        self.temps - 1
+    }
+
+    fn temp_node(&self, gc: &'gc GCContext, temp: usize, range: SourceRange) -> &'gc Node<'gc> {
+        self.context_member(gc, &format!("t{}", temp), range)
+    }
+
+    /// Store `value` in a new temporary and return it.
+    fn store(&mut self, gc: &'gc GCContext, value: &'gc Node<'gc>) -> usize {
+        let temp = self.temp();
+        let range = *value.range();
+        self.emit_assign(gc, self.temp_node(gc, temp, range), value);
+        temp
+    }
+
+    fn loc_node(&self, gc: &'gc GCContext, loc: Atom, range: SourceRange) -> &'gc Node<'gc> {
+        identifier(gc, loc, range)
+    }
+
+    /// Emit `_context.next = target; break;`.
+    fn jump_to(&mut self, gc: &'gc GCContext, target: &'gc Node<'gc>) {
+        let range = *target.range();
+        self.emit_assign(gc, self.context_member(gc, "next", range), target);
+        self.emit(break_statement(gc, range));
+    }
+
+    fn jump(&mut self, gc: &'gc GCContext, loc: Atom, range: SourceRange) {
+        self.jump_to(gc, self.loc_node(gc, loc, range));
+    }
+
+    /// Jump to `loc` if `test` is true.
+    fn jump_if(&mut self, gc: &'gc GCContext, test: &'gc Node<'gc>, loc: Atom) {
+        let range = *test.range();
+        let jump = vec![
+            expression_statement(
+                gc,
+                assign(
+                    gc,
+                    self.context_member(gc, "next", range),
+                    self.loc_node(gc, loc, range),
+                    range,
+                ),
+            ),
+            break_statement(gc, range),
+        ];
+        self.emit(if_statement(gc, test, block(gc, jump, range), range));
+    }
+
+    /// Jump to `loc` if `test` is false.
+    fn jump_if_not(&mut self, gc: &'gc GCContext, test: &'gc Node<'gc>, loc: Atom) {
+        self.jump_if(gc, not(gc, test), loc);
+    }
+
+    /// Emit `return _context.abrupt(kind, arg);`, completing with the runtime, which runs the
+    /// enclosing `finally` blocks.
+    fn abrupt(
+        &mut self,
+        gc: &'gc GCContext,
+        kind: &str,
+        arg: Option<&'gc Node<'gc>>,
+        range: SourceRange,
+    ) {
+        let mut args = vec![string(gc, kind, range)];
+        args.extend(arg);
+        let abrupt = call(gc, self.context_member(gc, "abrupt", range), args, range);
+        self.emit(return_statement(gc, Some(abrupt), range));
+    }
+
+    /// Emit `_context.prev = loc;`, the location used by the runtime to find the enclosing `try`
+    /// statements, when falling through into `loc`.
+    fn update_prev(&mut self, gc: &'gc GCContext, loc: Atom, range: SourceRange) {
+        self.emit_assign(
+            gc,
+            self.context_member(gc, "prev", range),
+            self.loc_node(gc, loc, range),
+        );
+    }
+
+    /// Return the location which `break` with `label` jumps to.
+    fn break_loc(&self, label: Option<&Node>) -> Option<Atom> {
+        let target = match label {
+            Some(Node::Identifier(Identifier { name, .. })) => self
+                .targets
+                .iter()
+                .rev()
+                .find(|target| target.label == Some(*name)),
+            _ => self.targets.iter().rev().find(|target| target.unlabeled),
+        };
+        target.map(|target| target.break_loc)
+    }
+
+    /// Return the location which `continue` with `label` jumps to.
+    fn continue_loc(&self, label: Option<&Node>) -> Option<Atom> {
+        match label {
+            Some(Node::Identifier(Identifier { name, .. })) => self
+                .targets
+                .iter()
+                .rev()
+                .find(|target| target.label == Some(*name))
+                .and_then(|target| target.continue_loc),
+            _ => self
+                .targets
+                .iter()
+                .rev()
+                .find_map(|target| target.continue_loc),
+        }
+    }
+
+    /// Explode the body of a loop, which `break` and `continue` jump out of to `break_loc` and
+    /// `continue_loc`.
+    fn explode_loop_body(
+        &mut self,
+        gc: &'gc GCContext,
+        body: &'gc Node<'gc>,
+        label: Option<Atom>,
+        break_loc: Atom,
+        continue_loc: Atom,
+    ) {
+        self.targets.push(JumpTarget {
+            label,
+            break_loc,
+            continue_loc: Some(continue_loc),
+            unlabeled: true,
+        });
+        self.explode_statement(gc, body, None);
+        self.targets.pop();
+    }
+
+    /// Emit the statement `stmt`, split at each `yield` and jump it contains, labeled with
+    /// `label` if it is a loop.
+    fn explode_statement(&mut self, gc: &'gc GCContext, stmt: &'gc Node<'gc>, label: Option<Atom>) {
+        // Blocks are flattened, their declarations having been hoisted.
+        if !contains_leap(gc, stmt) && !matches!(stmt, Node::BlockStatement(_)) {
+            self.emit(stmt);
+            return;
+        }
+        let range = *stmt.range();
+        match stmt {
+            Node::BlockStatement(BlockStatement { body, .. }) => {
+                for stmt in body {
+                    self.explode_statement(gc, stmt, None);
+                }
+            }
+            Node::ExpressionStatement(ExpressionStatement { expression, .. }) => {
+                self.explode_expression(gc, expression, true);
+            }
+            Node::LabeledStatement(LabeledStatement {
+                label: Node::Identifier(Identifier { name, .. }),
+                body,
+                ..
+            }) => match body {
+                Node::WhileStatement(_)
+                | Node::DoWhileStatement(_)
+                | Node::ForStatement(_)
+                | Node::ForInStatement(_)
+                | Node::ForOfStatement(_) => self.explode_statement(gc, body, Some(*name)),
+                _ => {
+                    let after = self.loc(gc);
+                    self.targets.push(JumpTarget {
+                        label: Some(*name),
+                        break_loc: after,
+                        continue_loc: None,
+                        unlabeled: false,
+                    });
+                    self.explode_statement(gc, body, None);
+                    self.targets.pop();
+                    self.mark(after);
+                }
+            },
+            Node::WhileStatement(WhileStatement { body, test, .. }) => {
+                let before = self.loc(gc);
+                let after = self.loc(gc);
+                self.mark(before);
+                let test = self.explode_value(gc, test);
+                self.jump_if_not(gc, test, after);
+                self.explode_loop_body(gc, body, label, after, before);
+                self.jump(gc, before, range);
+                self.mark(after);
+            }
+            Node::DoWhileStatement(DoWhileStatement { body, test, .. }) => {
+                let first = self.loc(gc);
+                let test_loc = self.loc(gc);
+                let after = self.loc(gc);
+                self.mark(first);
+                self.explode_loop_body(gc, body, label, after, test_loc);
+                self.mark(test_loc);
+                let test = self.explode_value(gc, test);
+                self.jump_if(gc, test, first);
+                self.mark(after);
+            }
+            Node::ForStatement(ForStatement {
+                init,
+                test,
+                update,
+                body,
+                ..
+            }) => {
+                if let Some(init) = init {
+                    self.explode_expression(gc, init, true);
+                }
+                let head = self.loc(gc);
+                let update_loc = self.loc(gc);
+                let after = self.loc(gc);
+                self.mark(head);
+                if let Some(test) = test {
+                    let test = self.explode_value(gc, test);
+                    self.jump_if_not(gc, test, after);
+                }
+                self.explode_loop_body(gc, body, label, after, update_loc);
+                self.mark(update_loc);
+                if let Some(update) = update {
+                    self.explode_expression(gc, update, true);
+                }
+                self.jump(gc, head, range);
+                self.mark(after);
+            }
+            Node::ForInStatement(ForInStatement {
+                left, right, body, ..
+            }) => {
+                // The runtime returns a function returning the keys of the object.
+                let right = self.explode_value(gc, right);
+                let keys = self.store(
+                    gc,
+                    call(
+                        gc,
+                        dotted_name(gc, "regeneratorRuntime.keys", range),
+                        vec![right],
+                        range,
+                    ),
+                );
+                let next = call(gc, self.temp_node(gc, keys, range), vec![], range);
+                self.explode_iteration(gc, left, next, body, label, range);
+            }
+            Node::ForOfStatement(ForOfStatement {
+                left, right, body, ..
+            }) => {
+                let right = self.explode_value(gc, right);
+                let iterator = call(
+                    gc,
+                    computed_member(gc, right, dotted_name(gc, "Symbol.iterator", range), range),
+                    vec![],
+                    range,
+                );
+                let iterator = self.store(gc, iterator);
+                let next = call(
+                    gc,
+                    member(
+                        gc,
+                        self.temp_node(gc, iterator, range),
+                        gc.atom("next"),
+                        range,
+                    ),
+                    vec![],
+                    range,
+                );
+                self.explode_iteration(gc, left, next, body, label, range);
+            }
+            Node::BreakStatement(BreakStatement { label, .. }) => {
+                let loc = self.break_loc(*label).expect("break without a target");
+                self.abrupt(gc, "break", Some(self.loc_node(gc, loc, range)), range);
+            }
+            Node::ContinueStatement(ContinueStatement { label, .. }) => {
+                let loc = self
+                    .continue_loc(*label)
+                    .expect("continue without a target");
+                self.abrupt(gc, "continue", Some(self.loc_node(gc, loc, range)), range);
+            }
+            Node::ReturnStatement(ReturnStatement { argument, .. }) => {
+                let argument = argument.map(|argument| self.explode_value(gc, argument));
+                self.abrupt(gc, "return", argument, range);
+            }
+            Node::ThrowStatement(throw @ ThrowStatement { argument, .. }) => {
+                let argument = self.explode_value(gc, argument);
+                let mut builder = ThrowStatementBuilder::from_node(throw);
+                builder.argument(argument);
+                self.emit(builder.build(gc).unwrap());
+            }
+            Node::IfStatement(IfStatement {
+                test,
+                consequent,
+                alternate,
+                ..
+            }) => {
+                let test = self.explode_value(gc, test);
+                let else_loc = alternate.map(|_| self.loc(gc));
+                let after = self.loc(gc);
+                self.jump_if_not(gc, test, else_loc.unwrap_or(after));
+                self.explode_statement(gc, consequent, None);
+                if let (Some(alternate), Some(else_loc)) = (alternate, else_loc) {
+                    self.jump(gc, after, range);
+                    self.mark(else_loc);
+                    self.explode_statement(gc, alternate, None);
+                }
+                self.mark(after);
+            }
+            Node::SwitchStatement(SwitchStatement {
+                discriminant,
+                cases,
+                ..
+            }) => self.explode_switch(gc, discriminant, cases, label, range),
+            Node::TryStatement(TryStatement {
+                block,
+                handler,
+                finalizer,
+                ..
+            }) => self.explode_try(gc, block, *handler, *finalizer, range),
+            _ => {
+                gc.sm().error(
+                    range,
+                    format!(
+                        "{} in generators and async functions can't be lowered",
+                        stmt.name()
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Emit a loop assigning the `value` of the results of `next` to `left` until they are
+    /// `done`.
+    fn explode_iteration(
+        &mut self,
+        gc: &'gc GCContext,
+        left: &'gc Node<'gc>,
+        next: &'gc Node<'gc>,
+        body: &'gc Node<'gc>,
+        label: Option<Atom>,
+        range: SourceRange,
+    ) {
+        let head = self.loc(gc);
+        let after = self.loc(gc);
+        self.mark(head);
+        let result = self.temp();
+        let step = assign(gc, self.temp_node(gc, result, range), next, range);
+        self.jump_if(gc, member(gc, step, gc.atom("done"), range), after);
+        let value = member(
+            gc,
+            self.temp_node(gc, result, range),
+            gc.atom("value"),
+            range,
+        );
+        self.emit_assign(gc, left, value);
+        self.explode_loop_body(gc, body, label, after, head);
+        self.jump(gc, head, range);
+        self.mark(after);
+    }
+
+    fn explode_switch(
+        &mut self,
+        gc: &'gc GCContext,
+        discriminant: &'gc Node<'gc>,
+        cases: &'gc [&'gc Node<'gc>],
+        label: Option<Atom>,
+        range: SourceRange,
+    ) {
+        let tests: Vec<Option<&'gc Node<'gc>>> = cases
+            .iter()
+            .map(|case| match case {
+                Node::SwitchCase(SwitchCase { test, .. }) => *test,
+                _ => unreachable!("invalid switch case {}", case.name()),
+            })
+            .collect();
+        if let Some(test) = tests.iter().flatten().find(|test| contains_leap(gc, test)) {
+            gc.sm().error(
+                *test.range(),
+                "switch cases with tests containing 'yield' can't be lowered",
+            );
+            return;
+        }
+        let discriminant = self.explode_value(gc, discriminant);
+        let discriminant = self.store(gc, discriminant);
+        let after = self.loc(gc);
+        let case_locs: Vec<Atom> = cases.iter().map(|_| self.loc(gc)).collect();
+
+        // Jump to the location of the first matching case, or to the default one.
+        let default = tests.iter().position(|test| test.is_none());
+        let mut target = self.loc_node(gc, default.map_or(after, |i| case_locs[i]), range);
+        for (test, &loc) in tests.iter().zip(&case_locs).rev() {
+            if let Some(test) = test {
+                let matches = binary(
+                    gc,
+                    BinaryExpressionOperator::StrictEquals,
+                    self.temp_node(gc, discriminant, range),
+                    test,
+                    range,
+                );
+                target = conditional(gc, matches, self.loc_node(gc, loc, range), target, range);
+            }
+        }
+        self.jump_to(gc, target);
+
+        self.targets.push(JumpTarget {
+            label,
+            break_loc: after,
+            continue_loc: None,
+            unlabeled: true,
+        });
+        for (case, &loc) in cases.iter().zip(&case_locs) {
+            self.mark(loc);
+            if let Node::SwitchCase(SwitchCase { consequent, .. }) = case {
+                for stmt in consequent {
+                    self.explode_statement(gc, stmt, None);
+                }
+            }
+        }
+        self.targets.pop();
+        self.mark(after);
+    }
+
+    fn explode_try(
+        &mut self,
+        gc: &'gc GCContext,
+        block: &'gc Node<'gc>,
+        handler: Option<&'gc Node<'gc>>,
+        finalizer: Option<&'gc Node<'gc>>,
+        range: SourceRange,
+    ) {
+        let try_loc = self.loc(gc);
+        let catch_loc = handler.map(|_| self.loc(gc));
+        let finally_loc = finalizer.map(|_| self.loc(gc));
+        let after = self.loc(gc);
+        self.try_entries.push(TryEntry {
+            try_loc,
+            catch_loc,
+            finally_loc,
+            after_loc: after,
+        });
+
+        self.mark(try_loc);
+        self.update_prev(gc, try_loc, range);
+        self.explode_statement(gc, block, None);
+        if let (Some(Node::CatchClause(CatchClause { param, body, .. })), Some(catch_loc)) =
+            (handler, catch_loc)
+        {
+            self.jump(gc, finally_loc.unwrap_or(after), range);
+            self.mark(catch_loc);
+            self.update_prev(gc, catch_loc, range);
+            // The runtime returns the exception thrown in the `try` block.
+            let exception = call(
+                gc,
+                self.context_member(gc, "catch", range),
+                vec![self.loc_node(gc, try_loc, range)],
+                range,
+            );
+            match param {
+                Some(param) => self.emit_assign(gc, param, exception),
+                None => self.emit(expression_statement(gc, exception)),
+            }
+            self.explode_statement(gc, body, None);
+        }
+        if let (Some(finalizer), Some(finally_loc)) = (finalizer, finally_loc) {
+            self.mark(finally_loc);
+            self.update_prev(gc, finally_loc, range);
+            self.explode_statement(gc, finalizer, None);
+            // The runtime resumes the completion interrupted by the `finally` block.
+            let finish = call(
+                gc,
+                self.context_member(gc, "finish", range),
+                vec![self.loc_node(gc, finally_loc, range)],
+                range,
+            );
+            self.emit(return_statement(gc, Some(finish), range));
+        }
+        self.mark(after);
+    }
+
+    /// Return the value of `expr`, emitting the statements evaluating it.
+    fn explode_value(&mut self, gc: &'gc GCContext, expr: &'gc Node<'gc>) -> &'gc Node<'gc> {
+        self.explode_expression(gc, expr, false)
+            .expect("exploded expression without a value")
+    }
+
+    /// Emit the statements evaluating `expr`, and return its value unless `ignore_result` is
+    /// true, in which case it is emitted as a statement.
+    fn explode_expression(
+        &mut self,
+        gc: &'gc GCContext,
+        expr: &'gc Node<'gc>,
+        ignore_result: bool,
+    ) -> Option<&'gc Node<'gc>> {
+        let range = *expr.range();
+        if !contains_leap(gc, expr) {
+            return self.finish_expression(gc, expr, ignore_result);
+        }
+        let value = match expr {
+            Node::YieldExpression(YieldExpression {
+                argument, delegate, ..
+            }) => {
+                let argument = argument.map(|argument| self.explode_value(gc, argument));
+                let after = self.loc(gc);
+                if *delegate {
+                    // The runtime stores the result of the delegated generator in the context.
+                    let temp = self.temp();
+                    let delegate = call(
+                        gc,
+                        self.context_member(gc, "delegateYield", range),
+                        vec![
+                            argument.unwrap_or_else(|| undefined(gc, range)),
+                            string(gc, &format!("t{}", temp), range),
+                            self.loc_node(gc, after, range),
+                        ],
+                        range,
+                    );
+                    self.emit(return_statement(gc, Some(delegate), range));
+                    self.mark(after);
+                    self.temp_node(gc, temp, range)
+                } else {
+                    self.yield_value(gc, argument, after, range)
+                }
+            }
+            Node::AwaitExpression(AwaitExpression { argument, .. }) => {
+                let argument = self.explode_value(gc, argument);
+                let after = self.loc(gc);
+                let awaited = call(
+                    gc,
+                    dotted_name(gc, "regeneratorRuntime.awrap", range),
+                    vec![argument],
+                    range,
+                );
+                self.yield_value(gc, Some(awaited), after, range)
+            }
+            Node::MemberExpression(MemberExpression {
+                object,
+                property,
+                computed,
+                ..
+            }) => {
+                if *computed {
+                    let values = self.explode_operands(gc, &[object, property]);
+                    computed_member(gc, values[0], values[1], range)
+                } else {
+                    let values = self.explode_operands(gc, &[object]);
+                    key_member(gc, values[0], property, false, range)
+                }
+            }
+            Node::CallExpression(CallExpression {
+                callee, arguments, ..
+            }) => match callee {
+                // The callee stays a member expression to be called with its object as `this`.
+                Node::MemberExpression(MemberExpression {
+                    object,
+                    property,
+                    computed,
+                    ..
+                }) if !matches!(object, Node::Super(_)) => {
+                    let mut operands = vec![*object];
+                    if *computed {
+                        operands.push(property);
+                    }
+                    operands.extend(arguments);
+                    let mut values = self.explode_operands(gc, &operands);
+                    let args = values.split_off(if *computed { 2 } else { 1 });
+                    let property = if *computed { values[1] } else { property };
+                    let callee = key_member(gc, values[0], property, *computed, range);
+                    call(gc, callee, args, range)
+                }
+                _ => {
+                    let mut operands = vec![*callee];
+                    operands.extend(arguments);
+                    let mut values = self.explode_operands(gc, &operands);
+                    let args = values.split_off(1);
+                    call(gc, values[0], args, range)
+                }
+            },
+            Node::NewExpression(NewExpression {
+                callee, arguments, ..
+            }) => {
+                let mut operands = vec![*callee];
+                operands.extend(arguments);
+                let mut values = self.explode_operands(gc, &operands);
+                let args = values.split_off(1);
+                new_expression(gc, values[0], args, range)
+            }
+            Node::ArrayExpression(array_expr @ ArrayExpression { elements, .. }) => {
+                let elements = self.explode_operands(gc, elements);
+                let mut builder = ArrayExpressionBuilder::from_node(array_expr);
+                builder.elements(elements);
+                builder.build(gc).unwrap()
+            }
+            Node::ObjectExpression(ObjectExpression { properties, .. }) => {
+                // The computed keys and the values of the properties, in order.
+                let mut operands = Vec::new();
+                for prop in properties {
+                    match prop {
+                        Node::Property(Property {
+                            key,
+                            value,
+                            computed,
+                            ..
+                        }) => {
+                            if *computed {
+                                operands.push(*key);
+                            }
+                            operands.push(*value);
+                        }
+                        _ => operands.push(*prop),
+                    }
+                }
+                let mut values = self.explode_operands(gc, &operands).into_iter();
+                let properties = properties
+                    .iter()
+                    .map(|prop| match prop {
+                        Node::Property(property @ Property { computed, .. }) => {
+                            let mut builder = PropertyBuilder::from_node(property);
+                            if *computed {
+                                builder.key(values.next().unwrap());
+                            }
+                            builder.value(values.next().unwrap());
+                            builder.build(gc).unwrap()
+                        }
+                        _ => values.next().unwrap(),
+                    })
+                    .collect();
+                object(gc, properties, range)
+            }
+            Node::TemplateLiteral(template @ TemplateLiteral { expressions, .. }) => {
+                let expressions = self.explode_operands(gc, expressions);
+                let mut builder = TemplateLiteralBuilder::from_node(template);
+                builder.expressions(expressions);
+                builder.build(gc).unwrap()
+            }
+            Node::TaggedTemplateExpression(
+                tagged @ TaggedTemplateExpression {
+                    tag,
+                    quasi: Node::TemplateLiteral(template @ TemplateLiteral { expressions, .. }),
+                    ..
+                },
+            ) => {
+                let mut operands = vec![*tag];
+                operands.extend(expressions);
+                let mut values = self.explode_operands(gc, &operands);
+                let mut quasi = TemplateLiteralBuilder::from_node(template);
+                quasi.expressions(values.split_off(1));
+                let mut builder = TaggedTemplateExpressionBuilder::from_node(tagged);
+                builder.tag(values[0]);
+                builder.quasi(quasi.build(gc).unwrap());
+                builder.build(gc).unwrap()
+            }
+            Node::SequenceExpression(SequenceExpression { expressions, .. }) => {
+                let (last, rest) = expressions.split_last().unwrap();
+                for expr in rest {
+                    self.explode_expression(gc, expr, true);
+                }
+                return self.explode_expression(gc, last, ignore_result);
+            }
+            Node::UnaryExpression(unary @ UnaryExpression { argument, .. }) => {
+                let argument = self.explode_value(gc, argument);
+                let mut builder = UnaryExpressionBuilder::from_node(unary);
+                builder.argument(argument);
+                builder.build(gc).unwrap()
+            }
+            Node::UpdateExpression(update @ UpdateExpression { argument, .. }) => {
+                let argument = self.explode_value(gc, argument);
+                let mut builder = UpdateExpressionBuilder::from_node(update);
+                builder.argument(argument);
+                builder.build(gc).unwrap()
+            }
+            Node::BinaryExpression(binary_expr @ BinaryExpression { left, right, .. }) => {
+                let values = self.explode_operands(gc, &[left, right]);
+                let mut builder = BinaryExpressionBuilder::from_node(binary_expr);
+                builder.left(values[0]);
+                builder.right(values[1]);
+                builder.build(gc).unwrap()
+            }
+            Node::LogicalExpression(LogicalExpression {
+                left,
+                right,
+                operator,
+                ..
+            }) => {
+                let left = self.explode_value(gc, left);
+                let after = self.loc(gc);
+                // `??` tests the value twice, so it is always stored.
+                let result =
+                    if ignore_result && *operator != LogicalExpressionOperator::NullishCoalesce {
+                        None
+                    } else {
+                        Some(self.store(gc, left))
+                    };
+                let test = match result {
+                    Some(result) => self.temp_node(gc, result, range),
+                    None => left,
+                };
+                self.jump_if_short_circuit(gc, *operator, test, result, after, range);
+                let right = self.explode_expression(gc, right, ignore_result);
+                if let (Some(result), Some(right)) = (result, right) {
+                    self.emit_assign(gc, self.temp_node(gc, result, range), right);
+                }
+                self.mark(after);
+                if ignore_result {
+                    return None;
+                }
+                self.temp_node(gc, result.unwrap(), range)
+            }
+            Node::ConditionalExpression(ConditionalExpression {
+                test,
+                consequent,
+                alternate,
+                ..
+            }) => {
+                let test = self.explode_value(gc, test);
+                let else_loc = self.loc(gc);
+                let after = self.loc(gc);
+                let result = if ignore_result {
+                    None
+                } else {
+                    Some(self.temp())
+                };
+                self.jump_if_not(gc, test, else_loc);
+                self.explode_branch(gc, consequent, result, ignore_result);
+                self.jump(gc, after, range);
+                self.mark(else_loc);
+                self.explode_branch(gc, alternate, result, ignore_result);
+                self.mark(after);
+                return result.map(|result| self.temp_node(gc, result, range));
+            }
+            Node::AssignmentExpression(AssignmentExpression {
+                operator,
+                left,
+                right,
+                ..
+            }) => {
+                if contains_leap(gc, left)
+                    && !matches!(left, Node::MemberExpression(_) | Node::Identifier(_))
+                {
+                    gc.sm().error(
+                        *left.range(),
+                        "destructuring patterns containing 'yield' can't be lowered",
+                    );
+                    return self.finish_expression(gc, expr, ignore_result);
+                }
+                match operator {
+                    AssignmentExpressionOperator::Assign => match left {
+                        Node::MemberExpression(MemberExpression {
+                            object,
+                            property,
+                            computed,
+                            ..
+                        }) => {
+                            let values = if *computed {
+                                self.explode_operands(gc, &[object, property, right])
+                            } else {
+                                self.explode_operands(gc, &[object, right])
+                            };
+                            let property = if *computed { values[1] } else { property };
+                            let target = key_member(gc, values[0], property, *computed, range);
+                            assign(gc, target, values[values.len() - 1], range)
+                        }
+                        _ => {
+                            let right = self.explode_value(gc, right);
+                            assign(gc, left, right, range)
+                        }
+                    },
+                    _ => {
+                        return self.explode_compound_assignment(
+                            gc,
+                            *operator,
+                            left,
+                            right,
+                            ignore_result,
+                            range,
+                        )
+                    }
+                }
+            }
+            _ => {
+                gc.sm().error(
+                    range,
+                    format!(
+                        "{} containing 'yield' or 'await' can't be lowered",
+                        expr.name()
+                    ),
+                );
+                expr
+            }
+        };
+        self.finish_expression(gc, value, ignore_result)
+    }
+
+    /// Emit the branch `expr` of a conditional expression, storing its value in `result`.
+    fn explode_branch(
+        &mut self,
+        gc: &'gc GCContext,
+        expr: &'gc Node<'gc>,
+        result: Option<usize>,
+        ignore_result: bool,
+    ) {
+        let value = self.explode_expression(gc, expr, ignore_result);
+        if let (Some(result), Some(value)) = (result, value) {
+            self.emit_assign(gc, self.temp_node(gc, result, *expr.range()), value);
+        }
+    }
+
+    /// Return `value`, or emit it as a statement if `ignore_result` is true.
+    fn finish_expression(
+        &mut self,
+        gc: &'gc GCContext,
+        value: &'gc Node<'gc>,
+        ignore_result: bool,
+    ) -> Option<&'gc Node<'gc>> {
+        if !ignore_result {
+            return Some(value);
+        }
+        // The values stored in the context don't have to be evaluated again.
+        if !matches!(
+            value,
+            Node::MemberExpression(MemberExpression { object: Node::Identifier(Identifier { name, .. }), .. })
+                if *name == self.context
+        ) {
+            self.emit(expression_statement(gc, value));
+        }
+        None
+    }
+
+    /// Emit the suspension of the state machine with `argument`, resumed at `after`, and
+    /// return the value it is resumed with.
+    fn yield_value(
+        &mut self,
+        gc: &'gc GCContext,
+        argument: Option<&'gc Node<'gc>>,
+        after: Atom,
+        range: SourceRange,
+    ) -> &'gc Node<'gc> {
+        self.emit_assign(
+            gc,
+            self.context_member(gc, "next", range),
+            self.loc_node(gc, after, range),
+        );
+        self.emit(return_statement(gc, argument, range));
+        self.mark(after);
+        self.context_member(gc, "sent", range)
+    }
+
+    /// Jump to `after` if the logical `operator` short-circuits on `test`, whose value is also
+    /// stored in the temporary `result` if there is one.
+    fn jump_if_short_circuit(
+        &mut self,
+        gc: &'gc GCContext,
+        operator: LogicalExpressionOperator,
+        test: &'gc Node<'gc>,
+        result: Option<usize>,
+        after: Atom,
+        range: SourceRange,
+    ) {
+        match operator {
+            LogicalExpressionOperator::And => self.jump_if_not(gc, test, after),
+            LogicalExpressionOperator::Or => self.jump_if(gc, test, after),
+            LogicalExpressionOperator::NullishCoalesce => {
+                let value = || match result {
+                    Some(result) => self.temp_node(gc, result, range),
+                    None => unreachable!("nullish coalescing without a temporary"),
+                };
+                let compare = |value, nullish| {
+                    binary(
+                        gc,
+                        BinaryExpressionOperator::StrictNotEquals,
+                        value,
+                        nullish,
+                        range,
+                    )
+                };
+                let test = logical(
+                    gc,
+                    LogicalExpressionOperator::And,
+                    compare(value(), literal(gc, Value::Null, range).unwrap()),
+                    compare(value(), undefined(gc, range)),
+                    range,
+                );
+                self.jump_if(gc, test, after);
+            }
+        }
+    }
+
+    /// Emit the compound assignment `left op= right`, reading `left` once before evaluating
+    /// `right`.
+    fn explode_compound_assignment(
+        &mut self,
+        gc: &'gc GCContext,
+        operator: AssignmentExpressionOperator,
+        left: &'gc Node<'gc>,
+        right: &'gc Node<'gc>,
+        ignore_result: bool,
+        range: SourceRange,
+    ) -> Option<&'gc Node<'gc>> {
+        // Store the object and the computed key of a member, which are evaluated once.
+        let target: Box<dyn Fn(&Self) -> &'gc Node<'gc>> = match left {
+            Node::MemberExpression(MemberExpression {
+                object,
+                property,
+                computed,
+                ..
+            }) => {
+                let object = self.explode_value(gc, object);
+                let object = match object {
+                    Node::ThisExpression(_) => None,
+                    _ => Some(self.store(gc, object)),
+                };
+                let property = if *computed {
+                    let property = self.explode_value(gc, property);
+                    if is_literal(property) {
+                        Err(property)
+                    } else {
+                        Ok(self.store(gc, property))
+                    }
+                } else {
+                    Err(*property)
+                };
+                let computed = *computed;
+                Box::new(move |this: &Self| {
+                    let object = match object {
+                        Some(object) => this.temp_node(gc, object, range),
+                        None => this_expression(gc, range),
+                    };
+                    match property {
+                        Ok(property) => {
+                            computed_member(gc, object, this.temp_node(gc, property, range), range)
+                        }
+                        Err(property) => key_member(gc, object, property, computed, range),
+                    }
+                })
+            }
+            Node::Identifier(Identifier { name, .. }) => {
+                let name = *name;
+                Box::new(move |_: &Self| identifier(gc, name, range))
+            }
+            _ => unreachable!("invalid assignment target {}", left.name()),
+        };
+        let old = self.store(gc, target(self));
+        let op = &operator.as_str()[..operator.as_str().len() - 1];
+        if let Ok(operator) = LogicalExpressionOperator::try_from(op) {
+            let after = self.loc(gc);
+            let old_value = self.temp_node(gc, old, range);
+            self.jump_if_short_circuit(gc, operator, old_value, Some(old), after, range);
+            let right = self.explode_value(gc, right);
+            self.emit_assign(
+                gc,
+                self.temp_node(gc, old, range),
+                assign(gc, target(self), right, range),
+            );
+            self.mark(after);
+            return if ignore_result {
+                None
+            } else {
+                Some(self.temp_node(gc, old, range))
+            };
+        }
+        let operator = BinaryExpressionOperator::try_from(op).expect("invalid compound assignment");
+        let right = self.explode_value(gc, right);
+        let value = binary(gc, operator, self.temp_node(gc, old, range), right, range);
+        let assignment = assign(gc, target(self), value, range);
+        self.finish_expression(gc, assignment, ignore_result)
+    }
+
+    /// Return the values of `operands`, evaluated in order. Values which could be changed by
+    /// the evaluation of a following operand containing `yield` are stored in the context.
+    /// Spread elements and array holes are kept.
+    fn explode_operands(
+        &mut self,
+        gc: &'gc GCContext,
+        operands: &[&'gc Node<'gc>],
+    ) -> NodeList<'gc> {
+        let leaps: Vec<bool> = operands
+            .iter()
+            .map(|operand| contains_leap(gc, operand))
+            .collect();
+        let mut values = NodeList::new();
+        for (i, operand) in operands.iter().enumerate() {
+            let stored = leaps[i + 1..].iter().any(|&leap| leap);
+            let explode = |this: &mut Self, expr: &'gc Node<'gc>| {
+                let value = this.explode_value(gc, expr);
+                if stored && !is_literal(value) {
+                    let temp = this.store(gc, value);
+                    this.temp_node(gc, temp, *value.range())
+                } else {
+                    value
+                }
+            };
+            values.push(match operand {
+                Node::Empty(_) => operand,
+                Node::SpreadElement(spread @ SpreadElement { argument, .. }) => {
+                    let argument = explode(self, argument);
+                    let mut builder = SpreadElementBuilder::from_node(spread);
+                    builder.argument(argument);
+                    builder.build(gc).unwrap()
+                }
+                _ => explode(self, operand),
+            });
+        }
+        values
+    }
+}
+
+/// Replaces the locations of the listing with the indices they mark.
+struct LocReplacer<'m> {
+    marks: &'m HashMap<Atom, usize>,
+}
+
+impl<'m, 'gc> VisitorMut<'gc> for LocReplacer<'m> {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        _parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        if let Node::Identifier(Identifier { metadata, name, .. }) = node {
+            if let Some(&position) = self.marks.get(name) {
+                return TransformResult::Changed(number(gc, position as f64, metadata.range));
+            }
+        }
+        node.visit_children_mut(NodeBuilder::from_node(node), gc, self)
+    }
+}
+
+/// Finds a `yield`, `await` or jump, which requires a statement to be split, outside of nested
+/// functions.
+struct LeapFinder {
+    found: bool,
+}
+
+impl<'gc> Visitor<'gc> for LeapFinder {
+    fn call(&mut self, gc: &'gc GCContext, node: &'gc Node<'gc>, _parent: Option<&'gc Node<'gc>>) {
+        if self.found {
+            return;
+        }
+        match node {
+            Node::YieldExpression(_)
+            | Node::AwaitExpression(_)
+            | Node::BreakStatement(_)
+            | Node::ContinueStatement(_)
+            | Node::ReturnStatement(_) => self.found = true,
+            Node::FunctionDeclaration(_)
+            | Node::FunctionExpression(_)
+            | Node::ArrowFunctionExpression(_) => {}
+            _ => node.visit_children(gc, self),
+        }
+    }
+}
+
+fn contains_leap<'gc>(gc: &'gc GCContext, node: &'gc Node<'gc>) -> bool {
+    let mut finder = LeapFinder { found: false };
+    finder.call(gc, node, None);
+    finder.found
+}
+
+/// Finds the first use of `super`, which can't be used in the state machine, or of a
+/// `for await` loop, outside of nested functions other than arrow functions.
+struct UnsupportedFinder {
+    found: Option<(SourceRange, &'static str)>,
+    /// Depth of the arrow functions being visited, whose loops aren't part of the function.
+    arrows: usize,
+}
+
+impl<'gc> Visitor<'gc> for UnsupportedFinder {
+    fn call(&mut self, gc: &'gc GCContext, node: &'gc Node<'gc>, _parent: Option<&'gc Node<'gc>>) {
+        match node {
+            _ if self.found.is_some() => {}
+            Node::Super(_) => {
+                self.found = Some((
+                    *node.range(),
+                    "generators and async functions using 'super' can't be lowered",
+                ))
+            }
+            Node::ForOfStatement(ForOfStatement { is_await: true, .. }) if self.arrows == 0 => {
+                self.found = Some((*node.range(), "'for await' loops can't be lowered"))
+            }
+            Node::FunctionDeclaration(_) | Node::FunctionExpression(_) => {}
+            Node::ArrowFunctionExpression(_) => {
+                self.arrows += 1;
+                node.visit_children(gc, self);
+                self.arrows -= 1;
+            }
+            _ => node.visit_children(gc, self),
+        }
+    }
+}
+
+/// Return true if `node` is the function of a method in `parent`.
+fn is_method_of<'gc>(parent: &'gc Node<'gc>, node: &'gc Node<'gc>) -> bool {
+    match parent {
+        Node::MethodDefinition(MethodDefinition { value, .. }) => std::ptr::eq(*value, node),
+        Node::Property(Property {
+            value,
+            method,
+            kind,
+            ..
+        }) => (*method || *kind != PropertyKind::Init) && std::ptr::eq(*value, node),
+        _ => false,
+    }
+}
+
+fn is_literal(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::NumericLiteral(_)
+            | Node::StringLiteral(_)
+            | Node::BooleanLiteral(_)
+            | Node::NullLiteral(_)
+    )
+}
+
+/// Return `!argument`.
+fn not<'gc>(gc: &'gc GCContext, argument: &'gc Node<'gc>) -> &'gc Node<'gc> {
+    UnaryExpressionBuilder::build_template(
+        gc,
+        UnaryExpressionTemplate {
+            metadata: template_metadata(*argument.range()),
+            operator: UnaryExpressionOperator::Not,
+            argument,
+            prefix: true,
+        },
+    )
+}
+
+fn break_statement<'gc>(gc: &'gc GCContext, range: SourceRange) -> &'gc Node<'gc> {
+    BreakStatementBuilder::build_template(
+        gc,
+        BreakStatementTemplate {
+            metadata: template_metadata(range),
+            label: None,
+        },
+    )
+}
+
+fn switch_case<'gc>(
+    gc: &'gc GCContext,
+    test: &'gc Node<'gc>,
+    consequent: NodeList<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    SwitchCaseBuilder::build_template(
+        gc,
+        SwitchCaseTemplate {
+            metadata: template_metadata(range),
+            test: Some(test),
+            consequent,
+        },
+    )
+}
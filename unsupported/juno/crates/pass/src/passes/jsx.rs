@@ -0,0 +1,568 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! JSX lowering.
+//!
+//! Compiles JSX elements and fragments to function calls, using either the classic runtime,
+//! which calls `React.createElement` (or another pragma) with the children as arguments, or
+//! the automatic runtime, which calls the `jsx` and `jsxs` functions imported from
+//! `react/jsx-runtime` with the children in the props.
+//!
+//! Text children are trimmed like Babel does: lines are trimmed, lines containing only
+//! whitespace are removed and the remaining lines are joined with a space. Entities have
+//! already been decoded by the parser.
+//!
+//! Transforms
+//! ```js
+//! <div className="a" {...props}>
+//!   Hello {name}
+//! </div>
+//! ```
+//! into, with the classic runtime,
+//! ```js
+//! React.createElement("div", {className: "a", ...props}, "Hello ", name)
+//! ```
+//! and with the automatic runtime,
+//! ```js
+//! import {jsxs as _jsxs} from "react/jsx-runtime";
+//! _jsxs("div", {className: "a", ...props, children: ["Hello ", name]})
+//! ```
+
+use crate::passes::const_fold::{literal, Value};
+use crate::passes::util::{
+    array, call, dotted_name, identifier, member, object, property, string, template_metadata,
+    UniqueNames,
+};
+use crate::Pass;
+use juno::ast::*;
+
+/// How JSX is compiled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum JsxRuntime {
+    /// Call the pragma with the children as arguments.
+    Classic,
+    /// Call the functions of the `jsx-runtime` module of the import source.
+    Automatic,
+}
+
+#[derive(Debug, Clone)]
+pub struct JsxOptions {
+    pub runtime: JsxRuntime,
+
+    /// Function creating elements with the classic runtime, e.g. `React.createElement` or `h`.
+    pub pragma: String,
+
+    /// Component of fragments with the classic runtime, e.g. `React.Fragment`.
+    pub pragma_frag: String,
+
+    /// Module the automatic runtime functions are imported from, suffixed with
+    /// `/jsx-runtime`.
+    pub import_source: String,
+}
+
+impl Default for JsxOptions {
+    fn default() -> Self {
+        JsxOptions {
+            runtime: JsxRuntime::Classic,
+            pragma: "React.createElement".to_string(),
+            pragma_frag: "React.Fragment".to_string(),
+            import_source: "react".to_string(),
+        }
+    }
+}
+
+pub struct LowerJsx {
+    options: JsxOptions,
+}
+
+impl LowerJsx {
+    pub fn new(options: JsxOptions) -> Self {
+        LowerJsx { options }
+    }
+}
+
+impl Pass for LowerJsx {
+    fn name(&self) -> &'static str {
+        "Lower JSX"
+    }
+    fn description(&self) -> &'static str {
+        "Compiles JSX to function calls"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let mut lowerer = Lowerer {
+            options: &self.options,
+            names: UniqueNames::new(gc, node),
+            imports: Default::default(),
+        };
+        match VisitorMut::call(&mut lowerer, gc, node, None) {
+            TransformResult::Changed(new_node) => {
+                TransformResult::Changed(lowerer.add_imports(gc, new_node))
+            }
+            TransformResult::Unchanged => TransformResult::Unchanged,
+        }
+    }
+}
+
+/// Local names of the functions imported for the automatic runtime.
+#[derive(Default)]
+struct Imports {
+    jsx: Option<Atom>,
+    jsxs: Option<Atom>,
+    fragment: Option<Atom>,
+    create_element: Option<Atom>,
+}
+
+struct Lowerer<'a> {
+    options: &'a JsxOptions,
+    names: UniqueNames,
+    imports: Imports,
+}
+
+impl<'gc> VisitorMut<'gc> for Lowerer<'_> {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        _parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let (node, changed) = match node.visit_children_mut(NodeBuilder::from_node(node), gc, self)
+        {
+            TransformResult::Changed(new_node) => (new_node, true),
+            TransformResult::Unchanged => (node, false),
+        };
+        match node {
+            Node::JSXElement(JSXElement {
+                metadata,
+                opening_element:
+                    Node::JSXOpeningElement(JSXOpeningElement {
+                        name, attributes, ..
+                    }),
+                children,
+                ..
+            }) => TransformResult::Changed(self.lower(
+                gc,
+                metadata.range,
+                element_type(gc, name),
+                attributes,
+                children,
+            )),
+            Node::JSXFragment(JSXFragment {
+                metadata, children, ..
+            }) => {
+                let range = metadata.range;
+                let fragment = match self.options.runtime {
+                    JsxRuntime::Classic => dotted_name(gc, &self.options.pragma_frag, range),
+                    JsxRuntime::Automatic => {
+                        let name = import_name(
+                            gc,
+                            &mut self.names,
+                            &mut self.imports.fragment,
+                            "_Fragment",
+                        );
+                        identifier(gc, name, range)
+                    }
+                };
+                TransformResult::Changed(self.lower(gc, range, fragment, &[], children))
+            }
+            _ if changed => TransformResult::Changed(node),
+            _ => TransformResult::Unchanged,
+        }
+    }
+}
+
+impl Lowerer<'_> {
+    /// Return the call creating the element of type `element_type`, whose children have
+    /// already been lowered.
+    fn lower<'gc>(
+        &mut self,
+        gc: &'gc GCContext,
+        range: SourceRange,
+        element_type: &'gc Node<'gc>,
+        attributes: &[&'gc Node<'gc>],
+        children: &[&'gc Node<'gc>],
+    ) -> &'gc Node<'gc> {
+        let children = lower_children(gc, children);
+        let key_after_spread = attributes
+            .iter()
+            .skip_while(|attr| !matches!(attr, Node::JSXSpreadAttribute(_)))
+            .any(|&attr| is_key(gc, attr));
+
+        if self.options.runtime == JsxRuntime::Classic || key_after_spread {
+            // The automatic runtime can't preserve the order of a key following a spread,
+            // so `createElement` is used instead.
+            let callee = match self.options.runtime {
+                JsxRuntime::Classic => dotted_name(gc, &self.options.pragma, range),
+                JsxRuntime::Automatic => {
+                    let name = import_name(
+                        gc,
+                        &mut self.names,
+                        &mut self.imports.create_element,
+                        "_createElement",
+                    );
+                    identifier(gc, name, range)
+                }
+            };
+            let props = if attributes.is_empty() {
+                literal(gc, Value::Null, range).unwrap()
+            } else {
+                object(gc, props(gc, attributes.iter().copied(), range), range)
+            };
+            let mut arguments = vec![element_type, props];
+            arguments.extend(children);
+            return call(gc, callee, arguments, range);
+        }
+
+        let mut key = None;
+        let mut properties = props(
+            gc,
+            attributes.iter().copied().filter(|&attr| {
+                if is_key(gc, attr) {
+                    key = Some(attr);
+                    false
+                } else {
+                    true
+                }
+            }),
+            range,
+        );
+        let is_static = children.len() > 1;
+        match children.len() {
+            0 => {}
+            1 => properties.push(property(gc, "children", children[0], range)),
+            _ => properties.push(property(gc, "children", array(gc, children, range), range)),
+        }
+        let name = if is_static {
+            import_name(gc, &mut self.names, &mut self.imports.jsxs, "_jsxs")
+        } else {
+            import_name(gc, &mut self.names, &mut self.imports.jsx, "_jsx")
+        };
+        let mut arguments = vec![element_type, object(gc, properties, range)];
+        if let Some(key) = key {
+            arguments.push(attribute_value(gc, key, range));
+        }
+        call(gc, identifier(gc, name, range), arguments, range)
+    }
+
+    /// Return `program` with the imports of the automatic runtime functions added.
+    fn add_imports<'gc>(&self, gc: &'gc GCContext, program: &'gc Node<'gc>) -> &'gc Node<'gc> {
+        let (body, mut builder) = match program {
+            Node::Program(node @ Program { body, .. }) => (body, ProgramBuilder::from_node(node)),
+            _ => return program,
+        };
+        let range = *program.range();
+        let runtime = format!("{}/jsx-runtime", self.options.import_source);
+        let imports = [
+            (&runtime, "jsx", self.imports.jsx),
+            (&runtime, "jsxs", self.imports.jsxs),
+            (&runtime, "Fragment", self.imports.fragment),
+            (
+                &self.options.import_source,
+                "createElement",
+                self.imports.create_element,
+            ),
+        ];
+        let mut declarations: Vec<(&String, NodeList)> = Vec::new();
+        for (source, imported, local) in imports {
+            let local = match local {
+                Some(local) => local,
+                None => continue,
+            };
+            let specifier = ImportSpecifierBuilder::build_template(
+                gc,
+                ImportSpecifierTemplate {
+                    metadata: template_metadata(range),
+                    imported: identifier(gc, gc.atom(imported), range),
+                    local: identifier(gc, local, range),
+                    import_kind: ImportKind::Value,
+                },
+            );
+            match declarations.last_mut() {
+                Some((last_source, specifiers)) if *last_source == source => {
+                    specifiers.push(specifier)
+                }
+                _ => declarations.push((source, vec![specifier])),
+            }
+        }
+        if declarations.is_empty() {
+            return program;
+        }
+
+        let directives = body
+            .iter()
+            .take_while(|stmt| {
+                matches!(
+                    stmt,
+                    Node::ExpressionStatement(ExpressionStatement {
+                        directive: Some(_),
+                        ..
+                    })
+                )
+            })
+            .count();
+        let mut new_body = body[..directives].to_vec();
+        for (source, specifiers) in declarations {
+            new_body.push(ImportDeclarationBuilder::build_template(
+                gc,
+                ImportDeclarationTemplate {
+                    metadata: template_metadata(range),
+                    specifiers,
+                    source: literal(gc, Value::String(source.encode_utf16().collect()), range)
+                        .unwrap(),
+                    assertions: None,
+                    import_kind: ImportKind::Value,
+                },
+            ));
+        }
+        new_body.extend_from_slice(&body[directives..]);
+        builder.body(new_body);
+        builder.build(gc).unwrap()
+    }
+}
+
+/// Return the local name of an imported runtime function, generating it on first use.
+fn import_name(
+    gc: &GCContext,
+    names: &mut UniqueNames,
+    local: &mut Option<Atom>,
+    base: &str,
+) -> Atom {
+    *local.get_or_insert_with(|| names.generate(gc, base))
+}
+
+/// Return whether `attr` is a `key` attribute.
+fn is_key(gc: &GCContext, attr: &Node) -> bool {
+    matches!(
+        attr,
+        Node::JSXAttribute(JSXAttribute {
+            name: Node::JSXIdentifier(JSXIdentifier { name, .. }),
+            ..
+        }) if gc.str(*name) == "key"
+    )
+}
+
+/// Return the expression of the type of an element named `name`: a string for intrinsic
+/// elements, whose names start with a lowercase letter or contain a dash, and a reference to
+/// the component otherwise.
+fn element_type<'gc>(gc: &'gc GCContext, name: &'gc Node<'gc>) -> &'gc Node<'gc> {
+    let range = *name.range();
+    match name {
+        Node::JSXIdentifier(JSXIdentifier { name, .. }) => {
+            let str = gc.str(*name);
+            if str == "this" {
+                ThisExpressionBuilder::build_template(
+                    gc,
+                    ThisExpressionTemplate {
+                        metadata: template_metadata(range),
+                    },
+                )
+            } else if str.starts_with(|c: char| c.is_ascii_lowercase()) || str.contains('-') {
+                string(gc, str, range)
+            } else {
+                identifier(gc, *name, range)
+            }
+        }
+        Node::JSXMemberExpression(JSXMemberExpression {
+            object, property, ..
+        }) => {
+            let object = match object {
+                Node::JSXIdentifier(JSXIdentifier { name, .. }) if gc.str(*name) != "this" => {
+                    identifier(gc, *name, *object.range())
+                }
+                _ => element_type(gc, object),
+            };
+            let property = match property {
+                Node::JSXIdentifier(JSXIdentifier { name, .. }) => name,
+                _ => unreachable!("JSX member expression property must be an identifier"),
+            };
+            member(gc, object, *property, range)
+        }
+        Node::JSXNamespacedName(_) => string(gc, &jsx_name(gc, name), range),
+        _ => unreachable!("invalid JSX element name {}", name.name()),
+    }
+}
+
+/// Return the source text of the JSX identifier or namespaced name `name`.
+fn jsx_name(gc: &GCContext, name: &Node) -> String {
+    match name {
+        Node::JSXIdentifier(JSXIdentifier { name, .. }) => gc.str(*name).to_string(),
+        Node::JSXNamespacedName(JSXNamespacedName {
+            namespace, name, ..
+        }) => {
+            format!("{}:{}", jsx_name(gc, namespace), jsx_name(gc, name))
+        }
+        _ => unreachable!("invalid JSX attribute name {}", name.name()),
+    }
+}
+
+/// Return the properties of the props object built from `attributes`.
+fn props<'gc>(
+    gc: &'gc GCContext,
+    attributes: impl Iterator<Item = &'gc Node<'gc>>,
+    range: SourceRange,
+) -> NodeList<'gc> {
+    attributes
+        .map(|attr| match attr {
+            Node::JSXSpreadAttribute(JSXSpreadAttribute { metadata, argument }) => {
+                SpreadElementBuilder::build_template(
+                    gc,
+                    SpreadElementTemplate {
+                        metadata: template_metadata(metadata.range),
+                        argument,
+                    },
+                )
+            }
+            Node::JSXAttribute(JSXAttribute { name, .. }) => {
+                let name = jsx_name(gc, name);
+                property(gc, &name, attribute_value(gc, attr, range), *attr.range())
+            }
+            _ => unreachable!("invalid JSX attribute {}", attr.name()),
+        })
+        .collect()
+}
+
+/// Return the value of the JSX attribute `attr`, `true` if there is none.
+fn attribute_value<'gc>(
+    gc: &'gc GCContext,
+    attr: &'gc Node<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    match attr {
+        Node::JSXAttribute(JSXAttribute { value: None, .. }) => {
+            literal(gc, Value::Bool(true), range).unwrap()
+        }
+        Node::JSXAttribute(JSXAttribute {
+            value: Some(Node::JSXExpressionContainer(JSXExpressionContainer { expression, .. })),
+            ..
+        }) => expression,
+        // Line breaks in strings are collapsed with the following indentation.
+        Node::JSXAttribute(JSXAttribute {
+            value:
+                Some(
+                    value @ Node::StringLiteral(StringLiteral {
+                        value: NodeString { str },
+                        ..
+                    }),
+                ),
+            ..
+        }) if str.contains(&(b'\n' as u16)) => {
+            let mut result = Vec::with_capacity(str.len());
+            let mut i = 0;
+            while i < str.len() {
+                if str[i] == b'\n' as u16 {
+                    let spaces = str[i + 1..].iter().take_while(|&&c| is_space(c)).count();
+                    if spaces > 0 {
+                        result.push(b' ' as u16);
+                        i += 1 + spaces;
+                        continue;
+                    }
+                }
+                result.push(str[i]);
+                i += 1;
+            }
+            literal(gc, Value::String(result), *value.range()).unwrap()
+        }
+        Node::JSXAttribute(JSXAttribute {
+            value: Some(value), ..
+        }) => value,
+        _ => unreachable!("invalid JSX attribute {}", attr.name()),
+    }
+}
+
+/// Return whether `c` is a JS whitespace or line terminator character.
+fn is_space(c: u16) -> bool {
+    char::from_u32(c as u32).is_some_and(|c| c.is_whitespace() || c == '\u{feff}')
+}
+
+/// Return the arguments for the JSX `children`, whose elements have already been lowered.
+fn lower_children<'gc>(gc: &'gc GCContext, children: &[&'gc Node<'gc>]) -> NodeList<'gc> {
+    let mut result = NodeList::with_capacity(children.len());
+    for &child in children {
+        match child {
+            Node::JSXText(JSXText {
+                metadata, value, ..
+            }) => {
+                if let Some(text) = trim_text(&value.str) {
+                    result.push(literal(gc, Value::String(text), metadata.range).unwrap());
+                }
+            }
+            Node::JSXExpressionContainer(JSXExpressionContainer {
+                expression: Node::JSXEmptyExpression(_),
+                ..
+            }) => {}
+            Node::JSXExpressionContainer(JSXExpressionContainer { expression, .. }) => {
+                result.push(expression)
+            }
+            Node::JSXSpreadChild(JSXSpreadChild {
+                metadata,
+                expression,
+            }) => result.push(SpreadElementBuilder::build_template(
+                gc,
+                SpreadElementTemplate {
+                    metadata: template_metadata(metadata.range),
+                    argument: expression,
+                },
+            )),
+            _ => result.push(child),
+        }
+    }
+    result
+}
+
+/// Return the JSX text `text` with the whitespace around line breaks removed, or `None` if
+/// nothing is left.
+/// Lines are trimmed, except for the start of the first line and the end of the last one,
+/// and the non-empty lines are joined with a space.
+fn trim_text(text: &[u16]) -> Option<Vec<u16>> {
+    const SPACE: u16 = b' ' as u16;
+    const TAB: u16 = b'\t' as u16;
+    const LF: u16 = b'\n' as u16;
+    const CR: u16 = b'\r' as u16;
+
+    let mut lines: Vec<&[u16]> = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < text.len() {
+        if text[i] == LF || text[i] == CR {
+            lines.push(&text[start..i]);
+            if text[i] == CR && text.get(i + 1) == Some(&LF) {
+                i += 1;
+            }
+            start = i + 1;
+        }
+        i += 1;
+    }
+    lines.push(&text[start..]);
+
+    let is_blank = |c: &u16| *c == SPACE || *c == TAB;
+    let last_non_empty = lines.iter().rposition(|line| !line.iter().all(is_blank))?;
+    let mut result = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let mut line: &[u16] = line;
+        if i != 0 {
+            line = &line[line.iter().take_while(|c| is_blank(c)).count()..];
+        }
+        if i != lines.len() - 1 {
+            line = &line[..line.len() - line.iter().rev().take_while(|c| is_blank(c)).count()];
+        }
+        if line.is_empty() {
+            continue;
+        }
+        result.extend(line.iter().map(|&c| if c == TAB { SPACE } else { c }));
+        if i != last_non_empty {
+            result.push(SPACE);
+        }
+    }
+    if result.is_empty() {
+        None
+    } else {
+        Some(result)
+    }
+}
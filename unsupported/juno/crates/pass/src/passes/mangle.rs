@@ -0,0 +1,321 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Identifier mangling.
+//!
+//! Renames the local variables of a program to the shortest available names, giving the
+//! shortest names to the most frequently used variables.
+//! Variables which may be accessed by name aren't renamed: globals, exports, and all variables
+//! visible from a direct `eval` or a `with` statement.
+//!
+//! Variables of sibling scopes share names: each variable is assigned a slot, which is the
+//! number of renamed variables declared before it in its scope and in the enclosing scopes, so
+//! variables visible from the same scope have different slots. The slots are then sorted by
+//! frequency of use and named in order, skipping reserved words and the names which are kept.
+//!
+//! Transforms
+//! ```js
+//! function sum(values) {
+//!   let total = 0;
+//!   for (const value of values) total += value;
+//!   return total;
+//! }
+//! ```
+//! into
+//! ```js
+//! function sum(t) {
+//!   let e = 0;
+//!   for (const n of t) e += n;
+//!   return e;
+//! }
+//! ```
+
+use crate::Pass;
+use juno::ast::*;
+use juno::sema::{
+    pattern_ids, resolve_program, BindingId, BindingKind, ScopeId, ScopeKind, SemContext,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Characters which can start an identifier, roughly in order of frequency in JS code so the
+/// output compresses well.
+const FIRST_CHARS: &[u8] = b"etnrisoucaldpfghbmyvwkxjqzETNRISOUCALDPFGHBMYVWKXJQZ_$";
+
+/// Characters which can follow the first one.
+const OTHER_CHARS: &[u8] = b"etnrisoucaldpfghbmyvwkxjqzETNRISOUCALDPFGHBMYVWKXJQZ_$0123456789";
+
+/// Names which can't be used for variables, or which have special meaning.
+const RESERVED_WORDS: &[&str] = &[
+    "arguments",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "eval",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+/// Return the `n`-th shortest identifier name.
+fn nth_name(mut n: usize) -> String {
+    let mut name = String::new();
+    name.push(FIRST_CHARS[n % FIRST_CHARS.len()] as char);
+    n /= FIRST_CHARS.len();
+    while n > 0 {
+        n -= 1;
+        name.push(OTHER_CHARS[n % OTHER_CHARS.len()] as char);
+        n /= OTHER_CHARS.len();
+    }
+    name
+}
+
+#[derive(Default)]
+pub struct Mangle {}
+
+impl Mangle {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Pass for Mangle {
+    fn name(&self) -> &'static str {
+        "Mangle"
+    }
+    fn description(&self) -> &'static str {
+        "Renames local variables to short names"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let sem = resolve_program(gc, node);
+        let exported = exported_bindings(&sem, node);
+        let renamable = |id: BindingId| {
+            let binding = sem.binding(id);
+            let scope = sem.scope(binding.scope);
+            scope.kind != ScopeKind::Global
+                && !scope.dynamic
+                && !exported.contains(&id)
+                // Functions declared in blocks may be visible outside of them in sloppy mode.
+                && (binding.kind != BindingKind::Function || scope.kind.is_var_scope())
+                // Lowercase JSX element names refer to intrinsic elements.
+                && !binding
+                    .references
+                    .iter()
+                    .any(|reference| !matches!(reference.node, Node::Identifier(_)))
+        };
+
+        // Assign the slots.
+        let mut slots: HashMap<BindingId, usize> = HashMap::new();
+        let mut scope_slots: HashMap<ScopeId, usize> = HashMap::new();
+        let mut uses: Vec<usize> = Vec::new();
+        let mut kept: HashSet<&str> = RESERVED_WORDS.iter().copied().collect();
+        for (scope_id, scope) in sem.scopes() {
+            let mut slot = scope.parent.map_or(0, |parent| scope_slots[&parent]);
+            for &id in &scope.bindings {
+                let binding = sem.binding(id);
+                if !renamable(id) {
+                    kept.insert(gc.str(binding.name));
+                    continue;
+                }
+                if uses.len() <= slot {
+                    uses.push(0);
+                }
+                uses[slot] += binding.declarations.len() + binding.references.len();
+                slots.insert(id, slot);
+                slot += 1;
+            }
+            scope_slots.insert(scope_id, slot);
+        }
+        kept.extend(sem.globals().map(|(name, _)| gc.str(name)));
+
+        // Name the most used slots first.
+        let mut order: Vec<usize> = (0..uses.len()).collect();
+        order.sort_by_key(|&slot| std::cmp::Reverse(uses[slot]));
+        let mut names = vec![INVALID_ATOM; uses.len()];
+        let mut n = 0;
+        for slot in order {
+            let name = loop {
+                let name = nth_name(n);
+                n += 1;
+                if !kept.contains(name.as_str()) {
+                    break name;
+                }
+            };
+            names[slot] = gc.atom(name);
+        }
+
+        let renames = slots
+            .into_iter()
+            .filter(|&(id, slot)| sem.binding(id).name != names[slot])
+            .map(|(id, slot)| (id, names[slot]))
+            .collect();
+        VisitorMut::call(&mut Renamer { sem: &sem, renames }, gc, node, None)
+    }
+}
+
+/// Return the bindings exported by the module `program`.
+fn exported_bindings<'gc>(sem: &SemContext<'gc>, program: &'gc Node<'gc>) -> HashSet<BindingId> {
+    let mut ids = Vec::new();
+    if let Node::Program(Program { body, .. }) = program {
+        for stmt in body {
+            match stmt {
+                Node::ExportNamedDeclaration(ExportNamedDeclaration {
+                    declaration,
+                    specifiers,
+                    source: None,
+                    ..
+                }) => {
+                    match declaration {
+                        Some(Node::VariableDeclaration(VariableDeclaration {
+                            declarations,
+                            ..
+                        })) => {
+                            for decl in declarations {
+                                if let Node::VariableDeclarator(VariableDeclarator { id, .. }) =
+                                    decl
+                                {
+                                    pattern_ids(id, &mut ids);
+                                }
+                            }
+                        }
+                        Some(Node::FunctionDeclaration(FunctionDeclaration {
+                            id: Some(id),
+                            ..
+                        }))
+                        | Some(Node::ClassDeclaration(ClassDeclaration { id: Some(id), .. })) => {
+                            ids.push(id)
+                        }
+                        _ => {}
+                    }
+                    for spec in specifiers {
+                        if let Node::ExportSpecifier(ExportSpecifier { local, .. }) = spec {
+                            ids.push(local);
+                        }
+                    }
+                }
+                Node::ExportDefaultDeclaration(ExportDefaultDeclaration {
+                    declaration:
+                        Node::FunctionDeclaration(FunctionDeclaration { id: Some(id), .. })
+                        | Node::ClassDeclaration(ClassDeclaration { id: Some(id), .. }),
+                    ..
+                }) => ids.push(id),
+                _ => {}
+            }
+        }
+    }
+    ids.into_iter()
+        .filter_map(|id| sem.binding_of(id))
+        .collect()
+}
+
+struct Renamer<'a, 'gc> {
+    sem: &'a SemContext<'gc>,
+
+    /// New name of every renamed binding.
+    renames: HashMap<BindingId, Atom>,
+}
+
+impl<'gc> Renamer<'_, 'gc> {
+    /// Return the new name of the identifier `node`, if it is renamed.
+    fn new_name(&self, node: &'gc Node<'gc>) -> Option<Atom> {
+        self.renames.get(&self.sem.binding_of(node)?).copied()
+    }
+}
+
+impl<'gc> VisitorMut<'gc> for Renamer<'_, 'gc> {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        _parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        match node {
+            Node::Identifier(ident) => {
+                if let Some(name) = self.new_name(node) {
+                    let mut builder = IdentifierBuilder::from_node(ident);
+                    builder.name(name);
+                    return node.visit_children_mut(NodeBuilder::Identifier(builder), gc, self);
+                }
+            }
+            // The key of a shorthand property is the same node as the variable, so only the
+            // value is renamed.
+            Node::Property(
+                prop @ Property {
+                    value,
+                    shorthand: true,
+                    ..
+                },
+            ) => {
+                return match VisitorMut::call(self, gc, value, Some(node)) {
+                    TransformResult::Changed(value) => {
+                        let mut builder = PropertyBuilder::from_node(prop);
+                        builder.value(value);
+                        builder.shorthand(false);
+                        builder.build(gc)
+                    }
+                    TransformResult::Unchanged => TransformResult::Unchanged,
+                };
+            }
+            // Likewise, the imported name may be the same node as the local one.
+            Node::ImportSpecifier(spec @ ImportSpecifier { local, .. }) => {
+                return match VisitorMut::call(self, gc, local, Some(node)) {
+                    TransformResult::Changed(local) => {
+                        let mut builder = ImportSpecifierBuilder::from_node(spec);
+                        builder.local(local);
+                        builder.build(gc)
+                    }
+                    TransformResult::Unchanged => TransformResult::Unchanged,
+                };
+            }
+            _ => {}
+        }
+        node.visit_children_mut(NodeBuilder::from_node(node), gc, self)
+    }
+}
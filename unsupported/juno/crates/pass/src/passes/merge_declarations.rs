@@ -0,0 +1,107 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Merging of consecutive variable declarations for minification.
+//!
+//! Transforms
+//! ```js
+//! var a = 1;
+//! var b = a;
+//! const c = 2;
+//! ```
+//! into
+//! ```js
+//! var a = 1, b = a;
+//! const c = 2;
+//! ```
+
+use crate::passes::util::{statement_list, with_statement_list};
+use crate::Pass;
+use juno::ast::*;
+
+#[derive(Default)]
+pub struct MergeDeclarations {}
+
+impl MergeDeclarations {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Pass for MergeDeclarations {
+    fn name(&self) -> &'static str {
+        "Merge declarations"
+    }
+    fn description(&self) -> &'static str {
+        "Merges consecutive variable declarations of the same kind"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        VisitorMut::call(self, gc, node, None)
+    }
+}
+
+impl<'gc> VisitorMut<'gc> for MergeDeclarations {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        _parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let (node, changed) = match node.visit_children_mut(NodeBuilder::from_node(node), gc, self)
+        {
+            TransformResult::Changed(new_node) => (new_node, true),
+            TransformResult::Unchanged => (node, false),
+        };
+        match statement_list(node).and_then(|list| merge(gc, list)) {
+            Some(list) => TransformResult::Changed(with_statement_list(gc, node, list)),
+            None if changed => TransformResult::Changed(node),
+            None => TransformResult::Unchanged,
+        }
+    }
+}
+
+/// Return the statement list `list` with its consecutive declarations merged, or `None` if
+/// there are none.
+fn merge<'gc>(gc: &'gc GCContext, list: &NodeList<'gc>) -> Option<NodeList<'gc>> {
+    let mut result: NodeList = Vec::with_capacity(list.len());
+    let mut changed = false;
+    for &stmt in list {
+        if let (
+            Node::VariableDeclaration(VariableDeclaration {
+                kind, declarations, ..
+            }),
+            Some(Node::VariableDeclaration(
+                previous @ VariableDeclaration {
+                    kind: previous_kind,
+                    declarations: previous_declarations,
+                    ..
+                },
+            )),
+        ) = (stmt, result.last())
+        {
+            if kind == previous_kind {
+                let mut builder = VariableDeclarationBuilder::from_node(previous);
+                let mut merged = previous_declarations.clone();
+                merged.extend(declarations);
+                builder.declarations(merged);
+                *result.last_mut().unwrap() = builder.build(gc).unwrap();
+                changed = true;
+                continue;
+            }
+        }
+        result.push(stmt);
+    }
+    if changed {
+        Some(result)
+    } else {
+        None
+    }
+}
@@ -6,3 +6,25 @@
  */
 
 pub mod add_negative;
+pub mod arrow_functions;
+pub mod async_functions;
+pub mod block_scoping;
+pub mod class_fields;
+pub mod classes;
+pub mod compact_literals;
+pub mod compact_statements;
+pub mod computed_properties;
+pub mod const_fold;
+pub mod dce;
+pub mod define;
+pub mod destructuring;
+pub mod generators;
+pub mod jsx;
+pub mod mangle;
+pub mod merge_declarations;
+pub mod optional_chaining;
+pub mod shorthand_properties;
+pub mod spread;
+pub mod strip_types;
+pub mod template_literals;
+mod util;
@@ -0,0 +1,381 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Optional chaining and nullish coalescing lowering.
+//!
+//! Compiles each optional chain to a conditional expression testing the values of its optional
+//! links, which are stored in temporaries so that they are evaluated once. Optional calls of
+//! members keep their `this` by calling the function with `call`. Nullish coalescing is compiled
+//! to a conditional expression in the same way.
+//!
+//! Transforms
+//! ```js
+//! x = a?.b.c(d);
+//! y = o.m?.();
+//! z = f() ?? 1;
+//! ```
+//! into
+//! ```js
+//! var _m, _o, _ref;
+//! x = a === null || a === void 0 ? void 0 : a.b.c(d);
+//! y = (_m = (_o = o).m) === null || _m === void 0 ? void 0 : _m.call(_o);
+//! z = (_ref = f()) !== null && _ref !== void 0 ? _ref : 1;
+//! ```
+
+use crate::passes::const_fold::{literal, Value};
+use crate::passes::util::*;
+use crate::Pass;
+use juno::ast::*;
+
+#[derive(Default)]
+pub struct OptionalChaining {}
+
+impl OptionalChaining {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Pass for OptionalChaining {
+    fn name(&self) -> &'static str {
+        "Lower optional chaining"
+    }
+    fn description(&self) -> &'static str {
+        "Compiles optional chains and nullish coalescing to conditional expressions"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let mut lowerer = Lowerer {
+            temps: Temporaries::new(UniqueNames::new(gc, node)),
+        };
+        VisitorMut::call(&mut lowerer, gc, node, None)
+    }
+}
+
+struct Lowerer {
+    temps: Temporaries,
+}
+
+impl<'gc> VisitorMut<'gc> for Lowerer {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let is_scope = is_var_scope(node);
+        if is_scope {
+            self.temps.enter();
+        }
+        // Chains are lowered as a whole by their outermost node, after their other children.
+        let in_chain = parent.is_some_and(|parent| is_chain_parent(parent, node));
+        let (mut node, mut changed) =
+            match node.visit_children_mut(NodeBuilder::from_node(node), gc, self) {
+                TransformResult::Changed(new_node) => (new_node, true),
+                TransformResult::Unchanged => (node, false),
+            };
+        if is_scope {
+            if let Some(new_node) = self.temps.exit(gc, node) {
+                node = new_node;
+                changed = true;
+            }
+        }
+        let range = *node.range();
+        let lowered = match node {
+            Node::OptionalMemberExpression(_) | Node::OptionalCallExpression(_) if !in_chain => {
+                let chain = self.lower_chain(gc, node);
+                Some(chain.finish(gc, undefined(gc, range), chain.value, range))
+            }
+            // `(a?.b)()` calls `a.b` with `a` as `this`.
+            Node::CallExpression(CallExpression {
+                callee:
+                    callee @ (Node::OptionalMemberExpression(_) | Node::OptionalCallExpression(_)),
+                arguments,
+                ..
+            }) => {
+                let chain = self.lower_chain(gc, callee);
+                Some(match split_member(chain.value) {
+                    Some((object, property, computed)) => {
+                        let (object, this) = self.reuse(gc, object);
+                        let value = key_member(gc, object, property, computed, range);
+                        let callee = chain.finish(gc, undefined(gc, range), value, range);
+                        method_call(gc, callee, this.node(gc), arguments, range)
+                    }
+                    None => call(
+                        gc,
+                        chain.finish(gc, undefined(gc, range), chain.value, range),
+                        arguments.clone(),
+                        range,
+                    ),
+                })
+            }
+            // `delete a?.b` is true when `a` is nullish.
+            Node::UnaryExpression(
+                unary @ UnaryExpression {
+                    operator: UnaryExpressionOperator::Delete,
+                    argument:
+                        argument @ (Node::OptionalMemberExpression(_) | Node::OptionalCallExpression(_)),
+                    ..
+                },
+            ) => {
+                let chain = self.lower_chain(gc, argument);
+                let mut builder = UnaryExpressionBuilder::from_node(unary);
+                builder.argument(chain.value);
+                let delete = builder.build(gc).unwrap();
+                let t = literal(gc, Value::Bool(true), range).unwrap();
+                Some(chain.finish(gc, t, delete, range))
+            }
+            Node::LogicalExpression(LogicalExpression {
+                left,
+                right,
+                operator: LogicalExpressionOperator::NullishCoalesce,
+                ..
+            }) => {
+                let (value, reference) = self.reuse(gc, left);
+                let test = logical(
+                    gc,
+                    LogicalExpressionOperator::And,
+                    compare(
+                        gc,
+                        BinaryExpressionOperator::StrictNotEquals,
+                        value,
+                        Value::Null,
+                    ),
+                    compare(
+                        gc,
+                        BinaryExpressionOperator::StrictNotEquals,
+                        reference.node(gc),
+                        Value::Undefined,
+                    ),
+                    range,
+                );
+                Some(conditional(gc, test, reference.node(gc), right, range))
+            }
+            _ => None,
+        };
+        match lowered {
+            Some(new_node) => TransformResult::Changed(new_node),
+            None if changed => TransformResult::Changed(node),
+            None => TransformResult::Unchanged,
+        }
+    }
+}
+
+/// An expression which can be evaluated again, with the same value and no side effects.
+#[derive(Copy, Clone)]
+enum Reusable {
+    This(SourceRange),
+    Name(Atom, SourceRange),
+}
+
+impl Reusable {
+    fn node<'gc>(self, gc: &'gc GCContext) -> &'gc Node<'gc> {
+        match self {
+            Reusable::This(range) => this_expression(gc, range),
+            Reusable::Name(name, range) => identifier(gc, name, range),
+        }
+    }
+}
+
+/// A lowered optional chain.
+struct Chain<'gc> {
+    /// Tests of the values of the optional links, any of which short-circuits the chain if true.
+    checks: NodeList<'gc>,
+    /// The value of the chain if it doesn't short-circuit.
+    value: &'gc Node<'gc>,
+}
+
+impl<'gc> Chain<'gc> {
+    /// Return the expression evaluating to `short_circuit` if any of the checks is true, and to
+    /// `value` otherwise.
+    fn finish(
+        &self,
+        gc: &'gc GCContext,
+        short_circuit: &'gc Node<'gc>,
+        value: &'gc Node<'gc>,
+        range: SourceRange,
+    ) -> &'gc Node<'gc> {
+        let test =
+            self.checks.iter().copied().reduce(|test, check| {
+                logical(gc, LogicalExpressionOperator::Or, test, check, range)
+            });
+        match test {
+            Some(test) => conditional(gc, test, short_circuit, value, range),
+            None => value,
+        }
+    }
+}
+
+impl Lowerer {
+    /// Return the lowered chain ending with `node`, whose children other than its links have
+    /// already been lowered.
+    fn lower_chain<'gc>(&mut self, gc: &'gc GCContext, node: &'gc Node<'gc>) -> Chain<'gc> {
+        let range = *node.range();
+        match node {
+            Node::OptionalMemberExpression(OptionalMemberExpression {
+                object,
+                property,
+                computed,
+                optional,
+                ..
+            }) => {
+                let mut chain = self.lower_chain(gc, object);
+                let object = if *optional {
+                    self.check(gc, &mut chain.checks, chain.value)
+                } else {
+                    chain.value
+                };
+                chain.value = key_member(gc, object, property, *computed, range);
+                chain
+            }
+            Node::OptionalCallExpression(OptionalCallExpression {
+                callee,
+                arguments,
+                optional,
+                ..
+            }) => {
+                let mut chain = self.lower_chain(gc, callee);
+                chain.value = match split_member(chain.value) {
+                    // Keep `this` by calling the function stored in a temporary with `call`.
+                    Some((object, property, computed)) if *optional => {
+                        let (object, this) = self.reuse(gc, object);
+                        let callee = key_member(gc, object, property, computed, range);
+                        let callee = self.check(gc, &mut chain.checks, callee);
+                        method_call(gc, callee, this.node(gc), arguments, range)
+                    }
+                    _ => {
+                        let callee = if *optional {
+                            self.check(gc, &mut chain.checks, chain.value)
+                        } else {
+                            chain.value
+                        };
+                        call(gc, callee, arguments.clone(), range)
+                    }
+                };
+                chain
+            }
+            _ => Chain {
+                checks: NodeList::new(),
+                value: node,
+            },
+        }
+    }
+
+    /// Add the tests of `value` being nullish to `checks`, and return the expression to use
+    /// instead of `value` afterwards.
+    fn check<'gc>(
+        &mut self,
+        gc: &'gc GCContext,
+        checks: &mut NodeList<'gc>,
+        value: &'gc Node<'gc>,
+    ) -> &'gc Node<'gc> {
+        let (value, reference) = self.reuse(gc, value);
+        checks.push(compare(
+            gc,
+            BinaryExpressionOperator::StrictEquals,
+            value,
+            Value::Null,
+        ));
+        checks.push(compare(
+            gc,
+            BinaryExpressionOperator::StrictEquals,
+            reference.node(gc),
+            Value::Undefined,
+        ));
+        reference.node(gc)
+    }
+
+    /// Return the expression evaluating `value` the first time, and how to evaluate it again.
+    /// `this` and identifiers are used as they are, `super` is reused as `this`, and other
+    /// values are stored in a temporary.
+    fn reuse<'gc>(
+        &mut self,
+        gc: &'gc GCContext,
+        value: &'gc Node<'gc>,
+    ) -> (&'gc Node<'gc>, Reusable) {
+        let range = *value.range();
+        match value {
+            Node::ThisExpression(_) | Node::Super(_) => (value, Reusable::This(range)),
+            Node::Identifier(Identifier { name, .. }) => (value, Reusable::Name(*name, range)),
+            _ => {
+                let base = match value {
+                    Node::MemberExpression(MemberExpression {
+                        property: Node::Identifier(Identifier { name, .. }),
+                        computed: false,
+                        ..
+                    }) => format!("_{}", gc.str(*name)),
+                    _ => "_ref".to_string(),
+                };
+                let temp = self.temps.generate(gc, &base);
+                (
+                    assign(gc, identifier(gc, temp, range), value, range),
+                    Reusable::Name(temp, range),
+                )
+            }
+        }
+    }
+}
+
+/// Return true if `node` is a link of the optional chain of `parent`, or if `parent` lowers the
+/// optional chain `node` itself.
+fn is_chain_parent<'gc>(parent: &'gc Node<'gc>, node: &'gc Node<'gc>) -> bool {
+    match parent {
+        Node::OptionalMemberExpression(OptionalMemberExpression { object: child, .. })
+        | Node::OptionalCallExpression(OptionalCallExpression { callee: child, .. })
+        | Node::CallExpression(CallExpression { callee: child, .. })
+        | Node::UnaryExpression(UnaryExpression {
+            operator: UnaryExpressionOperator::Delete,
+            argument: child,
+            ..
+        }) => std::ptr::eq(*child, node),
+        _ => false,
+    }
+}
+
+/// Return the object, property and whether it is computed, if `node` is a member expression.
+fn split_member<'gc>(node: &'gc Node<'gc>) -> Option<(&'gc Node<'gc>, &'gc Node<'gc>, bool)> {
+    match node {
+        Node::MemberExpression(MemberExpression {
+            object,
+            property,
+            computed,
+            ..
+        }) => Some((object, property, *computed)),
+        _ => None,
+    }
+}
+
+/// Return `callee.call(this, arguments)`.
+fn method_call<'gc>(
+    gc: &'gc GCContext,
+    callee: &'gc Node<'gc>,
+    this: &'gc Node<'gc>,
+    arguments: &[&'gc Node<'gc>],
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    let mut args = vec![this];
+    args.extend_from_slice(arguments);
+    call(gc, member(gc, callee, gc.atom("call"), range), args, range)
+}
+
+/// Return `value op null` or `value op void 0`.
+fn compare<'gc>(
+    gc: &'gc GCContext,
+    operator: BinaryExpressionOperator,
+    value: &'gc Node<'gc>,
+    nullish: Value,
+) -> &'gc Node<'gc> {
+    let range = *value.range();
+    let nullish = match nullish {
+        Value::Undefined => undefined(gc, range),
+        _ => literal(gc, nullish, range).unwrap(),
+    };
+    binary(gc, operator, value, nullish, range)
+}
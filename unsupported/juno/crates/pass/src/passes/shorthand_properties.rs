@@ -0,0 +1,128 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Shorthand property lowering.
+//!
+//! Expands shorthand properties of object literals and patterns and compiles methods to
+//! properties whose values are function expressions.
+//! Methods using `super` can't be lowered and are reported as errors.
+//!
+//! Transforms
+//! ```js
+//! ({a, m() {}});
+//! ```
+//! into
+//! ```js
+//! ({a: a, m: function () {}});
+//! ```
+
+use crate::passes::util::identifier;
+use crate::Pass;
+use juno::ast::*;
+
+#[derive(Default)]
+pub struct ShorthandProperties {}
+
+impl ShorthandProperties {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Pass for ShorthandProperties {
+    fn name(&self) -> &'static str {
+        "Lower shorthand properties"
+    }
+    fn description(&self) -> &'static str {
+        "Expands shorthand properties and methods of object literals"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        VisitorMut::call(self, gc, node, None)
+    }
+}
+
+impl<'gc> VisitorMut<'gc> for ShorthandProperties {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        _parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let (node, changed) = match node.visit_children_mut(NodeBuilder::from_node(node), gc, self)
+        {
+            TransformResult::Changed(new_node) => (new_node, true),
+            TransformResult::Unchanged => (node, false),
+        };
+        match node {
+            // The key of a shorthand property is the same node as its value, so a new key is
+            // created.
+            Node::Property(
+                prop @ Property {
+                    key: key @ Node::Identifier(Identifier { name, .. }),
+                    shorthand: true,
+                    ..
+                },
+            ) => {
+                let mut builder = PropertyBuilder::from_node(prop);
+                builder.key(identifier(gc, *name, *key.range()));
+                builder.shorthand(false);
+                TransformResult::Changed(builder.build(gc).unwrap())
+            }
+            Node::Property(
+                prop @ Property {
+                    value,
+                    method: true,
+                    ..
+                },
+            ) => {
+                if let Some(range) = find_super(gc, value) {
+                    gc.sm()
+                        .error(range, "methods using 'super' can't be lowered");
+                    return TransformResult::Unchanged;
+                }
+                let mut builder = PropertyBuilder::from_node(prop);
+                builder.method(false);
+                TransformResult::Changed(builder.build(gc).unwrap())
+            }
+            _ if changed => TransformResult::Changed(node),
+            _ => TransformResult::Unchanged,
+        }
+    }
+}
+
+/// Return the range of a `super` referring to the home object of the method `func`, if any.
+fn find_super<'gc>(gc: &'gc GCContext, func: &'gc Node<'gc>) -> Option<SourceRange> {
+    struct Finder {
+        found: Option<SourceRange>,
+    }
+    impl<'gc> Visitor<'gc> for Finder {
+        fn call(
+            &mut self,
+            gc: &'gc GCContext,
+            node: &'gc Node<'gc>,
+            _parent: Option<&'gc Node<'gc>>,
+        ) {
+            match node {
+                Node::Super(Super { metadata }) => self.found = Some(metadata.range),
+                // Only arrow functions share the home object of the method.
+                Node::FunctionExpression(_)
+                | Node::FunctionDeclaration(_)
+                | Node::ClassDeclaration(_)
+                | Node::ClassExpression(_) => {}
+                _ if self.found.is_none() => node.visit_children(gc, self),
+                _ => {}
+            }
+        }
+    }
+    let mut finder = Finder { found: None };
+    func.visit_children(gc, &mut finder);
+    finder.found
+}
@@ -0,0 +1,508 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Spread and rest lowering.
+//!
+//! Compiles spread elements in array literals, calls and `new` expressions to array
+//! concatenation and `Function.prototype.apply`, spread properties to calls of an `Object.assign`
+//! helper declared at the top of the program, and rest parameters to a slice of `arguments`.
+//! Spread values are converted to arrays by another helper, with `Array.from` in engines
+//! supporting iterators, and as array-like objects otherwise.
+//!
+//! Spread arguments of `super` calls are left to class lowering. Rest parameters of arrow
+//! functions, which don't have their own `arguments`, are reported as errors and must be lowered
+//! after the arrow functions.
+//!
+//! Transforms
+//! ```js
+//! function f(a, ...rest) {
+//!   return [a, ...rest, o.m(...rest), {...a}];
+//! }
+//! ```
+//! into
+//! ```js
+//! function _toArray(value) {...}
+//! function _extends(target) {...}
+//! function f(a) {
+//!   var rest = Array.prototype.slice.call(arguments, 1);
+//!   return [a].concat(_toArray(rest), [o.m.apply(o, _toArray(rest)), _extends({}, a)]);
+//! }
+//! ```
+
+use crate::passes::const_fold::{literal, Value};
+use crate::passes::util::*;
+use crate::Pass;
+use juno::ast::*;
+
+#[derive(Default)]
+pub struct Spread {}
+
+impl Spread {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Pass for Spread {
+    fn name(&self) -> &'static str {
+        "Lower spread"
+    }
+    fn description(&self) -> &'static str {
+        "Compiles spread elements and rest parameters to array and object operations"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let mut lowerer = Lowerer {
+            temps: Temporaries::new(UniqueNames::new(gc, node)),
+            helper: None,
+            array_helper: None,
+        };
+        VisitorMut::call(&mut lowerer, gc, node, None)
+    }
+}
+
+struct Lowerer {
+    temps: Temporaries,
+    /// Name of the helper assigning the spread properties, once it is used.
+    helper: Option<Atom>,
+    /// Name of the helper converting spread values to arrays, once it is used.
+    array_helper: Option<Atom>,
+}
+
+impl<'gc> VisitorMut<'gc> for Lowerer {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        _parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let is_scope = is_var_scope(node);
+        if is_scope {
+            self.temps.enter();
+        }
+        let (mut node, mut changed) =
+            match node.visit_children_mut(NodeBuilder::from_node(node), gc, self) {
+                TransformResult::Changed(new_node) => (new_node, true),
+                TransformResult::Unchanged => (node, false),
+            };
+        if is_scope {
+            if let Some(new_node) = self.temps.exit(gc, node) {
+                node = new_node;
+                changed = true;
+            }
+        }
+        if let (Node::Program(_), Some(helper)) = (node, self.helper) {
+            node = prepend_to_body(gc, node, vec![extends_helper(gc, helper, *node.range())]);
+            changed = true;
+        }
+        if let (Node::Program(_), Some(helper)) = (node, self.array_helper) {
+            node = prepend_to_body(gc, node, vec![to_array_helper(gc, helper, *node.range())]);
+            changed = true;
+        }
+        match self.lower(gc, node) {
+            Some(new_node) => TransformResult::Changed(new_node),
+            None if changed => TransformResult::Changed(node),
+            None => TransformResult::Unchanged,
+        }
+    }
+}
+
+impl Lowerer {
+    /// Return the object containing `properties`, some of which are spread, e.g.
+    /// `_extends({a: 1}, b)`.
+    fn assign_properties<'gc>(
+        &mut self,
+        gc: &'gc GCContext,
+        properties: &[&'gc Node<'gc>],
+        range: SourceRange,
+    ) -> &'gc Node<'gc> {
+        let mut arguments = NodeList::new();
+        let mut current = NodeList::new();
+        for &prop in properties {
+            match prop {
+                Node::SpreadElement(SpreadElement { argument, .. }) => {
+                    if !current.is_empty() || arguments.is_empty() {
+                        arguments.push(object(gc, std::mem::take(&mut current), range));
+                    }
+                    arguments.push(argument);
+                }
+                _ => current.push(prop),
+            }
+        }
+        if !current.is_empty() {
+            arguments.push(object(gc, current, range));
+        }
+        let helper = match self.helper {
+            Some(name) => name,
+            None => *self
+                .helper
+                .insert(self.temps.names.generate(gc, "_extends")),
+        };
+        call(gc, identifier(gc, helper, range), arguments, range)
+    }
+
+    /// Return the lowered `node`, whose children have already been lowered, or `None` if it
+    /// doesn't contain spread elements or rest parameters.
+    fn lower<'gc>(&mut self, gc: &'gc GCContext, node: &'gc Node<'gc>) -> Option<&'gc Node<'gc>> {
+        let range = *node.range();
+        match node {
+            Node::ArrayExpression(ArrayExpression { elements, .. }) if has_spread(elements) => {
+                Some(self.concat(gc, elements, range))
+            }
+            Node::ObjectExpression(ObjectExpression { properties, .. })
+                if has_spread(properties) =>
+            {
+                Some(self.assign_properties(gc, properties, range))
+            }
+            // `super(...args)` is lowered with the class.
+            Node::CallExpression(CallExpression {
+                callee: Node::Super(_),
+                ..
+            }) => None,
+            Node::CallExpression(CallExpression {
+                callee, arguments, ..
+            }) if has_spread(arguments) => {
+                let (callee, this) = match callee {
+                    Node::MemberExpression(MemberExpression {
+                        object: Node::Super(_),
+                        ..
+                    }) => (*callee, this_expression(gc, range)),
+                    Node::MemberExpression(MemberExpression {
+                        object: object @ Node::Identifier(Identifier { name, .. }),
+                        ..
+                    }) => (*callee, identifier(gc, *name, *object.range())),
+                    Node::MemberExpression(MemberExpression {
+                        object: object @ Node::ThisExpression(_),
+                        ..
+                    }) => (*callee, this_expression(gc, *object.range())),
+                    // Evaluate the object once.
+                    Node::MemberExpression(member @ MemberExpression { object, .. }) => {
+                        let temp = self.temps.generate(gc, "_obj");
+                        let object_range = *object.range();
+                        let mut builder = MemberExpressionBuilder::from_node(member);
+                        builder.object(assign(
+                            gc,
+                            identifier(gc, temp, object_range),
+                            object,
+                            object_range,
+                        ));
+                        (
+                            builder.build(gc).unwrap(),
+                            identifier(gc, temp, object_range),
+                        )
+                    }
+                    _ => (*callee, undefined(gc, range)),
+                };
+                Some(call(
+                    gc,
+                    member(gc, callee, gc.atom("apply"), range),
+                    vec![this, self.apply_arguments(gc, arguments, range)],
+                    range,
+                ))
+            }
+            // `new (Function.prototype.bind.apply(F, [null].concat(args)))()`
+            Node::NewExpression(NewExpression {
+                callee, arguments, ..
+            }) if has_spread(arguments) => {
+                let mut bind_arguments = vec![literal(gc, Value::Null, range).unwrap()];
+                bind_arguments.extend(arguments);
+                let bound = call(
+                    gc,
+                    dotted_name(gc, "Function.prototype.bind.apply", range),
+                    vec![callee, self.concat(gc, &bind_arguments, range)],
+                    range,
+                );
+                Some(new_expression(gc, bound, vec![], range))
+            }
+            Node::ArrowFunctionExpression(ArrowFunctionExpression { params, .. }) => {
+                if let Some(rest @ Node::RestElement(_)) = params.last() {
+                    gc.sm().error(
+                        *rest.range(),
+                        "rest parameters of arrow functions can't be lowered",
+                    );
+                }
+                None
+            }
+            Node::FunctionDeclaration(FunctionDeclaration { params, .. })
+            | Node::FunctionExpression(FunctionExpression { params, .. }) => {
+                let (argument, range) = match params.last() {
+                    Some(Node::RestElement(RestElement { metadata, argument })) => {
+                        (*argument, metadata.range)
+                    }
+                    _ => return None,
+                };
+                let start = params.len() - 1;
+                let decl = declaration(
+                    gc,
+                    VariableDeclarationKind::Var,
+                    [(
+                        argument,
+                        Some(array_slice(
+                            gc,
+                            identifier(gc, gc.atom("arguments"), range),
+                            start,
+                            range,
+                        )),
+                    )],
+                    range,
+                );
+                let node = prepend_to_body(gc, node, vec![decl]);
+                Some(match node {
+                    Node::FunctionDeclaration(func) => {
+                        let mut builder = FunctionDeclarationBuilder::from_node(func);
+                        builder.params(func.params[..start].to_vec());
+                        builder.build(gc).unwrap()
+                    }
+                    Node::FunctionExpression(func) => {
+                        let mut builder = FunctionExpressionBuilder::from_node(func);
+                        builder.params(func.params[..start].to_vec());
+                        builder.build(gc).unwrap()
+                    }
+                    _ => unreachable!(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the array containing `elements`, some of which are spread, e.g.
+    /// `[a].concat(_toArray(b))`.
+    fn concat<'gc>(
+        &mut self,
+        gc: &'gc GCContext,
+        elements: &[&'gc Node<'gc>],
+        range: SourceRange,
+    ) -> &'gc Node<'gc> {
+        let mut segments = NodeList::new();
+        let mut current = NodeList::new();
+        for &elem in elements {
+            match elem {
+                Node::SpreadElement(SpreadElement { metadata, argument }) => {
+                    if !current.is_empty() {
+                        segments.push(array(gc, std::mem::take(&mut current), range));
+                    }
+                    let helper = match self.array_helper {
+                        Some(name) => name,
+                        None => *self
+                            .array_helper
+                            .insert(self.temps.names.generate(gc, "_toArray")),
+                    };
+                    segments.push(call(
+                        gc,
+                        identifier(gc, helper, metadata.range),
+                        vec![argument],
+                        metadata.range,
+                    ));
+                }
+                _ => current.push(elem),
+            }
+        }
+        if !current.is_empty() {
+            segments.push(array(gc, current, range));
+        }
+        let first = segments.remove(0);
+        if segments.is_empty() {
+            return first;
+        }
+        call(
+            gc,
+            member(gc, first, gc.atom("concat"), range),
+            segments,
+            range,
+        )
+    }
+
+    /// Return the array passed to `apply` for the call `arguments`. Spread values other than array
+    /// literals are copied, since `apply` doesn't accept strings.
+    fn apply_arguments<'gc>(
+        &mut self,
+        gc: &'gc GCContext,
+        arguments: &[&'gc Node<'gc>],
+        range: SourceRange,
+    ) -> &'gc Node<'gc> {
+        match arguments {
+            [Node::SpreadElement(SpreadElement {
+                argument: argument @ Node::ArrayExpression(_),
+                ..
+            })] => argument,
+            _ => self.concat(gc, arguments, range),
+        }
+    }
+}
+
+fn has_spread(list: &NodeList) -> bool {
+    list.iter()
+        .any(|elem| matches!(elem, Node::SpreadElement(_)))
+}
+
+/// Return the declaration of the helper `name`, which copies the own enumerable properties of
+/// its other arguments to its first argument, like `Object.assign`.
+fn extends_helper<'gc>(gc: &'gc GCContext, name: Atom, range: SourceRange) -> &'gc Node<'gc> {
+    let id = |name: &str| identifier(gc, gc.atom(name), range);
+    let var = |name: &str, init| {
+        declaration(
+            gc,
+            VariableDeclarationKind::Var,
+            [(id(name), Some(init))],
+            range,
+        )
+    };
+
+    // if (Object.prototype.hasOwnProperty.call(source, key)) target[key] = source[key];
+    let copy = if_statement(
+        gc,
+        call(
+            gc,
+            dotted_name(gc, "Object.prototype.hasOwnProperty.call", range),
+            vec![id("source"), id("key")],
+            range,
+        ),
+        block(
+            gc,
+            vec![expression_statement(
+                gc,
+                assign(
+                    gc,
+                    computed_member(gc, id("target"), id("key"), range),
+                    computed_member(gc, id("source"), id("key"), range),
+                    range,
+                ),
+            )],
+            range,
+        ),
+        range,
+    );
+    let for_keys = ForInStatementBuilder::build_template(
+        gc,
+        ForInStatementTemplate {
+            metadata: template_metadata(range),
+            left: declaration(gc, VariableDeclarationKind::Var, [(id("key"), None)], range),
+            right: id("source"),
+            body: block(gc, vec![copy], range),
+        },
+    );
+    let for_sources = ForStatementBuilder::build_template(
+        gc,
+        ForStatementTemplate {
+            metadata: template_metadata(range),
+            init: Some(var("i", number(gc, 1.0, range))),
+            test: Some(binary(
+                gc,
+                BinaryExpressionOperator::Less,
+                id("i"),
+                dotted_name(gc, "arguments.length", range),
+                range,
+            )),
+            update: Some(UpdateExpressionBuilder::build_template(
+                gc,
+                UpdateExpressionTemplate {
+                    metadata: template_metadata(range),
+                    operator: UpdateExpressionOperator::Increment,
+                    argument: id("i"),
+                    prefix: false,
+                },
+            )),
+            body: block(
+                gc,
+                vec![
+                    var(
+                        "source",
+                        computed_member(gc, id("arguments"), id("i"), range),
+                    ),
+                    for_keys,
+                ],
+                range,
+            ),
+        },
+    );
+    FunctionDeclarationBuilder::build_template(
+        gc,
+        FunctionDeclarationTemplate {
+            metadata: template_metadata(range),
+            id: Some(identifier(gc, name, range)),
+            params: vec![id("target")],
+            body: block(
+                gc,
+                vec![for_sources, return_statement(gc, Some(id("target")), range)],
+                range,
+            ),
+            type_parameters: None,
+            return_type: None,
+            predicate: None,
+            generator: false,
+            is_async: false,
+        },
+    )
+}
+
+/// Return the declaration of the helper `name`, which converts an iterable or array-like object
+/// to an array:
+/// ```js
+/// function _toArray(value) {
+///   return typeof Symbol === "function" && value[Symbol.iterator] ? Array.from(value)
+///     : Array.prototype.slice.call(value);
+/// }
+/// ```
+fn to_array_helper<'gc>(gc: &'gc GCContext, name: Atom, range: SourceRange) -> &'gc Node<'gc> {
+    let id = |name: &str| identifier(gc, gc.atom(name), range);
+    let type_of = UnaryExpressionBuilder::build_template(
+        gc,
+        UnaryExpressionTemplate {
+            metadata: template_metadata(range),
+            operator: UnaryExpressionOperator::Typeof,
+            argument: id("Symbol"),
+            prefix: true,
+        },
+    );
+    let is_iterable = logical(
+        gc,
+        LogicalExpressionOperator::And,
+        binary(
+            gc,
+            BinaryExpressionOperator::StrictEquals,
+            type_of,
+            string(gc, "function", range),
+            range,
+        ),
+        computed_member(
+            gc,
+            id("value"),
+            dotted_name(gc, "Symbol.iterator", range),
+            range,
+        ),
+        range,
+    );
+    let result = conditional(
+        gc,
+        is_iterable,
+        call(
+            gc,
+            dotted_name(gc, "Array.from", range),
+            vec![id("value")],
+            range,
+        ),
+        array_slice(gc, id("value"), 0, range),
+        range,
+    );
+    FunctionDeclarationBuilder::build_template(
+        gc,
+        FunctionDeclarationTemplate {
+            metadata: template_metadata(range),
+            id: Some(identifier(gc, name, range)),
+            params: vec![id("value")],
+            body: block(gc, vec![return_statement(gc, Some(result), range)], range),
+            type_parameters: None,
+            return_type: None,
+            predicate: None,
+            generator: false,
+            is_async: false,
+        },
+    )
+}
@@ -0,0 +1,657 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Flow and TypeScript type stripping.
+//!
+//! Removes type annotations, type parameters and arguments, type casts, type declarations and
+//! type-only imports and exports, producing plain JavaScript.
+//! In TypeScript, imports which are only used as types and exports of type names are removed too.
+//! TypeScript parameter properties are lowered to assignments at the start of the constructor.
+//! Enums and namespaces, which generate code, are reported as errors.
+//!
+//! Transforms
+//! ```js
+//! import type {T} from "t";
+//! type U = Array<T>;
+//! export function f<V>(x: U, y?: V): number {
+//!   return ((x: any): number);
+//! }
+//! ```
+//! into
+//! ```js
+//! export function f(x, y) {
+//!   return x;
+//! }
+//! ```
+
+use crate::passes::util::{statement_list, with_statement_list};
+use crate::Pass;
+use juno::ast::*;
+use juno::sema::{is_type, resolve_program, BindingKind, Resolution};
+use std::collections::HashSet;
+
+pub struct StripTypes {
+    /// Whether the input is TypeScript, where imports aren't marked as types, so imports which
+    /// are only used as types are removed as well.
+    typescript: bool,
+}
+
+impl StripTypes {
+    pub fn new(typescript: bool) -> Self {
+        StripTypes { typescript }
+    }
+}
+
+impl Pass for StripTypes {
+    fn name(&self) -> &'static str {
+        "Strip types"
+    }
+    fn description(&self) -> &'static str {
+        "Removes Flow and TypeScript type syntax"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let unused = if self.typescript {
+            unused_imports(gc, node)
+        } else {
+            HashSet::new()
+        };
+        VisitorMut::call(&mut Stripper { unused }, gc, node, None)
+    }
+}
+
+struct Stripper<'gc> {
+    /// The local identifiers of the imports which are never used as values, and of the
+    /// exports of names which are only types.
+    unused: HashSet<NodeRef<'gc>>,
+}
+
+impl<'gc> VisitorMut<'gc> for Stripper<'gc> {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        _parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        // Types are removed by their parents, so there's no need to visit them.
+        if is_type(node) {
+            return TransformResult::Unchanged;
+        }
+        let (node, changed) = match node.visit_children_mut(NodeBuilder::from_node(node), gc, self)
+        {
+            TransformResult::Changed(new_node) => (new_node, true),
+            TransformResult::Unchanged => (node, false),
+        };
+        let (node, changed) = match self.strip_unused(gc, node) {
+            TransformResult::Changed(new_node) => (new_node, true),
+            TransformResult::Unchanged => (node, changed),
+        };
+        match strip(gc, node) {
+            TransformResult::Changed(new_node) => TransformResult::Changed(new_node),
+            TransformResult::Unchanged if changed => TransformResult::Changed(node),
+            TransformResult::Unchanged => TransformResult::Unchanged,
+        }
+    }
+}
+
+impl<'gc> Stripper<'gc> {
+    /// Return `node` without its unused import or export specifiers. Declarations left without
+    /// specifiers are marked as type-only so that they are removed.
+    fn strip_unused(
+        &self,
+        gc: &'gc GCContext,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let is_unused = |spec: &&Node<'gc>| match spec {
+            Node::ImportSpecifier(ImportSpecifier { local, .. })
+            | Node::ImportDefaultSpecifier(ImportDefaultSpecifier { local, .. })
+            | Node::ImportNamespaceSpecifier(ImportNamespaceSpecifier { local, .. })
+            | Node::ExportSpecifier(ExportSpecifier { local, .. }) => {
+                self.unused.contains(&NodeRef(local))
+            }
+            _ => false,
+        };
+        match node {
+            Node::ImportDeclaration(import @ ImportDeclaration { specifiers, .. })
+                if specifiers.iter().any(is_unused) =>
+            {
+                let mut builder = ImportDeclarationBuilder::from_node(import);
+                if specifiers.iter().all(is_unused) {
+                    builder.import_kind(ImportKind::Type);
+                } else {
+                    builder.specifiers(
+                        specifiers
+                            .iter()
+                            .copied()
+                            .filter(|s| !is_unused(s))
+                            .collect(),
+                    );
+                }
+                builder.build(gc)
+            }
+            Node::ExportNamedDeclaration(export @ ExportNamedDeclaration { specifiers, .. })
+                if specifiers.iter().any(is_unused) =>
+            {
+                let mut builder = ExportNamedDeclarationBuilder::from_node(export);
+                if specifiers.iter().all(is_unused) {
+                    builder.export_kind(ExportKind::Type);
+                } else {
+                    builder.specifiers(
+                        specifiers
+                            .iter()
+                            .copied()
+                            .filter(|s| !is_unused(s))
+                            .collect(),
+                    );
+                }
+                builder.build(gc)
+            }
+            _ => TransformResult::Unchanged,
+        }
+    }
+}
+
+/// Return the local identifiers of the imports in `program` which are never used as values,
+/// and of the exports of names which are only declared as types.
+/// Like the TypeScript compiler with `isolatedModules`, re-exporting an import counts as a use,
+/// since it can't be known whether the imported name is a type.
+fn unused_imports<'gc>(gc: &'gc GCContext, program: &'gc Node<'gc>) -> HashSet<NodeRef<'gc>> {
+    let body = match program {
+        Node::Program(Program { body, .. }) => body,
+        _ => return HashSet::new(),
+    };
+    let sem = resolve_program(gc, program);
+    let mut unused = HashSet::new();
+    for (_, binding) in sem.bindings() {
+        if binding.kind == BindingKind::Import && binding.references.is_empty() {
+            unused.extend(binding.declarations.iter().map(|&decl| NodeRef(decl)));
+        }
+    }
+
+    // Types aren't bindings, so exported type names resolve to globals.
+    let mut types = HashSet::new();
+    for stmt in body {
+        let decl = match stmt {
+            Node::ExportNamedDeclaration(ExportNamedDeclaration {
+                declaration: Some(decl),
+                ..
+            }) => decl,
+            _ => stmt,
+        };
+        if let Node::TSTypeAliasDeclaration(TSTypeAliasDeclaration {
+            id: Node::Identifier(Identifier { name, .. }),
+            ..
+        })
+        | Node::TSInterfaceDeclaration(TSInterfaceDeclaration {
+            id: Node::Identifier(Identifier { name, .. }),
+            ..
+        }) = decl
+        {
+            types.insert(*name);
+        }
+    }
+    for stmt in body {
+        if let Node::ExportNamedDeclaration(ExportNamedDeclaration {
+            specifiers,
+            source: None,
+            ..
+        }) = stmt
+        {
+            for spec in specifiers {
+                if let Node::ExportSpecifier(ExportSpecifier { local, .. }) = spec {
+                    if let Some(Resolution::Global(name)) = sem.resolution(local) {
+                        if types.contains(&name) {
+                            unused.insert(NodeRef(local));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    unused
+}
+
+/// Return `node`, whose children have already been stripped, without its own type syntax.
+fn strip<'gc>(gc: &'gc GCContext, node: &'gc Node<'gc>) -> TransformResult<&'gc Node<'gc>> {
+    match node {
+        Node::TypeCastExpression(TypeCastExpression { expression, .. })
+        | Node::TSAsExpression(TSAsExpression { expression, .. })
+        | Node::TSTypeAssertion(TSTypeAssertion { expression, .. }) => {
+            TransformResult::Changed(expression)
+        }
+        Node::EnumDeclaration(_) | Node::TSEnumDeclaration(_) => {
+            gc.sm()
+                .error(*node.range(), "enums generate code and can't be stripped");
+            TransformResult::Unchanged
+        }
+        Node::TSModuleDeclaration(_) => {
+            gc.sm().error(
+                *node.range(),
+                "namespaces generate code and can't be stripped",
+            );
+            TransformResult::Unchanged
+        }
+
+        Node::Identifier(
+            ident @ Identifier {
+                type_annotation,
+                optional,
+                ..
+            },
+        ) if type_annotation.is_some() || *optional => {
+            let mut builder = IdentifierBuilder::from_node(ident);
+            builder.type_annotation(None);
+            builder.optional(false);
+            builder.build(gc)
+        }
+        Node::ObjectPattern(
+            pattern @ ObjectPattern {
+                type_annotation: Some(_),
+                ..
+            },
+        ) => {
+            let mut builder = ObjectPatternBuilder::from_node(pattern);
+            builder.type_annotation(None);
+            builder.build(gc)
+        }
+        Node::ArrayPattern(
+            pattern @ ArrayPattern {
+                type_annotation: Some(_),
+                ..
+            },
+        ) => {
+            let mut builder = ArrayPatternBuilder::from_node(pattern);
+            builder.type_annotation(None);
+            builder.build(gc)
+        }
+
+        Node::FunctionExpression(FunctionExpression {
+            params,
+            type_parameters,
+            return_type,
+            predicate,
+            ..
+        })
+        | Node::ArrowFunctionExpression(ArrowFunctionExpression {
+            params,
+            type_parameters,
+            return_type,
+            predicate,
+            ..
+        })
+        | Node::FunctionDeclaration(FunctionDeclaration {
+            params,
+            type_parameters,
+            return_type,
+            predicate,
+            ..
+        }) => {
+            // The `this` parameter only declares the type of `this`.
+            let has_this = matches!(
+                params.first(),
+                Some(Node::Identifier(Identifier { name, .. })) if gc.str(*name) == "this"
+            );
+            if !has_this
+                && type_parameters.is_none()
+                && return_type.is_none()
+                && predicate.is_none()
+            {
+                return TransformResult::Unchanged;
+            }
+            let params = if has_this {
+                params[1..].to_vec()
+            } else {
+                params.clone()
+            };
+            match node {
+                Node::FunctionExpression(func) => {
+                    let mut builder = FunctionExpressionBuilder::from_node(func);
+                    builder.params(params);
+                    builder.type_parameters(None);
+                    builder.return_type(None);
+                    builder.predicate(None);
+                    builder.build(gc)
+                }
+                Node::ArrowFunctionExpression(func) => {
+                    let mut builder = ArrowFunctionExpressionBuilder::from_node(func);
+                    builder.params(params);
+                    builder.type_parameters(None);
+                    builder.return_type(None);
+                    builder.predicate(None);
+                    builder.build(gc)
+                }
+                Node::FunctionDeclaration(func) => {
+                    let mut builder = FunctionDeclarationBuilder::from_node(func);
+                    builder.params(params);
+                    builder.type_parameters(None);
+                    builder.return_type(None);
+                    builder.predicate(None);
+                    builder.build(gc)
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        Node::CallExpression(
+            call @ CallExpression {
+                type_arguments: Some(_),
+                ..
+            },
+        ) => {
+            let mut builder = CallExpressionBuilder::from_node(call);
+            builder.type_arguments(None);
+            builder.build(gc)
+        }
+        Node::OptionalCallExpression(
+            call @ OptionalCallExpression {
+                type_arguments: Some(_),
+                ..
+            },
+        ) => {
+            let mut builder = OptionalCallExpressionBuilder::from_node(call);
+            builder.type_arguments(None);
+            builder.build(gc)
+        }
+        Node::NewExpression(
+            new @ NewExpression {
+                type_arguments: Some(_),
+                ..
+            },
+        ) => {
+            let mut builder = NewExpressionBuilder::from_node(new);
+            builder.type_arguments(None);
+            builder.build(gc)
+        }
+
+        Node::ClassDeclaration(ClassDeclaration {
+            type_parameters,
+            super_type_parameters,
+            implements,
+            ..
+        })
+        | Node::ClassExpression(ClassExpression {
+            type_parameters,
+            super_type_parameters,
+            implements,
+            ..
+        }) => {
+            if type_parameters.is_none() && super_type_parameters.is_none() && implements.is_empty()
+            {
+                return TransformResult::Unchanged;
+            }
+            match node {
+                Node::ClassDeclaration(class) => {
+                    let mut builder = ClassDeclarationBuilder::from_node(class);
+                    builder.type_parameters(None);
+                    builder.super_type_parameters(None);
+                    builder.implements(NodeList::new());
+                    builder.build(gc)
+                }
+                Node::ClassExpression(class) => {
+                    let mut builder = ClassExpressionBuilder::from_node(class);
+                    builder.type_parameters(None);
+                    builder.super_type_parameters(None);
+                    builder.implements(NodeList::new());
+                    builder.build(gc)
+                }
+                _ => unreachable!(),
+            }
+        }
+        Node::ClassBody(class_body @ ClassBody { body, .. }) => {
+            // `declare` fields only declare a type.
+            let is_declare = |member: &&Node| {
+                matches!(
+                    member,
+                    Node::ClassProperty(ClassProperty { declare: true, .. })
+                        | Node::ClassPrivateProperty(ClassPrivateProperty { declare: true, .. })
+                )
+            };
+            if !body.iter().any(is_declare) {
+                return TransformResult::Unchanged;
+            }
+            let mut builder = ClassBodyBuilder::from_node(class_body);
+            builder.body(body.iter().copied().filter(|m| !is_declare(m)).collect());
+            builder.build(gc)
+        }
+        Node::ClassProperty(
+            prop @ ClassProperty {
+                optional,
+                variance,
+                type_annotation,
+                ..
+            },
+        ) if *optional || variance.is_some() || type_annotation.is_some() => {
+            let mut builder = ClassPropertyBuilder::from_node(prop);
+            builder.optional(false);
+            builder.variance(None);
+            builder.type_annotation(None);
+            builder.build(gc)
+        }
+        Node::ClassPrivateProperty(
+            prop @ ClassPrivateProperty {
+                optional,
+                variance,
+                type_annotation,
+                ..
+            },
+        ) if *optional || variance.is_some() || type_annotation.is_some() => {
+            let mut builder = ClassPrivatePropertyBuilder::from_node(prop);
+            builder.optional(false);
+            builder.variance(None);
+            builder.type_annotation(None);
+            builder.build(gc)
+        }
+        Node::MethodDefinition(
+            method @ MethodDefinition {
+                kind: MethodDefinitionKind::Constructor,
+                value,
+                ..
+            },
+        ) => match value {
+            Node::FunctionExpression(func) => match lower_parameter_properties(gc, func) {
+                Some(func) => {
+                    let mut builder = MethodDefinitionBuilder::from_node(method);
+                    builder.value(func);
+                    builder.build(gc)
+                }
+                None => TransformResult::Unchanged,
+            },
+            _ => TransformResult::Unchanged,
+        },
+
+        Node::ImportDeclaration(import @ ImportDeclaration { specifiers, .. }) => {
+            let is_type_specifier = |spec: &&Node| {
+                matches!(
+                    spec,
+                    Node::ImportSpecifier(ImportSpecifier {
+                        import_kind: ImportKind::Type | ImportKind::Typeof,
+                        ..
+                    })
+                )
+            };
+            if !specifiers.iter().any(is_type_specifier) {
+                return TransformResult::Unchanged;
+            }
+            let mut builder = ImportDeclarationBuilder::from_node(import);
+            if specifiers.iter().all(is_type_specifier) {
+                // Imports without specifiers are kept for their side effects, so mark the
+                // import as type-only to have it removed.
+                builder.import_kind(ImportKind::Type);
+            } else {
+                builder.specifiers(
+                    specifiers
+                        .iter()
+                        .copied()
+                        .filter(|spec| !is_type_specifier(spec))
+                        .collect(),
+                );
+            }
+            builder.build(gc)
+        }
+
+        _ => match statement_list(node) {
+            Some(list) if list.iter().any(|&stmt| is_type_statement(stmt)) => {
+                TransformResult::Changed(with_statement_list(
+                    gc,
+                    node,
+                    list.iter()
+                        .copied()
+                        .filter(|&stmt| !is_type_statement(stmt))
+                        .collect(),
+                ))
+            }
+            _ => TransformResult::Unchanged,
+        },
+    }
+}
+
+/// Return whether the statement `stmt` only declares, imports or exports types.
+fn is_type_statement(stmt: &Node) -> bool {
+    match stmt {
+        Node::ImportDeclaration(ImportDeclaration {
+            import_kind: ImportKind::Type | ImportKind::Typeof,
+            ..
+        }) => true,
+        Node::ExportNamedDeclaration(ExportNamedDeclaration {
+            export_kind: ExportKind::Type,
+            ..
+        })
+        | Node::ExportAllDeclaration(ExportAllDeclaration {
+            export_kind: ExportKind::Type,
+            ..
+        }) => true,
+        Node::ExportNamedDeclaration(ExportNamedDeclaration {
+            declaration: Some(declaration),
+            ..
+        })
+        | Node::ExportDefaultDeclaration(ExportDefaultDeclaration { declaration, .. }) => {
+            is_type(declaration)
+        }
+        _ => is_type(stmt),
+    }
+}
+
+/// Return a copy of the constructor `func` with its TypeScript parameter properties replaced
+/// by plain parameters, assigned to properties of `this` at the start of the body, after the
+/// `super()` call if there is one. Return `None` if there are no parameter properties.
+fn lower_parameter_properties<'gc>(
+    gc: &'gc GCContext,
+    func: &'gc FunctionExpression<'gc>,
+) -> Option<&'gc Node<'gc>> {
+    let FunctionExpression { params, body, .. } = func;
+    if !params
+        .iter()
+        .any(|param| matches!(param, Node::TSParameterProperty(_)))
+    {
+        return None;
+    }
+
+    let mut new_params = NodeList::with_capacity(params.len());
+    let mut assignments = NodeList::new();
+    for &param in params {
+        let param = match param {
+            Node::TSParameterProperty(TSParameterProperty { parameter, .. }) => parameter,
+            _ => {
+                new_params.push(param);
+                continue;
+            }
+        };
+        new_params.push(param);
+        let id = match param {
+            Node::AssignmentPattern(AssignmentPattern { left, .. }) => left,
+            _ => param,
+        };
+        let (name, range) = match id {
+            Node::Identifier(Identifier { metadata, name, .. }) => (*name, metadata.range),
+            _ => continue,
+        };
+        let metadata = || TemplateMetadata {
+            range,
+            ..Default::default()
+        };
+        let identifier = || {
+            IdentifierBuilder::build_template(
+                gc,
+                IdentifierTemplate {
+                    metadata: metadata(),
+                    name,
+                    type_annotation: None,
+                    optional: false,
+                },
+            )
+        };
+        let member = MemberExpressionBuilder::build_template(
+            gc,
+            MemberExpressionTemplate {
+                metadata: metadata(),
+                object: ThisExpressionBuilder::build_template(
+                    gc,
+                    ThisExpressionTemplate {
+                        metadata: metadata(),
+                    },
+                ),
+                property: identifier(),
+                computed: false,
+            },
+        );
+        let assignment = AssignmentExpressionBuilder::build_template(
+            gc,
+            AssignmentExpressionTemplate {
+                metadata: metadata(),
+                operator: AssignmentExpressionOperator::Assign,
+                left: member,
+                right: identifier(),
+            },
+        );
+        assignments.push(ExpressionStatementBuilder::build_template(
+            gc,
+            ExpressionStatementTemplate {
+                metadata: metadata(),
+                expression: assignment,
+                directive: None,
+            },
+        ));
+    }
+
+    let stmts = statement_list(body).unwrap();
+    let is_super_call = |stmt: &Node| {
+        matches!(
+            stmt,
+            Node::ExpressionStatement(ExpressionStatement {
+                expression: Node::CallExpression(CallExpression {
+                    callee: Node::Super(_),
+                    ..
+                }),
+                ..
+            })
+        )
+    };
+    let is_directive = |stmt: &Node| {
+        matches!(
+            stmt,
+            Node::ExpressionStatement(ExpressionStatement {
+                directive: Some(_),
+                ..
+            })
+        )
+    };
+    let position = match stmts.iter().position(|stmt| is_super_call(stmt)) {
+        Some(index) => index + 1,
+        None => stmts.iter().take_while(|stmt| is_directive(stmt)).count(),
+    };
+    let mut new_stmts = stmts[..position].to_vec();
+    new_stmts.extend(assignments);
+    new_stmts.extend_from_slice(&stmts[position..]);
+
+    let mut builder = FunctionExpressionBuilder::from_node(func);
+    builder.params(new_params);
+    builder.body(with_statement_list(gc, body, new_stmts));
+    Some(builder.build(gc).unwrap())
+}
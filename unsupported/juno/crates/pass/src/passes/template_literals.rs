@@ -0,0 +1,229 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Template literal lowering.
+//!
+//! Untagged templates are compiled to calls of `String.prototype.concat`, which converts the
+//! substitutions with `ToString` like templates do.
+//! Tagged templates call the tag with a frozen strings array, which has a frozen `raw` property
+//! and is created once per call site and cached in a top level variable.
+//!
+//! Transforms
+//! ```js
+//! `a${b}c`;
+//! tag`a${b}`;
+//! ```
+//! into
+//! ```js
+//! var _templateObject;
+//! "a".concat(b, "c");
+//! tag(_templateObject || (_templateObject = Object.freeze(Object.defineProperty(["a", ""],
+//!   "raw", {value: Object.freeze(["a", ""])}))), b);
+//! ```
+
+use crate::passes::const_fold::{literal, Value};
+use crate::passes::util::*;
+use crate::Pass;
+use juno::ast::*;
+
+#[derive(Default)]
+pub struct TemplateLiterals {}
+
+impl TemplateLiterals {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl Pass for TemplateLiterals {
+    fn name(&self) -> &'static str {
+        "Lower template literals"
+    }
+    fn description(&self) -> &'static str {
+        "Compiles template literals and tagged templates to string concatenation and calls"
+    }
+    fn run<'gc>(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let mut lowerer = Lowerer {
+            temps: Temporaries::new(UniqueNames::new(gc, node)),
+        };
+        VisitorMut::call(&mut lowerer, gc, node, None)
+    }
+}
+
+struct Lowerer {
+    temps: Temporaries,
+}
+
+impl<'gc> VisitorMut<'gc> for Lowerer {
+    fn call(
+        &mut self,
+        gc: &'gc GCContext<'_, '_>,
+        node: &'gc Node<'gc>,
+        parent: Option<&'gc Node<'gc>>,
+    ) -> TransformResult<&'gc Node<'gc>> {
+        let is_program = matches!(node, Node::Program(_));
+        if is_program {
+            self.temps.enter();
+        }
+        let (mut node, mut changed) =
+            match node.visit_children_mut(NodeBuilder::from_node(node), gc, self) {
+                TransformResult::Changed(new_node) => (new_node, true),
+                TransformResult::Unchanged => (node, false),
+            };
+        if is_program {
+            if let Some(new_node) = self.temps.exit(gc, node) {
+                node = new_node;
+                changed = true;
+            }
+        }
+        match node {
+            // The quasis of a tagged template are passed to the tag as they are.
+            Node::TemplateLiteral(_)
+                if matches!(parent, Some(Node::TaggedTemplateExpression(_))) =>
+            {
+                if changed {
+                    TransformResult::Changed(node)
+                } else {
+                    TransformResult::Unchanged
+                }
+            }
+            Node::TemplateLiteral(TemplateLiteral {
+                metadata,
+                quasis,
+                expressions,
+            }) => TransformResult::Changed(concat(gc, quasis, expressions, metadata.range)),
+            Node::TaggedTemplateExpression(TaggedTemplateExpression {
+                metadata,
+                tag,
+                quasi:
+                    Node::TemplateLiteral(TemplateLiteral {
+                        quasis,
+                        expressions,
+                        ..
+                    }),
+            }) => {
+                let range = metadata.range;
+                let cache = self.temps.generate_global(gc, "_templateObject");
+                let mut arguments = vec![logical(
+                    gc,
+                    LogicalExpressionOperator::Or,
+                    identifier(gc, cache, range),
+                    assign(
+                        gc,
+                        identifier(gc, cache, range),
+                        strings_object(gc, quasis, range),
+                        range,
+                    ),
+                    range,
+                )];
+                arguments.extend(expressions);
+                TransformResult::Changed(call(gc, tag, arguments, range))
+            }
+            _ if changed => TransformResult::Changed(node),
+            _ => TransformResult::Unchanged,
+        }
+    }
+}
+
+/// Return the string value of the template element `quasi`, which is `undefined` in tagged
+/// templates containing an invalid escape.
+fn cooked<'gc>(gc: &'gc GCContext, quasi: &'gc Node<'gc>) -> &'gc Node<'gc> {
+    match quasi {
+        Node::TemplateElement(TemplateElement {
+            metadata,
+            cooked: Some(cooked),
+            ..
+        }) => literal(gc, Value::String(cooked.str.clone()), metadata.range).unwrap(),
+        Node::TemplateElement(TemplateElement { metadata, .. }) => undefined(gc, metadata.range),
+        _ => unreachable!("invalid template element {}", quasi.name()),
+    }
+}
+
+/// Return the concatenation of the `quasis` of an untagged template with its `expressions`.
+fn concat<'gc>(
+    gc: &'gc GCContext,
+    quasis: &NodeList<'gc>,
+    expressions: &NodeList<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    let first = cooked(gc, quasis[0]);
+    if expressions.is_empty() {
+        return first;
+    }
+    let mut arguments = NodeList::with_capacity(quasis.len() + expressions.len());
+    for (&expression, &quasi) in expressions.iter().zip(&quasis[1..]) {
+        arguments.push(expression);
+        if !matches!(
+            quasi,
+            Node::TemplateElement(TemplateElement {
+                cooked: Some(NodeString { str }),
+                ..
+            }) if str.is_empty()
+        ) {
+            arguments.push(cooked(gc, quasi));
+        }
+    }
+    call(
+        gc,
+        member(gc, first, gc.atom("concat"), range),
+        arguments,
+        range,
+    )
+}
+
+/// Return the frozen strings array passed to the tag of a template, with its frozen `raw`
+/// property.
+fn strings_object<'gc>(
+    gc: &'gc GCContext,
+    quasis: &NodeList<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    let freeze = |array| {
+        call(
+            gc,
+            dotted_name(gc, "Object.freeze", range),
+            vec![array],
+            range,
+        )
+    };
+    let cooked_strings = array(
+        gc,
+        quasis.iter().map(|&quasi| cooked(gc, quasi)).collect(),
+        range,
+    );
+    let raw_strings = array(
+        gc,
+        quasis
+            .iter()
+            .map(|quasi| match quasi {
+                Node::TemplateElement(TemplateElement { metadata, raw, .. }) => {
+                    string(gc, gc.str(*raw), metadata.range)
+                }
+                _ => unreachable!("invalid template element {}", quasi.name()),
+            })
+            .collect(),
+        range,
+    );
+    freeze(call(
+        gc,
+        dotted_name(gc, "Object.defineProperty", range),
+        vec![
+            cooked_strings,
+            string(gc, "raw", range),
+            object(
+                gc,
+                vec![property(gc, "value", freeze(raw_strings), range)],
+                range,
+            ),
+        ],
+        range,
+    ))
+}
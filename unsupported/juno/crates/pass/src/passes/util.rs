@@ -0,0 +1,759 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Helpers shared by the passes.
+
+use crate::passes::const_fold::{literal, Value};
+use juno::ast::*;
+use std::collections::{HashMap, HashSet};
+
+/// Return the statements of `node` if it contains a statement list: a program, a block
+/// statement or a switch case.
+pub(crate) fn statement_list<'gc>(node: &'gc Node<'gc>) -> Option<&'gc NodeList<'gc>> {
+    match node {
+        Node::Program(Program { body, .. }) | Node::BlockStatement(BlockStatement { body, .. }) => {
+            Some(body)
+        }
+        Node::SwitchCase(SwitchCase { consequent, .. }) => Some(consequent),
+        _ => None,
+    }
+}
+
+/// Return a copy of `node`, which contains a statement list, with its statements replaced by
+/// `list`.
+pub(crate) fn with_statement_list<'gc>(
+    gc: &'gc GCContext,
+    node: &'gc Node<'gc>,
+    list: NodeList<'gc>,
+) -> &'gc Node<'gc> {
+    match node {
+        Node::Program(program) => {
+            let mut builder = ProgramBuilder::from_node(program);
+            builder.body(list);
+            builder.build(gc).unwrap()
+        }
+        Node::BlockStatement(block) => {
+            let mut builder = BlockStatementBuilder::from_node(block);
+            builder.body(list);
+            builder.build(gc).unwrap()
+        }
+        Node::SwitchCase(case) => {
+            let mut builder = SwitchCaseBuilder::from_node(case);
+            builder.consequent(list);
+            builder.build(gc).unwrap()
+        }
+        _ => panic!("{} doesn't contain a statement list", node.name()),
+    }
+}
+
+/// Generator of variable names which don't conflict with any identifier of a program.
+pub(crate) struct UniqueNames {
+    used: HashSet<Atom>,
+}
+
+impl UniqueNames {
+    /// Create a generator avoiding every identifier in the AST rooted at `root`.
+    pub(crate) fn new<'gc>(gc: &'gc GCContext, root: &'gc Node<'gc>) -> Self {
+        struct Collector {
+            used: HashSet<Atom>,
+        }
+        impl<'gc> Visitor<'gc> for Collector {
+            fn call(
+                &mut self,
+                gc: &'gc GCContext,
+                node: &'gc Node<'gc>,
+                _parent: Option<&'gc Node<'gc>>,
+            ) {
+                if let Node::Identifier(Identifier { name, .. }) = node {
+                    self.used.insert(*name);
+                }
+                node.visit_children(gc, self);
+            }
+        }
+        let mut collector = Collector {
+            used: HashSet::new(),
+        };
+        Visitor::call(&mut collector, gc, root, None);
+        UniqueNames {
+            used: collector.used,
+        }
+    }
+
+    /// Return an unused name based on `base`: `base`, `base2`, `base3`, etc.
+    pub(crate) fn generate(&mut self, gc: &GCContext, base: &str) -> Atom {
+        let mut name = gc.atom(base);
+        let mut n = 2;
+        while !self.used.insert(name) {
+            name = gc.atom(format!("{}{}", base, n));
+            n += 1;
+        }
+        name
+    }
+}
+
+/// Return whether `name` can be used as an identifier property key.
+pub(crate) fn is_identifier_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+pub(crate) fn template_metadata<'gc>(range: SourceRange) -> TemplateMetadata<'gc> {
+    TemplateMetadata {
+        range,
+        ..Default::default()
+    }
+}
+
+pub(crate) fn identifier<'gc>(
+    gc: &'gc GCContext,
+    name: Atom,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    IdentifierBuilder::build_template(
+        gc,
+        IdentifierTemplate {
+            metadata: template_metadata(range),
+            name,
+            type_annotation: None,
+            optional: false,
+        },
+    )
+}
+
+pub(crate) fn string<'gc>(gc: &'gc GCContext, str: &str, range: SourceRange) -> &'gc Node<'gc> {
+    literal(gc, Value::String(str.encode_utf16().collect()), range).unwrap()
+}
+
+pub(crate) fn member<'gc>(
+    gc: &'gc GCContext,
+    object: &'gc Node<'gc>,
+    property: Atom,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    MemberExpressionBuilder::build_template(
+        gc,
+        MemberExpressionTemplate {
+            metadata: template_metadata(range),
+            object,
+            property: identifier(gc, property, range),
+            computed: false,
+        },
+    )
+}
+
+/// Return the expression referring to the dotted name `name`, e.g. `React.createElement`.
+pub(crate) fn dotted_name<'gc>(
+    gc: &'gc GCContext,
+    name: &str,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    let mut parts = name.split('.');
+    let first = parts.next().unwrap();
+    let object = if first == "this" {
+        this_expression(gc, range)
+    } else {
+        identifier(gc, gc.atom(first), range)
+    };
+    parts.fold(object, |object, part| {
+        member(gc, object, gc.atom(part), range)
+    })
+}
+
+pub(crate) fn call<'gc>(
+    gc: &'gc GCContext,
+    callee: &'gc Node<'gc>,
+    arguments: NodeList<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    CallExpressionBuilder::build_template(
+        gc,
+        CallExpressionTemplate {
+            metadata: template_metadata(range),
+            callee,
+            type_arguments: None,
+            arguments,
+        },
+    )
+}
+
+pub(crate) fn object<'gc>(
+    gc: &'gc GCContext,
+    properties: NodeList<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    ObjectExpressionBuilder::build_template(
+        gc,
+        ObjectExpressionTemplate {
+            metadata: template_metadata(range),
+            properties,
+        },
+    )
+}
+
+pub(crate) fn array<'gc>(
+    gc: &'gc GCContext,
+    elements: NodeList<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    ArrayExpressionBuilder::build_template(
+        gc,
+        ArrayExpressionTemplate {
+            metadata: template_metadata(range),
+            elements,
+            trailing_comma: false,
+        },
+    )
+}
+
+/// Return the property `name: value`, quoting `name` if it isn't an identifier.
+pub(crate) fn property<'gc>(
+    gc: &'gc GCContext,
+    name: &str,
+    value: &'gc Node<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    let key = if is_identifier_name(name) {
+        identifier(gc, gc.atom(name), range)
+    } else {
+        string(gc, name, range)
+    };
+    PropertyBuilder::build_template(
+        gc,
+        PropertyTemplate {
+            metadata: template_metadata(range),
+            key,
+            value,
+            kind: PropertyKind::Init,
+            computed: false,
+            method: false,
+            shorthand: false,
+        },
+    )
+}
+
+pub(crate) fn number<'gc>(gc: &'gc GCContext, value: f64, range: SourceRange) -> &'gc Node<'gc> {
+    literal(gc, Value::Number(value), range).unwrap()
+}
+
+/// Return `void 0`.
+pub(crate) fn undefined<'gc>(gc: &'gc GCContext, range: SourceRange) -> &'gc Node<'gc> {
+    literal(gc, Value::Undefined, range).unwrap()
+}
+
+pub(crate) fn this_expression<'gc>(gc: &'gc GCContext, range: SourceRange) -> &'gc Node<'gc> {
+    ThisExpressionBuilder::build_template(
+        gc,
+        ThisExpressionTemplate {
+            metadata: template_metadata(range),
+        },
+    )
+}
+
+/// Return `object[property]`.
+pub(crate) fn computed_member<'gc>(
+    gc: &'gc GCContext,
+    object: &'gc Node<'gc>,
+    property: &'gc Node<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    MemberExpressionBuilder::build_template(
+        gc,
+        MemberExpressionTemplate {
+            metadata: template_metadata(range),
+            object,
+            property,
+            computed: true,
+        },
+    )
+}
+
+/// Return the member expression accessing the property `key` of `object`, which is computed
+/// if `computed` is true or the key isn't an identifier.
+pub(crate) fn key_member<'gc>(
+    gc: &'gc GCContext,
+    object: &'gc Node<'gc>,
+    key: &'gc Node<'gc>,
+    computed: bool,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    match key {
+        Node::Identifier(Identifier { name, .. }) if !computed => member(gc, object, *name, range),
+        _ => computed_member(gc, object, key, range),
+    }
+}
+
+/// Return the expression evaluating to the property `key`, e.g. to pass it to
+/// `Object.defineProperty`.
+pub(crate) fn key_value<'gc>(
+    gc: &'gc GCContext,
+    key: &'gc Node<'gc>,
+    computed: bool,
+) -> &'gc Node<'gc> {
+    match key {
+        Node::Identifier(Identifier { metadata, name, .. }) if !computed => {
+            string(gc, gc.str(*name), metadata.range)
+        }
+        _ => key,
+    }
+}
+
+pub(crate) fn new_expression<'gc>(
+    gc: &'gc GCContext,
+    callee: &'gc Node<'gc>,
+    arguments: NodeList<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    NewExpressionBuilder::build_template(
+        gc,
+        NewExpressionTemplate {
+            metadata: template_metadata(range),
+            callee,
+            type_arguments: None,
+            arguments,
+        },
+    )
+}
+
+/// Return `Array.prototype.slice.call(value, start)`, converting the array-like `value` to an
+/// array.
+pub(crate) fn array_slice<'gc>(
+    gc: &'gc GCContext,
+    value: &'gc Node<'gc>,
+    start: usize,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    let mut arguments = vec![value];
+    if start != 0 {
+        arguments.push(number(gc, start as f64, range));
+    }
+    call(
+        gc,
+        dotted_name(gc, "Array.prototype.slice.call", range),
+        arguments,
+        range,
+    )
+}
+
+/// Return `left = right`.
+pub(crate) fn assign<'gc>(
+    gc: &'gc GCContext,
+    left: &'gc Node<'gc>,
+    right: &'gc Node<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    AssignmentExpressionBuilder::build_template(
+        gc,
+        AssignmentExpressionTemplate {
+            metadata: template_metadata(range),
+            operator: AssignmentExpressionOperator::Assign,
+            left,
+            right,
+        },
+    )
+}
+
+pub(crate) fn binary<'gc>(
+    gc: &'gc GCContext,
+    operator: BinaryExpressionOperator,
+    left: &'gc Node<'gc>,
+    right: &'gc Node<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    BinaryExpressionBuilder::build_template(
+        gc,
+        BinaryExpressionTemplate {
+            metadata: template_metadata(range),
+            left,
+            right,
+            operator,
+        },
+    )
+}
+
+pub(crate) fn logical<'gc>(
+    gc: &'gc GCContext,
+    operator: LogicalExpressionOperator,
+    left: &'gc Node<'gc>,
+    right: &'gc Node<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    LogicalExpressionBuilder::build_template(
+        gc,
+        LogicalExpressionTemplate {
+            metadata: template_metadata(range),
+            left,
+            right,
+            operator,
+        },
+    )
+}
+
+pub(crate) fn conditional<'gc>(
+    gc: &'gc GCContext,
+    test: &'gc Node<'gc>,
+    consequent: &'gc Node<'gc>,
+    alternate: &'gc Node<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    ConditionalExpressionBuilder::build_template(
+        gc,
+        ConditionalExpressionTemplate {
+            metadata: template_metadata(range),
+            test,
+            alternate,
+            consequent,
+        },
+    )
+}
+
+/// Return the comma expression evaluating `expressions`, or the expression itself if there is
+/// only one.
+pub(crate) fn sequence<'gc>(
+    gc: &'gc GCContext,
+    mut expressions: NodeList<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    if expressions.len() == 1 {
+        return expressions.pop().unwrap();
+    }
+    SequenceExpressionBuilder::build_template(
+        gc,
+        SequenceExpressionTemplate {
+            metadata: template_metadata(range),
+            expressions,
+        },
+    )
+}
+
+/// Return `throw new TypeError(message);`.
+pub(crate) fn throw_type_error<'gc>(
+    gc: &'gc GCContext,
+    message: &str,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    ThrowStatementBuilder::build_template(
+        gc,
+        ThrowStatementTemplate {
+            metadata: template_metadata(range),
+            argument: new_expression(
+                gc,
+                identifier(gc, gc.atom("TypeError"), range),
+                vec![string(gc, message, range)],
+                range,
+            ),
+        },
+    )
+}
+
+/// Return a function expression without a name.
+pub(crate) fn function<'gc>(
+    gc: &'gc GCContext,
+    params: NodeList<'gc>,
+    body: NodeList<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    FunctionExpressionBuilder::build_template(
+        gc,
+        FunctionExpressionTemplate {
+            metadata: template_metadata(range),
+            id: None,
+            params,
+            body: block(gc, body, range),
+            type_parameters: None,
+            return_type: None,
+            predicate: None,
+            generator: false,
+            is_async: false,
+        },
+    )
+}
+
+pub(crate) fn expression_statement<'gc>(
+    gc: &'gc GCContext,
+    expression: &'gc Node<'gc>,
+) -> &'gc Node<'gc> {
+    ExpressionStatementBuilder::build_template(
+        gc,
+        ExpressionStatementTemplate {
+            metadata: template_metadata(*expression.range()),
+            expression,
+            directive: None,
+        },
+    )
+}
+
+/// Return a variable declaration of `kind` with the declarators `id = init`.
+pub(crate) fn declaration<'gc>(
+    gc: &'gc GCContext,
+    kind: VariableDeclarationKind,
+    declarators: impl IntoIterator<Item = (&'gc Node<'gc>, Option<&'gc Node<'gc>>)>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    let declarations = declarators
+        .into_iter()
+        .map(|(id, init)| {
+            VariableDeclaratorBuilder::build_template(
+                gc,
+                VariableDeclaratorTemplate {
+                    metadata: template_metadata(*id.range()),
+                    init,
+                    id,
+                },
+            )
+        })
+        .collect();
+    VariableDeclarationBuilder::build_template(
+        gc,
+        VariableDeclarationTemplate {
+            metadata: template_metadata(range),
+            kind,
+            declarations,
+        },
+    )
+}
+
+pub(crate) fn block<'gc>(
+    gc: &'gc GCContext,
+    body: NodeList<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    BlockStatementBuilder::build_template(
+        gc,
+        BlockStatementTemplate {
+            metadata: template_metadata(range),
+            body,
+        },
+    )
+}
+
+pub(crate) fn return_statement<'gc>(
+    gc: &'gc GCContext,
+    argument: Option<&'gc Node<'gc>>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    ReturnStatementBuilder::build_template(
+        gc,
+        ReturnStatementTemplate {
+            metadata: template_metadata(range),
+            argument,
+        },
+    )
+}
+
+pub(crate) fn if_statement<'gc>(
+    gc: &'gc GCContext,
+    test: &'gc Node<'gc>,
+    consequent: &'gc Node<'gc>,
+    range: SourceRange,
+) -> &'gc Node<'gc> {
+    IfStatementBuilder::build_template(
+        gc,
+        IfStatementTemplate {
+            metadata: template_metadata(range),
+            test,
+            consequent,
+            alternate: None,
+        },
+    )
+}
+
+/// Return true if `node` is a function or a program: a scope to which `var` declarations are
+/// hoisted.
+pub(crate) fn is_var_scope(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::Program(_)
+            | Node::FunctionDeclaration(_)
+            | Node::FunctionExpression(_)
+            | Node::ArrowFunctionExpression(_)
+    )
+}
+
+/// Return true if the identifier `node` is the name of a property in `parent` rather than a
+/// reference.
+pub(crate) fn is_property_name<'gc>(node: &'gc Node<'gc>, parent: Option<&'gc Node<'gc>>) -> bool {
+    match parent {
+        Some(Node::MemberExpression(MemberExpression {
+            property,
+            computed: false,
+            ..
+        }))
+        | Some(Node::OptionalMemberExpression(OptionalMemberExpression {
+            property,
+            computed: false,
+            ..
+        })) => std::ptr::eq(*property, node),
+        Some(Node::Property(Property {
+            key,
+            computed: false,
+            shorthand: false,
+            ..
+        })) => std::ptr::eq(*key, node),
+        _ => false,
+    }
+}
+
+/// Return the number of directives at the start of the statement list `stmts`.
+pub(crate) fn directive_count(stmts: &[&Node]) -> usize {
+    stmts
+        .iter()
+        .take_while(|stmt| {
+            matches!(
+                stmt,
+                Node::ExpressionStatement(ExpressionStatement {
+                    directive: Some(_),
+                    ..
+                })
+            )
+        })
+        .count()
+}
+
+/// Return a copy of the function or program `node` with `stmts` inserted at the start of its
+/// body, after the directives.
+/// The expression body of an arrow function is turned into a block returning it.
+pub(crate) fn prepend_to_body<'gc>(
+    gc: &'gc GCContext,
+    node: &'gc Node<'gc>,
+    stmts: NodeList<'gc>,
+) -> &'gc Node<'gc> {
+    let insert = |body: &'gc Node<'gc>| {
+        let list = statement_list(body).unwrap();
+        let directives = directive_count(list);
+        let mut new_list = list[..directives].to_vec();
+        new_list.extend(&stmts);
+        new_list.extend_from_slice(&list[directives..]);
+        with_statement_list(gc, body, new_list)
+    };
+    match node {
+        Node::Program(_) => insert(node),
+        Node::FunctionDeclaration(func) => {
+            let mut builder = FunctionDeclarationBuilder::from_node(func);
+            builder.body(insert(func.body));
+            builder.build(gc).unwrap()
+        }
+        Node::FunctionExpression(func) => {
+            let mut builder = FunctionExpressionBuilder::from_node(func);
+            builder.body(insert(func.body));
+            builder.build(gc).unwrap()
+        }
+        Node::ArrowFunctionExpression(func) => {
+            let mut builder = ArrowFunctionExpressionBuilder::from_node(func);
+            if let Node::BlockStatement(_) = func.body {
+                builder.body(insert(func.body));
+            } else {
+                let range = *func.body.range();
+                let mut body = stmts.clone();
+                body.push(return_statement(gc, Some(func.body), range));
+                builder.body(block(gc, body, range));
+                builder.expression(false);
+            }
+            builder.build(gc).unwrap()
+        }
+        _ => panic!("{} doesn't have a body", node.name()),
+    }
+}
+
+/// Statements to insert before and after statements of the lists being rebuilt, keyed by the
+/// new statements.
+pub(crate) type Splices<'gc> = HashMap<NodeRef<'gc>, (NodeList<'gc>, NodeList<'gc>)>;
+
+/// Return a copy of `node` with the statements in `splices` inserted around the statements of
+/// its statement list, removing them from `splices`, or `None` if there are none.
+pub(crate) fn splice_statements<'gc>(
+    gc: &'gc GCContext,
+    node: &'gc Node<'gc>,
+    splices: &mut Splices<'gc>,
+) -> Option<&'gc Node<'gc>> {
+    let list = statement_list(node)?;
+    if splices.is_empty()
+        || !list
+            .iter()
+            .any(|&stmt| splices.contains_key(&NodeRef(stmt)))
+    {
+        return None;
+    }
+    let mut new_list = NodeList::with_capacity(list.len());
+    for &stmt in list {
+        match splices.remove(&NodeRef(stmt)) {
+            Some((before, after)) => {
+                new_list.extend(before);
+                new_list.push(stmt);
+                new_list.extend(after);
+            }
+            None => new_list.push(stmt),
+        }
+    }
+    Some(with_statement_list(gc, node, new_list))
+}
+
+/// Temporary variables, declared with `var` at the top of the function or program in which
+/// they are used.
+pub(crate) struct Temporaries {
+    pub(crate) names: UniqueNames,
+    /// Names of the temporaries of each function being visited, innermost last.
+    /// The first element belongs to the program.
+    scopes: Vec<Vec<Atom>>,
+}
+
+impl Temporaries {
+    pub(crate) fn new(names: UniqueNames) -> Self {
+        Temporaries {
+            names,
+            scopes: Vec::new(),
+        }
+    }
+
+    /// Start collecting the temporaries of a function or program.
+    pub(crate) fn enter(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    /// Stop collecting the temporaries of the function or program `node`, and return it with
+    /// them declared, or `None` if there are none.
+    pub(crate) fn exit<'gc>(
+        &mut self,
+        gc: &'gc GCContext,
+        node: &'gc Node<'gc>,
+    ) -> Option<&'gc Node<'gc>> {
+        let names = self.scopes.pop().expect("unbalanced temporary scopes");
+        if names.is_empty() {
+            return None;
+        }
+        let range = *node.range();
+        let decl = declaration(
+            gc,
+            VariableDeclarationKind::Var,
+            names
+                .into_iter()
+                .map(|name| (identifier(gc, name, range), None)),
+            range,
+        );
+        Some(prepend_to_body(gc, node, vec![decl]))
+    }
+
+    /// Return a new temporary based on `base`, declared in the innermost function.
+    pub(crate) fn generate(&mut self, gc: &GCContext, base: &str) -> Atom {
+        let name = self.names.generate(gc, base);
+        self.scopes
+            .last_mut()
+            .expect("temporary outside of a program")
+            .push(name);
+        name
+    }
+
+    /// Return a new temporary based on `base`, declared at the top level of the program.
+    pub(crate) fn generate_global(&mut self, gc: &GCContext, base: &str) -> Atom {
+        let name = self.names.generate(gc, base);
+        self.scopes
+            .first_mut()
+            .expect("temporary outside of a program")
+            .push(name);
+        name
+    }
+}
@@ -30,6 +30,137 @@ pub fn number_to_string(m: f64) -> String {
     }
 }
 
+/// Convert a double number to the shortest numeric literal representing it, like `1e3` for
+/// 1000 and `.5` for 0.5. NaN and infinities are converted like `number_to_string`.
+pub fn number_to_shortest_string(m: f64) -> String {
+    let string = number_to_string(m);
+    if !m.is_finite() {
+        return string;
+    }
+    let (sign, digits) = match string.strip_prefix('-') {
+        Some(abs) => ("-", abs),
+        None => ("", string.as_str()),
+    };
+    let shortest = if let Some((mantissa, exp)) = digits.split_once('e') {
+        // `1e+21` becomes `1e21`.
+        format!("{}e{}", mantissa, exp.trim_start_matches('+'))
+    } else if let Some(fraction) = digits.strip_prefix("0.") {
+        // `0.0005` becomes `5e-4`, `0.05` becomes `.05`.
+        let significant = fraction.trim_start_matches('0');
+        let exp = format!("{}e-{}", significant, fraction.len());
+        if exp.len() < fraction.len() + 1 {
+            exp
+        } else {
+            format!(".{}", fraction)
+        }
+    } else if !digits.contains('.') {
+        // `5000` becomes `5e3`.
+        let significant = digits.trim_end_matches('0');
+        let zeros = digits.len() - significant.len();
+        let exp = format!("{}e{}", significant, zeros);
+        if exp.len() < digits.len() {
+            exp
+        } else {
+            digits.to_string()
+        }
+    } else {
+        digits.to_string()
+    };
+    format!("{}{}", sign, shortest)
+}
+
+/// Return true if `c` is a JS WhiteSpace or LineTerminator, which are ignored around numbers.
+fn is_js_space(c: char) -> bool {
+    // The Unicode White_Space property also contains NEL, which isn't whitespace in JS,
+    // but doesn't contain ZWNBSP.
+    c == '\u{FEFF}' || (c.is_whitespace() && c != '\u{85}')
+}
+
+/// Convert the digits of a binary, octal or hexadecimal integer to a number, rounding to the
+/// nearest number like a decimal literal. Return NaN if `digits` is empty or invalid.
+fn radix_to_number(digits: &str, radix: u32) -> f64 {
+    let bits = radix.trailing_zeros();
+    if digits.is_empty() {
+        return f64::NAN;
+    }
+    // Accumulate the most significant bits and count the omitted ones. The mantissa keeps more
+    // than 53 bits, so setting its lowest bit for non-zero omitted digits rounds correctly.
+    let mut mantissa: u64 = 0;
+    let mut exp: i32 = 0;
+    let mut sticky = false;
+    for c in digits.chars() {
+        let digit = match c.to_digit(radix) {
+            Some(digit) => digit as u64,
+            None => return f64::NAN,
+        };
+        if mantissa >> (64 - bits) == 0 {
+            mantissa = mantissa << bits | digit;
+        } else {
+            exp += bits as i32;
+            sticky |= digit != 0;
+        }
+    }
+    (mantissa | sticky as u64) as f64 * 2f64.powi(exp)
+}
+
+/// Convert a string to a number, following ES2022 7.1.4.1.1 StringToNumber.
+/// Return NaN if the string isn't a valid numeric literal.
+pub fn string_to_number(s: &str) -> f64 {
+    let s = s.trim_matches(is_js_space);
+    if s.is_empty() {
+        return 0.0;
+    }
+    if s.len() > 2 && s.starts_with('0') {
+        let radix = match &s[1..2] {
+            "x" | "X" => 16,
+            "o" | "O" => 8,
+            "b" | "B" => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            return radix_to_number(&s[2..], radix);
+        }
+    }
+    let (negative, unsigned) = match s.as_bytes()[0] {
+        b'-' => (true, &s[1..]),
+        b'+' => (false, &s[1..]),
+        _ => (false, s),
+    };
+    let value = if unsigned == "Infinity" {
+        f64::INFINITY
+    } else if unsigned.starts_with(['+', '-']) {
+        // Only one sign is allowed, but Rust would accept a second one.
+        f64::NAN
+    } else if unsigned
+        .bytes()
+        .all(|c| matches!(c, b'0'..=b'9' | b'.' | b'e' | b'E' | b'+' | b'-'))
+    {
+        // Rust accepts the same syntax once words like "inf" and "NaN" are excluded.
+        unsigned.parse::<f64>().unwrap_or(f64::NAN)
+    } else {
+        f64::NAN
+    };
+    if negative {
+        -value
+    } else {
+        value
+    }
+}
+
+/// Convert a number to a 32-bit unsigned integer, following ES2022 7.1.7 ToUint32.
+pub fn to_uint32(m: f64) -> u32 {
+    if !m.is_finite() {
+        return 0;
+    }
+    // Both the truncated number and the remainder are integers, so this is exact.
+    m.trunc().rem_euclid(4294967296.0) as u32
+}
+
+/// Convert a number to a 32-bit signed integer, following ES2022 7.1.6 ToInt32.
+pub fn to_int32(m: f64) -> i32 {
+    to_uint32(m) as i32
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -38,4 +169,59 @@ mod tests {
     fn test_number_to_string() {
         assert_eq!(number_to_string(1.0), "1");
     }
+
+    #[test]
+    fn test_number_to_shortest_string() {
+        assert_eq!(number_to_shortest_string(0.0), "0");
+        assert_eq!(number_to_shortest_string(100.0), "100");
+        assert_eq!(number_to_shortest_string(1000.0), "1e3");
+        assert_eq!(number_to_shortest_string(-12300000.0), "-123e5");
+        assert_eq!(number_to_shortest_string(0.5), ".5");
+        assert_eq!(number_to_shortest_string(0.001), ".001");
+        assert_eq!(number_to_shortest_string(0.00015), "15e-5");
+        assert_eq!(number_to_shortest_string(1.5), "1.5");
+        assert_eq!(number_to_shortest_string(1e21), "1e21");
+        assert_eq!(number_to_shortest_string(1.5e-7), "1.5e-7");
+        assert_eq!(number_to_shortest_string(f64::NAN), "NaN");
+    }
+
+    #[test]
+    fn test_string_to_number() {
+        assert_eq!(string_to_number(""), 0.0);
+        assert_eq!(string_to_number(" \n\u{FEFF}42\t"), 42.0);
+        assert_eq!(string_to_number("-1.5e3"), -1500.0);
+        assert_eq!(string_to_number("+.5"), 0.5);
+        assert_eq!(string_to_number("5."), 5.0);
+        assert_eq!(string_to_number("-Infinity"), f64::NEG_INFINITY);
+        assert_eq!(string_to_number("0x1F"), 31.0);
+        assert_eq!(string_to_number("0b101"), 5.0);
+        assert_eq!(string_to_number("0o17"), 15.0);
+        assert_eq!(
+            string_to_number("0x20000000000001"),
+            9007199254740992.0,
+            "rounds to even"
+        );
+        assert_eq!(string_to_number("0x20000000000003"), 9007199254740996.0);
+        assert_eq!(
+            string_to_number("0x200000000000010000000001"),
+            2f64.powi(93) + 2f64.powi(41)
+        );
+        assert!(string_to_number("-0").is_sign_negative());
+        for s in &[
+            "inf", "NaN", "infinity", "1e", "0x", "-0x1", "1_0", "\u{85}1", "1 2", ".", "--1",
+            "+-1", "-+1",
+        ] {
+            assert!(string_to_number(s).is_nan(), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn test_to_int32() {
+        assert_eq!(to_int32(f64::NAN), 0);
+        assert_eq!(to_int32(-1.5), -1);
+        assert_eq!(to_int32(2147483648.0), -2147483648);
+        assert_eq!(to_uint32(-1.0), 4294967295);
+        assert_eq!(to_uint32(4294967297.5), 1);
+        assert_eq!(to_uint32(f64::INFINITY), 0);
+    }
 }
@@ -114,9 +114,10 @@ fn fetch_data(url: &Url) -> Result<Data, FetchError> {
     let (mtype_enc, data) = path
         .split_once(',')
         .ok_or(FetchError::InvalidURL("data URL missing ',' delimiter"))?;
-    // Split using ';' into mtype and encoding. We require the encoding.
+    // Split using the last ';' into mtype (possibly with parameters like charset)
+    // and encoding. We require the encoding.
     let (_, enc) = mtype_enc
-        .split_once(';')
+        .rsplit_once(';')
         .ok_or(FetchError::InvalidURL("data URL missing ';encoding'"))?;
 
     // Only support base64 for now.
@@ -124,13 +125,26 @@ fn fetch_data(url: &Url) -> Result<Data, FetchError> {
         return Err(FetchError::InvalidURL("data URL unsupported encoding"));
     }
 
-    let buf = base64::decode_config(data, base64::URL_SAFE).map_err(|e| {
-        FetchError::DecodeError(anyhow::anyhow!(e).context("error decoding data URL"))
-    })?;
+    // Accept both the standard and the URL-safe alphabets.
+    let buf = base64::decode_config(data, base64::STANDARD)
+        .or_else(|_| base64::decode_config(data, base64::URL_SAFE))
+        .map_err(|e| {
+            FetchError::DecodeError(anyhow::anyhow!(e).context("error decoding data URL"))
+        })?;
 
     Ok(Data { buf })
 }
 
+/// Encode `data` with the specified media type as a base64 data URL, which can
+/// later be decoded by [fetch_url()].
+pub fn make_data_url(media_type: &str, data: &[u8]) -> String {
+    format!(
+        "data:{};base64,{}",
+        media_type,
+        base64::encode_config(data, base64::STANDARD)
+    )
+}
+
 /// Fetch from a file URL.
 fn fetch_file(url: &Url) -> Result<Data, FetchError> {
     let path = url
@@ -180,10 +194,28 @@ mod tests {
             FetchError::InvalidURL("data URL missing ';encoding'")
         ));
 
+        // Media type parameters and the URL-safe alphabet.
+        assert_eq!(
+            fetch_from_str_url("data:application/json;charset=utf-8;base64,-_8=")
+                .unwrap()
+                .as_ref(),
+            &[0xfb, 0xff]
+        );
+
         // Check invalid encoding.
         assert!(matches!(
             fetch_from_str_url("data:text/plain;base63,YQ==").unwrap_err(),
             FetchError::InvalidURL("data URL unsupported encoding")
         ));
     }
+
+    #[test]
+    fn test_make_data_url() {
+        let url = make_data_url("application/json;charset=utf-8", &[0xfb, 0xff, b'a']);
+        assert_eq!(url, "data:application/json;charset=utf-8;base64,+/9h");
+        assert_eq!(
+            fetch_from_str_url(&url).unwrap().as_ref(),
+            &[0xfb, 0xff, b'a']
+        );
+    }
 }
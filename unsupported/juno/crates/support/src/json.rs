@@ -197,9 +197,24 @@ impl<W: Write> JSONEmitter<W> {
         self.primitive_emit_string(string);
     }
 
-    /// Emit a valid UTF-8 string.
+    /// Emit a valid UTF-8 string, escaping the characters which may not appear
+    /// verbatim in a JSON string.
     pub fn primitive_emit_string(&mut self, string: &str) {
-        out!(self, "\"{}\"", string);
+        out!(self, "\"");
+        for c in string.chars() {
+            match c {
+                '"' => out!(self, "\\\""),
+                '\\' => out!(self, "\\\\"),
+                '\n' => out!(self, "\\n"),
+                '\r' => out!(self, "\\r"),
+                '\t' => out!(self, "\\t"),
+                '\x08' => out!(self, "\\b"),
+                '\x0c' => out!(self, "\\f"),
+                c if (c as u32) < 0x20 => out!(self, "\\u{:04x}", c as u32),
+                c => out!(self, "{}", c),
+            }
+        }
+        out!(self, "\"");
     }
 
     /// Consume the emitter and finish emitting.
@@ -333,6 +348,18 @@ mod tests {
         assert_eq!(String::from_utf8(out).unwrap(), "\"\\ud00a\\n\"\n");
     }
 
+    #[test]
+    fn test_escaped_string() {
+        let mut out = vec![];
+        let mut emitter = JSONEmitter::new(&mut out, Pretty::No);
+        emitter.emit_string("a\"b\\c\nd\x01é");
+        emitter.end().unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\"a\\\"b\\\\c\\nd\\u0001é\"\n"
+        );
+    }
+
     #[test]
     fn test_dict() {
         let mut out = vec![];
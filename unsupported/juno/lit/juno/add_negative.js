@@ -7,10 +7,13 @@
 
 // RUN: %juno --gen-js -O %s | %FileCheck %s --match-full-lines
 
+// Rewriting `x + -y` to `x - y` is wrong when `x` is a string, so the AddNegative pass isn't
+// part of the optimization pipeline.
+
 function foo() {
   x + -y;
 }
 
 // CHECK-LABEL: function foo() {
-// CHECK-NEXT:   x - y;
+// CHECK-NEXT:   x + -y;
 // CHECK-NEXT: }
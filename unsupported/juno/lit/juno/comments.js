@@ -0,0 +1,47 @@
+/**
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js %s | %FileCheck %s --match-full-lines
+// RUN: %juno --gen-js --comments=none %s | %FileCheck %s --match-full-lines --check-prefix=STRIP
+// RUN: %juno --gen-ast --no-pretty %s | %FileCheck %s --match-full-lines --check-prefix=AST
+
+/*! Legal notice */
+
+/**
+ * Adds two numbers.
+ */
+function add(a, b) {
+  // Leading.
+  return a + b; // Trailing.
+}
+
+var x = /*#__PURE__*/ make(1, /* two */ 2);
+
+function empty() {
+  // Inner.
+}
+
+// CHECK-LABEL: /*! Legal notice */
+// CHECK-NEXT: /**
+// CHECK-NEXT:  * Adds two numbers.
+// CHECK-NEXT:  */
+// CHECK-NEXT: function add(a, b) {
+// CHECK-NEXT:   // Leading.
+// CHECK-NEXT:   return a + b; // Trailing.
+// CHECK-NEXT: }
+// CHECK-NEXT: var x = /*#__PURE__*/ make(1, /* two */ 2);
+// CHECK-NEXT: function empty() {
+// CHECK-NEXT:   // Inner.
+// CHECK-NEXT: }
+
+// STRIP: function add(a, b) {
+// STRIP-NEXT:   return a + b;
+// STRIP-NEXT: }
+// STRIP-NEXT: var x = make(1, 2);
+// STRIP-NEXT: function empty() {}
+
+// AST: {"type":"Program","body":[{"type":"FunctionDeclaration",{{.*}},"leadingComments":[{"type":"CommentBlock","value":"*\n * Copyright{{.*}}"},{{.*}},{"type":"CommentBlock","value":"! Legal notice "},{"type":"CommentBlock","value":"*\n * Adds two numbers.\n "}]},{{.*}}"leadingComments":[{"type":"CommentBlock","value":"#__PURE__"}]{{.*}}"innerComments":[{"type":"CommentLine","value":" Inner."}]{{.*}}
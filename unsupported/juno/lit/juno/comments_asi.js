@@ -0,0 +1,49 @@
+/**
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js %s | %FileCheck %s --match-full-lines
+
+// Comments containing line breaks must not be printed where a line break would insert a
+// semicolon.
+
+function f(a) {
+  return (/* one
+  */ a + 1);
+}
+
+function g(a) {
+  throw (/* two
+  */ a);
+}
+
+function* h(a) {
+  yield (/* three
+  */ a);
+}
+
+(a.b /* four
+*/)++;
+
+var k = (x /* five
+*/) => x;
+
+// CHECK-LABEL: function f(a) {
+// CHECK-NEXT:   return (/* one
+// CHECK-NEXT:   */ a + 1);
+// CHECK-NEXT: }
+// CHECK-NEXT: function g(a) {
+// CHECK-NEXT:   throw (/* two
+// CHECK-NEXT:   */ a);
+// CHECK-NEXT: }
+// CHECK-NEXT: function* h(a) {
+// CHECK-NEXT:   yield (/* three
+// CHECK-NEXT:   */ a);
+// CHECK-NEXT: }
+// CHECK-NEXT: (a.b /* four
+// CHECK-NEXT: */)++;
+// CHECK-NEXT: var k = (x /* five
+// CHECK-NEXT: */) => x;
@@ -0,0 +1,45 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js -O --comments=none %s | %FileCheck %s --match-full-lines
+
+a = 60 * 60 * 24;
+// CHECK: a = 86400;
+b = "v" + 1.5 + null;
+// CHECK-NEXT: b = "v1.5null";
+c = -0 + -0;
+// CHECK-NEXT: c = -0;
+d = 5 % -3 + -5 % 3;
+// CHECK-NEXT: d = 0;
+e = "0x1F" * 1 + " 2e1 " * 1;
+// CHECK-NEXT: e = 51;
+f = 2 ** 10 + (1 ** (0 / 0) === 1 ** (0 / 0));
+// CHECK-NEXT: f = 1024;
+g = "10" == 10 && null != 0 && true == "1";
+// CHECK-NEXT: g = true;
+h = "b" < "a" || 1 < 0 / 0 || "B" >= "a";
+// CHECK-NEXT: h = false;
+i = typeof null + typeof void 0;
+// CHECK-NEXT: i = "objectundefined";
+j = ~5 | (1 << 3) ^ (-16 >> 2);
+// CHECK-NEXT: j = -2;
+
+// Results which are longer, or which don't have a literal form, are not folded.
+k = 1 / 3 + (0 / 0);
+// CHECK-NEXT: k = 1 / 3 + 0 / 0;
+
+// Only the evaluated branch is kept.
+l = 1 < 2 ? x : y;
+// CHECK-NEXT: l = x;
+m = 0 && foo();
+// CHECK-NEXT: m = 0;
+n = null ?? bar;
+// CHECK-NEXT: n = bar;
+
+// The value of a reference is used where the reference itself matters.
+(true && obj.f)();
+// CHECK-NEXT: (0, obj.f)();
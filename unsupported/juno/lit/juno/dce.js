@@ -0,0 +1,93 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js -O --comments=none %s | %FileCheck %s --match-full-lines
+
+function f(x) {
+  var unused = 1, used = 2;
+  let obj = { a: 1, b: [2, function () {}] };
+  const effect = g();
+  function helper() { return other(); }
+  function other() {}
+  if (false) {
+    log(x);
+  } else {
+    x++;
+  }
+  if ("production" !== "production") {
+    var dev = 1;
+  }
+  if (1) {
+    let scoped = x;
+    use(scoped);
+  }
+  ;
+  {}
+  true ? a() : b();
+  0 && c();
+  {
+    d();
+  }
+  return x + used + dev + hoisted + late;
+  x++;
+  var late = 3;
+  function hoisted() {}
+}
+// CHECK-LABEL: function f(x) {
+// CHECK-NEXT:   var used = 2;
+// CHECK-NEXT:   const effect = g();
+// CHECK-NEXT:   x++;
+// CHECK-NEXT:   var dev;
+// CHECK-NEXT:   {
+// CHECK-NEXT:     let scoped = x;
+// CHECK-NEXT:     use(scoped);
+// CHECK-NEXT:   }
+// CHECK-NEXT:   a();
+// CHECK-NEXT:   d();
+// CHECK-NEXT:   return x + used + dev + hoisted + late;
+// CHECK-NEXT:   var late;
+// CHECK-NEXT:   function hoisted() {}
+// CHECK-NEXT: }
+
+function s(k) {
+  switch (k) {
+    case 1:
+      break;
+      k();
+    default:
+      throw k;
+  }
+  while (k) {
+    continue;
+    k();
+  }
+}
+// CHECK-LABEL: function s(k) {
+// CHECK-NEXT:   switch (k) {
+// CHECK-NEXT:   case 1:
+// CHECK-NEXT:     break;
+// CHECK-NEXT:   default:
+// CHECK-NEXT:     throw k;
+// CHECK-NEXT:   }
+// CHECK-NEXT:   while (k) {
+// CHECK-NEXT:     continue;
+// CHECK-NEXT:   }
+// CHECK-NEXT: }
+
+function e(x) {
+  var keep = 1;
+  eval(x);
+}
+// CHECK-LABEL: function e(x) {
+// CHECK-NEXT:   var keep = 1;
+// CHECK-NEXT:   eval(x);
+// CHECK-NEXT: }
+
+var global = 1;
+// CHECK-NEXT: var global = 1;
+(true && obj.m)();
+// CHECK-NEXT: (0, obj.m)();
@@ -0,0 +1,40 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js --comments=none --define __DEV__=false --define 'process.env.NODE_ENV="production"' --define 'Platform.OS="ios"' --define 'CONFIG={"a": [1, null], "b": -2}' %s | %FileCheck %s --match-full-lines
+// RUN: %juno --gen-js -O --comments=none --define __DEV__=false --define 'Platform.OS="ios"' %s | %FileCheck %s --match-full-lines --check-prefix=OPT
+// RUN: (%juno --gen-js --define 'process.env.=1' %s 2>&1 || true) | %FileCheck %s --match-full-lines --check-prefix=NAME
+// RUN: (%juno --gen-js --define 'process.env.NODE_ENV=production' %s 2>&1 || true) | %FileCheck %s --match-full-lines --check-prefix=VALUE
+
+if (__DEV__) check();
+// CHECK: if (false)
+// CHECK-NEXT:   check();
+// OPT-NOT: check();
+mode = process.env.NODE_ENV + process.env["NODE_ENV"];
+// CHECK: mode = "production" + "production";
+os = Platform.OS === "ios" ? a : b;
+// CHECK-NEXT: os = "ios" === "ios" ? a : b;
+// OPT: os = a;
+config = {__DEV__, config: CONFIG};
+// CHECK-NEXT: config = {__DEV__: false, config: {"a": [1, null], "b": -2}};
+
+// Assignments and shadowing declarations aren't replaced.
+process.env.NODE_ENV = "test";
+// CHECK-NEXT: process.env.NODE_ENV = "test";
+__DEV__ = true;
+// CHECK-NEXT: __DEV__ = true;
+function f(__DEV__) {
+  return __DEV__ && Platform.OS;
+}
+// CHECK-NEXT: function f(__DEV__) {
+// CHECK-NEXT:   return __DEV__ && "ios";
+// CHECK-NEXT: }
+obj.__DEV__ + process.env.OTHER;
+// CHECK-NEXT: obj.__DEV__ + process.env.OTHER;
+
+// NAME: error: Invalid value for '--define <KEY=VALUE>...': 'process.env.': invalid name
+// VALUE: error: Invalid value for '--define <KEY=VALUE>...': 'process.env.NODE_ENV': invalid value: expected value at line 1 column 1
@@ -0,0 +1,15 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: (%juno --diagnostics-format=json %s 2>&1 || true) | %FileCheck %s --match-full-lines
+
+'use strict';
+var x = 010;
+var y = "\u{110000}";
+
+// CHECK: {"severity":"error","file":"{{.*}}diagnostics_json.js","startLine":11,"startColumn":9,"endLine":11,"endColumn":9,"message":"Octal literals must use '0o' in strict mode","notes":[]}
+// CHECK-NEXT: {"severity":"error","file":"{{.*}}diagnostics_json.js","startLine":12,"startColumn":{{[0-9]+}},"endLine":12,"endColumn":{{[0-9]+}},"message":"{{.*}}","notes":[]}
@@ -0,0 +1,20 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ *
+ * @flow
+ */
+
+// RUN: %juno --gen-js %s | %FileCheck %s --match-full-lines --check-prefix=FLOW
+// RUN: cp %s %t.jsx && %juno --gen-js --dialect=flow %t.jsx | %FileCheck %s --match-full-lines --check-prefix=JSX
+// RUN: cp %s %t.ts && %juno --gen-ast --no-pretty --comments=none %t.ts | %FileCheck %s --match-full-lines --check-prefix=TS
+// RUN: (%juno --gen-js --dialect=javascript %s 2>&1 || true) | %FileCheck %s --match-full-lines --check-prefix=JS
+
+let x: number = 1;
+
+// FLOW: let x: number = 1;
+// JSX: let x: number = 1;
+// TS: {"type":"Program","body":[{"type":"VariableDeclaration","kind":"let","declarations":[{"type":"VariableDeclarator","init":{"type":"NumericLiteral","value":1},"id":{"type":"Identifier","name":"x","typeAnnotation":{"type":"TSTypeAnnotation","typeAnnotation":{"type":"TSNumberKeyword"}},"optional":false}}]}]}
+// JS: {{.*}}dialect.js:15:6: error: {{.*}}
@@ -0,0 +1,35 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: (%juno %s 2>&1 || true) | %FileCheck %s --match-full-lines
+
+let a;
+var a;
+
+function f() {
+  outer: for (;;) {
+    continue inner;
+  }
+}
+
+({__proto__: null, __proto__: null});
+
+// CHECK: {{.*}}early_errors.js:11:5: error: 'a' is already declared
+// CHECK-NEXT: var a;
+// CHECK-NEXT:     ^
+// CHECK-NEXT: {{.*}}early_errors.js:10:5: note: previous declaration
+// CHECK-NEXT: let a;
+// CHECK-NEXT:     ^
+// CHECK-NEXT: {{.*}}early_errors.js:15:14: error: label 'inner' is not defined
+// CHECK-NEXT:     continue inner;
+// CHECK-NEXT:              ^~~~~
+// CHECK-NEXT: {{.*}}early_errors.js:19:20: error: duplicate '__proto__' property
+// CHECK-NEXT: ({__proto__: null, __proto__: null});
+// CHECK-NEXT:                    ^~~~~~~~~~~~~~~
+// CHECK-NEXT: {{.*}}early_errors.js:19:3: note: previous definition
+// CHECK-NEXT: ({__proto__: null, __proto__: null});
+// CHECK-NEXT:   ^~~~~~~~~~~~~~~
@@ -0,0 +1,13 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js --jsx %s | %FileCheck %s --match-full-lines
+// RUN: cp %s %t.jsx && %juno --gen-js %t.jsx | %FileCheck %s --match-full-lines
+
+let x = <div className="a">{y}</div>;
+
+// CHECK: let x = <div className="a">{y}</div>;
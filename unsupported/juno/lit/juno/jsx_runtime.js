@@ -0,0 +1,48 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js --jsx --jsx-runtime=classic --comments=none %s | %FileCheck %s --match-full-lines --check-prefix=CLASSIC
+// RUN: %juno --gen-js --jsx --jsx-runtime=classic --jsx-pragma=h --jsx-pragma-frag=Fragment --comments=none %s | %FileCheck %s --match-full-lines --check-prefix=PRAGMA
+// RUN: %juno --gen-js --jsx --jsx-runtime=automatic --comments=none %s | %FileCheck %s --match-full-lines --check-prefix=AUTO
+// RUN: %juno --gen-js --jsx --jsx-runtime=automatic --jsx-import-source=preact --comments=none %s | %FileCheck %s --check-prefix=SOURCE
+
+function _jsx() {}
+
+let a = <div className="a" hidden aria-label="x
+    y" {...props}>
+  Hello &amp;   {name}
+  <span>&lt;world&gt;&#33;</span>
+  {/* comment */}
+</div>;
+let b = <My.Component key="k" value={1} />;
+let c = <>
+  <this.item />
+  <svg:rect />
+</>;
+let d = <li {...props} key={id}>{...items}</li>;
+
+// CLASSIC: function _jsx() {}
+// CLASSIC-NEXT: let a = React.createElement("div", {className: "a", hidden: true, "aria-label": "x y", ...props}, "Hello & ", name, React.createElement("span", null, "<world>!"));
+// CLASSIC-NEXT: let b = React.createElement(My.Component, {key: "k", value: 1});
+// CLASSIC-NEXT: let c = React.createElement(React.Fragment, null, React.createElement(this.item, null), React.createElement("svg:rect", null));
+// CLASSIC-NEXT: let d = React.createElement("li", {...props, key: id}, ...items);
+
+// PRAGMA: let a = h("div", {className: "a", hidden: true, "aria-label": "x y", ...props}, "Hello & ", name, h("span", null, "<world>!"));
+// PRAGMA: let c = h(Fragment, null, h(this.item, null), h("svg:rect", null));
+
+// AUTO: import {jsx as _jsx2, jsxs as _jsxs, Fragment as _Fragment} from "react/jsx-runtime"
+// AUTO-NEXT: ;
+// AUTO-NEXT: import {createElement as _createElement} from "react"
+// AUTO-NEXT: ;
+// AUTO-NEXT: function _jsx() {}
+// AUTO-NEXT: let a = _jsxs("div", {className: "a", hidden: true, "aria-label": "x y", ...props, children: ["Hello & ", name, _jsx2("span", {children: "<world>!"})]});
+// AUTO-NEXT: let b = _jsx2(My.Component, {value: 1}, "k");
+// AUTO-NEXT: let c = _jsxs(_Fragment, {children: [_jsx2(this.item, {}), _jsx2("svg:rect", {})]});
+// AUTO-NEXT: let d = _createElement("li", {...props, key: id}, ...items);
+
+// SOURCE: from "preact/jsx-runtime"
+// SOURCE: from "preact"
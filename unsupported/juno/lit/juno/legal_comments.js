@@ -0,0 +1,25 @@
+/**
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js --no-pretty --comments=legal %s | %FileCheck %s --match-full-lines
+// RUN: %juno --gen-js --no-pretty --comments=legalhoisted %s | %FileCheck %s --match-full-lines --check-prefix=HOIST
+
+/*! Library v1.0 | MIT */
+
+function add(a, b) {
+  /** @license Apache-2.0 */
+  return a + b; // Dropped.
+}
+
+/* @preserve */ add(1, 2);
+
+// CHECK: /*! Library v1.0 | MIT */function add(a,b){/** {{@}}license Apache-2.0 */return a+b;}/* {{@}}preserve */add(1,2);
+
+// HOIST: /*! Library v1.0 | MIT */
+// HOIST-NEXT: /** {{@}}license Apache-2.0 */
+// HOIST-NEXT: /* {{@}}preserve */
+// HOIST-NEXT: function add(a,b){return a+b;}add(1,2);
@@ -0,0 +1,178 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js --comments=none --lower=Classes %s | %FileCheck %s --match-full-lines
+// RUN: %juno --gen-js --comments=none --lower=ClassFields %s | %FileCheck %s --match-full-lines --check-prefix=FIELDS
+// RUN: echo 'export default class { m() {} }' > %t.default.js
+// RUN: %juno --gen-js --no-pretty --lower=ES2015 %t.default.js | %FileCheck %s --match-full-lines --check-prefix=DEFAULT
+// RUN: echo 'class A extends B { m() { super.x = 1; } } class C { x = y; constructor() { var y; } } class D { static s = super.x; }' > %t.js
+// RUN: (%juno --gen-js --lower=Classes %t.js 2>&1 || true) | %FileCheck %s --check-prefix=ERR
+
+class A extends B {
+  constructor(x) {
+    super(x);
+  }
+  m() {
+    return super.m(1) + super.n;
+  }
+  get x() {
+    return 1;
+  }
+  set x(v) {}
+  static s() {
+    return super.s();
+  }
+  y = 1;
+  static z = this;
+  [k] = 2;
+}
+
+var E = class {
+  a = 1;
+  static b = 2;
+};
+
+class C extends D {
+  f = () => this;
+}
+
+class F extends D {
+  x = 1;
+  constructor(a) {
+    if (a) super(1);
+    else super(2);
+  }
+}
+
+// CHECK: function _get(object, key, receiver) {
+// CHECK-NEXT:   while (object !== null) {
+// CHECK-NEXT:     var desc = Object.getOwnPropertyDescriptor(object, key);
+// CHECK-NEXT:     if (desc)
+// CHECK-NEXT:       return desc.get ? desc.get.call(receiver) : desc.value;
+// CHECK-NEXT: {{^ +$}}
+// CHECK-NEXT:     object = Object.getPrototypeOf(object);
+// CHECK-NEXT:   }
+// CHECK-NEXT: }
+// CHECK-NEXT: var _class;
+// CHECK-NEXT: var _key = k;
+// CHECK-NEXT: let A = function(_super) {
+// CHECK-NEXT:   function A(x) {
+// CHECK-NEXT:     if (!(this instanceof A))
+// CHECK-NEXT:       throw new TypeError("Cannot call a class as a function");
+// CHECK-NEXT: {{^ +$}}
+// CHECK-NEXT:     _super.call(this, x);
+// CHECK-NEXT:     this.y = 1;
+// CHECK-NEXT:     this[_key] = 2;
+// CHECK-NEXT:   }
+// CHECK-NEXT:   A.prototype = Object.create(_super.prototype, {constructor: {value: A, writable: true, configurable: true}});
+// CHECK-NEXT:   Object.setPrototypeOf(A, _super);
+// CHECK-NEXT:   Object.defineProperty(A.prototype, "m", {value: function() {
+// CHECK-NEXT:     return _get(_super.prototype, "m", this).call(this, 1) + _get(_super.prototype, "n", this);
+// CHECK-NEXT:   }, writable: true, configurable: true});
+// CHECK-NEXT:   Object.defineProperty(A.prototype, "x", {get: function() {
+// CHECK-NEXT:     return 1;
+// CHECK-NEXT:   }, set: function(v) {}, configurable: true});
+// CHECK-NEXT:   Object.defineProperty(A, "s", {value: function() {
+// CHECK-NEXT:     return _get(_super, "s", this).call(this);
+// CHECK-NEXT:   }, writable: true, configurable: true});
+// CHECK-NEXT:   return A;
+// CHECK-NEXT: }(B);
+// CHECK-NEXT: A.z = A;
+// CHECK-NEXT: var E = (_class = function() {
+// CHECK-NEXT:   function _class2() {
+// CHECK-NEXT:     if (!(this instanceof _class2))
+// CHECK-NEXT:       throw new TypeError("Cannot call a class as a function");
+// CHECK-NEXT: {{^ +$}}
+// CHECK-NEXT:     this.a = 1;
+// CHECK-NEXT:   }
+// CHECK-NEXT:   return _class2;
+// CHECK-NEXT: }(), _class.b = 2, _class);
+// CHECK-NEXT: let C = function(_super2) {
+// CHECK-NEXT:   function C(...args) {
+// CHECK-NEXT:     if (!(this instanceof C))
+// CHECK-NEXT:       throw new TypeError("Cannot call a class as a function");
+// CHECK-NEXT: {{^ +$}}
+// CHECK-NEXT:     _super2.call(this, ...args);
+// CHECK-NEXT:     this.f = (() => this);
+// CHECK-NEXT:   }
+// CHECK-NEXT:   C.prototype = Object.create(_super2.prototype, {constructor: {value: C, writable: true, configurable: true}});
+// CHECK-NEXT:   Object.setPrototypeOf(C, _super2);
+// CHECK-NEXT:   return C;
+// CHECK-NEXT: }(D);
+// CHECK-NEXT: let F = function(_super3) {
+// CHECK-NEXT:   function F(a) {
+// CHECK-NEXT:     if (!(this instanceof F))
+// CHECK-NEXT:       throw new TypeError("Cannot call a class as a function");
+// CHECK-NEXT: {{^ +$}}
+// CHECK-NEXT:     var _initFields = function() {
+// CHECK-NEXT:       this.x = 1;
+// CHECK-NEXT:     };
+// CHECK-NEXT:     if (a) {
+// CHECK-NEXT:       _super3.call(this, 1);
+// CHECK-NEXT:       _initFields.call(this);
+// CHECK-NEXT:     }else  {
+// CHECK-NEXT:       _super3.call(this, 2);
+// CHECK-NEXT:       _initFields.call(this);
+// CHECK-NEXT:     }
+// CHECK-NEXT:   }
+// CHECK-NEXT:   F.prototype = Object.create(_super3.prototype, {constructor: {value: F, writable: true, configurable: true}});
+// CHECK-NEXT:   Object.setPrototypeOf(F, _super3);
+// CHECK-NEXT:   return F;
+// CHECK-NEXT: }(D);
+
+// FIELDS: var _class;
+// FIELDS-NEXT: var _key = k;
+// FIELDS-NEXT: class A extends B {
+// FIELDS-NEXT:   constructor(x) {
+// FIELDS-NEXT:     super(x);
+// FIELDS-NEXT:     this.y = 1;
+// FIELDS-NEXT:     this[_key] = 2;
+// FIELDS-NEXT:   }
+// FIELDS-NEXT:   m() {
+// FIELDS-NEXT:     return super.m(1) + super.n;
+// FIELDS-NEXT:   }
+// FIELDS-NEXT:   get x() {
+// FIELDS-NEXT:     return 1;
+// FIELDS-NEXT:   }
+// FIELDS-NEXT:   set x(v) {}
+// FIELDS-NEXT:   static s() {
+// FIELDS-NEXT:     return super.s();
+// FIELDS-NEXT:   }
+// FIELDS-NEXT:   }
+// FIELDS: A.z = A;
+// FIELDS-NEXT: var E = (_class = class {
+// FIELDS-NEXT:   constructor() {
+// FIELDS-NEXT:     this.a = 1;
+// FIELDS-NEXT:   }
+// FIELDS-NEXT:   }
+// FIELDS-NEXT: , _class.b = 2, _class);
+// FIELDS-NEXT: class C extends D {
+// FIELDS-NEXT:   constructor(...args) {
+// FIELDS-NEXT:     super(...args);
+// FIELDS-NEXT:     this.f = (() => this);
+// FIELDS-NEXT:   }
+// FIELDS-NEXT:   }
+// FIELDS: class F extends D {
+// FIELDS-NEXT:   constructor(a) {
+// FIELDS-NEXT:     var _initFields = function() {
+// FIELDS-NEXT:       this.x = 1;
+// FIELDS-NEXT:     };
+// FIELDS-NEXT:     if (a) {
+// FIELDS-NEXT:       super(1);
+// FIELDS-NEXT:       _initFields.call(this);
+// FIELDS-NEXT:     }else  {
+// FIELDS-NEXT:       super(2);
+// FIELDS-NEXT:       _initFields.call(this);
+// FIELDS-NEXT:     }
+// FIELDS-NEXT:   }
+// FIELDS-NEXT:   }
+
+// ERR: error: class field initializers can't refer to 'y', which is declared in the constructor
+// ERR: error: static fields using 'super' can't be lowered
+// ERR: error: assignments to 'super' properties can't be lowered
+
+// DEFAULT: var _class=function(){function _class(){if(!(this instanceof _class))throw new TypeError("Cannot call a class as a function");}Object.defineProperty(_class.prototype,"m",{value:function(){},writable:true,configurable:true});return _class;}();export default _class;
@@ -0,0 +1,157 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js --comments=none --lower=es2015 %s | %FileCheck %s --match-full-lines
+// RUN: %juno --gen-js --comments=none --lower=ArrowFunctions --lower=TemplateLiterals %s | %FileCheck %s --match-full-lines --check-prefix=ARROW
+// RUN: %juno --gen-js --comments=none --lower=BlockScoping %s | %FileCheck %s --match-full-lines --check-prefix=BLOCK
+// RUN: echo 'const k = 1; for (k of a); var o = { m() { return () => super.x; } };' > %t.js
+// RUN: (%juno --gen-js --lower=es2015 %t.js 2>&1 || true) | %FileCheck %s --check-prefix=ERR
+// RUN: echo 'outer: for (;;) { for (let i = 0; i < 3; i++) { f(() => i); if (i) i++; else continue outer; } } const c = 1; c = 2; c ||= 3;' > %t.loops.js
+// RUN: %juno --gen-js --comments=none --lower=BlockScoping %t.loops.js | %FileCheck %s --match-full-lines --check-prefix=LOOPS
+// RUN: echo 'var f = (...a) => a;' > %t.rest.js
+// RUN: (%juno --gen-js --lower=Spread %t.rest.js 2>&1 || true) | %FileCheck %s --check-prefix=REST
+
+function f(a, ...rest) {
+  var g = () => this.x + arguments[0];
+  return [a, ...rest, o.m(...rest), {...a}, `x${a}y`, tag`a${a}b`];
+}
+
+var {a, b: [c, d = 1], ...r} = obj;
+
+function h({x, y}, [z], w = 3) {
+  return {x, y, [z]: w, get v() { return 1; }};
+}
+
+function loop() {
+  outer: for (let i in o) {
+    let x;
+    fns.push(() => i + x);
+    if (i) continue;
+    for (;;) { if (x) break outer; break; }
+    if (this) return i;
+  }
+}
+
+{
+  let a = 1;
+  const q = 2;
+  print(a, q);
+}
+
+// CHECK: function _toArray(value) {
+// CHECK-NEXT:   return typeof Symbol === "function" && value[(Symbol.iterator)] ? Array.from(value) : Array.prototype.slice.call(value);
+// CHECK-NEXT: }
+// CHECK-NEXT: function _extends(target) {
+// CHECK-NEXT:   for(var i = 1; i < arguments.length; i++) {
+// CHECK-NEXT:     var source = arguments[i];
+// CHECK-NEXT:     for(var key in source) {
+// CHECK-NEXT:       if (Object.prototype.hasOwnProperty.call(source, key)) {
+// CHECK-NEXT:         target[key] = source[key];
+// CHECK-NEXT:       }
+// CHECK-NEXT:     }
+// CHECK-NEXT:   }
+// CHECK-NEXT:   return target;
+// CHECK-NEXT: }
+// CHECK-NEXT: var _templateObject;
+// CHECK-NEXT: function f(a) {
+// CHECK-NEXT:   var rest = Array.prototype.slice.call(arguments, 1);
+// CHECK-NEXT:   var _this = this, _arguments = arguments;
+// CHECK-NEXT:   var g = function() {
+// CHECK-NEXT:     return _this.x + _arguments[0];
+// CHECK-NEXT:   };
+// CHECK-NEXT:   return [a].concat(_toArray(rest), [o.m.apply(o, _toArray(rest)), _extends({}, a), "x".concat(a, "y"), tag(_templateObject || (_templateObject = Object.freeze(Object.defineProperty(["a", "b"], "raw", {value: Object.freeze(["a", "b"])}))), a)]);
+// CHECK-NEXT: }
+// CHECK-NEXT: var _ref = obj, a = _ref.a, _ref2 = _ref.b, c = _ref2[0], _ref3 = _ref2[1], d = _ref3 === void 0 ? 1 : _ref3, _ref4 = Object.assign({}, _ref), r = (delete _ref4.a, delete _ref4.b, _ref4);
+// CHECK-NEXT: function h(_ref5, _ref7, w) {
+// CHECK-NEXT:   var _obj;
+// CHECK-NEXT:   var _ref6 = _ref5, x = _ref6.x, y = _ref6.y;
+// CHECK-NEXT:   var z = _ref7[0];
+// CHECK-NEXT:   if (w === void 0)
+// CHECK-NEXT:     w = 3;
+// CHECK-NEXT: {{^ +$}}
+// CHECK-NEXT:   return _obj = {x: x, y: y}, _obj[z] = w, Object.defineProperty(_obj, "v", {get: function() {
+// CHECK-NEXT:     return 1;
+// CHECK-NEXT:   }, configurable: true, enumerable: true}), _obj;
+// CHECK-NEXT: }
+// CHECK-NEXT: function loop() {
+// CHECK-NEXT:   {
+// CHECK-NEXT:     var _loop = function(_i) {
+// CHECK-NEXT:       var _x = void 0;
+// CHECK-NEXT:       fns.push(function() {
+// CHECK-NEXT:         return _i + _x;
+// CHECK-NEXT:       });
+// CHECK-NEXT:       if (_i)
+// CHECK-NEXT:         return;
+// CHECK-NEXT: {{^ +$}}
+// CHECK-NEXT:       for(;;) {
+// CHECK-NEXT:         if (_x)
+// CHECK-NEXT:           return "break";
+// CHECK-NEXT: {{^ +$}}
+// CHECK-NEXT:         break;
+// CHECK-NEXT:       }
+// CHECK-NEXT:       if (this)
+// CHECK-NEXT:         return {v: _i};
+// CHECK-NEXT: {{^ +$}}
+// CHECK-NEXT:     };
+// CHECK-NEXT:     outer:
+// CHECK-NEXT:     for(var _i in o) {
+// CHECK-NEXT:       var _ret = _loop.call(this, _i);
+// CHECK-NEXT:       if (_ret === "break")
+// CHECK-NEXT:         break;
+// CHECK-NEXT: {{^ +$}}
+// CHECK-NEXT:       if (typeof _ret === "object")
+// CHECK-NEXT:         return _ret.v;
+// CHECK-NEXT: {{^ +$}}
+// CHECK-NEXT:     }
+// CHECK-NEXT:   }
+// CHECK-NEXT: }
+// CHECK-NEXT: {
+// CHECK-NEXT:   var _a = 1;
+// CHECK-NEXT:   var q = 2;
+// CHECK-NEXT:   print(_a, q);
+// CHECK-NEXT: }
+
+// ARROW: function f(a, ...rest) {
+// ARROW-NEXT:   var _this = this, _arguments = arguments;
+// ARROW: var {a, b: [c, d = 1], ...r} = obj;
+
+// BLOCK: function f(a, ...rest) {
+// BLOCK-NEXT:   var g = () => this.x + arguments[0];
+// BLOCK:       fns.push(() => i + _x);
+// BLOCK:   var _a = 1;
+
+// LOOPS: function _readOnlyError(name) {
+// LOOPS-NEXT:   throw new TypeError("\"" + name + "\" is read-only");
+// LOOPS-NEXT: }
+// LOOPS-NEXT: outer:
+// LOOPS-NEXT: for(;;) {
+// LOOPS-NEXT:   {
+// LOOPS-NEXT:     var _loop = function(i) {
+// LOOPS-NEXT:       f(() => i);
+// LOOPS-NEXT:       if (i)
+// LOOPS-NEXT:         i++;
+// LOOPS-NEXT:       else {{$}}
+// LOOPS-NEXT:         return "continue|outer";
+// LOOPS-NEXT: {{^ +$}}
+// LOOPS-NEXT:       _i = i;
+// LOOPS-NEXT:     };
+// LOOPS-NEXT:     for(var _i = 0; _i < 3; _i++) {
+// LOOPS-NEXT:       var _ret = _loop(_i);
+// LOOPS-NEXT:       if (_ret === "continue|outer")
+// LOOPS-NEXT:         continue outer;
+// LOOPS-NEXT: {{^ +$}}
+// LOOPS-NEXT:     }
+// LOOPS-NEXT:   }
+// LOOPS-NEXT: }
+// LOOPS-NEXT: var c = 1;
+// LOOPS-NEXT: 2, _readOnlyError("c");
+// LOOPS-NEXT: c || (3, _readOnlyError("c"));
+
+// ERR: error: arrow functions using 'super' can't be lowered
+// ERR: error: constants assigned by destructuring or for-in/for-of loops can't be lowered
+
+// REST: error: rest parameters of arrow functions can't be lowered
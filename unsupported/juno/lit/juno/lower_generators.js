@@ -0,0 +1,202 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js --comments=none --lower=Generators %s | %FileCheck %s --match-full-lines
+// RUN: %juno --gen-js --comments=none --lower=AsyncFunctions %s | %FileCheck %s --match-full-lines --check-prefix=ASYNC
+// RUN: echo 'function* g(x) { let y = 1; { let y = 2; yield y; } yield y; { const x = 5; yield x; } try {} catch (y) { yield y; } yield x; }' > %t.shadow.js
+// RUN: %juno --gen-js --no-pretty --lower=Generators %t.shadow.js | %FileCheck %s --match-full-lines --check-prefix=SHADOW
+// RUN: echo 'async function* a() {} var o = { async m() { super.x; } }; function* w() { with (o) yield 1; } async function f() { for await (const x of y); } function* c() { for (let i of a) yield () => i; }' > %t.js
+// RUN: (%juno --gen-js --lower=Generators %t.js 2>&1 || true) | %FileCheck %s --check-prefix=ERR
+// RUN: echo 'var a = async () => arguments; async function f() { for await (const x of y); }' > %t.async.js
+// RUN: (%juno --gen-js --lower=AsyncFunctions %t.async.js 2>&1 || true) | %FileCheck %s --check-prefix=ASYNC-ERR
+
+function* f(x) {
+  var y = yield x;
+  return y + 1;
+}
+var g = function* (a) {
+  try {
+    for (const k in a) yield* a[k];
+  } catch (e) {
+    log(e);
+  } finally {
+    done();
+  }
+};
+var o = { *m() { yield this; } };
+async function h(p) {
+  "use strict";
+  const r = (await p) || arguments[0];
+  return r;
+}
+var i = async () => await q;
+
+// CHECK: regeneratorRuntime.mark(f);
+// CHECK-NEXT: function f(x) {
+// CHECK-NEXT:   var y;
+// CHECK-NEXT:   return regeneratorRuntime.wrap(function f$(_context) {
+// CHECK-NEXT:     while (1)
+// CHECK-NEXT:       switch (_context.prev = _context.next) {
+// CHECK-NEXT:       case 0:
+// CHECK-NEXT:         _context.next = 2;
+// CHECK-NEXT:         return x;
+// CHECK-NEXT:       case 2:
+// CHECK-NEXT:         y = _context.sent;
+// CHECK-NEXT:         return _context.abrupt("return", y + 1);
+// CHECK-NEXT:       case "end":
+// CHECK-NEXT:         return _context.stop();
+// CHECK-NEXT:       }
+// CHECK:   }, f, this);
+// CHECK-NEXT: }
+// CHECK-NEXT: var g = regeneratorRuntime.mark(function _callee(a) {
+// CHECK-NEXT:   var k, e;
+// CHECK-NEXT:   return regeneratorRuntime.wrap(function _callee$(_context2) {
+// CHECK-NEXT:     while (1)
+// CHECK-NEXT:       switch (_context2.prev = _context2.next) {
+// CHECK-NEXT:       case 0:
+// CHECK-NEXT:         _context2.prev = 0;
+// CHECK-NEXT:         _context2.t0 = regeneratorRuntime.keys(a);
+// CHECK-NEXT:       case 2:
+// CHECK-NEXT:         if ((_context2.t1 = _context2.t0()).done) {
+// CHECK-NEXT:           _context2.next = 7;
+// CHECK-NEXT:           break;
+// CHECK-NEXT:         }
+// CHECK-NEXT:         k = _context2.t1.value;
+// CHECK-NEXT:         return _context2.delegateYield(a[k], "t2", 5);
+// CHECK-NEXT:       case 5:
+// CHECK-NEXT:         _context2.next = 2;
+// CHECK-NEXT:         break;
+// CHECK-NEXT:       case 7:
+// CHECK-NEXT:         _context2.next = 12;
+// CHECK-NEXT:         break;
+// CHECK-NEXT:       case 9:
+// CHECK-NEXT:         _context2.prev = 9;
+// CHECK-NEXT:         e = _context2.catch(0);
+// CHECK-NEXT:         log(e);
+// CHECK-NEXT:       case 12:
+// CHECK-NEXT:         _context2.prev = 12;
+// CHECK-NEXT:         done();
+// CHECK-NEXT:         return _context2.finish(12);
+// CHECK-NEXT:       case 15:
+// CHECK-NEXT:       case "end":
+// CHECK-NEXT:         return _context2.stop();
+// CHECK-NEXT:       }
+// CHECK:   }, _callee, this, {{\[\[}}0, 9, 12, 15]]);
+// CHECK-NEXT: });
+// CHECK-NEXT: var o = {m() {
+// CHECK-NEXT:   return regeneratorRuntime.wrap(function _callee$2(_context3) {
+// CHECK-NEXT:     while (1)
+// CHECK-NEXT:       switch (_context3.prev = _context3.next) {
+// CHECK-NEXT:       case 0:
+// CHECK-NEXT:         _context3.next = 2;
+// CHECK-NEXT:         return this;
+// CHECK-NEXT:       case 2:
+// CHECK-NEXT:       case "end":
+// CHECK-NEXT:         return _context3.stop();
+// CHECK-NEXT:       }
+// CHECK:   }, null, this);
+// CHECK-NEXT: }};
+// CHECK-NEXT: function h(p) {
+// CHECK-NEXT:   "use strict";
+// CHECK-NEXT:   var r, _args = arguments;
+// CHECK-NEXT:   return regeneratorRuntime.async(function h$(_context4) {
+// CHECK-NEXT:     while (1)
+// CHECK-NEXT:       switch (_context4.prev = _context4.next) {
+// CHECK-NEXT:       case 0:
+// CHECK-NEXT:         _context4.next = 2;
+// CHECK-NEXT:         return regeneratorRuntime.awrap(p);
+// CHECK-NEXT:       case 2:
+// CHECK-NEXT:         _context4.t0 = _context4.sent;
+// CHECK-NEXT:         if (_context4.t0) {
+// CHECK-NEXT:           _context4.next = 5;
+// CHECK-NEXT:           break;
+// CHECK-NEXT:         }
+// CHECK-NEXT:         _context4.t0 = _args[0];
+// CHECK-NEXT:       case 5:
+// CHECK-NEXT:         r = _context4.t0;
+// CHECK-NEXT:         return _context4.abrupt("return", r);
+// CHECK-NEXT:       case "end":
+// CHECK-NEXT:         return _context4.stop();
+// CHECK-NEXT:       }
+// CHECK:   }, null, this);
+// CHECK-NEXT: }
+// CHECK-NEXT: var i = () => {
+// CHECK-NEXT:   return regeneratorRuntime.async(function _callee$3(_context5) {
+// CHECK-NEXT:     while (1)
+// CHECK-NEXT:       switch (_context5.prev = _context5.next) {
+// CHECK-NEXT:       case 0:
+// CHECK-NEXT:         _context5.next = 2;
+// CHECK-NEXT:         return regeneratorRuntime.awrap(q);
+// CHECK-NEXT:       case 2:
+// CHECK-NEXT:         return _context5.abrupt("return", _context5.sent);
+// CHECK-NEXT:       case "end":
+// CHECK-NEXT:         return _context5.stop();
+// CHECK-NEXT:       }
+// CHECK:   }, null, this);
+// CHECK-NEXT: };
+
+// ASYNC: function _async(fn, self, args) {
+// ASYNC-NEXT:   return new Promise(function(resolve, reject) {
+// ASYNC-NEXT:     var gen = fn.apply(self, args);
+// ASYNC-NEXT:     function step(method, arg) {
+// ASYNC-NEXT:       try {
+// ASYNC-NEXT:         var result = gen[method](arg);
+// ASYNC-NEXT:       } catch (error) {
+// ASYNC-NEXT:         reject(error);
+// ASYNC-NEXT:         return;
+// ASYNC-NEXT:       }
+// ASYNC-NEXT:       if (result.done) {
+// ASYNC-NEXT:         resolve(result.value);
+// ASYNC-NEXT:       }else  {
+// ASYNC-NEXT:         Promise.resolve(result.value).then(function(value) {
+// ASYNC-NEXT:           step("next", value);
+// ASYNC-NEXT:         }, function(error) {
+// ASYNC-NEXT:           step("throw", error);
+// ASYNC-NEXT:         });
+// ASYNC-NEXT:       }
+// ASYNC-NEXT:     }
+// ASYNC-NEXT:     step("next");
+// ASYNC-NEXT:   });
+// ASYNC-NEXT: }
+// ASYNC-NEXT: function* f(x) {
+// ASYNC-NEXT:   var y = yield x;
+// ASYNC-NEXT:   return y + 1;
+// ASYNC-NEXT: }
+// ASYNC-NEXT: var g = function*(a) {
+// ASYNC-NEXT:   try {
+// ASYNC-NEXT:     for(const k in a)
+// ASYNC-NEXT:       yield* a[k];
+// ASYNC:   } catch (e) {
+// ASYNC-NEXT:     log(e);
+// ASYNC-NEXT:   }finally  {
+// ASYNC-NEXT:     done();
+// ASYNC-NEXT:   }
+// ASYNC-NEXT: };
+// ASYNC-NEXT: var o = {* m() {
+// ASYNC-NEXT:   yield this;
+// ASYNC-NEXT: }};
+// ASYNC-NEXT: function h(p) {
+// ASYNC-NEXT:   "use strict";
+// ASYNC-NEXT:   return _async(function*() {
+// ASYNC-NEXT:     const r = (yield p) || arguments[0];
+// ASYNC-NEXT:     return r;
+// ASYNC-NEXT:   }, this, arguments);
+// ASYNC-NEXT: }
+// ASYNC-NEXT: var i = () => _async(function*() {
+// ASYNC-NEXT:   return yield q;
+// ASYNC-NEXT: }, this);
+
+// ERR: error: loops of generators and async functions capturing their variables must be lowered by BlockScoping first
+// ERR: error: async generators can't be lowered
+// ERR: error: generators and async functions using 'super' can't be lowered
+// ERR: error: WithStatement in generators and async functions can't be lowered
+// ERR: error: 'for await' loops can't be lowered
+
+// ASYNC-ERR: error: async arrow functions using 'arguments' can't be lowered
+// ASYNC-ERR: error: 'for await' loops can't be lowered
+
+// SHADOW: regeneratorRuntime.mark(g);function g(x){var y,_y,_x,_y2;return regeneratorRuntime.wrap(function g$(_context){while(1)switch(_context.prev=_context.next){case 0:y=1;_y=2;_context.next=4;return _y;case 4:_context.next=6;return y;case 6:_x=5;_context.next=9;return _x;case 9:_context.prev=9;_context.next=16;break;case 12:_context.prev=12;_y2=_context.catch(9);_context.next=16;return _y2;case 16:_context.next=18;return x;case 18:case"end":return _context.stop();}},g,this,{{\[\[9,12\]\]}});}
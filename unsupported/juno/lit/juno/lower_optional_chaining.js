@@ -0,0 +1,39 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js --comments=none --lower=OptionalChaining %s | %FileCheck %s --match-full-lines
+
+x = a?.b.c(d);
+y = o.m?.();
+z = f() ?? 1;
+w = a?.b?.[k]?.(1).e;
+v = (a?.b)();
+u = delete a?.b;
+t = g().h?.i.j;
+class A extends B { m() { return super.n?.(1); } }
+var q = () => p()?.r;
+s = a ?? b ?? c;
+
+// CHECK: var _m, _ref, _b, _ref2, _h, _ref4;
+// CHECK-NEXT: x = a === null || a === void 0 ? void 0 : a.b.c(d);
+// CHECK-NEXT: y = (_m = o.m) === null || _m === void 0 ? void 0 : _m.call(o);
+// CHECK-NEXT: z = (_ref = f()) !== null && _ref !== void 0 ? _ref : 1;
+// CHECK-NEXT: w = a === null || a === void 0 || (_b = a.b) === null || _b === void 0 || (_ref2 = _b[k]) === null || _ref2 === void 0 ? void 0 : _ref2.call(_b, 1).e;
+// CHECK-NEXT: v = (a === null || a === void 0 ? void 0 : a.b).call(a);
+// CHECK-NEXT: u = a === null || a === void 0 ? true : delete a.b;
+// CHECK-NEXT: t = (_h = g().h) === null || _h === void 0 ? void 0 : _h.i.j;
+// CHECK-NEXT: class A extends B {
+// CHECK-NEXT:   m() {
+// CHECK-NEXT:     var _n;
+// CHECK-NEXT:     return (_n = super.n) === null || _n === void 0 ? void 0 : _n.call(this, 1);
+// CHECK-NEXT:   }
+// CHECK-NEXT:   }
+// CHECK: var q = () => {
+// CHECK-NEXT:   var _ref3;
+// CHECK-NEXT:   return (_ref3 = p()) === null || _ref3 === void 0 ? void 0 : _ref3.r;
+// CHECK-NEXT: };
+// CHECK-NEXT: s = (_ref4 = a !== null && a !== void 0 ? a : b) !== null && _ref4 !== void 0 ? _ref4 : c;
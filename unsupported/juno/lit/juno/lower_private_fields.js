@@ -0,0 +1,66 @@
+/**
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js --comments=none --lower=ClassFields %s | %FileCheck %s --match-full-lines
+// RUN: echo 'class A { #x = 1; get #g() { return this.#x; } #m() { this.#m = 1; } } let C = class D { #m() { return D; } }' > %t.js
+// RUN: (%juno --gen-js --lower=ClassFields %t.js 2>&1 || true) | %FileCheck %s --check-prefix=ERR
+
+class A {
+  #x = 1;
+  static #s = 2;
+  m(o) {
+    o.#x = this.#x + A.#s;
+    return this.#x++;
+  }
+  static t() {
+    return A.#s;
+  }
+  #p(y) {
+    return this.#x + y;
+  }
+  static #q() {
+    return A.#s;
+  }
+  n() {
+    return [this.#p(1), this.#p, A.#q()];
+  }
+}
+
+// CHECK: function _checkPrivate(set, obj) {
+// CHECK-NEXT:   if (!set.has(obj))
+// CHECK-NEXT:     throw new TypeError("Cannot access a private member of an object whose class did not declare it");
+// CHECK-NEXT: {{^ +$}}
+// CHECK-NEXT:   return obj;
+// CHECK-NEXT: }
+// CHECK-NEXT: var _brand = new WeakSet(), _staticBrand = new WeakSet(), _x = new WeakMap(), _s = new WeakMap(), _p = function(y) {
+// CHECK-NEXT:   return _x.get(_checkPrivate(_x, this)) + y;
+// CHECK-NEXT: }, _q = function() {
+// CHECK-NEXT:   return _s.get(_checkPrivate(_s, A));
+// CHECK-NEXT: };
+// CHECK-NEXT: class A {
+// CHECK-NEXT:   constructor() {
+// CHECK-NEXT:     _brand.add(this);
+// CHECK-NEXT:     _x.set(this, 1);
+// CHECK-NEXT:   }
+// CHECK-NEXT:   m(o) {
+// CHECK-NEXT:     var _value;
+// CHECK-NEXT:     _x.set(_checkPrivate(_x, o), _x.get(_checkPrivate(_x, this)) + _s.get(_checkPrivate(_s, A)));
+// CHECK-NEXT:     return _x.set(_checkPrivate(_x, this), (_value = +_x.get(this)) + 1), _value;
+// CHECK-NEXT:   }
+// CHECK-NEXT:   static t() {
+// CHECK-NEXT:     return _s.get(_checkPrivate(_s, A));
+// CHECK-NEXT:   }
+// CHECK-NEXT:   n() {
+// CHECK-NEXT:     return [_p.call(_checkPrivate(_brand, this), 1), (_checkPrivate(_brand, this), _p), _q.call(_checkPrivate(_staticBrand, A))];
+// CHECK-NEXT:   }
+// CHECK-NEXT:   }
+// CHECK: _staticBrand.add(A);
+// CHECK-NEXT: _s.set(A, 2);
+
+// ERR: error: private method #m can't be assigned
+// ERR: error: private accessors can't be lowered
+// ERR: error: private methods referring to the name of a class expression can't be lowered
@@ -0,0 +1,49 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js -O --no-pretty --comments=none %s | %FileCheck %s
+// RUN: %juno --gen-js -O --no-pretty --comments=none --sourcemap %s -o %t.js && cat %t.js.map | %FileCheck %s --match-full-lines --check-prefix=MAP
+// RUN: echo 'import {a} from "x"; export function f(longName) { return a(longName); } let local = 2; export {local}; let priv = 3; use(priv);' > %t.mjs
+// RUN: %juno --gen-js -O --no-pretty %t.mjs | %FileCheck %s --match-full-lines --check-prefix=MODULE
+
+var global = 1;
+function sum(values) {
+  let total = 0;
+  for (const value of values) total += value;
+  return total;
+}
+// CHECK: {{^}}var global=1;function sum(t){let e=0;for(const n of t)e+=n;return e;}
+
+// Shorthand properties keep their keys, and variables of sibling scopes share names.
+function props(e, t) {
+  var {e: x, t} = e;
+  const o = {x, t};
+  try {
+    h(x);
+  } catch (err) {
+    log(err, e);
+  }
+  return function inner(n) {
+    return inner(n, o);
+  };
+}
+// CHECK-SAME: function props(t,e){var {e:n,t:e}=t;const i={x:n,t:e};try{h(n);}catch(r){log(r,t);}return function r(s){return r(s,i);};}
+
+// Variables visible from a direct eval are kept.
+function dynamic(code) {
+  var keep = 1;
+  return function () {
+    var local = 2;
+    eval(code);
+  };
+}
+// CHECK-SAME: function dynamic(code){var keep=1;return function(){var local=2;eval(code);};}{{$}}
+
+// MAP: {"version":3,"file":"mangle.js.tmp.js","sources":["{{.*}}mangle.js"],"names":["global","sum","values","total","value","props","e","t","x","o","h","err","log","inner","n","dynamic","code","keep","local","eval"],"mappings":"{{.*}}"}
+
+// Exported variables are kept.
+// MODULE: import {a as e}from "x";export function f(n){return e(n);}let local=2;export {local as local};let t=3;use(t);
@@ -0,0 +1,57 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js -O --no-pretty --comments=none %s | %FileCheck %s
+// RUN: %juno --gen-js -O --comments=none %s | %FileCheck %s --check-prefix=PRETTY
+// RUN: echo 'a(); b(); function f(x) { if (x) return "x"; throw ["y"]; } f(typeof {}); function g(x) { return (x(), 2) ? `t` : {}; }' > %t.js
+// RUN: %juno --gen-js -O --no-pretty --comments=none %t.js | %FileCheck %s --match-full-lines --check-prefix=BYTES
+// BYTES: a(),b();function f(e){if(e)return"x";throw["y"];}f(typeof{});function g(e){return(e(),2)?`t`:{};}
+
+function run(a, b) {
+  if (a) b();
+  if (!a) {
+    b();
+  } else {
+    a();
+  }
+  log();
+  if (b) return a;
+  return b;
+}
+// CHECK: {{^}}function run(e,t){return(e&&t(),e?e():t(),log(),t)?e:t;}
+// PRETTY: if (a)
+
+function check(c) {
+  if (c) {
+    return true;
+  } else {
+    return;
+  }
+}
+// CHECK-SAME: function check(e){return e?!0:void 0;}
+
+function fail(c) {
+  if (c) throw 1;
+  else throw 2;
+}
+// CHECK-SAME: function fail(e){throw e?1:2;}
+
+function shadow(undefined) {
+  return undefined;
+}
+// CHECK-SAME: function shadow(e){return e;}
+
+var numbers = [1000, 0.5, 0.00015, 123];
+var flags = [true, false, undefined];
+delete undefined;
+var short = {undefined};
+let first = 1;
+let second = 2;
+// CHECK-SAME: var numbers=[1e3,.5,15e-5,123],flags=[!0,!1,void 0];delete undefined;var short={undefined};let first=1,second=2;
+// PRETTY: var numbers = [1000, 0.5, 0.00015, 123];
+
+use(run, check, fail, shadow, first, second);
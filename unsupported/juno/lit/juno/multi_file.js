@@ -0,0 +1,52 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: rm -rf %t && mkdir -p %t/src/sub
+// RUN: echo "a = 1 + 2;" > %t/src/a.js
+// RUN: echo "x = 2 * 3;" > %t/src/sub/b.js
+// RUN: %juno --gen-js -O %t/src --out-dir %t/out
+// RUN: cat %t/out/a.js %t/out/sub/b.js | %FileCheck %s --match-full-lines
+// RUN: %juno --gen-js "%t/src/**/*.js" --out-dir %t/glob
+// RUN: cat %t/glob/sub/b.js | %FileCheck %s --match-full-lines --check-prefix=GLOB
+
+// Compilation continues after an error, but the exit status reflects it.
+// RUN: echo "var = ;" > %t/src/bad.js
+// RUN: (%juno --gen-js %t/src --out-dir %t/err 2>&1; echo "status $?") | %FileCheck %s --match-full-lines --check-prefix=ERR
+// RUN: cat %t/err/sub/b.js | %FileCheck %s --match-full-lines --check-prefix=GLOB
+// RUN: test ! -e %t/err/bad.js
+
+// Nothing is written when a pass fails.
+// RUN: echo "var o = { m() { return () => super.x; } };" > %t/super.js
+// RUN: (%juno --gen-js --lower=ArrowFunctions %t/super.js -o %t/super.out.js 2>&1; echo "status $?") | %FileCheck %s --match-full-lines --check-prefix=SUPER
+// RUN: test ! -e %t/super.out.js
+
+// Outputs have the extension of the generated format.
+// RUN: mkdir -p %t/ts && echo "let t: number = 1;" > %t/ts/t.ts
+// RUN: %juno --gen-js --strip-types %t/ts --out-dir %t/ts_js
+// RUN: cat %t/ts_js/t.js | %FileCheck %s --match-full-lines --check-prefix=TS
+// RUN: %juno --gen-ast %t/src/sub --out-dir %t/ast
+// RUN: test -f %t/ast/b.json
+
+// Inputs mapping to the same output are rejected.
+// RUN: mkdir -p %t/other && echo "y;" > %t/other/a.js
+// RUN: (%juno --gen-js %t/src/a.js %t/other/a.js --out-dir %t/clash 2>&1; echo "status $?") | %FileCheck %s --match-full-lines --check-prefix=CLASH
+
+// CHECK: a = 3;
+// CHECK-NEXT: x = 6;
+
+// GLOB: x = 2 * 3;
+
+// ERR: {{.*}}bad.js:1:5: error: 'identifier' expected in declaration
+// ERR: status 1
+
+// SUPER: {{.*}}super.js:1:30: error: arrow functions using 'super' can't be lowered
+// SUPER: status 1
+
+// TS: let t = 1;
+
+// CLASH: {{.*}}src/a.js and {{.*}}other/a.js would both be written to {{.*}}clash/a.js
+// CLASH-NEXT: status 1
@@ -0,0 +1,19 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: (%juno %s 2>&1 || true) | %FileCheck %s --match-full-lines
+
+'use strict';
+var x = 010;
+var y = 011;
+
+// CHECK: {{.*}}parse_errors.js:11:9: error: Octal literals must use '0o' in strict mode
+// CHECK-NEXT: var x = 010;
+// CHECK-NEXT:         ^
+// CHECK-NEXT: {{.*}}parse_errors.js:12:9: error: Octal literals must use '0o' in strict mode
+// CHECK-NEXT: var y = 011;
+// CHECK-NEXT:         ^
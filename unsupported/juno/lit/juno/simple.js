@@ -5,7 +5,7 @@
  * LICENSE file in the root directory of this source tree.
  */
 
-// RUN: %juno --gen-ast %s | %FileCheck %s --match-full-lines
+// RUN: %juno --gen-ast --comments=none %s | %FileCheck %s --match-full-lines
 
 1 + 1;
 
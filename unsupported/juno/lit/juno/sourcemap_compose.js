@@ -0,0 +1,17 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js --comments=none --sourcemap %s -o %t.js && cat %t.js.map | %FileCheck %s --match-full-lines
+
+// The input source map is an indexed map whose sections map this file to different
+// sources. Names, source contents and the ignore list are carried over to the output map.
+
+a + b;
+
+//# sourceMappingURL=data:application/json;charset=utf-8;base64,eyJ2ZXJzaW9uIjozLCJzZWN0aW9ucyI6W3sib2Zmc2V0Ijp7ImxpbmUiOjEyLCJjb2x1bW4iOjB9LCJtYXAiOnsidmVyc2lvbiI6Mywic291cmNlcyI6WyJhcHAudHMiXSwic291cmNlc0NvbnRlbnQiOlsibGV0IGEgPSAxO1xuYSArIGI7XG4iXSwibmFtZXMiOlsiYSJdLCJtYXBwaW5ncyI6IkFBQ0FBIn19LHsib2Zmc2V0Ijp7ImxpbmUiOjEyLCJjb2x1bW4iOjR9LCJtYXAiOnsidmVyc2lvbiI6Mywic291cmNlcyI6WyJsaWIudHMiXSwibmFtZXMiOltdLCJtYXBwaW5ncyI6IkFBQUEiLCJ4X2dvb2dsZV9pZ25vcmVMaXN0IjpbMF19fV19
+
+// CHECK: {"version":3,"file":"sourcemap_compose.js.tmp.js","sources":["app.ts","lib.ts"],"sourcesContent":["let a = 1;\na + b;\n",null],"names":["a","b"],"mappings":"AACAA,ICDAC","x_google_ignoreList":[1]}
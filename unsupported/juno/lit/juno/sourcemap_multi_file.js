@@ -0,0 +1,18 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// Every input has its own inline source map, which is composed with the map of its output.
+// RUN: rm -rf %t && mkdir -p %t/orig %t/src
+// RUN: echo "first;" > %t/orig/a.js
+// RUN: echo "second;" > %t/orig/b.js
+// RUN: %juno --gen-js --sourcemap --sourcemap-mode=inline %t/orig --out-dir %t/src
+// RUN: %juno --gen-js --sourcemap %t/src --out-dir %t/out
+// RUN: cat %t/out/a.js.map | %FileCheck %s --match-full-lines --check-prefix=A
+// RUN: cat %t/out/b.js.map | %FileCheck %s --match-full-lines --check-prefix=B
+
+// A: {"version":3,"file":"a.js","sources":["{{.*}}orig/a.js"],"names":["first"],"mappings":"{{.+}}"}
+// B: {"version":3,"file":"b.js","sources":["{{.*}}orig/b.js"],"names":["second"],"mappings":"{{.+}}"}
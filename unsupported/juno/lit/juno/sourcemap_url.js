@@ -0,0 +1,24 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js --comments=none --sourcemap --sourcemap-mode=inline %s | %FileCheck %s --match-full-lines --check-prefix=INLINE
+// RUN: %juno --gen-js --comments=none --sourcemap --sourcemap-mode=linked %s -o %t.js && cat %t.js | %FileCheck %s --match-full-lines --check-prefix=LINKED
+// RUN: cat %t.js.map | %FileCheck %s --match-full-lines --check-prefix=MAP
+
+// Inline source maps produced by Juno can be consumed as input source maps.
+// RUN: %juno --gen-js --comments=none --sourcemap --sourcemap-mode=inline %s > %t.inline.js
+// RUN: %juno --gen-js --comments=none --sourcemap %t.inline.js -o %t.2.js && cat %t.2.js.map | %FileCheck %s --match-full-lines --check-prefix=MAP
+
+a + b;
+
+// INLINE: a + b;
+// INLINE-NEXT: //# sourceMappingURL=data:application/json;charset=utf-8;base64,{{[A-Za-z0-9+/=]+}}
+
+// LINKED: a + b;
+// LINKED-NEXT: //# sourceMappingURL=sourcemap_url.js.tmp.js.map
+
+// MAP: {"version":3,"file":"sourcemap_url.js.tmp{{.*}}.js","sources":["{{.*}}sourcemap_url.js"],"names":["a","b"],"mappings":"{{.+}}"}
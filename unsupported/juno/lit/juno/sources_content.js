@@ -0,0 +1,12 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js --sourcemap --sources-content %s -o %t.js && cat %t.js.map | %FileCheck %s --match-full-lines
+
+x;
+
+// CHECK: {"version":3,"file":"sources_content.js.tmp.js","sources":["{{.*}}sources_content.js"],"sourcesContent":["/*\n * Copyright{{.*}}\n\nx;\n\n// CHECK: {{.*}}"],"names":["x"],"mappings":"{{.+}}"}
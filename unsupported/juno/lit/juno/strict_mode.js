@@ -0,0 +1,14 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js %s | %FileCheck %s --match-full-lines --check-prefix=SLOPPY
+// RUN: (%juno --gen-js --strict-mode %s 2>&1 || true) | %FileCheck %s --match-full-lines --check-prefix=STRICT
+
+var x = 010;
+
+// SLOPPY: var x = 8;
+// STRICT: {{.*}}strict_mode.js:11:9: error: Octal literals must use '0o' in strict mode
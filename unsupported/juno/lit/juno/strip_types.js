@@ -0,0 +1,67 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+// RUN: %juno --gen-js --strip-types --comments=none %s | %FileCheck %s --match-full-lines
+// RUN: echo 'import type {A} from "a"; type T = string; export interface I { x: number } let v = (1 as any) as T; function f<U>(x: U, y?: number): U { return <U>x; } f<number>(v);' > %t.ts
+// RUN: %juno --gen-js --strip-types --no-pretty %t.ts | %FileCheck %s --match-full-lines --check-prefix=TS
+// RUN: echo 'import {A, B, C as D} from "a"; import E from "e"; import * as F from "f"; import "g"; type T = A; interface I {} let x: A = new B(); let y: typeof E = 1; export {D as Z, B}; export {F, T, I};' > %t.imports.ts
+// RUN: %juno --gen-js --strip-types --no-pretty %t.imports.ts | %FileCheck %s --match-full-lines --check-prefix=IMPORTS
+// RUN: echo 'enum E {A}' > %t.enum.js
+// RUN: (%juno --gen-js --strip-types --dialect=flow %t.enum.js 2>&1 || true) | %FileCheck %s --check-prefix=ENUM
+
+// @flow
+
+import type {A} from "a";
+import typeof B from "b";
+import {type C, typeof D} from "c";
+import {type E, F} from "e";
+import "side-effect";
+// CHECK: import {F as F} from "e"
+// CHECK-NEXT: ;
+// CHECK-NEXT: import "side-effect"
+// CHECK-NEXT: ;
+
+export type {A};
+export type * from "x";
+export type T = number;
+type U = Array<string>;
+opaque type O = string;
+interface I { x: number }
+declare var dv: number;
+declare function df(): void;
+declare export function de(): void;
+
+class K<T> extends F<T> implements I {
+  declare z: number;
+  +x: number = 1;
+  m<V>(p?: V, ...r: Array<V>): void {}
+}
+// CHECK-NEXT: class K extends F {
+// CHECK-NEXT:   x = 1;
+// CHECK-NEXT:   m(p, ...r) {}
+// CHECK-NEXT:   }
+
+function g(this: number, x?: number, {a}: {a: number} = {}, [b]: Array<number>): boolean %checks {
+  return !!x;
+}
+// CHECK-EMPTY:
+// CHECK-NEXT: function g(x, {a} = {}, [b]) {
+// CHECK-NEXT:   return !!x;
+// CHECK-NEXT: }
+
+let v = ((1: any): number);
+let f = <V>(x: V): V => x;
+g<string>(v, new K<number>(), f);
+// CHECK-NEXT: let v = 1;
+// CHECK-NEXT: let f = x => x;
+// CHECK-NEXT: g(v, new K(), f);
+
+// TS: let v=1;function f(x,y){return x;}f(v);
+
+// IMPORTS: import {B as B,C as D}from "a";import * as F from "f";import "g";let x=new B();let y=1;export {D as Z,B as B};export {F as F};
+
+// ENUM: error: enums generate code and can't be stripped